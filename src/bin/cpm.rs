@@ -30,6 +30,17 @@ struct BuildCommand;
 struct DevCommand;
 struct TestCommand;
 struct RunCommand;
+struct SbomCommand;
+struct ApproveScriptsCommand;
+struct PackCommand;
+struct VersionCommand;
+struct ReleaseCommand;
+struct ChangelogCommand;
+struct LinkCommand;
+struct UnlinkCommand;
+struct CacheServerCommand;
+struct EnvCommand;
+struct ExecCommand;
 
 
 impl InitCommand {
@@ -43,37 +54,16 @@ impl InitCommand {
         let mut package_json: serde_json::Value = serde_json::from_str(&package_json_content)?;
 
         // Add CPM scripts
-        if let Some(scripts) = package_json.get_mut("scripts") {
-            if let Some(scripts_obj) = scripts.as_object_mut() {
-                scripts_obj.insert(
-                    "dev".to_string(),
-                    serde_json::Value::String("cpm dev".to_string()),
-                );
-                scripts_obj.insert(
-                    "build".to_string(),
-                    serde_json::Value::String("cpm build".to_string()),
-                );
-                scripts_obj.insert(
-                    "test".to_string(),
-                    serde_json::Value::String("cpm test".to_string()),
-                );
-                scripts_obj.insert(
-                    "preinstall".to_string(),
-                    serde_json::Value::String("echo 'preinstall' || true".to_string()),
-                );
-                scripts_obj.insert(
-                    "postinstall".to_string(),
-                    serde_json::Value::String("echo 'postinstall' || true".to_string()),
+        if !package_json.get("scripts").is_some_and(|s| s.is_object()) {
+            package_json["scripts"] = serde_json::Value::Object(serde_json::Map::new());
+        }
+        if let Some(scripts) = package_json["scripts"].as_object_mut() {
+            for (name, command) in cpm::templates::CPM_SCRIPTS {
+                scripts.insert(
+                    name.to_string(),
+                    serde_json::Value::String(command.to_string()),
                 );
             }
-        } else {
-            let mut scripts = serde_json::Map::new();
-            scripts.insert("dev".to_string(), serde_json::Value::String("cpm dev".to_string()));
-            scripts.insert("build".to_string(), serde_json::Value::String("cpm build".to_string()));
-            scripts.insert("test".to_string(), serde_json::Value::String("cpm test".to_string()));
-            scripts.insert("preinstall".to_string(), serde_json::Value::String("echo preinstall || true".to_string()));
-            scripts.insert("postinstall".to_string(), serde_json::Value::String("echo postinstall || true".to_string()));
-            package_json["scripts"] = serde_json::Value::Object(scripts);
         }
 
         // Write back to package.json
@@ -167,62 +157,14 @@ impl CliCommand for InitCommand {
         // Add CPM scripts to package.json
         self.add_cpm_scripts_to_package_json()?;
 
-        // Create basic index.js
-        let index_js = r#"// CPM JavaScript Project
-console.log('Hello from CPM! 🦀');
-
-// Example function
-function greet(name) {
-    console.log(`Hello, ${name}! Welcome to CPM.`);
-}
-
-// Call the function
-greet('World');
-"#;
+        // Start with an empty install script allow-list
+        std::fs::write(cpm::config::CONFIG_FILE, cpm::templates::CPM_TOML_TEMPLATE)?;
 
-        std::fs::write("index.js", index_js)?;
+        // Create basic index.js
+        std::fs::write("index.js", cpm::templates::INDEX_JS_TEMPLATE)?;
 
         // Create README
-        let readme = format!(
-            r#"# {project_name} - CPM JavaScript Project
-
-This is a JavaScript project managed by CPM (Crab Package Manager).
-
-## Getting Started
-
-1. **Install dependencies:**
-   ```bash
-   cpm install
-   ```
-
-2. **Start development server:**
-   ```bash
-   cpm dev
-   ```
-
-3. **Build the project:**
-   ```bash
-   cpm build
-   ```
-
-## Adding Rust (Optional)
-
-To add Rust to this project later:
-```bash
-cpm add-rust
-```
-
-## Available Commands
-
-- `cpm install` - Install dependencies
-- `cpm build` - Build the project
-- `cpm dev` - Start development server
-- `cpm test` - Run tests
-- `cpm add-rust` - Add Rust to the project
-- `cpm rust-status` - Check Rust status
-"#
-        );
-
+        let readme = cpm::templates::README_TEMPLATE.replace("PROJECT_NAME", project_name);
         std::fs::write("README.md", readme)?;
 
         eprintln!("✅ JavaScript project initialized successfully!");
@@ -259,19 +201,9 @@ impl AddRustCommand {
 
         // Add WASM dependencies
         let mut dependencies = toml::map::Map::new();
-        dependencies.insert(
-            "wasm-bindgen".to_string(),
-            toml::Value::String("0.2".to_string()),
-        );
-        dependencies.insert("serde".to_string(), toml::Value::String("1.0".to_string()));
-        dependencies.insert(
-            "serde-wasm-bindgen".to_string(),
-            toml::Value::String("0.6".to_string()),
-        );
-        dependencies.insert(
-            "web-sys".to_string(),
-            toml::Value::String("0.3".to_string()),
-        );
+        for (name, version) in cpm::templates::WASM_CARGO_DEPENDENCIES {
+            dependencies.insert(name.to_string(), toml::Value::String(version.to_string()));
+        }
 
         cargo_toml["dependencies"] = toml::Value::Table(dependencies);
 
@@ -290,54 +222,7 @@ impl AddRustCommand {
         std::fs::create_dir_all("src")?;
 
         // Create lib.rs
-        let lib_rs = r#"use wasm_bindgen::prelude::*;
-
-// Import console.log from web-sys
-use web_sys::console;
-
-// A macro to provide `eprintln!(..)`-style syntax for `console.log` logging.
-macro_rules! log {
-    ( $( $t:tt )* ) => {
-        console::log_1(&format!( $( $t )* ).into());
-    }
-}
-
-#[wasm_bindgen]
-extern "C" {
-    fn alert(s: &str);
-}
-
-/// Greet a user with a message from Rust
-#[wasm_bindgen]
-pub fn greet(name: &str) {
-    alert(&format!("Hello, {}! You've been greeted from Rust!", name));
-}
-
-/// Add two numbers and return the result
-#[wasm_bindgen]
-pub fn add(a: i32, b: i32) -> i32 {
-    log!("Adding {} + {}", a, b);
-    a + b
-}
-
-/// Calculate the nth Fibonacci number
-#[wasm_bindgen]
-pub fn fibonacci(n: i32) -> i32 {
-    if n <= 1 {
-        n
-    } else {
-        fibonacci(n - 1) + fibonacci(n - 2)
-    }
-}
-
-/// Get the current Rust version
-#[wasm_bindgen]
-pub fn get_rust_version() -> String {
-    env!("CARGO_PKG_VERSION").to_string()
-}
-"#;
-
-        std::fs::write("src/lib.rs", lib_rs)?;
+        std::fs::write("src/lib.rs", cpm::templates::WASM_LIB_TEMPLATE)?;
 
         // Create pkg directory
         std::fs::create_dir_all("pkg")?;
@@ -347,20 +232,16 @@ pub fn get_rust_version() -> String {
         let mut package_json: serde_json::Value = serde_json::from_str(&package_json_content)?;
 
         // Add WASM dependency
-        if let Some(dependencies) = package_json.get_mut("dependencies") {
-            if let Some(deps_obj) = dependencies.as_object_mut() {
-                deps_obj.insert(
-                    "wasm-bindgen".to_string(),
-                    serde_json::Value::String("^0.2".to_string()),
+        if !package_json.get("dependencies").is_some_and(|d| d.is_object()) {
+            package_json["dependencies"] = serde_json::Value::Object(serde_json::Map::new());
+        }
+        if let Some(dependencies) = package_json["dependencies"].as_object_mut() {
+            for (name, range) in cpm::templates::WASM_NPM_DEPENDENCIES {
+                dependencies.insert(
+                    name.to_string(),
+                    serde_json::Value::String(range.to_string()),
                 );
             }
-        } else {
-            let mut dependencies = serde_json::Map::new();
-            dependencies.insert(
-                "wasm-bindgen".to_string(),
-                serde_json::Value::String("^0.2".to_string()),
-            );
-            package_json["dependencies"] = serde_json::Value::Object(dependencies);
         }
 
         // Write back to package.json
@@ -615,6 +496,18 @@ impl CliCommand for AddCommand {
             .about("Add a package (delegates to npm install)")
             .arg(clap::Arg::new("packages").required(true).num_args(1..))
             .arg(clap::Arg::new("save_dev").short('D').long("save-dev").action(clap::ArgAction::SetTrue))
+            .arg(
+                clap::Arg::new("ignore_scripts")
+                    .long("ignore-scripts")
+                    .help("Do not run any lifecycle scripts")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("allow")
+                    .long("allow")
+                    .help("Override a policy entry (package name or rule)")
+                    .action(clap::ArgAction::Append),
+            )
     }
 
     fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
@@ -630,20 +523,44 @@ impl CliCommand for AddCommand {
             eprintln!("Usage: cpm add <package> [packages...]");
             return Ok(());
         }
+        let overrides: Vec<String> = matches.get_many::<String>("allow").unwrap_or_default().cloned().collect();
+        let specs: Vec<String> = packages.iter().map(|s| (*s).clone()).collect();
+        cpm::policy::enforce_additions(std::path::Path::new("."), &specs, &overrides)?;
         let save_dev = matches.get_flag("save_dev");
+        let ignore_scripts = matches.get_flag("ignore_scripts");
         let npm_cmd = if cfg!(target_os = "windows") { "npm.cmd" } else { "npm" };
+        // Same rules as `cpm install`: with an allow-list only approved scripts run
+        let config = cpm::config::CpmConfig::load(std::path::Path::new("."))?;
+        // Only packages this add installs or changes get their scripts run
+        let before = match (&config.install_scripts, ignore_scripts) {
+            (Some(_), false) => cpm::lifecycle::packages_with_install_scripts(std::path::Path::new("."))?,
+            _ => Vec::new(),
+        };
         let mut args = vec!["install".to_string()];
         if save_dev {
             args.push("--save-dev".to_string());
         }
+        if ignore_scripts || config.install_scripts.is_some() {
+            args.push("--ignore-scripts".to_string());
+        }
         args.extend(packages.iter().map(|s| (*s).clone()));
         let status = std::process::Command::new(npm_cmd).args(&args).status()?;
         if !status.success() {
             return Err(CliError::ExecutionError {
-                command: format!("npm install {}", packages.join(" ")),
+                command: format!("npm install {}", packages.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ")),
                 message: "npm install failed".to_string(),
             });
         }
+        if ignore_scripts {
+            eprintln!("⏭️  Lifecycle scripts skipped (--ignore-scripts)");
+        } else if let Some(allow_list) = &config.install_scripts {
+            let added: Vec<cpm::lifecycle::ScriptPackage> =
+                cpm::lifecycle::packages_with_install_scripts(std::path::Path::new("."))?
+                    .into_iter()
+                    .filter(|package| !before.contains(package))
+                    .collect();
+            InstallCommand.run_approved_scripts(npm_cmd, allow_list, added)?;
+        }
         eprintln!("Packages added successfully.");
         Ok(())
     }
@@ -657,9 +574,17 @@ impl CliCommand for LockCommand {
     fn build_clap_command(&self) -> clap::Command {
         clap::Command::new("lock")
             .about("Update lockfiles (package-lock.json, Cargo.lock)")
+            .arg(
+                clap::Arg::new("allow")
+                    .long("allow")
+                    .help("Override a policy entry (package name or rule)")
+                    .action(clap::ArgAction::Append),
+            )
     }
 
-    fn execute(&self, _context: &mut CliContext, _matches: &ArgMatches) -> CliResult<()> {
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let overrides: Vec<String> = matches.get_many::<String>("allow").unwrap_or_default().cloned().collect();
+        cpm::policy::enforce_project(std::path::Path::new("."), &overrides, true)?;
         eprintln!("Updating lockfiles...");
         if std::path::Path::new("package.json").exists() {
             let npm_cmd = if cfg!(target_os = "windows") { "npm.cmd" } else { "npm" };
@@ -723,7 +648,7 @@ impl CliCommand for RemoveCommand {
         let status = std::process::Command::new(npm_cmd).args(&args).status()?;
         if !status.success() {
             return Err(CliError::ExecutionError {
-                command: format!("npm uninstall {}", packages.join(" ")),
+                command: format!("npm uninstall {}", packages.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(" ")),
                 message: "npm uninstall failed".to_string(),
             });
         }
@@ -739,51 +664,178 @@ impl CliCommand for NpxCommand {
 
     fn build_clap_command(&self) -> clap::Command {
         clap::Command::new("npx")
-            .about("Execute packages using npx")
+            .about("Run a package binary, installing it into the cpm cache when needed")
+            .trailing_var_arg(true)
             .arg(
                 clap::Arg::new("package")
-                    .help("Package to execute")
+                    .short('p')
+                    .long("package")
+                    .value_name("SPEC")
+                    .action(clap::ArgAction::Append)
+                    .help("Package to install; the command is then the binary to run"),
+            )
+            .arg(
+                clap::Arg::new("yes")
+                    .short('y')
+                    .long("yes")
+                    .help("Install missing packages without asking")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("allow")
+                    .long("allow")
+                    .help("Override a policy entry (package name or rule)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                clap::Arg::new("command")
+                    .help("Package to execute, like create-foo@1.2, or binary with --package")
                     .index(1),
             )
             .arg(
                 clap::Arg::new("args")
                     .help("Arguments to pass to the package")
+                    .index(2)
                     .num_args(0..)
-                    .last(true),
+                    .allow_hyphen_values(true),
             )
     }
 
     fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
-        let package = matches.get_one::<String>("package");
-        let args: Vec<&String> = matches
+        use cpm::exec::{Install, Spec};
+        use std::io::IsTerminal;
+
+        let Some(command) = matches.get_one::<String>("command") else {
+            eprintln!("❌ No package specified for npx");
+            eprintln!("💡 Usage: cpm npx [--package <spec>] <package> [args...]");
+            return Ok(());
+        };
+        let args: Vec<String> = matches
             .get_many::<String>("args")
             .unwrap_or_default()
+            .cloned()
+            .collect();
+        let packages: Vec<Spec> = matches
+            .get_many::<String>("package")
+            .unwrap_or_default()
+            .map(|spec| Spec::parse(spec))
             .collect();
+        // Without --package, the command names the package to run
+        let (specs, bin) = if packages.is_empty() {
+            (vec![Spec::parse(command)], None)
+        } else {
+            (packages, Some(command.as_str()))
+        };
+        let name = bin.unwrap_or(specs[0].default_bin()).to_string();
 
-        if let Some(pkg) = package {
-            eprintln!("🦀 CPM detected npx command for package: {pkg}");
-            eprintln!("📦 Executing with npx...");
+        // 1. A local binary, unless a version or source was asked for
+        if specs.iter().all(Spec::accepts_local) {
+            if let Some(path) = cpm::exec::resolve_local(std::path::Path::new("."), &name)? {
+                eprintln!("📦 Using local {}", name);
+                return run_bin(&name, &path, &args, None);
+            }
+        }
 
-            // Build the npx command
-            let mut npx_cmd = std::process::Command::new("npx");
-            npx_cmd.arg(pkg);
-            npx_cmd.args(&args);
+        // 2. A cached install, made or refreshed as needed
+        let cache = cpm::exec::cache_dir().ok_or_else(|| CliError::InternalError {
+            message: format!("No cache directory, set {}", cpm::exec::CACHE_ENV),
+        })?;
+        // Packages from the cache are held to the same policy as `cpm add`
+        let root = std::path::Path::new(".");
+        let overrides: Vec<String> = matches.get_many::<String>("allow").unwrap_or_default().cloned().collect();
+        let spec_args: Vec<String> = specs.iter().map(ToString::to_string).collect();
+        cpm::policy::enforce_additions(root, &spec_args, &overrides)?;
+        let config = cpm::config::CpmConfig::load(root)?;
+
+        let install = Install::new(&cache, specs);
+        let list = install
+            .specs
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if install.is_reusable() {
+            eprintln!("⚡ Using cached {}", list);
+        } else {
+            if !install.exists() && !matches.get_flag("yes") && std::io::stdin().is_terminal() {
+                eprintln!("📦 Need to install: {}", list);
+                eprintln!("   Ok to proceed? (y/N): ");
+                let mut input = String::new();
+                std::io::stdin().read_line(&mut input)?;
+                if !input.trim().to_lowercase().starts_with('y') {
+                    eprintln!("❌ Operation cancelled");
+                    return Ok(());
+                }
+            }
+            eprintln!("📦 Installing {}...", list);
+            match install.install(config.install_scripts.as_ref()) {
+                Ok(skipped) if !skipped.is_empty() => {
+                    let mut names: Vec<&str> = skipped.iter().map(|p| p.name.as_str()).collect();
+                    names.dedup();
+                    eprintln!("⏭️  Install scripts not run for: {}", names.join(", "));
+                    eprintln!("💡 Add them to [install-scripts] allow in cpm.toml to run them");
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    if !install.exists() {
+                        return Err(e);
+                    }
+                    eprintln!("⚠️  {}", e);
+                    eprintln!("💡 Using the previous install of {}", list);
+                }
+            }
+        }
 
-            // Execute the command
-            let status = npx_cmd.status()?;
+        let path = install.bin(bin).ok_or_else(|| CliError::ExecutionError {
+            command: format!("npx {}", command),
+            message: format!("{} provides no binary named '{}'", list, name),
+        })?;
+        run_bin(&name, &path, &args, Some(&install.bin_dir()))
+    }
+}
 
-            if !status.success() {
-                return Err(CliError::ExecutionError {
-                    command: format!("npx {pkg}"),
-                    message: "npx command failed".to_string(),
-                });
-            }
-        } else {
-            eprintln!("❌ No package specified for npx");
-            eprintln!("💡 Usage: cpm npx <package> [args...]");
+impl CliCommand for ExecCommand {
+    fn name(&self) -> &'static str {
+        "exec"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("exec")
+            .about("Run a binary from node_modules/.bin or a workspace package, without npx")
+            .trailing_var_arg(true)
+            .arg(clap::Arg::new("bin").help("Binary to run").required(true).index(1))
+            .arg(
+                clap::Arg::new("args")
+                    .help("Arguments to pass to the binary")
+                    .index(2)
+                    .num_args(0..)
+                    .allow_hyphen_values(true),
+            )
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let name = matches.get_one::<String>("bin").unwrap();
+        let args: Vec<String> = matches
+            .get_many::<String>("args")
+            .unwrap_or_default()
+            .cloned()
+            .collect();
+        let bins = cpm::exec::local_bins(std::path::Path::new("."))?;
+        if let Some((_, path)) = bins.iter().find(|(bin, _)| bin == name) {
+            return run_bin(name, path, &args, None);
         }
 
-        Ok(())
+        eprintln!("❌ No local binary named '{}'", name);
+        let suggestions = cpm::fuzzy::closest_matches(name, bins.iter().map(|(bin, _)| bin.as_str()), 2);
+        if !suggestions.is_empty() {
+            let suggestions: Vec<&str> = suggestions.into_iter().take(3).collect();
+            eprintln!("💡 Did you mean: {}?", suggestions.join(", "));
+        }
+        eprintln!("💡 Run 'cpm npx {}' to install and run it", name);
+        Err(CliError::ExecutionError {
+            command: format!("exec {}", name),
+            message: "Binary not found".to_string(),
+        })
     }
 }
 
@@ -795,9 +847,32 @@ impl CliCommand for PublishCommand {
     fn build_clap_command(&self) -> clap::Command {
         clap::Command::new("publish")
             .about("Publish package to registry (delegates to npm publish)")
+            .arg(clap::Arg::new("tag").long("tag").help("Dist-tag to publish under (default: latest)"))
+            .arg(
+                clap::Arg::new("access")
+                    .long("access")
+                    .value_parser(["public", "restricted"])
+                    .help("Access level for scoped packages"),
+            )
+            .arg(clap::Arg::new("registry").long("registry").help("Registry URL to publish to"))
+            .arg(clap::Arg::new("otp").long("otp").help("One-time password for two-factor authentication"))
+            .arg(
+                clap::Arg::new("dry_run")
+                    .long("dry-run")
+                    .help("Run the checks and build the tarball without publishing")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("test")
+                    .long("test")
+                    .help("Run the test suite as part of the pre-publish checks")
+                    .action(clap::ArgAction::SetTrue),
+            )
     }
 
-    fn execute(&self, _context: &mut CliContext, _matches: &ArgMatches) -> CliResult<()> {
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        use cpm::publish::{CheckResult, CheckStatus};
+
         if !std::path::Path::new("package.json").exists() {
             return Err(CliError::FileOperationError {
                 operation: "publish".to_string(),
@@ -805,484 +880,2576 @@ impl CliCommand for PublishCommand {
                 message: "Not in a project. Run 'cpm init' first.".to_string(),
             });
         }
+        let root = std::path::Path::new(".");
+        let manifest: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("package.json")?)?;
+        let registry = matches.get_one::<String>("registry");
+        let dry_run = matches.get_flag("dry_run");
+        // Before the scripts, whose build output may not be committed
+        let git_clean = cpm::publish::check_git_clean(root);
+        // npm runs these when it packs the folder itself, but not for a tarball
+        run_lifecycle(root, cpm::script::PREPUBLISH_EVENTS)?;
+        let plan = cpm::pack::plan(root)?;
+
+        eprintln!("📋 Pre-publish checks for {}@{}", plan.name, plan.version);
+        let tests = if matches.get_flag("test") {
+            cpm::publish::check_tests(root, &manifest)
+        } else {
+            CheckResult {
+                name: "Tests pass",
+                status: CheckStatus::Skipped,
+                detail: "pass --test to run them".to_string(),
+            }
+        };
+        let checks = [
+            git_clean,
+            cpm::publish::check_version_unpublished(
+                &plan.name,
+                &plan.version,
+                registry.map(String::as_str),
+            ),
+            cpm::publish::check_build_output(root),
+            tests,
+            cpm::publish::check_secrets(root, &plan),
+        ];
+        for check in &checks {
+            eprintln!("   {} {}: {}", check.status.symbol(), check.name, check.detail);
+        }
+        let failed = checks
+            .iter()
+            .filter(|c| c.status == CheckStatus::Failed)
+            .count();
+        if failed > 0 {
+            return Err(CliError::ExecutionError {
+                command: "cpm publish".to_string(),
+                message: format!("{} pre-publish check(s) failed", failed),
+            });
+        }
+
+        let tarball = cpm::pack::build_tarball(root, &plan)?;
+        // Often undoes what prepack did, so it runs for dry runs too
+        run_lifecycle(root, cpm::script::POSTPACK_EVENTS)?;
+        let tag = matches.get_one::<String>("tag").map(String::as_str).unwrap_or("latest");
+        if dry_run {
+            eprintln!(
+                "💡 Dry run: would publish {} ({}, {} files) with tag '{}'",
+                plan.tarball_name(),
+                cpm::pack::format_size(tarball.bytes.len() as u64),
+                plan.entries.len(),
+                tag
+            );
+            return Ok(());
+        }
+
+        // Publish exactly the tarball cpm pack would produce
+        let staging = std::env::temp_dir().join(format!("cpm-publish-{}", std::process::id()));
+        std::fs::create_dir_all(&staging)?;
+        let tarball_path = staging.join(plan.tarball_name());
+        std::fs::write(&tarball_path, &tarball.bytes)?;
+
         let npm_cmd = if cfg!(target_os = "windows") { "npm.cmd" } else { "npm" };
-        let status = std::process::Command::new(npm_cmd)
-            .arg("publish")
-            .status()?;
-        if !status.success() {
+        let mut command = std::process::Command::new(npm_cmd);
+        command.arg("publish").arg(&tarball_path).args(["--tag", tag]);
+        for option in ["access", "registry", "otp"] {
+            if let Some(value) = matches.get_one::<String>(option) {
+                command.arg(format!("--{}", option)).arg(value);
+            }
+        }
+        let status = command.status();
+        let _ = std::fs::remove_dir_all(&staging);
+        if !status?.success() {
             return Err(CliError::ExecutionError {
                 command: "npm publish".to_string(),
                 message: "Publish failed".to_string(),
             });
         }
-        eprintln!("Package published successfully.");
-        Ok(())
+        eprintln!("✅ Published {}@{} ({})", plan.name, plan.version, tag);
+        run_lifecycle(root, cpm::script::POSTPUBLISH_EVENTS)
     }
 }
 
-impl CliCommand for WorkspaceCommand {
-    fn name(&self) -> &'static str {
-        "workspace"
+/// ANSI colors cycled through for the prefixes of `cpm run -p`
+const PREFIX_COLORS: [&str; 6] = ["36", "35", "33", "32", "34", "31"];
+
+/// Whether to color output on stderr: a terminal, and `NO_COLOR` unset
+fn use_color() -> bool {
+    use std::io::IsTerminal;
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Print a summary table of finished jobs, one row per name
+fn print_job_summary<'a, I>(header: &str, jobs: I)
+where
+    I: IntoIterator<Item = (&'a str, &'a cpm::parallel::JobResult)>,
+{
+    use cpm::parallel::JobStatus;
+
+    let rows: Vec<[String; 3]> = jobs
+        .into_iter()
+        .map(|(name, result)| {
+            let duration = cpm::parallel::format_duration(result.duration);
+            let (status, duration) = match &result.status {
+                JobStatus::Passed => ("✅ passed".to_string(), duration),
+                JobStatus::Failed(Some(code)) => (format!("❌ failed (exit {})", code), duration),
+                JobStatus::Failed(None) => ("❌ failed".to_string(), duration),
+                JobStatus::Skipped(reason) => (format!("⏭️  skipped: {}", reason), "-".to_string()),
+                JobStatus::Stopped => ("🛑 stopped".to_string(), duration),
+            };
+            [name.to_string(), status, duration]
+        })
+        .collect();
+    eprintln!();
+    eprintln!("📋 Summary");
+    print_table([header, "STATUS", "DURATION"], &rows);
+}
+
+/// Print rows as left-aligned columns on stderr
+fn print_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) {
+    let mut widths = header.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let format_row = |cells: [&str; N]| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    eprintln!("{}", format_row(header));
+    for row in rows {
+        eprintln!("{}", format_row(row.each_ref().map(String::as_str)));
     }
+}
 
-    fn build_clap_command(&self) -> clap::Command {
-        clap::Command::new("workspace")
-            .about("List workspace packages (npm workspaces)")
-            .arg(clap::Arg::new("ls").short('l').long("ls").action(clap::ArgAction::SetTrue))
+/// `--filter` for commands that can be limited to some workspace packages
+fn filter_arg() -> clap::Arg {
+    clap::Arg::new("filter")
+        .short('F')
+        .long("filter")
+        .value_name("SELECTOR")
+        .help(
+            "Only workspace packages matching the selector \
+             (name glob, ./path, ...pkg, pkg..., [git-ref])",
+        )
+        .action(clap::ArgAction::Append)
+}
+
+/// The workspace graph and the packages selected by `--filter`, dependencies first
+///
+/// Returns `None` when no filter was given.
+fn filtered_packages(
+    matches: &ArgMatches,
+) -> CliResult<Option<(cpm::graph::WorkspaceGraph, Vec<usize>)>> {
+    let filters: Vec<String> = matches
+        .get_many::<String>("filter")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    if filters.is_empty() {
+        return Ok(None);
+    }
+    let root = std::path::Path::new(".");
+    if !cpm::workspace::is_workspace(root)? {
+        return Err(CliError::ExecutionError {
+            command: format!("--filter {}", filters.join(" --filter ")),
+            message: "No workspaces configured".to_string(),
+        });
     }
+    let graph = cpm::graph::WorkspaceGraph::load(root)?;
+    let selected = cpm::filter::select(root, &graph, &filters)?;
+    let order = graph
+        .topological_order()?
+        .into_iter()
+        .filter(|idx| selected.contains(idx))
+        .collect();
+    Ok(Some((graph, order)))
+}
 
-    fn execute(&self, _context: &mut CliContext, _matches: &ArgMatches) -> CliResult<()> {
-        if !std::path::Path::new("package.json").exists() {
-            return Err(CliError::FileOperationError {
-                operation: "read package.json".to_string(),
-                path: "package.json".to_string(),
-                message: "Not in a project. Run 'cpm init' first.".to_string(),
-            });
-        }
-        let content = std::fs::read_to_string("package.json")?;
-        let pkg: serde_json::Value = serde_json::from_str(&content)?;
-        let workspaces = pkg.get("workspaces");
-        match workspaces {
-            Some(serde_json::Value::Array(arr)) => {
-                eprintln!("Workspace packages:");
-                for w in arr {
-                    if let Some(s) = w.as_str() {
-                        eprintln!("  - {}", s);
-                    }
+/// Run `cpm <command>` in each selected package, dependencies first
+fn run_in_filtered_packages(
+    command: &str,
+    graph: &cpm::graph::WorkspaceGraph,
+    selected: &[usize],
+) -> CliResult<()> {
+    if selected.is_empty() {
+        eprintln!("⚠️  No workspace packages match the filter");
+        return Ok(());
+    }
+    eprintln!("🚀 Running 'cpm {}' in {} package(s)", command, selected.len());
+    let exe = std::env::current_exe()?;
+    let schedule = cpm::parallel::Schedule { concurrency: 1, bail: true };
+    let label = format!("cpm {}", command);
+    WorkspaceCommand.run_in_packages(&label, graph, selected, schedule, |member| {
+        let mut child = std::process::Command::new(&exe);
+        child.arg(command).current_dir(&member.dir);
+        child
+    })
+}
+
+/// Run a `cpm.toml` task after its dependencies, replaying cached results where possible
+fn run_task(dir: &std::path::Path, name: &str, args: &[String]) -> CliResult<()> {
+    use std::io::Write;
+
+    let nodes = cpm::task::plan(dir, name, args)?;
+    let config = cpm::config::CpmConfig::load(dir)?;
+    let remote = cpm::cache::remote::RemoteCache::configured(config.cache.as_ref())?;
+    let root = nodes.last().map(|node| node.package.clone()).unwrap_or_default();
+    let mut fingerprints: Vec<Option<String>> = Vec::with_capacity(nodes.len());
+    let (mut hits, mut misses) = (0, 0);
+    for node in &nodes {
+        // Tasks of other workspace packages are shown as `package#task`
+        let prefix = if node.package == root {
+            String::new()
+        } else {
+            format!("{}#", node.package)
+        };
+        let label = format!("{}{}", prefix, node.name);
+        let Some(task) = node.task.as_ref().filter(|t| t.is_cached() && !node.steps.is_empty()) else {
+            run_task_steps(node, &prefix, false)?;
+            fingerprints.push(None);
+            continue;
+        };
+
+        let upstream: Vec<String> = node
+            .dependencies
+            .iter()
+            .filter_map(|idx| fingerprints[*idx].clone())
+            .collect();
+        let fingerprint = cpm::task::fingerprint(&node.dir, task, &node.steps, &upstream)?;
+        let short = &fingerprint[..12];
+        let mut source = "cache";
+        let mut restored = cpm::cache::restore(&node.dir, &fingerprint)?;
+        if let (None, Some(remote)) = (&restored, &remote) {
+            match remote.get(&fingerprint) {
+                Ok(Some(artifact)) => {
+                    cpm::cache::unpack(&node.dir, &fingerprint, &artifact)?;
+                    restored = cpm::cache::restore(&node.dir, &fingerprint)?;
+                    source = "remote cache";
                 }
+                Ok(None) => {}
+                Err(e) => eprintln!("⚠️  {}", e),
             }
-            Some(serde_json::Value::Object(obj)) => {
-                if let Some(arr) = obj.get("packages").and_then(|v| v.as_array()) {
-                    eprintln!("Workspace packages:");
-                    for w in arr {
-                        if let Some(s) = w.as_str() {
-                            eprintln!("  - {}", s);
-                        }
-                    }
+        }
+        if let Some(captured) = restored {
+            eprintln!("⚡ {} › {} hit {}, replaying output", label, source, short);
+            std::io::stdout().write_all(&captured.stdout)?;
+            std::io::stderr().write_all(&captured.stderr)?;
+            hits += 1;
+        } else {
+            let captured = run_task_steps(node, &prefix, true)?;
+            let stored = cpm::cache::store(&node.dir, &fingerprint, &task.outputs, &captured)?;
+            eprintln!("💾 {} › cached {} output file(s) as {}", label, stored, short);
+            if let Some(remote) = &remote {
+                let artifact = cpm::cache::pack(&node.dir, &fingerprint)?;
+                match remote.put(&fingerprint, &artifact) {
+                    Ok(()) => eprintln!("☁️  {} › uploaded to the remote cache", label),
+                    Err(e) => eprintln!("⚠️  {}", e),
                 }
             }
-            _ => eprintln!("No workspaces configured. Add \"workspaces\": [\"packages/*\"] to package.json."),
+            misses += 1;
         }
-        Ok(())
+        fingerprints.push(Some(fingerprint));
+    }
+    if hits + misses > 0 {
+        eprintln!("✅ {} cached task(s): {} hit, {} run", hits + misses, hits, misses);
     }
+    Ok(())
 }
 
-impl CliCommand for InstallCommand {
-    fn name(&self) -> &'static str {
-        "install"
+/// Run the steps of one task graph node, keeping its output when `capture` is set
+fn run_task_steps(
+    node: &cpm::task::TaskNode,
+    prefix: &str,
+    capture: bool,
+) -> CliResult<cpm::script::Captured> {
+    let package = cpm::script::Package::at(&node.dir)?;
+    let shell = cpm::script::ScriptShell::for_package(&node.dir)?;
+    let icon = if node.task.is_some() { "🧩" } else { "📜" };
+    let mut captured = cpm::script::Captured::default();
+    for step in &node.steps {
+        eprintln!("{} {}{} › {}", icon, prefix, step.event, step.command_line(shell));
+        if capture {
+            let output = step.run_captured(&package, shell)?;
+            captured.stdout.extend(output.stdout);
+            captured.stderr.extend(output.stderr);
+        } else {
+            step.run(&package, shell)?;
+        }
     }
+    Ok(captured)
+}
 
-    fn build_clap_command(&self) -> clap::Command {
-        clap::Command::new("install")
-            .about("Install dependencies (supports npm workspaces)")
+/// Build a single-file Rust script unless its binary is cached, then run it
+fn run_rust_script(path: &std::path::Path, args: &[String]) -> CliResult<()> {
+    let content = std::fs::read_to_string(path)?;
+    let cache = cpm::exec::cache_dir().ok_or_else(|| CliError::InternalError {
+        message: format!("No cache directory, set {}", cpm::exec::CACHE_ENV),
+    })?;
+    let script_crate = cpm::rust_script::ScriptCrate::new(&cache, path, &content);
+    if !script_crate.is_built() {
+        let script = cpm::rust_script::Script::parse(&content).map_err(|message| {
+            CliError::FileOperationError {
+                operation: "read embedded manifest".to_string(),
+                path: path.display().to_string(),
+                message,
+            }
+        })?;
+        eprintln!("🦀 Compiling {}...", path.display());
+        script_crate.build(&script)?;
     }
 
-    fn execute(&self, _context: &mut CliContext, _matches: &ArgMatches) -> CliResult<()> {
-        eprintln!("📦 Installing dependencies...");
-
-        // Check if we're in a JavaScript project
-        if std::path::Path::new("package.json").exists() {
-            eprintln!("🟨 Installing JavaScript dependencies with npm...");
-
-            // Try npm.cmd on Windows first, then npm
-            let npm_cmd = if cfg!(target_os = "windows") {
-                "npm.cmd"
-            } else {
-                "npm"
-            };
-
-            let npm_output = std::process::Command::new(npm_cmd)
-                .args(["install"])
-                .output()?;
+    let status = std::process::Command::new(script_crate.binary())
+        .args(args)
+        .status()
+        .map_err(|e| CliError::ExecutionError {
+            command: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+    if !status.success() {
+        return Err(CliError::ScriptFailed {
+            script: path.display().to_string(),
+            code: status.code().unwrap_or(1),
+        });
+    }
+    Ok(())
+}
 
-            if !npm_output.status.success() {
-                return Err(CliError::ExecutionError {
-                    command: "npm install".to_string(),
-                    message: String::from_utf8_lossy(&npm_output.stderr).to_string(),
-                });
-            }
-            eprintln!("✅ JavaScript dependencies installed!");
+/// Run a package binary with the project's bin directories, and `extra`, on `PATH`
+fn run_bin(
+    name: &str,
+    path: &std::path::Path,
+    args: &[String],
+    extra: Option<&std::path::Path>,
+) -> CliResult<()> {
+    let mut paths: Vec<std::path::PathBuf> = extra.map(std::path::Path::to_path_buf).into_iter().collect();
+    paths.extend(std::env::split_paths(&cpm::script::search_path(std::path::Path::new("."))?));
+    let search_path = std::env::join_paths(paths).map_err(|e| CliError::InternalError {
+        message: format!("cannot build PATH: {}", e),
+    })?;
+
+    // `bin` entries of workspace packages may be plain, non-executable scripts
+    let is_js = path
+        .extension()
+        .is_some_and(|extension| ["js", "mjs", "cjs"].iter().any(|js| extension == *js));
+    let mut command = if is_js {
+        let mut command = std::process::Command::new("node");
+        command.arg(path);
+        command
+    } else {
+        std::process::Command::new(path)
+    };
+    let status = command.args(args).env("PATH", search_path).status().map_err(|e| {
+        CliError::ExecutionError {
+            command: name.to_string(),
+            message: e.to_string(),
         }
+    })?;
+    if !status.success() {
+        return Err(CliError::ScriptFailed {
+            script: name.to_string(),
+            code: status.code().unwrap_or(1),
+        });
+    }
+    Ok(())
+}
 
-        // Check if we're in a Rust project
-        if std::path::Path::new("Cargo.toml").exists() {
-            eprintln!("🦀 Installing Rust dependencies with cargo...");
-            let cargo_output = std::process::Command::new("cargo").arg("build").output()?;
+/// `--runtime` choosing the JavaScript runtime
+fn runtime_arg() -> clap::Arg {
+    clap::Arg::new("runtime")
+        .long("runtime")
+        .value_name("NAME")
+        .help("JavaScript runtime to use: jetcrab, node, deno or bun (comma-separated to try several)")
+}
 
-            if !cargo_output.status.success() {
-                return Err(CliError::ExecutionError {
-                    command: "cargo build".to_string(),
-                    message: String::from_utf8_lossy(&cargo_output.stderr).to_string(),
-                });
-            }
-            eprintln!("✅ Rust dependencies installed!");
-        }
+/// The JavaScript runtime for the project in `dir`, from `--runtime` or
+/// `cpm.toml`, that satisfies `engines` and supports `mode`
+fn select_runtime(
+    dir: &std::path::Path,
+    engines: Option<&serde_json::Value>,
+    matches: &ArgMatches,
+    mode: cpm::runtime::Mode,
+) -> CliResult<cpm::runtime::Selected> {
+    let config = cpm::config::CpmConfig::load(dir)?;
+    let candidates = cpm::runtime::preference(
+        matches.get_one::<String>("runtime").map(String::as_str),
+        config.runtime.as_ref(),
+    )?;
+    let selected = cpm::runtime::select(candidates, engines, mode);
+    if selected.is_err() {
+        eprintln!("💡 Install one of these runtimes, or choose another with --runtime or [runtime] prefer in {}", cpm::config::CONFIG_FILE);
+    }
+    selected
+}
 
-        Ok(())
+/// Start `entry` with the selected runtime
+fn run_with_runtime(runtime: &cpm::runtime::Selected, entry: &str, args: &[String]) -> CliResult<()> {
+    eprintln!("{} Using {}...", runtime.runtime.icon(), runtime.describe());
+    let status = runtime.command(entry, args).status()?;
+    if !status.success() {
+        return Err(CliError::ScriptFailed {
+            script: entry.to_string(),
+            code: status.code().unwrap_or(1),
+        });
     }
+    Ok(())
 }
 
-impl CliCommand for BuildCommand {
-    fn name(&self) -> &'static str {
-        "build"
+/// Run the lifecycle `events` the package in `dir` defines, such as
+/// [`cpm::script::PREPUBLISH_EVENTS`] before packing it
+fn run_lifecycle(dir: &std::path::Path, events: &[&str]) -> CliResult<()> {
+    let package = cpm::script::Package::at(dir)?;
+    let shell = cpm::script::ScriptShell::for_package(dir)?;
+    for step in cpm::script::lifecycle(&package, events) {
+        eprintln!("📜 {} › {}", step.event, step.command_line(shell));
+        step.run(&package, shell)?;
     }
+    Ok(())
+}
 
-    fn build_clap_command(&self) -> clap::Command {
-        clap::Command::new("build").about("Build the project")
+/// `--mode` selecting the `.env.<mode>` files to load
+fn mode_arg(default: Option<&'static str>) -> clap::Arg {
+    let arg = clap::Arg::new("mode")
+        .long("mode")
+        .value_name("MODE")
+        .help("Also load .env.<mode> and .env.<mode>.local");
+    match default {
+        Some(default) => arg.default_value(default),
+        None => arg,
     }
+}
 
-    fn execute(&self, _context: &mut CliContext, _matches: &ArgMatches) -> CliResult<()> {
-        eprintln!("🔨 Building project...");
+/// Load the `.env` files of `dir` for `--mode` into the environment of the commands to run
+fn load_dotenv(dir: &std::path::Path, matches: &ArgMatches) -> CliResult<()> {
+    let mode = matches.get_one::<String>("mode").map(String::as_str);
+    let loaded = cpm::dotenv::apply(dir, mode)?;
+    if !loaded.is_empty() {
+        eprintln!("🌱 Loaded {}", loaded.join(", "));
+    }
+    Ok(())
+}
 
-        // Check if we're in a Rust project
-        if std::path::Path::new("Cargo.toml").exists() {
-            eprintln!("🦀 Building Rust project...");
-            let cargo_output = std::process::Command::new("cargo").arg("build").output()?;
+/// `--filter`, `--concurrency`, `--bail` and `--no-bail` for running a script in workspace packages
+fn workspace_run_args(command: clap::Command) -> clap::Command {
+    command
+        .arg(filter_arg())
+        .arg(
+            clap::Arg::new("concurrency")
+                .long("concurrency")
+                .value_name("N")
+                .help("Number of packages to run at once")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            clap::Arg::new("bail")
+                .long("bail")
+                .help("Stop starting packages after the first failure (default)")
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("no-bail"),
+        )
+        .arg(
+            clap::Arg::new("no-bail")
+                .long("no-bail")
+                .help("Keep running packages that do not depend on a failed one")
+                .action(clap::ArgAction::SetTrue)
+                .overrides_with("bail"),
+        )
+}
 
-            if !cargo_output.status.success() {
+impl WorkspaceCommand {
+    /// Run `script` in every workspace package that defines it, dependencies first
+    fn run_script(&self, script: &str, args: &[String], matches: &ArgMatches) -> CliResult<()> {
+        let root = std::path::Path::new(".");
+        let (graph, candidates) = match filtered_packages(matches)? {
+            Some(filtered) => filtered,
+            None if cpm::workspace::is_workspace(root)? => {
+                let graph = cpm::graph::WorkspaceGraph::load(root)?;
+                let order = graph.topological_order()?;
+                (graph, order)
+            }
+            None => {
                 return Err(CliError::ExecutionError {
-                    command: "cargo build".to_string(),
-                    message: String::from_utf8_lossy(&cargo_output.stderr).to_string(),
+                    command: format!("workspace run {}", script),
+                    message: "No workspaces configured".to_string(),
                 });
             }
+        };
+        let selected: Vec<usize> = candidates
+            .into_iter()
+            .filter(|idx| graph.members[*idx].scripts.iter().any(|s| s == script))
+            .collect();
+        if selected.is_empty() {
+            eprintln!("⚠️  No selected workspace package defines a '{}' script", script);
+            return Ok(());
+        }
 
-            // If wasm-pack is available, build WASM
-            if std::process::Command::new("wasm-pack")
-                .arg("--version")
-                .output()
-                .is_ok()
-            {
-                eprintln!("🌐 Building WebAssembly...");
-                let wasm_output = std::process::Command::new("wasm-pack")
-                    .args(["build", "--release", "--target", "web", "--out-dir", "pkg"])
-                    .output()?;
-
-                if !wasm_output.status.success() {
-                    eprintln!("⚠️  WASM build failed, but continuing...");
-                } else {
-                    eprintln!("✅ WebAssembly built successfully!");
+        let schedule = cpm::parallel::Schedule {
+            concurrency: *matches.get_one::<usize>("concurrency").unwrap_or(&1),
+            bail: !matches.get_flag("no-bail"),
+        };
+        eprintln!(
+            "🚀 Running '{}' in {} package(s) (parallel: {})",
+            script,
+            selected.len(),
+            schedule.concurrency.max(1)
+        );
+        let exe = std::env::current_exe()?;
+        let label = format!("workspace run {}", script);
+        self.run_in_packages(&label, &graph, &selected, schedule, |member| {
+            let mut command = std::process::Command::new(&exe);
+            command.args(["run", script]).current_dir(&member.dir);
+            if !args.is_empty() {
+                command.arg("--").args(args);
+            }
+            command
+        })
+    }
+
+    /// Run a command in each of the `selected` packages and print a summary table
+    ///
+    /// `selected` must be in dependency order; a package starts once the
+    /// selected packages it depends on have passed.
+    fn run_in_packages<F>(
+        &self,
+        label: &str,
+        graph: &cpm::graph::WorkspaceGraph,
+        selected: &[usize],
+        schedule: cpm::parallel::Schedule,
+        make_command: F,
+    ) -> CliResult<()>
+    where
+        F: Fn(&cpm::workspace::Member) -> std::process::Command + Sync,
+    {
+        use cpm::parallel::JobStatus;
+
+        let width = selected.iter().map(|idx| graph.members[*idx].name.len()).max().unwrap_or(0);
+        let results = cpm::parallel::run_jobs(&graph.restricted(selected), schedule, |job| {
+            let member = &graph.members[selected[job]];
+            let prefix = format!("{:<width$} │ ", member.name, width = width);
+            match cpm::parallel::run_prefixed(&mut make_command(member), &prefix) {
+                Ok(status) => JobStatus::from_exit(status),
+                Err(e) => {
+                    eprintln!("{}❌ {}", prefix, e);
+                    JobStatus::Failed(None)
                 }
             }
+        });
 
-            eprintln!("✅ Rust project built!");
+        print_job_summary(
+            "PACKAGE",
+            selected.iter().map(|idx| graph.members[*idx].name.as_str()).zip(&results),
+        );
+
+        let failed = results.iter().filter(|r| matches!(r.status, JobStatus::Failed(_))).count();
+        if failed > 0 {
+            return Err(CliError::ExecutionError {
+                command: label.to_string(),
+                message: format!("{} of {} package(s) failed", failed, selected.len()),
+            });
+        }
+        eprintln!("✅ {} passed in {} package(s)", label, selected.len());
+        Ok(())
+    }
+
+    /// Report inconsistent dependency ranges between workspace members, optionally fixing them
+    fn check(&self, fix: bool) -> CliResult<()> {
+        let root = std::path::Path::new(".");
+        if !cpm::workspace::is_workspace(root)? {
+            return Err(CliError::ExecutionError {
+                command: "workspace check".to_string(),
+                message: "No workspaces configured".to_string(),
+            });
+        }
+        let graph = cpm::graph::WorkspaceGraph::load(root)?;
+        eprintln!(
+            "🔍 Checking dependency ranges in {} workspace package(s)...",
+            graph.members.len()
+        );
+        let report = cpm::consistency::check(&graph);
+        if report.is_clean() {
+            eprintln!("✅ Dependency ranges are consistent");
+            return Ok(());
+        }
+
+        for mismatch in &report.mismatches {
+            let ecosystem = if mismatch.cargo { "crate" } else { "npm" };
+            eprintln!(
+                "⚠️  {} ({}) is required with different ranges:",
+                mismatch.name, ecosystem
+            );
+            let width = mismatch.uses.iter().map(|u| u.range.len()).max().unwrap_or(0);
+            for usage in &mismatch.uses {
+                let marker = if mismatch.highest.as_deref() == Some(usage.range.as_str()) {
+                    "  ← highest"
+                } else {
+                    ""
+                };
+                eprintln!(
+                    "    {:<width$}  {} ({}){}",
+                    usage.range,
+                    graph.members[usage.member].name,
+                    usage.kind.label(),
+                    marker,
+                    width = width
+                );
+            }
+        }
+        for unsatisfied in &report.unsatisfied {
+            eprintln!(
+                "❌ {} requires {}@{} ({}), but the workspace has {}",
+                graph.members[unsatisfied.usage.member].name,
+                graph.members[unsatisfied.sibling].name,
+                unsatisfied.usage.range,
+                unsatisfied.usage.kind.label(),
+                unsatisfied.version
+            );
+        }
+
+        if fix {
+            let changed = cpm::consistency::fix(&graph, &report)?;
+            for path in &changed {
+                eprintln!("📝 Updated {}", cpm::glob::relative_path(root, path));
+            }
+            let remaining = cpm::consistency::check(&cpm::graph::WorkspaceGraph::load(root)?);
+            if remaining.is_clean() {
+                eprintln!("✅ Dependency ranges aligned");
+                eprintln!("💡 Run 'cpm install' to update the lockfiles");
+                return Ok(());
+            }
+            return Err(CliError::ExecutionError {
+                command: "workspace check --fix".to_string(),
+                message: format!(
+                    "{} issue(s) could not be fixed automatically",
+                    remaining.mismatches.len() + remaining.unsatisfied.len()
+                ),
+            });
+        }
+        eprintln!("💡 Run 'cpm workspace check --fix' to align them");
+        Err(CliError::ExecutionError {
+            command: "workspace check".to_string(),
+            message: format!(
+                "{} mismatched range(s), {} unsatisfied workspace dependency(ies)",
+                report.mismatches.len(),
+                report.unsatisfied.len()
+            ),
+        })
+    }
+
+    /// Print the dependency graph between workspace members on stdout
+    fn graph(&self, format: &str) -> CliResult<()> {
+        use std::io::Write;
+
+        let root = std::path::Path::new(".");
+        if !cpm::workspace::is_workspace(root)? {
+            return Err(CliError::ExecutionError {
+                command: "workspace graph".to_string(),
+                message: "No workspaces configured".to_string(),
+            });
+        }
+        let graph = cpm::graph::WorkspaceGraph::load(root)?;
+        let rendered = match format {
+            "mermaid" => graph.to_mermaid(),
+            "json" => format!("{}\n", serde_json::to_string_pretty(&graph.to_json())?),
+            _ => graph.to_dot(),
+        };
+        write!(std::io::stdout().lock(), "{}", rendered)?;
+
+        let cycles = graph.cycles();
+        if cycles.is_empty() {
+            eprintln!(
+                "✅ {} package(s), {} dependency edge(s), no cycles",
+                graph.members.len(),
+                graph.edges().len()
+            );
+            return Ok(());
+        }
+        for cycle in &cycles {
+            eprintln!("❌ Dependency cycle: {}", graph.describe_cycle(cycle));
+        }
+        Err(CliError::ExecutionError {
+            command: "workspace graph".to_string(),
+            message: format!("{} dependency cycle(s) between workspace packages", cycles.len()),
+        })
+    }
+
+    /// Scaffold a new member, register it with the root manifests and wire dependents
+    fn new_member(&self, matches: &ArgMatches) -> CliResult<()> {
+        let root = std::path::Path::new(".");
+        let path = matches.get_one::<String>("path").unwrap().replace('\\', "/");
+        let path = path.trim_start_matches("./").trim_end_matches('/').to_string();
+        let ecosystem = if matches.get_flag("rust") {
+            cpm::workspace::Ecosystem::Rust
+        } else if matches.get_flag("hybrid") {
+            cpm::workspace::Ecosystem::Hybrid
         } else {
-            // It's a JavaScript project
-            eprintln!("📦 JavaScript project detected.");
-            eprintln!("🦀 Bundling with JetCrab Runtime...");
+            cpm::workspace::Ecosystem::JavaScript
+        };
+        let name = match matches.get_one::<String>("name") {
+            Some(name) => name.clone(),
+            None => path.rsplit('/').next().unwrap_or(&path).to_string(),
+        };
+        if path.is_empty() || name.is_empty() {
+            return Err(CliError::ExecutionError {
+                command: "workspace new".to_string(),
+                message: "Expected a member directory such as packages/ui".to_string(),
+            });
+        }
+        let member = cpm::scaffold::NewMember { path, name, ecosystem };
+
+        // Resolve --add-to before touching anything so typos don't leave a half-made member
+        let mut dependents = Vec::new();
+        let add_to: Vec<&String> = matches.get_many::<String>("add-to").unwrap_or_default().collect();
+        if !add_to.is_empty() {
+            let graph = cpm::graph::WorkspaceGraph::load(root)?;
+            for target in add_to {
+                let idx = graph.index_of(target).ok_or_else(|| CliError::ExecutionError {
+                    command: "workspace new".to_string(),
+                    message: format!("No workspace package named '{}'", target),
+                })?;
+                dependents.push(graph.members[idx].path.clone());
+            }
+        }
 
-            let mut entry_file = "index.js";
-            if std::path::Path::new("js/index.js").exists() {
-                entry_file = "js/index.js";
+        eprintln!("🚀 Creating {} package '{}' in {}", ecosystem.label(), member.name, member.path);
+        for file in cpm::scaffold::create(root, &member)? {
+            eprintln!("📝 Created {}", file.display());
+        }
+        for manifest in cpm::scaffold::register(root, &member)? {
+            eprintln!("📋 Registered in {}", manifest.display());
+        }
+        for dependent in &dependents {
+            for manifest in cpm::scaffold::add_as_dependency(root, &member, dependent)? {
+                eprintln!("🔗 Added '{}' to {}", member.name, manifest.display());
             }
-            
-            // Get project name from package.json
-            let mut output_name = "app.exe".to_string();
-            if let Ok(content) = std::fs::read_to_string("package.json") {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) {
-                    if let Some(name) = json["name"].as_str() {
-                        output_name = if cfg!(windows) {
-                            format!("{}.exe", name)
-                        } else {
-                            name.to_string()
-                        };
-                    }
+        }
+        eprintln!("✅ Workspace package '{}' created", member.name);
+        eprintln!("💡 Next steps:");
+        eprintln!("   cpm install");
+        eprintln!("   cpm workspace ls");
+        Ok(())
+    }
+
+    /// Print the workspace members as a table, or as JSON on stdout
+    fn list(&self, matches: &ArgMatches) -> CliResult<()> {
+        let json = matches.get_flag("json");
+        let root = std::path::Path::new(".");
+        if !cpm::workspace::is_workspace(root)? {
+            eprintln!("No workspaces configured. Add \"workspaces\": [\"packages/*\"] to package.json or [workspace] members to Cargo.toml.");
+            return Ok(());
+        }
+        let members = match filtered_packages(matches)? {
+            Some((graph, selected)) => {
+                let mut selected = selected;
+                selected.sort_unstable();
+                selected.into_iter().map(|idx| graph.members[idx].clone()).collect()
+            }
+            None => cpm::workspace::discover(root)?,
+        };
+        if json {
+            use std::io::Write;
+            writeln!(std::io::stdout().lock(), "{}", serde_json::to_string_pretty(&members)?)?;
+            return Ok(());
+        }
+        if members.is_empty() {
+            eprintln!("⚠️  No packages match the workspace patterns");
+            return Ok(());
+        }
+
+        let rows: Vec<[String; 5]> = members
+            .iter()
+            .map(|m| {
+                [
+                    m.name.clone(),
+                    m.version.clone().unwrap_or_else(|| "-".to_string()),
+                    m.path.clone(),
+                    if m.private { "yes" } else { "no" }.to_string(),
+                    m.ecosystem.label().to_string(),
+                ]
+            })
+            .collect();
+        eprintln!("📦 {} workspace package(s)", rows.len());
+        print_table(["NAME", "VERSION", "PATH", "PRIVATE", "ECOSYSTEM"], &rows);
+        Ok(())
+    }
+}
+
+impl CliCommand for WorkspaceCommand {
+    fn name(&self) -> &'static str {
+        "workspace"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        let json = clap::Arg::new("json")
+            .long("json")
+            .help("Print the packages as JSON")
+            .action(clap::ArgAction::SetTrue);
+        clap::Command::new("workspace")
+            .about("List workspace packages (npm workspaces and Cargo workspaces)")
+            .arg(clap::Arg::new("ls").short('l').long("ls").help("List packages (default)").action(clap::ArgAction::SetTrue))
+            .arg(json.clone())
+            .arg(filter_arg())
+            .subcommand(
+                clap::Command::new("list")
+                    .visible_alias("ls")
+                    .about("List workspace packages")
+                    .arg(json)
+                    .arg(filter_arg()),
+            )
+            .subcommand(
+                clap::Command::new("check")
+                    .about("Check that workspace packages agree on dependency ranges")
+                    .arg(
+                        clap::Arg::new("fix")
+                            .long("fix")
+                            .help("Align ranges to the highest one and to sibling versions")
+                            .action(clap::ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("graph")
+                    .about("Print the dependency graph between workspace packages")
+                    .arg(
+                        clap::Arg::new("format")
+                            .long("format")
+                            .help("Output format")
+                            .value_parser(["dot", "mermaid", "json"])
+                            .default_value("dot"),
+                    ),
+            )
+            .subcommand(
+                clap::Command::new("new")
+                    .about("Create a workspace package and register it with the workspace")
+                    .arg(clap::Arg::new("path").help("Package directory, e.g. packages/ui").required(true))
+                    .arg(
+                        clap::Arg::new("js")
+                            .long("js")
+                            .help("JavaScript package (default)")
+                            .action(clap::ArgAction::SetTrue),
+                    )
+                    .arg(
+                        clap::Arg::new("rust")
+                            .long("rust")
+                            .help("Rust crate")
+                            .action(clap::ArgAction::SetTrue),
+                    )
+                    .arg(
+                        clap::Arg::new("hybrid")
+                            .long("hybrid")
+                            .help("JavaScript package with a Rust/WASM crate")
+                            .action(clap::ArgAction::SetTrue),
+                    )
+                    .group(clap::ArgGroup::new("kind").args(["js", "rust", "hybrid"]))
+                    .arg(
+                        clap::Arg::new("name")
+                            .long("name")
+                            .help("Package name (defaults to the directory name)"),
+                    )
+                    .arg(
+                        clap::Arg::new("add-to")
+                            .long("add-to")
+                            .value_name("PACKAGE")
+                            .help("Add the new package as a dependency of this workspace package")
+                            .action(clap::ArgAction::Append),
+                    ),
+            )
+            .subcommand(workspace_run_args(
+                clap::Command::new("run")
+                    .about("Run a script in every package that defines it, dependencies first")
+                    .arg(clap::Arg::new("script").help("Script name").required(true).index(1))
+                    .arg(
+                        clap::Arg::new("args")
+                            .help("Arguments to pass to the script")
+                            .index(2)
+                            .num_args(0..)
+                            .last(true),
+                    ),
+            ))
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        match matches.subcommand() {
+            Some(("list", sub_matches)) => self.list(sub_matches),
+            Some(("check", sub_matches)) => self.check(sub_matches.get_flag("fix")),
+            Some(("graph", sub_matches)) => {
+                self.graph(sub_matches.get_one::<String>("format").unwrap())
+            }
+            Some(("new", sub_matches)) => self.new_member(sub_matches),
+            Some(("run", sub_matches)) => {
+                let script = sub_matches.get_one::<String>("script").unwrap();
+                let args: Vec<String> = sub_matches
+                    .get_many::<String>("args")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect();
+                self.run_script(script, &args, sub_matches)
+            }
+            _ => self.list(matches),
+        }
+    }
+}
+
+impl InstallCommand {
+    /// Run install scripts of the approved ones among `packages` after an
+    /// `--ignore-scripts` install
+    fn run_approved_scripts(
+        &self,
+        npm_cmd: &str,
+        config: &cpm::config::InstallScriptsConfig,
+        packages: Vec<cpm::lifecycle::ScriptPackage>,
+    ) -> CliResult<()> {
+        let groups = cpm::lifecycle::partition_by_approval(packages, config);
+
+        if !groups.allowed.is_empty() {
+            // name@version, so other installed versions are not rebuilt
+            let mut names: Vec<String> = groups
+                .allowed
+                .iter()
+                .map(|p| match p.version.as_str() {
+                    "" => p.name.clone(),
+                    version => format!("{}@{}", p.name, version),
+                })
+                .collect();
+            names.sort_unstable();
+            names.dedup();
+            eprintln!(
+                "🔧 Running install scripts for {} approved package(s)...",
+                names.len()
+            );
+            let rebuild_output = std::process::Command::new(npm_cmd)
+                .arg("rebuild")
+                .args(&names)
+                .output()?;
+            if !rebuild_output.status.success() {
+                return Err(CliError::ExecutionError {
+                    command: format!("npm rebuild {}", names.join(" ")),
+                    message: String::from_utf8_lossy(&rebuild_output.stderr).to_string(),
+                });
+            }
+        }
+
+        if !groups.denied.is_empty() {
+            eprintln!(
+                "🚫 Skipped install scripts of {} denied package(s)",
+                groups.denied.len()
+            );
+        }
+
+        if !groups.pending.is_empty() {
+            eprintln!(
+                "⚠️  {} package(s) have install scripts that were not run:",
+                groups.pending.len()
+            );
+            for package in &groups.pending {
+                let events: Vec<&str> = package.scripts.iter().map(|(e, _)| e.as_str()).collect();
+                eprintln!(
+                    "   - {}@{} ({})",
+                    package.name,
+                    package.version,
+                    events.join(", ")
+                );
+            }
+            eprintln!("💡 Run 'cpm approve-scripts' to review them");
+        }
+
+        Ok(())
+    }
+
+    /// Run the project's own lifecycle scripts for `events`, which
+    /// `--ignore-scripts` skipped as well
+    fn run_project_scripts(&self, npm_cmd: &str, events: &[&str]) -> CliResult<()> {
+        let package_json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string("package.json")?)?;
+        for event in events {
+            if package_json["scripts"].get(event).is_some() {
+                let status = std::process::Command::new(npm_cmd)
+                    .args(["run", event])
+                    .status()?;
+                if !status.success() {
+                    return Err(CliError::ExecutionError {
+                        command: format!("npm run {event}"),
+                        message: format!("Project {event} script failed"),
+                    });
                 }
             }
+        }
 
-            eprintln!("🔨 Bundling '{}' into '{}'...", entry_file, output_name);
+        Ok(())
+    }
+}
 
-            // Bundling Logic (Native)
-            let build_dir = std::path::Path::new("jetcrab_build");
-            if build_dir.exists() {
-                std::fs::remove_dir_all(build_dir)?;
+impl CliCommand for InstallCommand {
+    fn name(&self) -> &'static str {
+        "install"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("install")
+            .about("Install dependencies (supports npm workspaces)")
+            .arg(
+                clap::Arg::new("ignore_scripts")
+                    .long("ignore-scripts")
+                    .help("Do not run any lifecycle scripts")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("allow")
+                    .long("allow")
+                    .help("Override a policy entry (package name or rule)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(filter_arg())
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let ignore_scripts = matches.get_flag("ignore_scripts");
+        // With --filter, only the selected npm workspaces and crates are installed
+        let filtered = filtered_packages(matches)?;
+        let (npm_workspaces, crates): (Vec<String>, Vec<String>) = match &filtered {
+            Some((graph, selected)) => (
+                selected
+                    .iter()
+                    .map(|idx| &graph.members[*idx])
+                    .filter(|m| m.ecosystem != cpm::workspace::Ecosystem::Rust)
+                    .map(|m| m.name.clone())
+                    .collect(),
+                selected
+                    .iter()
+                    .filter_map(|idx| graph.members[*idx].crate_name.clone())
+                    .collect(),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        if let Some((_, selected)) = &filtered {
+            if selected.is_empty() {
+                eprintln!("⚠️  No workspace packages match the filter");
+                return Ok(());
             }
-            std::fs::create_dir_all(build_dir.join("src"))?;
+            eprintln!("🔎 {} workspace package(s) selected", selected.len());
+        }
+        let overrides: Vec<String> = matches
+            .get_many::<String>("allow")
+            .unwrap_or_default()
+            .cloned()
+            .collect();
+        cpm::policy::enforce_project(std::path::Path::new("."), &overrides, true)?;
+        let links = cpm::link::load(std::path::Path::new("."))?;
+        if !links.is_empty() {
+            let names: Vec<&str> = links.iter().map(|link| link.name.as_str()).collect();
+            eprintln!("⚠️  {} local link(s) active: {}", links.len(), names.join(", "));
+            eprintln!("💡 Run 'cpm link --list' to review or 'cpm unlink' to remove them");
+        }
+        eprintln!("📦 Installing dependencies...");
 
-            // Copy entry file
-            std::fs::copy(entry_file, build_dir.join("app.js"))?;
+        // Check if we're in a JavaScript project
+        if std::path::Path::new("package.json").exists()
+            && (filtered.is_none() || !npm_workspaces.is_empty())
+        {
+            eprintln!("🟨 Installing JavaScript dependencies with npm...");
 
-            // Write templates
-            // We need to determine the path to JetCrab library
-            // For development, we assume it's a sibling. For release, we might want to use git or registry.
-            // Here we prioritize JETCRAB_PATH env var, then sibling directory.
-            let current_dir = std::env::current_dir()?;
-            let jetcrab_path = std::env::var("JETCRAB_PATH")
-                .map(std::path::PathBuf::from)
-                .unwrap_or_else(|_| {
-                    // Try to find JetCrab relative to cpm executable or current dir?
-                    // Assuming we are in a workspace:
-                    // ../JetCrab
-                    current_dir.join("../JetCrab").canonicalize().unwrap_or(current_dir.join("../JetCrab"))
+            // Try npm.cmd on Windows first, then npm
+            let npm_cmd = if cfg!(target_os = "windows") {
+                "npm.cmd"
+            } else {
+                "npm"
+            };
+
+            // With an allow-list, scripts are skipped here and approved ones run afterwards
+            let config = cpm::config::CpmConfig::load(std::path::Path::new("."))?;
+            if !ignore_scripts && config.install_scripts.is_some() {
+                // npm runs the project's preinstall before installing anything
+                self.run_project_scripts(npm_cmd, &["preinstall"])?;
+            }
+            let mut npm_args = vec!["install"];
+            if ignore_scripts || config.install_scripts.is_some() {
+                npm_args.push("--ignore-scripts");
+            }
+            for name in &npm_workspaces {
+                npm_args.extend(["-w", name.as_str()]);
+            }
+
+            let npm_output = std::process::Command::new(npm_cmd)
+                .args(&npm_args)
+                .output()?;
+
+            if !npm_output.status.success() {
+                return Err(CliError::ExecutionError {
+                    command: npm_args.iter().fold("npm".to_string(), |c, a| c + " " + a),
+                    message: String::from_utf8_lossy(&npm_output.stderr).to_string(),
                 });
-            
-            let jetcrab_path_str = jetcrab_path.display().to_string().replace("\\", "/");
+            }
 
-            std::fs::write(
-                build_dir.join("src/main.rs"), 
-                cpm::templates::STANDALONE_TEMPLATE
-            )?;
+            // npm prunes packages it doesn't know about, linked ones included
+            let restored = cpm::link::restore_npm(std::path::Path::new("."))?;
+            if !restored.is_empty() {
+                eprintln!("🔗 Restored {} linked package(s) in node_modules", restored.len());
+            }
 
-            let cargo_toml = cpm::templates::CARGO_TOML_TEMPLATE
-                .replace("standalone-app", output_name.trim_end_matches(".exe"))
-                .replace("JETCRAB_PATH", &jetcrab_path_str);
+            if ignore_scripts {
+                eprintln!("⏭️  Lifecycle scripts skipped (--ignore-scripts)");
+            } else if let Some(allow_list) = &config.install_scripts {
+                let packages = cpm::lifecycle::packages_with_install_scripts(std::path::Path::new("."))?;
+                self.run_approved_scripts(npm_cmd, allow_list, packages)?;
+                self.run_project_scripts(npm_cmd, &["install", "postinstall", "prepare"])?;
+            } else {
+                let with_scripts =
+                    cpm::lifecycle::packages_with_install_scripts(std::path::Path::new("."))?;
+                if !with_scripts.is_empty() {
+                    eprintln!(
+                        "⚠️  {} dependencies ran install scripts without review",
+                        with_scripts.len()
+                    );
+                    eprintln!("💡 Run 'cpm approve-scripts' to start an allow-list");
+                }
+            }
+            eprintln!("✅ JavaScript dependencies installed!");
+        }
 
-            std::fs::write(build_dir.join("Cargo.toml"), cargo_toml)?;
+        // Check if we're in a Rust project
+        if std::path::Path::new("Cargo.toml").exists()
+            && (filtered.is_none() || !crates.is_empty())
+        {
+            eprintln!("🦀 Installing Rust dependencies with cargo...");
+            let mut cargo_args = vec!["build"];
+            for name in &crates {
+                cargo_args.extend(["-p", name.as_str()]);
+            }
+            let cargo_output = std::process::Command::new("cargo").args(&cargo_args).output()?;
+
+            if !cargo_output.status.success() {
+                return Err(CliError::ExecutionError {
+                    command: "cargo build".to_string(),
+                    message: String::from_utf8_lossy(&cargo_output.stderr).to_string(),
+                });
+            }
+            eprintln!("✅ Rust dependencies installed!");
+        }
+
+        Ok(())
+    }
+}
+
+impl CliCommand for ApproveScriptsCommand {
+    fn name(&self) -> &'static str {
+        "approve-scripts"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("approve-scripts")
+            .about("Review and approve dependency install scripts")
+            .arg(
+                clap::Arg::new("packages")
+                    .help("Packages to approve (lists pending packages when omitted)")
+                    .num_args(0..),
+            )
+            .arg(
+                clap::Arg::new("all")
+                    .long("all")
+                    .help("Approve every pending package")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("deny")
+                    .long("deny")
+                    .help("Deny the given packages instead of approving them")
+                    .action(clap::ArgAction::SetTrue),
+            )
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        if !std::path::Path::new("package.json").exists() {
+            return Err(CliError::FileOperationError {
+                operation: "approve scripts".to_string(),
+                path: "package.json".to_string(),
+                message: "Not in a JavaScript project. Run 'cpm init' first.".to_string(),
+            });
+        }
+
+        let project_dir = std::path::Path::new(".");
+        let config = cpm::config::CpmConfig::load(project_dir)?;
+        let allow_list = config.install_scripts.clone().unwrap_or_default();
+        let packages = cpm::lifecycle::packages_with_install_scripts(project_dir)?;
+        let groups = cpm::lifecycle::partition_by_approval(packages.clone(), &allow_list);
+        let pending = groups.pending.clone();
+
+        let mut selected: Vec<String> = matches
+            .get_many::<String>("packages")
+            .unwrap_or_default()
+            .cloned()
+            .collect();
+        if matches.get_flag("all") {
+            selected.extend(pending.iter().map(|p| p.name.clone()));
+        }
+        selected.sort();
+        selected.dedup();
+
+        if selected.is_empty() {
+            if pending.is_empty() {
+                eprintln!("✅ No pending install scripts");
+            } else {
+                eprintln!("📜 Packages with install scripts awaiting review:");
+                for package in &pending {
+                    eprintln!("   {}@{} ({})", package.name, package.version, package.path);
+                    for (event, command) in &package.scripts {
+                        eprintln!("      {event}: {command}");
+                    }
+                }
+                eprintln!();
+                eprintln!("💡 Run 'cpm approve-scripts <package>...' to allow them");
+                eprintln!("💡 Run 'cpm approve-scripts --deny <package>...' to reject them");
+            }
+            for (label, group) in [("Allowed", &groups.allowed), ("Denied", &groups.denied)] {
+                if !group.is_empty() {
+                    let names: Vec<&str> = group.iter().map(|p| p.name.as_str()).collect();
+                    eprintln!("{label}: {}", names.join(", "));
+                }
+            }
+            return Ok(());
+        }
+
+        for name in &selected {
+            if !packages.iter().any(|p| &p.name == name) {
+                eprintln!("⚠️  {name} has no install scripts in this project, recording anyway");
+            }
+        }
+
+        let deny = matches.get_flag("deny");
+        let (add_to, remove_from) = if deny {
+            ("deny", "allow")
+        } else {
+            ("allow", "deny")
+        };
+        cpm::config::edit_config(project_dir, |doc| {
+            cpm::config::push_unique(doc, "install-scripts", add_to, &selected);
+            cpm::config::remove_values(doc, "install-scripts", remove_from, &selected);
+            Ok(())
+        })?;
+
+        if deny {
+            eprintln!("🚫 Denied install scripts for: {}", selected.join(", "));
+        } else {
+            eprintln!("✅ Approved install scripts for: {}", selected.join(", "));
+            eprintln!("💡 Run 'cpm install' to run them");
+        }
+        Ok(())
+    }
+}
+
+impl CliCommand for BuildCommand {
+    fn name(&self) -> &'static str {
+        "build"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("build").about("Build the project").arg(filter_arg())
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        if let Some((graph, selected)) = filtered_packages(matches)? {
+            return run_in_filtered_packages("build", &graph, &selected);
+        }
+        if cpm::config::CpmConfig::load(std::path::Path::new("."))?.tasks.contains_key("build") {
+            eprintln!("🧩 Running the 'build' task from {}", cpm::config::CONFIG_FILE);
+            return run_task(std::path::Path::new("."), "build", &[]);
+        }
+        eprintln!("🔨 Building project...");
+
+        // Check if we're in a Rust project
+        if std::path::Path::new("Cargo.toml").exists() {
+            eprintln!("🦀 Building Rust project...");
+            let cargo_output = std::process::Command::new("cargo").arg("build").output()?;
+
+            if !cargo_output.status.success() {
+                return Err(CliError::ExecutionError {
+                    command: "cargo build".to_string(),
+                    message: String::from_utf8_lossy(&cargo_output.stderr).to_string(),
+                });
+            }
+
+            // If wasm-pack is available, build WASM
+            if std::process::Command::new("wasm-pack")
+                .arg("--version")
+                .output()
+                .is_ok()
+            {
+                eprintln!("🌐 Building WebAssembly...");
+                let wasm_output = std::process::Command::new("wasm-pack")
+                    .args(["build", "--release", "--target", "web", "--out-dir", "pkg"])
+                    .output()?;
+
+                if !wasm_output.status.success() {
+                    eprintln!("⚠️  WASM build failed, but continuing...");
+                } else {
+                    eprintln!("✅ WebAssembly built successfully!");
+                }
+            }
+
+            eprintln!("✅ Rust project built!");
+        } else {
+            // It's a JavaScript project
+            eprintln!("📦 JavaScript project detected.");
+            eprintln!("🦀 Bundling with JetCrab Runtime...");
+
+            let mut entry_file = "index.js";
+            if std::path::Path::new("js/index.js").exists() {
+                entry_file = "js/index.js";
+            }
+            
+            // Get project name from package.json
+            let mut output_name = "app.exe".to_string();
+            if let Ok(content) = std::fs::read_to_string("package.json") {
+                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&content) {
+                    if let Some(name) = json["name"].as_str() {
+                        output_name = if cfg!(windows) {
+                            format!("{}.exe", name)
+                        } else {
+                            name.to_string()
+                        };
+                    }
+                }
+            }
+
+            eprintln!("🔨 Bundling '{}' into '{}'...", entry_file, output_name);
+
+            // Bundling Logic (Native)
+            let build_dir = std::path::Path::new("jetcrab_build");
+            if build_dir.exists() {
+                std::fs::remove_dir_all(build_dir)?;
+            }
+            std::fs::create_dir_all(build_dir.join("src"))?;
+
+            // Copy entry file
+            std::fs::copy(entry_file, build_dir.join("app.js"))?;
+
+            // Write templates
+            // We need to determine the path to JetCrab library
+            // For development, we assume it's a sibling. For release, we might want to use git or registry.
+            // Here we prioritize JETCRAB_PATH env var, then sibling directory.
+            let current_dir = std::env::current_dir()?;
+            let jetcrab_path = std::env::var("JETCRAB_PATH")
+                .map(std::path::PathBuf::from)
+                .unwrap_or_else(|_| {
+                    // Try to find JetCrab relative to cpm executable or current dir?
+                    // Assuming we are in a workspace:
+                    // ../JetCrab
+                    current_dir.join("../JetCrab").canonicalize().unwrap_or(current_dir.join("../JetCrab"))
+                });
+            
+            let jetcrab_path_str = jetcrab_path.display().to_string().replace("\\", "/");
+
+            std::fs::write(
+                build_dir.join("src/main.rs"), 
+                cpm::templates::STANDALONE_TEMPLATE
+            )?;
+
+            let cargo_toml = cpm::templates::CARGO_TOML_TEMPLATE
+                .replace("standalone-app", output_name.trim_end_matches(".exe"))
+                .replace("JETCRAB_PATH", &jetcrab_path_str);
+
+            std::fs::write(build_dir.join("Cargo.toml"), cargo_toml)?;
+
+            // Run cargo build
+            let status = std::process::Command::new("cargo")
+                .arg("build")
+                .arg("--release")
+                .current_dir(build_dir)
+                .status()?;
+
+            if !status.success() {
+                 return Err(CliError::ExecutionError {
+                     command: "cargo build (bundling)".to_string(),
+                     message: "Bundling failed".to_string(),
+                 });
+            }
+
+            // Move binary
+            let binary_name = if cfg!(windows) {
+                format!("{}.exe", output_name.trim_end_matches(".exe"))
+            } else {
+                output_name.to_string()
+            };
+
+            let target_path = build_dir.join("target/release").join(&binary_name);
+            if target_path.exists() {
+                std::fs::copy(&target_path, &binary_name)?;
+                eprintln!("✨ Success! Standalone binary created: {}", binary_name);
+            } else {
+                return Err(CliError::InternalError {
+                    message: "Binary not found after build".into(),
+                });
+            }
+            
+            // Clean up
+            // std::fs::remove_dir_all(build_dir)?; // Keep for debugging for now or if user wants to see it
+            
+
+        }
+
+        Ok(())
+    }
+}
+
+
+impl CliCommand for DevCommand {
+    fn name(&self) -> &'static str {
+        "dev"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("dev")
+            .about("Start development server")
+            .arg(clap::Arg::new("watch").short('w').long("watch").help("Watch for changes and reload").action(clap::ArgAction::SetTrue))
+            .arg(
+                clap::Arg::new("inspect")
+                    .long("inspect")
+                    .help("Start with the debugger listening")
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with("watch"),
+            )
+            .arg(runtime_arg())
+            .arg(mode_arg(Some(cpm::dotenv::DEVELOPMENT)))
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let mode = if matches.get_flag("watch") {
+            cpm::runtime::Mode::Watch
+        } else if matches.get_flag("inspect") {
+            cpm::runtime::Mode::Inspect
+        } else {
+            cpm::runtime::Mode::Run
+        };
+        eprintln!("🚀 Starting development server...");
+        load_dotenv(std::path::Path::new("."), matches)?;
+
+        let js_entry = if std::path::Path::new("js/index.js").exists() {
+            "js/index.js"
+        } else if std::path::Path::new("index.js").exists() {
+            "index.js"
+        } else {
+            eprintln!("❌ Error: No JavaScript entry point found");
+            eprintln!("💡 Tip: Run 'cpm init' to create a new project or ensure index.js exists");
+            return Err(CliError::FileOperationError {
+                operation: "read JavaScript entry point".to_string(),
+                path: "index.js".to_string(),
+                message: "File not found".to_string(),
+            });
+        };
+
+        eprintln!("🔍 Looking for JavaScript runtime...");
+        let package = cpm::script::Package::find(std::path::Path::new("."))?;
+        let runtime = select_runtime(
+            std::path::Path::new("."),
+            package.as_ref().and_then(|package| package.manifest.get("engines")),
+            matches,
+            mode,
+        )?;
+        run_with_runtime(&runtime, js_entry, &[])
+    }
+}
+
+impl RunCommand {
+    /// Print the scripts of `package` and the tasks in `cpm.toml`
+    fn list(&self, package: &cpm::script::Package, config: &cpm::config::CpmConfig) {
+        let print = |name: &str, command: &str, description: Option<&str>| {
+            match description {
+                Some(description) => eprintln!("  {} — {}", name, description),
+                None => eprintln!("  {}", name),
+            }
+            eprintln!("    {}", command);
+        };
+        let scripts = package.scripts();
+        let label = package
+            .manifest
+            .get("name")
+            .and_then(|n| n.as_str())
+            .unwrap_or("package.json");
+        if !scripts.is_empty() {
+            eprintln!("📜 Scripts in {}:", label);
+            for (name, command) in &scripts {
+                print(name, command, package.description(name));
+            }
+        }
+        if !config.tasks.is_empty() {
+            eprintln!("🧩 Tasks in {}:", cpm::config::CONFIG_FILE);
+            for (name, task) in &config.tasks {
+                let command = match (&task.command, package.script(name)) {
+                    (Some(command), _) => command.clone(),
+                    (None, Some(script)) => script.to_string(),
+                    (None, None) => format!("depends on {}", task.depends_on.join(", ")),
+                };
+                print(name, &command, task.description.as_deref());
+            }
+        }
+        if scripts.is_empty() && config.tasks.is_empty() {
+            eprintln!("No scripts in package.json and no [tasks] in {}", cpm::config::CONFIG_FILE);
+        }
+    }
+
+    /// Run the scripts and tasks matching `patterns` with `-p` or `-s`, then print a summary
+    fn run_many(
+        &self,
+        package: &cpm::script::Package,
+        config: &cpm::config::CpmConfig,
+        patterns: &[String],
+        args: &[String],
+        matches: &ArgMatches,
+    ) -> CliResult<()> {
+        use cpm::parallel::{JobResult, JobStatus};
+
+        let scripts = package.scripts();
+        let candidates = scripts
+            .iter()
+            .map(|(script, _)| script.as_str())
+            .chain(config.tasks.keys().map(String::as_str));
+        let selected = cpm::script::expand_patterns(candidates, patterns).map_err(|pattern| {
+            CliError::ExecutionError {
+                command: format!("run {}", pattern),
+                message: "No script or task matches this pattern".to_string(),
+            }
+        })?;
+        if let Some(name) = selected
+            .iter()
+            .find(|name| package.script(name).is_none() && !config.tasks.contains_key(*name))
+        {
+            return Err(self.unknown(name, package, config));
+        }
+
+        let exe = std::env::current_exe()?;
+        let command = |name: &str| {
+            let mut command = std::process::Command::new(&exe);
+            command.args(["run", name]).current_dir(&package.dir);
+            if !args.is_empty() {
+                command.arg("--").args(args);
+            }
+            command
+        };
+        let continue_on_error = matches.get_flag("continue-on-error");
+        let results: Vec<JobResult> = if matches.get_flag("concurrent") {
+            eprintln!("🚀 Running {} script(s) in parallel: {}", selected.len(), selected.join(", "));
+            let width = selected.iter().map(String::len).max().unwrap_or(0);
+            let colored = use_color();
+            let commands = selected
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| {
+                    let label = format!("{:<width$} │", name, width = width);
+                    let prefix = if colored {
+                        format!("\x1b[{}m{}\x1b[0m ", PREFIX_COLORS[idx % PREFIX_COLORS.len()], label)
+                    } else {
+                        format!("{} ", label)
+                    };
+                    (command(name), prefix)
+                })
+                .collect();
+            let options = cpm::parallel::GroupOptions {
+                race: matches.get_flag("race"),
+                continue_on_error,
+            };
+            cpm::parallel::run_group(commands, options)
+        } else {
+            eprintln!("🚀 Running {} script(s) in sequence: {}", selected.len(), selected.join(", "));
+            let mut failed = false;
+            selected
+                .iter()
+                .map(|name| {
+                    if failed && !continue_on_error {
+                        return JobResult {
+                            status: JobStatus::Skipped("an earlier script failed".to_string()),
+                            duration: std::time::Duration::ZERO,
+                        };
+                    }
+                    let started = std::time::Instant::now();
+                    let status = match command(name).status() {
+                        Ok(status) => JobStatus::from_exit(status),
+                        Err(e) => {
+                            eprintln!("❌ {}: {}", name, e);
+                            JobStatus::Failed(None)
+                        }
+                    };
+                    failed |= status != JobStatus::Passed;
+                    JobResult {
+                        status,
+                        duration: started.elapsed(),
+                    }
+                })
+                .collect()
+        };
+
+        print_job_summary("SCRIPT", selected.iter().map(String::as_str).zip(&results));
+        let failed = results.iter().filter(|r| matches!(r.status, JobStatus::Failed(_))).count();
+        if failed > 0 {
+            return Err(CliError::ExecutionError {
+                command: format!("run {}", patterns.join(" ")),
+                message: format!("{} of {} script(s) failed", failed, selected.len()),
+            });
+        }
+        Ok(())
+    }
+
+    /// Error for an unknown script, with the closest names as suggestions
+    fn unknown(
+        &self,
+        name: &str,
+        package: &cpm::script::Package,
+        config: &cpm::config::CpmConfig,
+    ) -> CliError {
+        let scripts = package.scripts();
+        let candidates = scripts
+            .iter()
+            .map(|(script, _)| script.as_str())
+            .chain(config.tasks.keys().map(String::as_str));
+        let suggestions = cpm::fuzzy::closest_matches(name, candidates, 2);
+        eprintln!("❌ No script, task or file named '{}'", name);
+        if !suggestions.is_empty() {
+            let suggestions: Vec<&str> = suggestions.into_iter().take(3).collect();
+            eprintln!("💡 Did you mean: {}?", suggestions.join(", "));
+        }
+        eprintln!("💡 Run 'cpm run' to list available scripts");
+        CliError::ExecutionError {
+            command: format!("run {}", name),
+            message: "Missing script".to_string(),
+        }
+    }
+}
+
+impl CliCommand for RunCommand {
+    fn name(&self) -> &'static str {
+        "run"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        workspace_run_args(clap::Command::new("run"))
+            .about("Run a script or file")
+            .arg(
+                clap::Arg::new("script")
+                    .help("Script, task or file to run; lists scripts and tasks when omitted")
+                    .index(1)
+                    .num_args(1..),
+            )
+            .arg(
+                clap::Arg::new("args")
+                    .help("Arguments to pass to the script")
+                    .index(2)
+                    .num_args(0..)
+                    .last(true),
+            )
+            .arg(
+                clap::Arg::new("recursive")
+                    .short('r')
+                    .long("recursive")
+                    .help("Run the script in every workspace package, dependencies first")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("concurrent")
+                    .short('p')
+                    .long("concurrent")
+                    .help("Run several scripts at once; names may be globs like \"watch:*\"")
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with_all(["sequential", "recursive"]),
+            )
+            .arg(
+                clap::Arg::new("sequential")
+                    .short('s')
+                    .long("sequential")
+                    .help("Run several scripts one after another; names may be globs")
+                    .action(clap::ArgAction::SetTrue)
+                    .conflicts_with("recursive"),
+            )
+            .arg(
+                clap::Arg::new("race")
+                    .long("race")
+                    .help("With -p, stop all scripts as soon as one exits")
+                    .action(clap::ArgAction::SetTrue)
+                    .requires("concurrent"),
+            )
+            .arg(
+                clap::Arg::new("continue-on-error")
+                    .long("continue-on-error")
+                    .help("With -p or -s, keep running the other scripts after a failure")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(mode_arg(None))
+            .arg(runtime_arg())
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let args: Vec<String> = matches
+            .get_many::<String>("args")
+            .unwrap_or_default()
+            .cloned()
+            .collect();
+        let package = match cpm::script::Package::find(std::path::Path::new("."))? {
+            Some(package) => package,
+            None => cpm::script::Package::at(std::path::Path::new("."))?,
+        };
+        let config = cpm::config::CpmConfig::load(&package.dir)?;
+        let scripts: Vec<String> = matches
+            .get_many::<String>("script")
+            .unwrap_or_default()
+            .cloned()
+            .collect();
+        let Some(script) = scripts.first() else {
+            self.list(&package, &config);
+            return Ok(());
+        };
+        load_dotenv(&package.dir, matches)?;
+        if matches.get_flag("concurrent") || matches.get_flag("sequential") {
+            return self.run_many(&package, &config, &scripts, &args, matches);
+        }
+        if scripts.len() > 1 {
+            return Err(CliError::ExecutionError {
+                command: format!("run {}", scripts.join(" ")),
+                message: "Pass -p or -s to run several scripts".to_string(),
+            });
+        }
+
+        if matches.get_flag("recursive") {
+            return WorkspaceCommand.run_script(script, &args, matches);
+        }
+
+        eprintln!("🚀 Running: {}", script);
+        let shell = cpm::script::ScriptShell::for_package(&package.dir)?;
+
+        // 1. Try to run as a task from cpm.toml, with its dependencies
+        if config.tasks.contains_key(script) {
+            return run_task(&package.dir, script, &args);
+        }
+
+        // 2. Then as a package.json script
+        if let Some(steps) = cpm::script::plan(&package, script, &args) {
+            for step in &steps {
+                eprintln!("📜 {} › {}", step.event, step.command_line(shell));
+                step.run(&package, shell)?;
+            }
+            return Ok(());
+        }
+
+        // 3. Finally as a file: Rust scripts are compiled, JS goes to JetCrab
+        let script_path = std::path::Path::new(script);
+        if script_path.is_file() && script.ends_with(".rs") {
+            return run_rust_script(script_path, &args);
+        }
+        if script_path.exists() && script.ends_with(".js") {
+            let runtime = select_runtime(
+                &package.dir,
+                package.manifest.get("engines"),
+                matches,
+                cpm::runtime::Mode::Run,
+            )?;
+            return run_with_runtime(&runtime, script, &args);
+        }
+        
+        Err(self.unknown(script, &package, &config))
+    }
+}
+
+
+impl CliCommand for TestCommand {
+    fn name(&self) -> &'static str {
+        "test"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("test")
+            .about("Run tests")
+            .arg(filter_arg())
+            .arg(mode_arg(Some(cpm::dotenv::TEST)))
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        load_dotenv(std::path::Path::new("."), matches)?;
+        if let Some((graph, selected)) = filtered_packages(matches)? {
+            return run_in_filtered_packages("test", &graph, &selected);
+        }
+        if cpm::config::CpmConfig::load(std::path::Path::new("."))?.tasks.contains_key("test") {
+            eprintln!("🧩 Running the 'test' task from {}", cpm::config::CONFIG_FILE);
+            return run_task(std::path::Path::new("."), "test", &[]);
+        }
+        eprintln!("🧪 Running tests...");
+
+        // Check if we're in a JavaScript project
+        if std::path::Path::new("package.json").exists() {
+            eprintln!("🟨 Running JavaScript tests...");
+
+            // Try npm.cmd on Windows first, then npm
+            let npm_cmd = if cfg!(target_os = "windows") {
+                "npm.cmd"
+            } else {
+                "npm"
+            };
+
+            let npm_output = std::process::Command::new(npm_cmd).arg("test").output()?;
+
+            if !npm_output.status.success() {
+                eprintln!("⚠️  No test script found in package.json");
+                eprintln!("💡 Add a test script to package.json or run tests manually");
+            } else {
+                eprintln!("✅ JavaScript tests completed!");
+            }
+        }
+
+        // Check if we're in a Rust project
+        if std::path::Path::new("Cargo.toml").exists() {
+            eprintln!("🦀 Running Rust tests...");
+            let cargo_output = std::process::Command::new("cargo").arg("test").output()?;
+
+            if !cargo_output.status.success() {
+                return Err(CliError::ExecutionError {
+                    command: "cargo test".to_string(),
+                    message: String::from_utf8_lossy(&cargo_output.stderr).to_string(),
+                });
+            }
+            eprintln!("✅ Rust tests completed!");
+        }
+
+        Ok(())
+    }
+}
+
+impl CliCommand for SbomCommand {
+    fn name(&self) -> &'static str {
+        "sbom"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("sbom")
+            .about("Generate a Software Bill of Materials for the project")
+            .arg(
+                clap::Arg::new("format")
+                    .long("format")
+                    .help("Output format")
+                    .value_parser(["cyclonedx-json", "spdx-json"])
+                    .default_value("cyclonedx-json"),
+            )
+            .arg(
+                clap::Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .help("Write the SBOM to a file instead of stdout"),
+            )
+            .arg(
+                clap::Arg::new("dev")
+                    .long("dev")
+                    .help("Include devDependencies")
+                    .action(clap::ArgAction::SetTrue),
+            )
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        use cpm::sbom::{write_sbom, Sbom, SbomFormat, SbomOptions};
+
+        let format_name = matches.get_one::<String>("format").unwrap();
+        let format = SbomFormat::parse(format_name).ok_or_else(|| CliError::InternalError {
+            message: format!("Unsupported SBOM format: {format_name}"),
+        })?;
+
+        if !std::path::Path::new("package.json").exists()
+            && !std::path::Path::new("Cargo.toml").exists()
+        {
+            return Err(CliError::FileOperationError {
+                operation: "generate SBOM".to_string(),
+                path: "package.json".to_string(),
+                message: "Not in a project. Run 'cpm init' first.".to_string(),
+            });
+        }
+        if std::path::Path::new("package.json").exists()
+            && !std::path::Path::new("package-lock.json").exists()
+        {
+            eprintln!("⚠️  No package-lock.json found, npm dependencies will be missing");
+            eprintln!("💡 Run 'cpm lock' first");
+        }
+        if !std::path::Path::new("jetcrab_build/Cargo.lock").exists() {
+            eprintln!("⚠️  No bundle lockfile found, only direct JetCrab runtime crates are listed");
+            eprintln!("💡 Run 'cpm build' first for the full runtime crate graph");
+        }
+
+        let options = SbomOptions {
+            include_dev: matches.get_flag("dev"),
+        };
+        let sbom = Sbom::collect(std::path::Path::new("."), &options)?;
+        let document = sbom.render(format, cpm::timestamp::build_epoch());
+        let output = matches.get_one::<String>("output").map(PathBuf::from);
+        write_sbom(&document, output.as_deref())?;
+
+        if let Some(path) = output {
+            eprintln!(
+                "✅ SBOM with {} components written to {}",
+                sbom.components.len(),
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl CliCommand for PackCommand {
+    fn name(&self) -> &'static str {
+        "pack"
+    }
+
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("pack")
+            .about("Create a package tarball and preview its contents")
+            .arg(
+                clap::Arg::new("dry_run")
+                    .long("dry-run")
+                    .help("Show the file list without writing the tarball")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("pack_destination")
+                    .long("pack-destination")
+                    .help("Directory to write the tarball to"),
+            )
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        use cpm::pack::format_size;
+
+        let dry_run = matches.get_flag("dry_run");
+        let root = std::path::Path::new(".");
+        let plan = cpm::pack::plan(root)?;
+        let tarball = cpm::pack::build_tarball(root, &plan)?;
+
+        eprintln!("📦 {}@{}", plan.name, plan.version);
+        eprintln!("Tarball Contents");
+        for entry in &plan.entries {
+            eprintln!("{:>9}  {}", format_size(entry.size), entry.path);
+        }
+
+        if !plan.warnings.is_empty() {
+            eprintln!();
+            eprintln!("⚠️  Suspicious files in the package:");
+            for warning in &plan.warnings {
+                eprintln!("   {} ({})", warning.path, warning.reason);
+            }
+            eprintln!("💡 Exclude them with the \"files\" field or .npmignore");
+        }
+
+        eprintln!();
+        eprintln!("Tarball Details");
+        eprintln!("   name:          {}", plan.name);
+        eprintln!("   version:       {}", plan.version);
+        eprintln!("   filename:      {}", plan.tarball_name());
+        eprintln!(
+            "   package size:  {}",
+            format_size(tarball.bytes.len() as u64)
+        );
+        eprintln!("   unpacked size: {}", format_size(plan.unpacked_size()));
+        eprintln!("   integrity:     {}", tarball.integrity);
+        eprintln!("   total files:   {}", plan.entries.len());
+
+        if dry_run {
+            eprintln!("💡 Dry run, tarball not written");
+            return Ok(());
+        }
+
+        let destination = matches
+            .get_one::<String>("pack_destination")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("."));
+        std::fs::create_dir_all(&destination)?;
+        let output = destination.join(plan.tarball_name());
+        std::fs::write(&output, &tarball.bytes)?;
+        eprintln!("✅ Wrote {}", output.display());
+        // Print the file name on stdout like npm pack, for scripting
+        println!("{}", plan.tarball_name());
+        Ok(())
+    }
+}
 
-            // Run cargo build
-            let status = std::process::Command::new("cargo")
-                .arg("build")
-                .arg("--release")
-                .current_dir(build_dir)
-                .status()?;
+impl CliCommand for VersionCommand {
+    fn name(&self) -> &'static str {
+        "version"
+    }
 
-            if !status.success() {
-                 return Err(CliError::ExecutionError {
-                     command: "cargo build (bundling)".to_string(),
-                     message: "Bundling failed".to_string(),
-                 });
-            }
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("version")
+            .about("Bump the version in package.json and Cargo.toml together")
+            .arg(
+                clap::Arg::new("new_version")
+                    .help("major, minor, patch, prerelease or an explicit x.y.z"),
+            )
+            .arg(clap::Arg::new("preid").long("preid").help("Prerelease identifier (e.g. beta)"))
+            .arg(
+                clap::Arg::new("check")
+                    .long("check")
+                    .help("Fail if package.json and Cargo.toml disagree")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("changelog")
+                    .long("changelog")
+                    .help("Turn the Unreleased section of CHANGELOG.md into the new release")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("no_git_tag_version")
+                    .long("no-git-tag-version")
+                    .help("Do not create a git commit and tag")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("message")
+                    .short('m')
+                    .long("message")
+                    .default_value("v%s")
+                    .help("Commit message; %s is replaced with the new version"),
+            )
+    }
 
-            // Move binary
-            let binary_name = if cfg!(windows) {
-                format!("{}.exe", output_name.trim_end_matches(".exe"))
-            } else {
-                output_name.to_string()
-            };
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let root = std::path::Path::new(".");
+        let versions = cpm::version::ProjectVersions::read(root)?;
 
-            let target_path = build_dir.join("target/release").join(&binary_name);
-            if target_path.exists() {
-                std::fs::copy(&target_path, &binary_name)?;
-                eprintln!("✨ Success! Standalone binary created: {}", binary_name);
-            } else {
-                return Err(CliError::InternalError {
-                    message: "Binary not found after build".into(),
+        if matches.get_flag("check") {
+            if let Some(mismatch) = versions.mismatch() {
+                return Err(CliError::ExecutionError {
+                    command: "cpm version --check".to_string(),
+                    message: mismatch,
                 });
             }
-            
-            // Clean up
-            // std::fs::remove_dir_all(build_dir)?; // Keep for debugging for now or if user wants to see it
-            
-
+            match versions.current() {
+                Some(version) => eprintln!("✅ Versions agree: {}", version),
+                None => eprintln!("⚠️  No version found in package.json or Cargo.toml"),
+            }
+            return Ok(());
         }
 
+        let Some(new_version) = matches.get_one::<String>("new_version") else {
+            if let Some((name, version)) = &versions.npm {
+                eprintln!("📦 package.json: {}@{}", name, version);
+            }
+            if let Some((name, version)) = &versions.cargo {
+                eprintln!("🦀 Cargo.toml:   {}@{}", name, version);
+            }
+            if let Some(mismatch) = versions.mismatch() {
+                eprintln!("⚠️  {}", mismatch);
+            }
+            return Ok(());
+        };
+
+        let options = cpm::version::VersionOptions {
+            bump: cpm::version::Bump::parse(new_version)?,
+            preid: matches.get_one::<String>("preid").map(String::as_str),
+            changelog: matches.get_flag("changelog"),
+            git: !matches.get_flag("no_git_tag_version"),
+            message: matches.get_one::<String>("message").map(String::as_str).unwrap_or("v%s"),
+        };
+        let outcome = cpm::version::bump_project(root, &options)?;
+
+        eprintln!("🚀 {} → {}", outcome.previous, outcome.version);
+        for file in &outcome.files {
+            eprintln!("   📝 {}", file);
+        }
+        if let Some(tag) = &outcome.tag {
+            eprintln!("✅ Committed and tagged {}", tag);
+        }
+        println!("v{}", outcome.version);
         Ok(())
     }
 }
 
-
-impl CliCommand for DevCommand {
+impl CliCommand for ReleaseCommand {
     fn name(&self) -> &'static str {
-        "dev"
+        "release"
     }
 
     fn build_clap_command(&self) -> clap::Command {
-        clap::Command::new("dev")
-            .about("Start development server")
-            .arg(clap::Arg::new("watch").short('w').long("watch").help("Watch for changes and reload").action(clap::ArgAction::SetTrue))
+        clap::Command::new("release")
+            .about("Bump, build, test and publish to npm and crates.io in one go")
+            .arg(
+                clap::Arg::new("new_version")
+                    .required(true)
+                    .help("major, minor, patch, prerelease or an explicit x.y.z"),
+            )
+            .arg(clap::Arg::new("preid").long("preid").help("Prerelease identifier (e.g. beta)"))
+            .arg(clap::Arg::new("tag").long("tag").help("npm dist-tag to publish under (default: latest)"))
+            .arg(
+                clap::Arg::new("access")
+                    .long("access")
+                    .value_parser(["public", "restricted"])
+                    .help("Access level for scoped packages"),
+            )
+            .arg(clap::Arg::new("otp").long("otp").help("One-time password for two-factor authentication"))
+            .arg(
+                clap::Arg::new("registry_dir")
+                    .long("registry-dir")
+                    .help("Publish into this directory instead of the real registries"),
+            )
+            .arg(
+                clap::Arg::new("no_cargo")
+                    .long("no-cargo")
+                    .help("Do not publish the Rust crate")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("changelog")
+                    .long("changelog")
+                    .help("Turn the Unreleased section of CHANGELOG.md into the new release")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("dry_run")
+                    .long("dry-run")
+                    .help("Show the release plan without changing anything")
+                    .action(clap::ArgAction::SetTrue),
+            )
     }
 
     fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
-        let watch = matches.get_flag("watch");
-        eprintln!("🚀 Starting development server...");
+        use cpm::release::{Destination, PublishedArtifact};
+
+        let root = std::path::Path::new(".");
+        let config = cpm::config::CpmConfig::load(root)?;
+        let registry_dir = matches
+            .get_one::<String>("registry_dir")
+            .map(PathBuf::from)
+            .or_else(|| config.release.and_then(|r| r.registry_dir));
+        let tag = matches.get_one::<String>("tag").cloned().unwrap_or_else(|| "latest".to_string());
+        let destination = match &registry_dir {
+            Some(dir) => Destination::Directory(dir.clone()),
+            None => Destination::Registries {
+                tag: tag.clone(),
+                otp: matches.get_one::<String>("otp").cloned(),
+                access: matches.get_one::<String>("access").cloned(),
+            },
+        };
 
-        let js_entry = if std::path::Path::new("js/index.js").exists() {
-            "js/index.js"
-        } else if std::path::Path::new("index.js").exists() {
-            "index.js"
-        } else {
-            eprintln!("❌ Error: No JavaScript entry point found");
-            eprintln!("💡 Tip: Run 'cpm init' to create a new project or ensure index.js exists");
+        let bump = cpm::version::Bump::parse(matches.get_one::<String>("new_version").unwrap())?;
+        let preid = matches.get_one::<String>("preid").map(String::as_str);
+        let versions = cpm::version::ProjectVersions::read(root)?;
+        let Some(current) = versions.current() else {
             return Err(CliError::FileOperationError {
-                operation: "read JavaScript entry point".to_string(),
-                path: "index.js".to_string(),
-                message: "File not found".to_string(),
+                operation: "release".to_string(),
+                path: ".".to_string(),
+                message: "No version found in package.json or Cargo.toml".to_string(),
             });
         };
+        let next = bump.apply(current, preid)?;
+        let publish_npm = cpm::release::npm_publishable(root)?;
+        let is_rust = std::path::Path::new("Cargo.toml").exists();
+        let publish_cargo = !matches.get_flag("no_cargo") && cpm::release::cargo_publishable(root)?;
+        let name = versions
+            .npm
+            .as_ref()
+            .or(versions.cargo.as_ref())
+            .map(|(name, _)| name.clone())
+            .unwrap_or_default();
+
+        // JavaScript-only packages are published as-is; `cpm build` bundles a standalone binary
+        let build = if is_rust { "Build (cpm build)" } else { "Build (nothing to build)" };
+        let mut steps = vec![
+            format!("Bump version {} → {} (commit and tag v{})", current, next, next),
+            build.to_string(),
+            "Test".to_string(),
+            "Pack".to_string(),
+        ];
+        if publish_npm {
+            steps.push(format!("Publish {} to npm", name));
+        }
+        if is_rust {
+            steps.push("Publish pkg/ (wasm-pack output) to npm".to_string());
+        }
+        if publish_cargo {
+            steps.push("cargo publish".to_string());
+        }
 
-        eprintln!("🔍 Looking for JavaScript runtime...");
+        eprintln!("🚀 Release plan for {}", name);
+        for (idx, step) in steps.iter().enumerate() {
+            eprintln!("   {}. {}", idx + 1, step);
+        }
+        match &destination {
+            Destination::Directory(dir) => eprintln!("📦 Local registry: {}", dir.display()),
+            Destination::Registries { tag, .. } => eprintln!("📦 Registries: npm (tag '{}') and crates.io", tag),
+        }
+        if matches.get_flag("dry_run") {
+            if let Some(mismatch) = versions.mismatch() {
+                eprintln!("⚠️  {}", mismatch);
+            }
+            eprintln!("💡 Dry run, nothing was changed");
+            return Ok(());
+        }
 
-        if watch {
-            let npm_cmd = if cfg!(target_os = "windows") { "npm.cmd" } else { "npm" };
-            let nodemon_ok = std::process::Command::new(npm_cmd)
-                .args(["exec", "nodemon", "--version"])
-                .output()
-                .map(|o| o.status.success())
-                .unwrap_or(false);
-            if nodemon_ok {
-                eprintln!("Watching with nodemon...");
-                let runner = if std::process::Command::new("jetcrab").arg("--version").output().is_ok() {
-                    "jetcrab run"
-                } else {
-                    "node"
-                };
-                let status = std::process::Command::new(npm_cmd)
-                    .args(["exec", "nodemon", "--exec", runner, js_entry])
-                    .status()?;
+        // Step 1: bump, commit and tag
+        eprintln!("🔖 [1/{}] {}", steps.len(), steps[0]);
+        let outcome = cpm::version::bump_project(
+            root,
+            &cpm::version::VersionOptions {
+                bump,
+                preid,
+                changelog: matches.get_flag("changelog"),
+                git: true,
+                message: "v%s",
+            },
+        )?;
+        let version = outcome.version.to_string();
+
+        let mut published: Vec<PublishedArtifact> = Vec::new();
+        let staging = std::env::temp_dir().join(format!("cpm-release-{}", std::process::id()));
+        let result = (|| -> CliResult<()> {
+            eprintln!("🔨 [2/{}] {}", steps.len(), steps[1]);
+            if is_rust {
+                let status = std::process::Command::new(std::env::current_exe()?).arg("build").status()?;
                 if !status.success() {
                     return Err(CliError::ExecutionError {
-                        command: "nodemon".to_string(),
-                        message: "nodemon failed".to_string(),
+                        command: "cpm build".to_string(),
+                        message: "Build failed".to_string(),
                     });
                 }
-                return Ok(());
             }
-        }
 
-        let jetcrab_available = std::process::Command::new("jetcrab")
-            .arg("--version")
-            .output()
-            .is_ok();
-
-        if jetcrab_available {
-            eprintln!("🦀 Using JetCrab runtime...");
-            let mut child = std::process::Command::new("jetcrab")
-                .arg("run")
-                .arg(js_entry)
-                .spawn()?;
+            eprintln!("🧪 [3/{}] Test", steps.len());
+            let manifest: serde_json::Value = match std::fs::read_to_string("package.json") {
+                Ok(content) => serde_json::from_str(&content)?,
+                Err(_) => serde_json::Value::Null,
+            };
+            let tests = cpm::publish::check_tests(root, &manifest);
+            if tests.status == cpm::publish::CheckStatus::Failed {
+                return Err(CliError::ExecutionError {
+                    command: "cpm release".to_string(),
+                    message: tests.detail,
+                });
+            }
 
-            child.wait()?;
-        } else {
-            eprintln!("🟨 Using Node.js runtime...");
-            let mut child = std::process::Command::new("node").arg(js_entry).spawn()?;
+            eprintln!("📦 [4/{}] Pack", steps.len());
+            let mut tarballs = Vec::new();
+            if publish_npm {
+                run_lifecycle(root, cpm::script::PREPUBLISH_EVENTS)?;
+                let (plan, path) = cpm::release::stage_tarball(root, &staging)?;
+                run_lifecycle(root, cpm::script::POSTPACK_EVENTS)?;
+                tarballs.push((root, format!("{}@{}", plan.name, plan.version), path));
+            }
+            let wasm_pkg = std::path::Path::new("pkg");
+            if is_rust {
+                if wasm_pkg.join("package.json").exists() {
+                    run_lifecycle(wasm_pkg, cpm::script::PREPUBLISH_EVENTS)?;
+                    let (plan, path) = cpm::release::stage_tarball(wasm_pkg, &staging.join("pkg"))?;
+                    run_lifecycle(wasm_pkg, cpm::script::POSTPACK_EVENTS)?;
+                    tarballs.push((wasm_pkg, format!("{}@{} (pkg/)", plan.name, plan.version), path));
+                } else {
+                    eprintln!("⚠️  pkg/package.json not found, skipping the wasm package");
+                }
+            }
 
-            child.wait()?;
+            for (step, (dir, label, tarball)) in (5..).zip(&tarballs) {
+                eprintln!("🚀 [{}/{}] Publish {}", step, steps.len(), label);
+                published.push(cpm::release::publish_tarball(tarball, label, &destination)?);
+                run_lifecycle(dir, cpm::script::POSTPUBLISH_EVENTS)?;
+            }
+            if publish_cargo {
+                let crate_name = versions.cargo.as_ref().map(|(n, _)| n.as_str()).unwrap_or(&name);
+                eprintln!("🦀 [{}/{}] cargo publish", steps.len(), steps.len());
+                published.push(cpm::release::publish_crate(root, crate_name, &version, &destination)?);
+            }
+            Ok(())
+        })();
+        let _ = std::fs::remove_dir_all(&staging);
+
+        if let Err(error) = result {
+            eprintln!("❌ Release failed, rolling back...");
+            let remaining = match cpm::release::rollback(root, &outcome, &published) {
+                Ok(remaining) => remaining,
+                Err(rollback_error) => {
+                    // The release failure is what the user needs to fix first
+                    eprintln!("❌ Rollback failed: {}", rollback_error);
+                    match &outcome.tag {
+                        Some(tag) => eprintln!("💡 Check the version commit and tag {} by hand", tag),
+                        None => eprintln!("💡 Check the version in {} by hand", outcome.files.join(", ")),
+                    }
+                    return Err(error);
+                }
+            };
+            if remaining.is_empty() {
+                match &outcome.tag {
+                    Some(tag) => eprintln!("↩️  Removed tag {} and the version commit", tag),
+                    None => eprintln!("↩️  Restored {}", outcome.files.join(", ")),
+                }
+            } else {
+                eprintln!("⚠️  Already published, keeping version {}:", version);
+                for artifact in &remaining {
+                    eprintln!("   {}", artifact);
+                }
+            }
+            return Err(error);
         }
 
+        for artifact in &published {
+            eprintln!("   ✅ {}", artifact.description);
+        }
+        eprintln!("✅ Released {} {}", name, version);
+        if outcome.tag.is_some() {
+            eprintln!("💡 Push the release with: git push --follow-tags");
+        }
         Ok(())
     }
 }
 
-impl CliCommand for RunCommand {
+impl CliCommand for ChangelogCommand {
     fn name(&self) -> &'static str {
-        "run"
+        "changelog"
     }
 
     fn build_clap_command(&self) -> clap::Command {
-        clap::Command::new("run")
-            .about("Run a script or file")
-            .arg(
-                clap::Arg::new("script")
-                    .help("Script name or file path")
-                    .required(true)
-                    .index(1),
-            )
+        clap::Command::new("changelog")
+            .about("Generate the Unreleased changelog section from Conventional Commits")
+            .arg(clap::Arg::new("since").long("since").help("Tag or commit to start from (default: last version tag)"))
             .arg(
-                clap::Arg::new("args")
-                    .help("Arguments to pass to the script")
-                    .num_args(0..)
-                    .last(true),
+                clap::Arg::new("write")
+                    .long("write")
+                    .help("Add the entries to the Unreleased section of CHANGELOG.md instead of printing them")
+                    .action(clap::ArgAction::SetTrue),
             )
     }
 
     fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
-        let script = matches.get_one::<String>("script").unwrap();
-        let args: Vec<&String> = matches
-            .get_many::<String>("args")
-            .unwrap_or_default()
-            .collect();
+        use std::io::Write;
 
-        eprintln!("🚀 Running: {}", script);
+        let root = std::path::Path::new(".");
+        if !cpm::git::is_repo(root) {
+            return Err(CliError::ExecutionError {
+                command: "cpm changelog".to_string(),
+                message: "Not a git repository".to_string(),
+            });
+        }
+        let since = matches
+            .get_one::<String>("since")
+            .cloned()
+            .or_else(|| cpm::changelog::last_version_tag(root));
+        let changes = cpm::changelog::collect(root, since.as_deref())?;
+
+        match &since {
+            Some(since) => eprintln!("📜 {} conventional commit(s) since {}", changes.commits.len(), since),
+            None => eprintln!("📜 {} conventional commit(s) in the whole history", changes.commits.len()),
+        }
+        if changes.skipped > 0 {
+            eprintln!("💡 {} commit(s) without a Conventional Commit message were skipped", changes.skipped);
+        }
+        let body = changes.render();
+        if body.is_empty() {
+            eprintln!("⚠️  Nothing to add to the changelog");
+            return Ok(());
+        }
 
-        // 1. Try to run as a package.json script via npm
-        if std::path::Path::new("package.json").exists() {
-             if let Ok(content) = std::fs::read_to_string("package.json") {
-                 if let Ok(package_json) = serde_json::from_str::<serde_json::Value>(&content) {
-                     if let Some(scripts) = package_json.get("scripts").and_then(|s| s.as_object()) {
-                         if scripts.contains_key(script) {
-                             eprintln!("📜 Found npm script '{}'", script);
-                             
-                             let npm_cmd = if cfg!(target_os = "windows") { "npm.cmd" } else { "npm" };
-                             let mut cmd = std::process::Command::new(npm_cmd);
-                             cmd.arg("run").arg(script);
-                             if !args.is_empty() {
-                                 cmd.arg("--").args(args);
-                             }
-                             
-                             let status = cmd.status()?;
-                             if !status.success() {
-                                 return Err(CliError::ExecutionError {
-                                     command: format!("npm run {}", script),
-                                     message: "Script failed".to_string(),
-                                 });
-                             }
-                             return Ok(());
-                         }
-                     }
-                 }
-             }
-        }
-
-        // 2. Try to run as a JS file with JetCrab
-        let script_path = std::path::Path::new(script);
-        if script_path.exists() && (script.ends_with(".js") || script.ends_with(".rs")) {
-             eprintln!("🦀 Executing file with JetCrab...");
-             let mut cmd = std::process::Command::new("jetcrab");
-             cmd.arg("run").arg(script);
-             cmd.args(args);
-             
-             let status = cmd.status()?;
-             if !status.success() {
-                 return Err(CliError::ExecutionError {
-                     command: format!("jetcrab run {}", script),
-                     message: "Execution failed".to_string(),
-                 });
-             }
-             return Ok(());
+        if changes.has_breaking() {
+            eprintln!("⚠️  Contains breaking changes");
+        }
+        let versions = cpm::version::ProjectVersions::read(root)?;
+        if let Some(current) = versions.current() {
+            let bump = changes.suggested_bump(current);
+            let next = bump.apply(current, None)?;
+            eprintln!("💡 Suggested next version: {} → {} (cpm version {})", current, next, bump);
+        }
+
+        if matches.get_flag("write") {
+            let path = root.join(cpm::changelog::CHANGELOG_FILE);
+            let content = std::fs::read_to_string(&path).unwrap_or_default();
+            std::fs::write(&path, cpm::changelog::write_unreleased(&content, &body))?;
+            eprintln!("✅ Updated {}", cpm::changelog::CHANGELOG_FILE);
+        } else {
+            write!(std::io::stdout().lock(), "## [Unreleased]\n\n{}", body)?;
         }
-        
-        eprintln!("❌ Could not find script or file: {}", script);
         Ok(())
     }
 }
 
+impl LinkCommand {
+    /// Print the active links
+    fn list(&self) -> CliResult<()> {
+        let links = cpm::link::load(std::path::Path::new("."))?;
+        if links.is_empty() {
+            eprintln!("No active links. Link a local package with 'cpm link <path>'.");
+            return Ok(());
+        }
+        let rows: Vec<[String; 4]> = links
+            .iter()
+            .map(|link| {
+                [
+                    link.name.clone(),
+                    link.kind.label().to_string(),
+                    link.path.display().to_string(),
+                    if link.source_exists() { "ok" } else { "missing" }.to_string(),
+                ]
+            })
+            .collect();
+        eprintln!("🔗 {} active link(s)", rows.len());
+        print_table(["NAME", "KIND", "PATH", "STATUS"], &rows);
+        Ok(())
+    }
+}
 
-impl CliCommand for TestCommand {
+impl CliCommand for LinkCommand {
     fn name(&self) -> &'static str {
-        "test"
+        "link"
     }
 
     fn build_clap_command(&self) -> clap::Command {
-        clap::Command::new("test").about("Run tests")
+        clap::Command::new("link")
+            .about("Link a local package into node_modules or as a Cargo [patch] override")
+            .arg(clap::Arg::new("path").help("Directory of the package to link"))
+            .arg(
+                clap::Arg::new("list")
+                    .long("list")
+                    .help("Show active links")
+                    .conflicts_with("path")
+                    .action(clap::ArgAction::SetTrue),
+            )
     }
 
-    fn execute(&self, _context: &mut CliContext, _matches: &ArgMatches) -> CliResult<()> {
-        eprintln!("🧪 Running tests...");
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let Some(path) = matches.get_one::<String>("path") else {
+            return self.list();
+        };
+        for link in cpm::link::link(std::path::Path::new("."), std::path::Path::new(path))? {
+            match link.kind {
+                cpm::link::LinkKind::Npm => {
+                    eprintln!("🔗 node_modules/{} → {}", link.name, link.path.display())
+                }
+                cpm::link::LinkKind::Cargo => eprintln!(
+                    "🔗 [patch.crates-io] {} → {}",
+                    link.name,
+                    link.path.display()
+                ),
+            }
+        }
+        eprintln!("✅ Linked. Run 'cpm unlink' to go back to the registry versions");
+        Ok(())
+    }
+}
 
-        // Check if we're in a JavaScript project
-        if std::path::Path::new("package.json").exists() {
-            eprintln!("🟨 Running JavaScript tests...");
+impl CliCommand for UnlinkCommand {
+    fn name(&self) -> &'static str {
+        "unlink"
+    }
 
-            // Try npm.cmd on Windows first, then npm
-            let npm_cmd = if cfg!(target_os = "windows") {
-                "npm.cmd"
-            } else {
-                "npm"
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("unlink")
+            .about("Remove local package links (all of them without a name)")
+            .arg(clap::Arg::new("name").help("Package name or linked directory"))
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let name = matches.get_one::<String>("name").map(String::as_str);
+        let removed = cpm::link::unlink(std::path::Path::new("."), name)?;
+        if removed.is_empty() {
+            return match name {
+                Some(name) => Err(CliError::ExecutionError {
+                    command: format!("unlink {}", name),
+                    message: "No active link with this name or path".to_string(),
+                }),
+                None => {
+                    eprintln!("No active links");
+                    Ok(())
+                }
             };
+        }
+        for link in &removed {
+            eprintln!("🗑️  Unlinked {} ({})", link.name, link.kind.label());
+        }
+        if removed.iter().any(|link| link.kind == cpm::link::LinkKind::Npm) {
+            eprintln!("💡 Run 'cpm install' to restore the registry versions in node_modules");
+        }
+        Ok(())
+    }
+}
 
-            let npm_output = std::process::Command::new(npm_cmd).arg("test").output()?;
+impl CliCommand for CacheServerCommand {
+    fn name(&self) -> &'static str {
+        "cache-server"
+    }
 
-            if !npm_output.status.success() {
-                eprintln!("⚠️  No test script found in package.json");
-                eprintln!("💡 Add a test script to package.json or run tests manually");
-            } else {
-                eprintln!("✅ JavaScript tests completed!");
-            }
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("cache-server")
+            .about("Serve a remote task cache from a local directory")
+            .arg(
+                clap::Arg::new("host")
+                    .long("host")
+                    .default_value("127.0.0.1")
+                    .help("Address to listen on"),
+            )
+            .arg(
+                clap::Arg::new("port")
+                    .long("port")
+                    .default_value("4874")
+                    .value_parser(clap::value_parser!(u16))
+                    .help("Port to listen on (0 for any free port)"),
+            )
+            .arg(
+                clap::Arg::new("dir")
+                    .long("dir")
+                    .default_value(".cpm/cache-server")
+                    .help("Directory storing the artifacts"),
+            )
+    }
+
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let host = matches.get_one::<String>("host").unwrap();
+        let port = matches.get_one::<u16>("port").copied().unwrap_or(4874);
+        let dir = std::path::PathBuf::from(matches.get_one::<String>("dir").unwrap());
+        let key = std::env::var(cpm::cache::remote::KEY_ENV)
+            .ok()
+            .filter(|key| !key.is_empty());
+
+        let server = cpm::cache::server::CacheServer::bind(
+            &format!("{}:{}", host, port),
+            &dir,
+            key.as_deref().map(str::as_bytes),
+        )?;
+        let addr = server.local_addr()?;
+        eprintln!("☁️  Cache server listening on http://{}", addr);
+        eprintln!("📦 Storing artifacts in {}", dir.display());
+        if key.is_some() {
+            eprintln!("🔐 Uploads must be signed with {}", cpm::cache::remote::KEY_ENV);
+        } else {
+            eprintln!("⚠️  {} is not set, uploads are not verified", cpm::cache::remote::KEY_ENV);
         }
+        eprintln!(
+            "💡 Point clients at it with {}=http://{}",
+            cpm::cache::remote::URL_ENV,
+            addr
+        );
+        server.serve(|method, path, status| eprintln!("{} {} → {}", method, path, status))
+    }
+}
 
-        // Check if we're in a Rust project
-        if std::path::Path::new("Cargo.toml").exists() {
-            eprintln!("🦀 Running Rust tests...");
-            let cargo_output = std::process::Command::new("cargo").arg("test").output()?;
+impl CliCommand for EnvCommand {
+    fn name(&self) -> &'static str {
+        "env"
+    }
 
-            if !cargo_output.status.success() {
-                return Err(CliError::ExecutionError {
-                    command: "cargo test".to_string(),
-                    message: String::from_utf8_lossy(&cargo_output.stderr).to_string(),
-                });
-            }
-            eprintln!("✅ Rust tests completed!");
-        }
+    fn build_clap_command(&self) -> clap::Command {
+        clap::Command::new("env")
+            .about("Show the variables loaded from .env files, with secrets masked")
+            .arg(mode_arg(None))
+    }
 
+    fn execute(&self, _context: &mut CliContext, matches: &ArgMatches) -> CliResult<()> {
+        let mode = matches.get_one::<String>("mode").map(String::as_str);
+        let vars = cpm::dotenv::load(std::path::Path::new("."), mode)?;
+        if vars.is_empty() {
+            eprintln!("No variables in {}", cpm::dotenv::files(mode).join(", "));
+            return Ok(());
+        }
+        let rows: Vec<[String; 3]> = vars
+            .iter()
+            .map(|var| {
+                let value = if !cpm::secrets::is_secret(&var.name, &var.value) {
+                    var.value.replace('\n', "\\n")
+                } else if var.value.chars().count() >= 16 {
+                    cpm::secrets::mask(&var.value)
+                } else {
+                    "********".to_string()
+                };
+                [var.name.clone(), value, var.source.clone()]
+            })
+            .collect();
+        print_table(["NAME", "VALUE", "SOURCE"], &rows);
         Ok(())
     }
 }
@@ -1295,6 +3462,7 @@ fn main() {
         .add_command(Box::new(RemoveRustCommand::new()))
         .add_command(Box::new(RustStatusCommand))
         .add_command(Box::new(NpxCommand::new()))
+        .add_command(Box::new(ExecCommand))
         .add_command(Box::new(AddCommand))
         .add_command(Box::new(RemoveCommand))
         .add_command(Box::new(LockCommand))
@@ -1304,7 +3472,17 @@ fn main() {
         .add_command(Box::new(BuildCommand))
         .add_command(Box::new(DevCommand))
         .add_command(Box::new(TestCommand))
-        .add_command(Box::new(RunCommand));
+        .add_command(Box::new(RunCommand))
+        .add_command(Box::new(SbomCommand))
+        .add_command(Box::new(ApproveScriptsCommand))
+        .add_command(Box::new(PackCommand))
+        .add_command(Box::new(VersionCommand))
+        .add_command(Box::new(ReleaseCommand))
+        .add_command(Box::new(ChangelogCommand))
+        .add_command(Box::new(LinkCommand))
+        .add_command(Box::new(UnlinkCommand))
+        .add_command(Box::new(CacheServerCommand))
+        .add_command(Box::new(EnvCommand));
 
     if should_trigger_easter_egg() {
         show_walking_claw();
@@ -1312,6 +3490,6 @@ fn main() {
 
     if let Err(e) = app.run() {
         eprintln!("Error: {e}");
-        std::process::exit(1);
+        std::process::exit(e.exit_code());
     }
 }
@@ -0,0 +1,214 @@
+//! Minimal HTTP/1.1 for the remote cache
+//!
+//! Just enough of the protocol for `GET`/`PUT` of whole artifacts: one
+//! request per connection, bodies sized by `Content-Length` and no chunked
+//! encoding, redirects or TLS.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+/// Largest body accepted, in bytes
+pub const MAX_BODY: u64 = 1 << 30;
+
+/// A request or response without its first line
+#[derive(Debug, Clone, Default)]
+pub struct Message {
+    /// Header names lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Message {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Read the first line, headers and body of a message
+///
+/// Without `Content-Length`, the body runs to the end of the stream when
+/// `body_to_eof` is set (responses) and is empty otherwise (requests).
+pub fn read_message<R: Read>(reader: R, body_to_eof: bool) -> std::io::Result<(String, Message)> {
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let first = line.trim_end().to_string();
+    if first.is_empty() {
+        return Err(invalid("empty message"));
+    }
+
+    let mut message = Message::default();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("headers not terminated"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid("malformed header"))?;
+        message
+            .headers
+            .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+
+    match message.header("content-length") {
+        Some(length) => {
+            let length: u64 = length
+                .parse()
+                .map_err(|_| invalid("invalid Content-Length"))?;
+            if length > MAX_BODY {
+                return Err(invalid("body too large"));
+            }
+            // Grown as data arrives, a bare header must not reserve the memory
+            let mut body = Vec::new();
+            reader.take(length).read_to_end(&mut body)?;
+            if body.len() as u64 != length {
+                return Err(invalid("body shorter than Content-Length"));
+            }
+            message.body = body;
+        }
+        None if body_to_eof => {
+            reader.take(MAX_BODY).read_to_end(&mut message.body)?;
+        }
+        None => {}
+    }
+    Ok((first, message))
+}
+
+/// Write a message with the given first line, adding `Content-Length`
+pub fn write_message<W: Write>(
+    mut writer: W,
+    first: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> std::io::Result<()> {
+    let mut head = format!("{first}\r\n");
+    for (name, value) in headers {
+        head.push_str(&format!("{name}: {value}\r\n"));
+    }
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    ));
+    writer.write_all(head.as_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()
+}
+
+/// An `http://host[:port][/prefix]` base URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    pub host: String,
+    pub port: u16,
+    /// Path prefix without a trailing `/`
+    pub prefix: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            if url.starts_with("https://") {
+                "https is not supported, put a TLS-terminating proxy in front of the cache"
+                    .to_string()
+            } else {
+                "expected an http:// URL".to_string()
+            }
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse().map_err(|_| format!("invalid port '{port}'"))?,
+            ),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err("missing host".to_string());
+        }
+        Ok(Url {
+            host: host.to_string(),
+            port,
+            prefix: path.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn host_header(&self) -> String {
+        if self.port == 80 {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Send one request and read the response, returning the status code
+pub fn request(
+    url: &Url,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> std::io::Result<(u16, Message)> {
+    let timeout = Some(std::time::Duration::from_secs(30));
+    let stream = TcpStream::connect((url.host.as_str(), url.port))?;
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+    let host = url.host_header();
+    let mut all_headers = vec![("Host", host.as_str())];
+    all_headers.extend_from_slice(headers);
+    write_message(
+        &stream,
+        &format!("{method} {}{path} HTTP/1.1", url.prefix),
+        &all_headers,
+        body,
+    )?;
+    let (status_line, response) = read_message(&stream, true)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid("malformed status line"))?;
+    Ok((status, response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url_and_message() {
+        let url = Url::parse("http://cache.internal:8080/team/").unwrap();
+        assert_eq!(url.host, "cache.internal");
+        assert_eq!(url.port, 8080);
+        assert_eq!(url.prefix, "/team");
+        assert_eq!(Url::parse("http://localhost").unwrap().port, 80);
+        assert!(Url::parse("https://cache.example.com").is_err());
+
+        let mut raw = Vec::new();
+        write_message(
+            &mut raw,
+            "PUT /v1/artifacts/abc HTTP/1.1",
+            &[("X-Artifact-Tag", "t")],
+            b"data",
+        )
+        .unwrap();
+        let (first, message) = read_message(raw.as_slice(), false).unwrap();
+        assert_eq!(first, "PUT /v1/artifacts/abc HTTP/1.1");
+        assert_eq!(message.header("x-artifact-tag"), Some("t"));
+        assert_eq!(message.body, b"data");
+    }
+}
@@ -0,0 +1,174 @@
+//! Local task cache
+//!
+//! Cached tasks (see [`crate::task`]) store one entry per fingerprint in
+//! `.cpm/cache/<fingerprint>` of their package: the captured output as
+//! `stdout.log` and `stderr.log` and a copy of the declared outputs under
+//! `outputs/`. On a hit the outputs are copied back and the logs replayed
+//! instead of running the command.
+//!
+//! Entries can also be shared through a [`remote`] cache as artifacts made
+//! with [`pack`], for example one run with `cpm cache-server`.
+
+mod http;
+pub mod remote;
+pub mod server;
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::glob::{glob_files, relative_path, walk_files};
+use crate::script::Captured;
+use std::path::{Path, PathBuf};
+
+/// Where entries are stored, relative to the package
+pub const CACHE_DIR: &str = ".cpm/cache";
+
+fn entry_dir(dir: &Path, fingerprint: &str) -> PathBuf {
+    dir.join(CACHE_DIR).join(fingerprint)
+}
+
+fn copy_file(from: &Path, to: &Path) -> CliResult<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(from, to).map_err(|e| CliError::FileOperationError {
+        operation: "copy".to_string(),
+        path: from.display().to_string(),
+        message: e.to_string(),
+    })?;
+    Ok(())
+}
+
+/// Restore the entry for `fingerprint` into the package in `dir`
+///
+/// Returns the captured output to replay, or `None` on a cache miss.
+pub fn restore(dir: &Path, fingerprint: &str) -> CliResult<Option<Captured>> {
+    let entry = entry_dir(dir, fingerprint);
+    if !entry.join("stdout.log").is_file() {
+        return Ok(None);
+    }
+    let outputs = entry.join("outputs");
+    for file in walk_files(&outputs, |_| true) {
+        copy_file(&file, &dir.join(relative_path(&outputs, &file)))?;
+    }
+    Ok(Some(Captured {
+        stdout: std::fs::read(entry.join("stdout.log"))?,
+        stderr: std::fs::read(entry.join("stderr.log"))?,
+    }))
+}
+
+/// Store the `outputs` of a task that just ran, and its output, under `fingerprint`
+///
+/// Returns the number of output files stored.
+pub fn store(
+    dir: &Path,
+    fingerprint: &str,
+    outputs: &[String],
+    captured: &Captured,
+) -> CliResult<usize> {
+    let entry = entry_dir(dir, fingerprint);
+    // Written next to the entry and renamed, so a partial entry is never a hit
+    let staging = dir
+        .join(CACHE_DIR)
+        .join(format!("{fingerprint}.{}.tmp", std::process::id()));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(staging.join("outputs"))?;
+    let files = glob_files(dir, outputs);
+    for file in &files {
+        copy_file(
+            file,
+            &staging.join("outputs").join(relative_path(dir, file)),
+        )?;
+    }
+    std::fs::write(staging.join("stdout.log"), &captured.stdout)?;
+    std::fs::write(staging.join("stderr.log"), &captured.stderr)?;
+    if entry.exists() {
+        std::fs::remove_dir_all(&entry)?;
+    }
+    std::fs::rename(&staging, &entry)?;
+    Ok(files.len())
+}
+
+/// The entry for `fingerprint` as a gzipped tar, for a remote cache
+pub fn pack(dir: &Path, fingerprint: &str) -> CliResult<Vec<u8>> {
+    let entry = entry_dir(dir, fingerprint);
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.mode(tar::HeaderMode::Deterministic);
+    builder.append_dir_all(".", &entry)?;
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// Store an artifact made by [`pack`] as the entry for `fingerprint`
+///
+/// Call [`restore`] afterwards to apply it.
+pub fn unpack(dir: &Path, fingerprint: &str, artifact: &[u8]) -> CliResult<()> {
+    let staging = dir
+        .join(CACHE_DIR)
+        .join(format!("{fingerprint}.{}.tmp", std::process::id()));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    std::fs::create_dir_all(&staging)?;
+    let decoder = flate2::read::GzDecoder::new(artifact);
+    // `unpack` refuses entries that would land outside `staging`
+    tar::Archive::new(decoder)
+        .unpack(&staging)
+        .map_err(|e| CliError::FileOperationError {
+            operation: "unpack cache artifact".to_string(),
+            path: fingerprint.to_string(),
+            message: e.to_string(),
+        })?;
+    if !staging.join("stdout.log").is_file() {
+        std::fs::remove_dir_all(&staging)?;
+        return Err(CliError::FileOperationError {
+            operation: "unpack cache artifact".to_string(),
+            path: fingerprint.to_string(),
+            message: "not a task cache entry".to_string(),
+        });
+    }
+    let entry = entry_dir(dir, fingerprint);
+    if entry.exists() {
+        std::fs::remove_dir_all(&entry)?;
+    }
+    std::fs::rename(&staging, &entry)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_restore_outputs() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("dist/types")).unwrap();
+        std::fs::write(dir.join("dist/index.js"), "built").unwrap();
+        std::fs::write(dir.join("dist/types/index.d.ts"), "types").unwrap();
+        std::fs::write(dir.join("index.ts"), "source").unwrap();
+        let captured = Captured {
+            stdout: b"compiled\n".to_vec(),
+            stderr: b"1 warning\n".to_vec(),
+        };
+
+        assert_eq!(restore(dir, "abc").unwrap(), None);
+        let outputs = vec!["dist/**".to_string()];
+        assert_eq!(store(dir, "abc", &outputs, &captured).unwrap(), 2);
+
+        std::fs::remove_dir_all(dir.join("dist")).unwrap();
+        assert_eq!(restore(dir, "abc").unwrap(), Some(captured.clone()));
+        assert_eq!(
+            std::fs::read_to_string(dir.join("dist/types/index.d.ts")).unwrap(),
+            "types"
+        );
+        assert!(!dir.join(CACHE_DIR).join("abc/outputs/index.ts").exists());
+
+        // Through an artifact, as a remote cache would
+        let artifact = pack(dir, "abc").unwrap();
+        let other = temp.path().join("other");
+        unpack(&other, "abc", &artifact).unwrap();
+        assert_eq!(restore(&other, "abc").unwrap(), Some(captured));
+        assert!(other.join("dist/types/index.d.ts").exists());
+    }
+}
@@ -0,0 +1,164 @@
+//! Remote task cache client
+//!
+//! Artifacts are the gzipped tar of a local cache entry, stored under their
+//! fingerprint:
+//!
+//! - `GET /v1/artifacts/<fingerprint>`: `200` with the artifact, or `404`
+//! - `PUT /v1/artifacts/<fingerprint>`: store the artifact
+//!
+//! With a signing key, uploads carry an `x-artifact-tag` header with the
+//! base64 HMAC-SHA256 of the fingerprint and artifact, and downloads without
+//! a valid tag are rejected, so a shared cache cannot inject outputs for
+//! anyone holding the key. Configured in `cpm.toml`:
+//!
+//! ```toml
+//! [cache]
+//! remote = "http://cache.internal:4874"
+//! ```
+//!
+//! `CPM_CACHE_URL` overrides the URL and `CPM_CACHE_KEY` holds the key.
+
+use super::http::{self, Url};
+use crate::cli::framework::{CliError, CliResult};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+/// Environment variable overriding `[cache] remote`
+pub const URL_ENV: &str = "CPM_CACHE_URL";
+/// Environment variable holding the signing key
+pub const KEY_ENV: &str = "CPM_CACHE_KEY";
+/// Header carrying the artifact signature
+pub const TAG_HEADER: &str = "x-artifact-tag";
+
+/// The `[cache]` section of `cpm.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CacheConfig {
+    /// Base URL of the remote cache
+    pub remote: Option<String>,
+}
+
+fn mac(key: &[u8], fingerprint: &str, artifact: &[u8]) -> Hmac<Sha256> {
+    // HMAC takes keys of any length, longer ones are hashed first
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC key of any length");
+    mac.update(fingerprint.as_bytes());
+    mac.update(artifact);
+    mac
+}
+
+/// Base64 HMAC-SHA256 of a fingerprint and its artifact
+pub fn sign(key: &[u8], fingerprint: &str, artifact: &[u8]) -> String {
+    let signature = mac(key, fingerprint, artifact).finalize().into_bytes();
+    base64::engine::general_purpose::STANDARD.encode(signature)
+}
+
+/// Whether `tag` is the signature of the artifact, compared in constant time
+pub fn verify(key: &[u8], fingerprint: &str, artifact: &[u8], tag: &str) -> bool {
+    base64::engine::general_purpose::STANDARD
+        .decode(tag)
+        .is_ok_and(|tag| mac(key, fingerprint, artifact).verify_slice(&tag).is_ok())
+}
+
+/// Path of an artifact on the server
+pub fn artifact_path(fingerprint: &str) -> String {
+    format!("/v1/artifacts/{fingerprint}")
+}
+
+/// A configured remote cache
+#[derive(Debug, Clone)]
+pub struct RemoteCache {
+    url: Url,
+    key: Option<Vec<u8>>,
+}
+
+impl RemoteCache {
+    pub fn new(url: &str, key: Option<&[u8]>) -> CliResult<Self> {
+        let url = Url::parse(url).map_err(|message| CliError::ExecutionError {
+            command: format!("remote cache {url}"),
+            message,
+        })?;
+        Ok(RemoteCache {
+            url,
+            key: key.map(<[u8]>::to_vec),
+        })
+    }
+
+    /// The remote cache from the environment or `config`, if any
+    pub fn configured(config: Option<&CacheConfig>) -> CliResult<Option<Self>> {
+        let url = std::env::var(URL_ENV)
+            .ok()
+            .filter(|url| !url.is_empty())
+            .or_else(|| config.and_then(|c| c.remote.clone()));
+        let key = std::env::var(KEY_ENV).ok().filter(|key| !key.is_empty());
+        url.map(|url| Self::new(&url, key.as_deref().map(str::as_bytes)))
+            .transpose()
+    }
+
+    /// Whether artifacts are signed and verified
+    pub fn is_signed(&self) -> bool {
+        self.key.is_some()
+    }
+
+    fn error(&self, action: &str, fingerprint: &str, message: String) -> CliError {
+        CliError::ExecutionError {
+            command: format!(
+                "{action} {}:{}{}",
+                self.url.host,
+                self.url.port,
+                artifact_path(fingerprint)
+            ),
+            message,
+        }
+    }
+
+    /// Download an artifact, `None` when the cache does not have it
+    pub fn get(&self, fingerprint: &str) -> CliResult<Option<Vec<u8>>> {
+        let (status, response) =
+            http::request(&self.url, "GET", &artifact_path(fingerprint), &[], &[])
+                .map_err(|e| self.error("GET", fingerprint, e.to_string()))?;
+        match status {
+            200 => {}
+            404 => return Ok(None),
+            _ => return Err(self.error("GET", fingerprint, format!("HTTP {status}"))),
+        }
+        if let Some(key) = &self.key {
+            let valid = response
+                .header(TAG_HEADER)
+                .is_some_and(|tag| verify(key, fingerprint, &response.body, tag));
+            if !valid {
+                return Err(self.error(
+                    "GET",
+                    fingerprint,
+                    "artifact signature is missing or invalid".to_string(),
+                ));
+            }
+        }
+        Ok(Some(response.body))
+    }
+
+    /// Upload an artifact
+    pub fn put(&self, fingerprint: &str, artifact: &[u8]) -> CliResult<()> {
+        let tag = self
+            .key
+            .as_ref()
+            .map(|key| sign(key, fingerprint, artifact));
+        let mut headers = vec![("Content-Type", "application/octet-stream")];
+        if let Some(tag) = &tag {
+            headers.push((TAG_HEADER, tag));
+        }
+        let (status, _) = http::request(
+            &self.url,
+            "PUT",
+            &artifact_path(fingerprint),
+            &headers,
+            artifact,
+        )
+        .map_err(|e| self.error("PUT", fingerprint, e.to_string()))?;
+        if !(200..300).contains(&status) {
+            return Err(self.error("PUT", fingerprint, format!("HTTP {status}")));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+//! Built-in remote cache server (`cpm cache-server`)
+//!
+//! Serves the protocol of [`super::remote`] from a directory, one file per
+//! artifact plus a `.tag` file with its signature. It is meant for a single
+//! machine, tests and small teams; with a key it also refuses uploads that
+//! are not signed with it.
+
+use super::http::{self, Message};
+use super::remote::{TAG_HEADER, verify};
+use crate::cli::framework::{CliError, CliResult};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Whether a fingerprint is safe to use as a file name
+fn valid_fingerprint(fingerprint: &str) -> bool {
+    !fingerprint.is_empty()
+        && fingerprint.len() <= 128
+        && fingerprint
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+struct Store {
+    dir: PathBuf,
+    key: Option<Vec<u8>>,
+}
+
+impl Store {
+    /// Status, headers and body of the response to a request
+    fn respond(
+        &self,
+        method: &str,
+        path: &str,
+        request: &Message,
+    ) -> (u16, Vec<(&'static str, String)>, Vec<u8>) {
+        let Some(fingerprint) = path
+            .strip_prefix("/v1/artifacts/")
+            .filter(|f| valid_fingerprint(f))
+        else {
+            return (404, Vec::new(), Vec::new());
+        };
+        let file = self.dir.join(fingerprint);
+        let tag_file = self.dir.join(format!("{fingerprint}.tag"));
+        match method {
+            "GET" => match std::fs::read(&file) {
+                Ok(body) => {
+                    let headers = std::fs::read_to_string(&tag_file)
+                        .map(|tag| vec![(TAG_HEADER, tag)])
+                        .unwrap_or_default();
+                    (200, headers, body)
+                }
+                Err(_) => (404, Vec::new(), Vec::new()),
+            },
+            "PUT" => {
+                let tag = request.header(TAG_HEADER);
+                if let Some(key) = &self.key {
+                    if !tag.is_some_and(|tag| verify(key, fingerprint, &request.body, tag)) {
+                        return (403, Vec::new(), b"invalid artifact signature".to_vec());
+                    }
+                }
+                match self.write(&file, &tag_file, tag, &request.body) {
+                    Ok(()) => (201, Vec::new(), Vec::new()),
+                    Err(e) => (500, Vec::new(), e.to_string().into_bytes()),
+                }
+            }
+            _ => (405, vec![("Allow", "GET, PUT".to_string())], Vec::new()),
+        }
+    }
+
+    /// Write through a temporary file, so readers never see a partial artifact
+    fn write(
+        &self,
+        file: &Path,
+        tag_file: &Path,
+        tag: Option<&str>,
+        body: &[u8],
+    ) -> std::io::Result<()> {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        let staging = self
+            .dir
+            .join(format!("{name}.{:?}.tmp", std::thread::current().id()));
+        std::fs::write(&staging, body)?;
+        match tag {
+            Some(tag) => std::fs::write(tag_file, tag)?,
+            None if tag_file.exists() => std::fs::remove_file(tag_file)?,
+            None => {}
+        }
+        std::fs::rename(&staging, file)
+    }
+
+    fn handle(&self, stream: TcpStream) -> std::io::Result<(String, String, u16)> {
+        let timeout = Some(std::time::Duration::from_secs(30));
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        let (status, method, path) = match http::read_message(&stream, false) {
+            Ok((line, request)) => {
+                let mut parts = line.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_string();
+                let path = parts.next().unwrap_or_default().to_string();
+                let (status, headers, body) = self.respond(&method, &path, &request);
+                let headers: Vec<(&str, &str)> = headers
+                    .iter()
+                    .map(|(name, value)| (*name, value.as_str()))
+                    .collect();
+                let first = format!("HTTP/1.1 {status} {}", reason(status));
+                http::write_message(&stream, &first, &headers, &body)?;
+                (status, method, path)
+            }
+            Err(e) => {
+                let message = e.to_string().into_bytes();
+                http::write_message(&stream, "HTTP/1.1 400 Bad Request", &[], &message)?;
+                (400, String::new(), String::new())
+            }
+        };
+        Ok((method, path, status))
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    }
+}
+
+/// A bound cache server
+pub struct CacheServer {
+    listener: TcpListener,
+    store: Arc<Store>,
+}
+
+impl CacheServer {
+    /// Listen on `addr`, storing artifacts in `dir`
+    ///
+    /// With a `key`, uploads must be signed with it.
+    pub fn bind(addr: &str, dir: &Path, key: Option<&[u8]>) -> CliResult<Self> {
+        std::fs::create_dir_all(dir)?;
+        let listener = TcpListener::bind(addr).map_err(|e| CliError::ExecutionError {
+            command: format!("cache-server {addr}"),
+            message: e.to_string(),
+        })?;
+        Ok(CacheServer {
+            listener,
+            store: Arc::new(Store {
+                dir: dir.to_path_buf(),
+                key: key.map(<[u8]>::to_vec),
+            }),
+        })
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Answer requests, one thread per connection, calling `log` with the
+    /// method, path and status of each
+    pub fn serve<F>(self, log: F) -> CliResult<()>
+    where
+        F: Fn(&str, &str, u16) + Send + Sync + 'static,
+    {
+        let log = Arc::new(log);
+        for stream in self.listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let store = Arc::clone(&self.store);
+            let log = Arc::clone(&log);
+            std::thread::spawn(move || {
+                if let Ok((method, path, status)) = store.handle(stream) {
+                    log(&method, &path, status);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::remote::RemoteCache;
+
+    fn start(dir: &Path, key: Option<&[u8]>) -> String {
+        let server = CacheServer::bind("127.0.0.1:0", dir, key).unwrap();
+        let url = format!("http://{}", server.local_addr().unwrap());
+        std::thread::spawn(move || server.serve(|_, _, _| {}));
+        url
+    }
+
+    #[test]
+    fn test_round_trip_and_signatures() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let url = start(&temp.path().join("open"), None);
+        let client = RemoteCache::new(&url, None).unwrap();
+        assert_eq!(client.get("abc123").unwrap(), None);
+        client.put("abc123", b"artifact").unwrap();
+        assert_eq!(client.get("abc123").unwrap().unwrap(), b"artifact");
+        assert!(client.put("../escape", b"x").is_err());
+
+        // A signing client rejects the unsigned artifact
+        let signing = RemoteCache::new(&url, Some(b"secret")).unwrap();
+        assert!(signing.get("abc123").is_err());
+        signing.put("def456", b"signed").unwrap();
+        assert_eq!(signing.get("def456").unwrap().unwrap(), b"signed");
+        let other_key = RemoteCache::new(&url, Some(b"other")).unwrap();
+        assert!(other_key.get("def456").is_err());
+
+        // A server with a key only accepts uploads signed with it
+        let url = start(&temp.path().join("signed"), Some(b"secret"));
+        assert!(
+            RemoteCache::new(&url, None)
+                .unwrap()
+                .put("abc", b"x")
+                .is_err()
+        );
+        let signing = RemoteCache::new(&url, Some(b"secret")).unwrap();
+        signing.put("abc", b"x").unwrap();
+    }
+}
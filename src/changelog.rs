@@ -0,0 +1,454 @@
+//! CHANGELOG.md maintenance
+//!
+//! Edits a [Keep a Changelog](https://keepachangelog.com) style file: release
+//! sections are `## [x.y.z] - YYYY-MM-DD` headings, newest first, below an
+//! optional `## [Unreleased]` section. The `Unreleased` section can be
+//! generated from [Conventional Commits](https://www.conventionalcommits.org)
+//! in the git history since the last version tag.
+
+use crate::cli::framework::CliResult;
+use crate::version::Bump;
+use std::path::Path;
+
+/// Name of the changelog file in the project root
+pub const CHANGELOG_FILE: &str = "CHANGELOG.md";
+
+const DEFAULT_HEADER: &str =
+    "# Changelog\n\nAll notable changes to this project will be documented in this file.\n";
+
+fn is_unreleased_heading(line: &str) -> bool {
+    let Some(title) = line.strip_prefix("## ") else {
+        return false;
+    };
+    title
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .eq_ignore_ascii_case("unreleased")
+}
+
+/// Turn the `Unreleased` section into a `version` section dated `date`
+///
+/// A fresh empty `Unreleased` heading is kept above it. Without an
+/// `Unreleased` section, an empty release section is inserted above the
+/// newest release.
+pub fn promote_unreleased(content: &str, version: &str, date: &str) -> String {
+    let release_heading = format!("## [{version}] - {date}");
+    let content = if content.trim().is_empty() {
+        DEFAULT_HEADER.to_string()
+    } else {
+        content.to_string()
+    };
+
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    if let Some(idx) = lines.iter().position(|l| is_unreleased_heading(l)) {
+        lines.splice(
+            idx..=idx,
+            [
+                "## [Unreleased]".to_string(),
+                String::new(),
+                release_heading,
+            ],
+        );
+    } else {
+        let idx = lines
+            .iter()
+            .position(|l| l.starts_with("## "))
+            .unwrap_or(lines.len());
+        if idx < lines.len() {
+            lines.splice(idx..idx, [release_heading, String::new()]);
+        } else {
+            if lines.last().is_some_and(|l| !l.is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(release_heading);
+        }
+    }
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+/// A commit message parsed as a Conventional Commit (`type(scope)!: description`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    pub kind: String,
+    pub scope: Option<String>,
+    pub description: String,
+    /// `!` after the type/scope, or a `BREAKING CHANGE:` footer
+    pub breaking: bool,
+}
+
+impl ConventionalCommit {
+    /// Parse a commit subject and body; `None` for non-conventional messages
+    pub fn parse(subject: &str, body: &str) -> Option<Self> {
+        let (header, description) = subject.split_once(": ")?;
+        let (header, bang) = match header.strip_suffix('!') {
+            Some(header) => (header, true),
+            None => (header, false),
+        };
+        let (kind, scope) = match header.split_once('(') {
+            Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?.to_string())),
+            None => (header, None),
+        };
+        if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        let breaking = bang
+            || body.lines().any(|line| {
+                line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+            });
+        Some(ConventionalCommit {
+            kind: kind.to_ascii_lowercase(),
+            scope,
+            description: description.trim().to_string(),
+            breaking,
+        })
+    }
+
+    /// Keep a Changelog section this commit belongs to, if any
+    pub fn section(&self) -> Option<&'static str> {
+        match self.kind.as_str() {
+            "feat" => Some("Added"),
+            "fix" => Some("Fixed"),
+            "perf" | "refactor" | "revert" => Some("Changed"),
+            // Breaking changes are always worth a line, whatever their type
+            _ if self.breaking => Some("Changed"),
+            _ => None,
+        }
+    }
+
+    /// The changelog line for this commit
+    pub fn entry(&self) -> String {
+        let mut description = self.description.clone();
+        if let Some(first) = description.get(..1) {
+            description.replace_range(..1, &first.to_uppercase());
+        }
+        let mut line = match &self.scope {
+            Some(scope) => format!("- **{}**: {}", scope, description),
+            None => format!("- {}", description),
+        };
+        if self.breaking {
+            line.push_str(" (**BREAKING**)");
+        }
+        line
+    }
+}
+
+/// Section names in the order they are written
+const SECTIONS: &[&str] = &["Added", "Changed", "Fixed"];
+
+/// Changes grouped into changelog sections
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSet {
+    pub commits: Vec<ConventionalCommit>,
+    /// Commits whose message does not follow the convention
+    pub skipped: usize,
+}
+
+impl ChangeSet {
+    /// Build a change set from `(subject, body)` pairs, newest first
+    pub fn from_messages<'a, I>(messages: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        let mut changes = ChangeSet::default();
+        for (subject, body) in messages {
+            match ConventionalCommit::parse(subject, body) {
+                Some(commit) => changes.commits.push(commit),
+                None => changes.skipped += 1,
+            }
+        }
+        changes
+    }
+
+    /// Whether any commit is a breaking change
+    pub fn has_breaking(&self) -> bool {
+        self.commits.iter().any(|c| c.breaking)
+    }
+
+    /// The semver bump these changes call for
+    ///
+    /// Before 1.0.0 a breaking change only bumps the minor version.
+    pub fn suggested_bump(&self, current: &semver::Version) -> Bump {
+        if self.has_breaking() {
+            if current.major == 0 {
+                Bump::Minor
+            } else {
+                Bump::Major
+            }
+        } else if self.commits.iter().any(|c| c.kind == "feat") {
+            Bump::Minor
+        } else {
+            Bump::Patch
+        }
+    }
+
+    /// Render the body of a release section (`### Added` … lists)
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for section in SECTIONS {
+            let entries: Vec<String> = self
+                .commits
+                .iter()
+                .filter(|c| c.section() == Some(section))
+                .map(ConventionalCommit::entry)
+                .collect();
+            if entries.is_empty() {
+                continue;
+            }
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("### {}\n", section));
+            for entry in entries {
+                out.push_str(&entry);
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// The most recent `v*` tag reachable from `HEAD`
+pub fn last_version_tag(root: &Path) -> Option<String> {
+    crate::git::run(
+        root,
+        &["describe", "--tags", "--abbrev=0", "--match", "v[0-9]*"],
+    )
+    .ok()
+}
+
+/// Read the commits after `since` (or all of history) as a change set
+pub fn collect(root: &Path, since: Option<&str>) -> CliResult<ChangeSet> {
+    let range = since.map(|tag| format!("{tag}..HEAD"));
+    let mut args = vec!["log", "--format=%s%x1f%b%x1e"];
+    if let Some(range) = &range {
+        args.push(range);
+    }
+    let log = crate::git::run(root, &args)?;
+    Ok(ChangeSet::from_messages(
+        log.split('\x1e')
+            .map(str::trim_start)
+            .filter(|record| !record.is_empty())
+            .map(|record| record.split_once('\x1f').unwrap_or((record, ""))),
+    ))
+}
+
+/// Lines before the first `### ` heading, then each subsection with its lines
+fn split_subsections(lines: &[String]) -> (Vec<String>, Vec<(String, Vec<String>)>) {
+    let mut preamble = Vec::new();
+    let mut subsections: Vec<(String, Vec<String>)> = Vec::new();
+    for line in lines {
+        if line.starts_with("### ") {
+            subsections.push((line.clone(), Vec::new()));
+        } else {
+            match subsections.last_mut() {
+                Some((_, body)) => body.push(line.clone()),
+                None => preamble.push(line.clone()),
+            }
+        }
+    }
+    (preamble, subsections)
+}
+
+fn trim_blank_lines(lines: &mut Vec<String>) {
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+    let leading = lines.iter().take_while(|l| l.trim().is_empty()).count();
+    lines.drain(..leading);
+}
+
+/// Add the entries of `body` to the `Unreleased` body `existing`
+///
+/// Entries join the subsection of the same name after the ones already there,
+/// lines already present are not repeated, and missing subsections are added
+/// in [`SECTIONS`] order.
+fn merge_unreleased(existing: &[String], body: &str) -> Vec<String> {
+    let order = |heading: &str| {
+        let name = heading.trim_start_matches('#').trim();
+        SECTIONS
+            .iter()
+            .position(|section| section.eq_ignore_ascii_case(name))
+            .unwrap_or(SECTIONS.len())
+    };
+    let (mut preamble, mut subsections) = split_subsections(existing);
+    let body: Vec<String> = body.lines().map(str::to_string).collect();
+    let (_, additions) = split_subsections(&body);
+    for (heading, entries) in additions {
+        let entries: Vec<String> = entries
+            .into_iter()
+            .filter(|l| !l.trim().is_empty())
+            .collect();
+        let same = subsections
+            .iter()
+            .position(|(existing, _)| existing.trim().eq_ignore_ascii_case(heading.trim()));
+        match same {
+            Some(idx) => {
+                let lines = &mut subsections[idx].1;
+                trim_blank_lines(lines);
+                for entry in entries {
+                    if !lines.contains(&entry) {
+                        lines.push(entry);
+                    }
+                }
+            }
+            None => {
+                let idx = subsections
+                    .iter()
+                    .position(|(existing, _)| order(existing) > order(&heading))
+                    .unwrap_or(subsections.len());
+                subsections.insert(idx, (heading, entries));
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    trim_blank_lines(&mut preamble);
+    if !preamble.is_empty() {
+        out.extend(preamble);
+        out.push(String::new());
+    }
+    for (heading, mut lines) in subsections {
+        trim_blank_lines(&mut lines);
+        out.push(heading);
+        out.extend(lines);
+        out.push(String::new());
+    }
+    out
+}
+
+/// Add `body` to the `Unreleased` section, keeping the entries already there
+///
+/// The section is created above the newest release when missing.
+pub fn write_unreleased(content: &str, body: &str) -> String {
+    let content = if content.trim().is_empty() {
+        DEFAULT_HEADER.to_string()
+    } else {
+        content.to_string()
+    };
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    match lines.iter().position(|l| is_unreleased_heading(l)) {
+        Some(start) => {
+            let end = lines[start + 1..]
+                .iter()
+                .position(|l| l.starts_with("## "))
+                .map_or(lines.len(), |offset| start + 1 + offset);
+            let mut section = vec![lines[start].clone(), String::new()];
+            section.extend(merge_unreleased(&lines[start + 1..end], body));
+            lines.splice(start..end, section);
+        }
+        None => {
+            let mut section = vec!["## [Unreleased]".to_string(), String::new()];
+            section.extend(body.lines().map(str::to_string));
+            section.push(String::new());
+            let idx = lines
+                .iter()
+                .position(|l| l.starts_with("## "))
+                .unwrap_or(lines.len());
+            if idx == lines.len() && lines.last().is_some_and(|l| !l.is_empty()) {
+                lines.push(String::new());
+            }
+            lines.splice(idx..idx, section);
+        }
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    let mut result = lines.join("\n");
+    result.push('\n');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_promote_unreleased_section() {
+        let content = "# Changelog\n\n## [Unreleased]\n\n### Added\n- Pack command\n\n## [0.4.0] - 2025-09-02\n";
+        assert_eq!(
+            promote_unreleased(content, "0.5.0", "2025-10-01"),
+            "# Changelog\n\n## [Unreleased]\n\n## [0.5.0] - 2025-10-01\n\n### Added\n- Pack command\n\n## [0.4.0] - 2025-09-02\n"
+        );
+    }
+
+    #[test]
+    fn test_promote_without_unreleased_section() {
+        let content = "# Changelog\n\n## [0.4.0] - 2025-09-02\n";
+        assert_eq!(
+            promote_unreleased(content, "0.4.1", "2025-10-01"),
+            "# Changelog\n\n## [0.4.1] - 2025-10-01\n\n## [0.4.0] - 2025-09-02\n"
+        );
+        assert!(
+            promote_unreleased("", "1.0.0", "2025-10-01")
+                .ends_with("file.\n\n## [1.0.0] - 2025-10-01\n")
+        );
+    }
+
+    #[test]
+    fn test_parse_conventional_commits() {
+        let commit = ConventionalCommit::parse("feat(pack)!: drop npm 6 support", "").unwrap();
+        assert_eq!(commit.kind, "feat");
+        assert_eq!(commit.scope.as_deref(), Some("pack"));
+        assert!(commit.breaking);
+        assert_eq!(
+            commit.entry(),
+            "- **pack**: Drop npm 6 support (**BREAKING**)"
+        );
+
+        let commit =
+            ConventionalCommit::parse("refactor: split cli", "BREAKING CHANGE: new flags").unwrap();
+        assert!(commit.breaking);
+        assert_eq!(commit.section(), Some("Changed"));
+        assert_eq!(
+            ConventionalCommit::parse("docs: typo", "")
+                .unwrap()
+                .section(),
+            None
+        );
+        assert!(ConventionalCommit::parse("Merge branch 'main'", "").is_none());
+        assert!(ConventionalCommit::parse("[user-029] Add cpm pack: tarballs", "").is_none());
+    }
+
+    #[test]
+    fn test_change_set_render_and_bump() {
+        let changes = ChangeSet::from_messages([
+            ("fix: handle empty lockfile", ""),
+            ("feat(sbom): add SPDX output", ""),
+            ("chore: bump deps", ""),
+            ("update readme", ""),
+        ]);
+        assert_eq!(changes.skipped, 1);
+        assert_eq!(
+            changes.render(),
+            "### Added\n- **sbom**: Add SPDX output\n\n### Fixed\n- Handle empty lockfile\n"
+        );
+        let v = |s| semver::Version::parse(s).unwrap();
+        assert_eq!(changes.suggested_bump(&v("1.2.3")), Bump::Minor);
+
+        let breaking = ChangeSet::from_messages([("fix!: rename flag", "")]);
+        assert_eq!(breaking.suggested_bump(&v("1.2.3")), Bump::Major);
+        assert_eq!(breaking.suggested_bump(&v("0.4.0")), Bump::Minor);
+    }
+
+    #[test]
+    fn test_write_unreleased_keeps_existing_entries() {
+        let content = "# Changelog\n\n## [Unreleased]\n\nHighlights first.\n\n### Added\n- Hand-written\n\n### Fixed\n- Old\n\n## [0.4.0] - 2025-09-02\n- Initial\n";
+        let body = "### Added\n- New\n\n### Changed\n- Faster\n\n### Fixed\n- Old\n";
+        let merged = "# Changelog\n\n## [Unreleased]\n\nHighlights first.\n\n### Added\n- Hand-written\n- New\n\n### Changed\n- Faster\n\n### Fixed\n- Old\n\n## [0.4.0] - 2025-09-02\n- Initial\n";
+        assert_eq!(write_unreleased(content, body), merged);
+        // Writing the same changes again adds nothing
+        assert_eq!(write_unreleased(merged, body), merged);
+        assert_eq!(
+            write_unreleased(
+                "# Changelog\n\n## [0.4.0] - 2025-09-02\n",
+                "### Added\n- New\n"
+            ),
+            "# Changelog\n\n## [Unreleased]\n\n### Added\n- New\n\n## [0.4.0] - 2025-09-02\n"
+        );
+    }
+}
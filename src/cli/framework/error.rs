@@ -17,6 +17,10 @@ pub enum CliError {
     ExecutionError { command: String, message: String },
     /// Internal error
     InternalError { message: String },
+    /// Dependency policy violation
+    PolicyViolation { message: String },
+    /// A package script exited unsuccessfully; cpm exits with the same code
+    ScriptFailed { script: String, code: i32 },
     /// IO error
     IoError(std::io::Error),
     /// JSON error
@@ -47,6 +51,12 @@ impl fmt::Display for CliError {
             CliError::InternalError { message } => {
                 write!(f, "Internal error: {message}")
             }
+            CliError::PolicyViolation { message } => {
+                write!(f, "Dependency policy violated:{message}")
+            }
+            CliError::ScriptFailed { script, code } => {
+                write!(f, "Script '{script}' exited with code {code}")
+            }
             CliError::IoError(e) => {
                 write!(f, "IO error: {e}")
             }
@@ -60,6 +70,16 @@ impl fmt::Display for CliError {
     }
 }
 
+impl CliError {
+    /// Process exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::ScriptFailed { code, .. } => *code,
+            _ => 1,
+        }
+    }
+}
+
 impl std::error::Error for CliError {}
 
 impl From<std::io::Error> for CliError {
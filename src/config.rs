@@ -0,0 +1,205 @@
+//! Project configuration
+//!
+//! CPM keeps its own per-project settings in a `cpm.toml` file next to
+//! `package.json`. All sections are optional; a missing file means defaults.
+
+use crate::cache::remote::CacheConfig;
+use crate::cli::framework::{CliError, CliResult};
+use crate::policy::PolicyConfig;
+use crate::release::ReleaseConfig;
+use crate::runtime::RuntimeConfig;
+use crate::script::ScriptsConfig;
+use crate::task::TaskConfig;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+
+/// Name of the project configuration file
+pub const CONFIG_FILE: &str = "cpm.toml";
+
+/// Settings for dependency lifecycle (install) scripts
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct InstallScriptsConfig {
+    /// Dependencies allowed to run their install scripts
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Dependencies whose install scripts were reviewed and rejected
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// Approval state of a dependency's install scripts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptApproval {
+    Allowed,
+    Denied,
+    Pending,
+}
+
+impl InstallScriptsConfig {
+    /// Look up the approval state of a package
+    pub fn approval(&self, package: &str) -> ScriptApproval {
+        if self.deny.iter().any(|p| p == package) {
+            ScriptApproval::Denied
+        } else if self.allow.iter().any(|p| p == package) {
+            ScriptApproval::Allowed
+        } else {
+            ScriptApproval::Pending
+        }
+    }
+}
+
+/// Contents of `cpm.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CpmConfig {
+    /// Remote task cache
+    pub cache: Option<CacheConfig>,
+    /// Install script allow-list; when present, dependency scripts only run if approved
+    pub install_scripts: Option<InstallScriptsConfig>,
+    /// Dependency policy checked by `cpm add`, `cpm install` and `cpm lock`
+    pub policy: Option<PolicyConfig>,
+    /// Settings for `cpm release`
+    pub release: Option<ReleaseConfig>,
+    /// JavaScript runtime preference for `cpm dev` and `cpm run file.js`
+    pub runtime: Option<RuntimeConfig>,
+    /// How `cpm run` executes `package.json` scripts
+    pub scripts: Option<ScriptsConfig>,
+    /// Tasks runnable with `cpm run <name>`
+    #[serde(default)]
+    pub tasks: BTreeMap<String, TaskConfig>,
+}
+
+impl CpmConfig {
+    /// Path of the configuration file for a project directory
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(CONFIG_FILE)
+    }
+
+    /// Load the configuration of the project in `dir`, or defaults when absent
+    pub fn load(dir: &Path) -> CliResult<Self> {
+        let path = Self::path(dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content).map_err(|e| CliError::FileOperationError {
+            operation: "parse config".to_string(),
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })
+    }
+}
+
+/// Edit `cpm.toml` in place, preserving comments and formatting
+///
+/// The file is created when it does not exist yet.
+pub fn edit_config<F>(dir: &Path, edit: F) -> CliResult<()>
+where
+    F: FnOnce(&mut DocumentMut) -> CliResult<()>,
+{
+    let path = CpmConfig::path(dir);
+    let content = if path.exists() {
+        std::fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    let mut document: DocumentMut =
+        content
+            .parse()
+            .map_err(|e: toml_edit::TomlError| CliError::FileOperationError {
+                operation: "parse config".to_string(),
+                path: path.display().to_string(),
+                message: e.to_string(),
+            })?;
+    edit(&mut document)?;
+    std::fs::write(&path, document.to_string())?;
+    Ok(())
+}
+
+/// Add values to a string array `key` in `[section]`, skipping duplicates
+pub fn push_unique(document: &mut DocumentMut, section: &str, key: &str, values: &[String]) {
+    let table = document
+        .entry(section)
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut();
+    let Some(table) = table else {
+        return;
+    };
+    let item = table
+        .entry(key)
+        .or_insert(toml_edit::value(toml_edit::Array::new()));
+    if let Some(array) = item.as_array_mut() {
+        for value in values {
+            if !array.iter().any(|v| v.as_str() == Some(value)) {
+                array.push(value.as_str());
+            }
+        }
+    }
+}
+
+/// Remove values from a string array `key` in `[section]`
+pub fn remove_values(document: &mut DocumentMut, section: &str, key: &str, values: &[String]) {
+    if let Some(array) = document
+        .get_mut(section)
+        .and_then(|s| s.get_mut(key))
+        .and_then(|k| k.as_array_mut())
+    {
+        array.retain(|v| !values.iter().any(|value| v.as_str() == Some(value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_missing_config_is_default() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let config = CpmConfig::load(temp.path()).unwrap();
+        assert!(config.install_scripts.is_none());
+    }
+
+    #[test]
+    fn test_load_scripts_and_tasks() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp.path().join(CONFIG_FILE),
+            "[scripts]\nshell = \"builtin\"\n\n[tasks.ci]\ncommand = \"cpm test\"\ndescription = \"CI\"\n",
+        )
+        .unwrap();
+        let config = CpmConfig::load(temp.path()).unwrap();
+        assert_eq!(
+            config.scripts.unwrap().shell,
+            crate::script::ScriptShell::Builtin
+        );
+        assert_eq!(config.tasks["ci"].command.as_deref(), Some("cpm test"));
+        assert_eq!(config.tasks["ci"].description.as_deref(), Some("CI"));
+    }
+
+    #[test]
+    fn test_edit_config_preserves_comments() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp.path().join(CONFIG_FILE),
+            "# reviewed by security\n[install-scripts]\nallow = [\"esbuild\"]\n",
+        )
+        .unwrap();
+        edit_config(temp.path(), |doc| {
+            push_unique(doc, "install-scripts", "allow", &["sharp".to_string()]);
+            push_unique(doc, "install-scripts", "allow", &["esbuild".to_string()]);
+            Ok(())
+        })
+        .unwrap();
+
+        let content = std::fs::read_to_string(temp.path().join(CONFIG_FILE)).unwrap();
+        assert!(content.starts_with("# reviewed by security"));
+        let config = CpmConfig::load(temp.path()).unwrap();
+        let scripts = config.install_scripts.unwrap();
+        assert_eq!(scripts.allow, vec!["esbuild", "sharp"]);
+        assert_eq!(scripts.approval("sharp"), ScriptApproval::Allowed);
+        assert_eq!(scripts.approval("core-js"), ScriptApproval::Pending);
+    }
+}
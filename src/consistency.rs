@@ -0,0 +1,360 @@
+//! Dependency range consistency across workspace members
+//!
+//! `cpm workspace check` looks for two problems: the same npm package or crate
+//! required with different ranges by different members, and members whose
+//! range on a sibling does not accept the sibling's current version. Fixes go
+//! through the format-preserving manifest editor.
+
+use crate::cli::framework::CliResult;
+use crate::graph::WorkspaceGraph;
+use crate::manifest::{JsonManifest, TomlManifest, rewrite_cargo_requirements};
+use crate::policy::{DependencySource, classify_npm_spec};
+use crate::range::{NpmRange, cargo_matches, cargo_min_version};
+use crate::version::{bump_cargo_requirement, bump_npm_range};
+use crate::workspace::DependencyKind;
+use semver::Version;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// One member's requirement on a dependency
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeUse {
+    /// Index of the member in the graph
+    pub member: usize,
+    pub kind: DependencyKind,
+    pub range: String,
+}
+
+/// A dependency required with different ranges by different members
+#[derive(Debug, Clone)]
+pub struct RangeMismatch {
+    pub name: String,
+    /// Whether this is a crate rather than an npm package
+    pub cargo: bool,
+    pub uses: Vec<RangeUse>,
+    /// The range accepting the highest minimum version, when any range parses
+    pub highest: Option<String>,
+}
+
+/// A member requiring a sibling with a range its current version does not satisfy
+#[derive(Debug, Clone)]
+pub struct UnsatisfiedSibling {
+    pub usage: RangeUse,
+    /// Index of the sibling in the graph
+    pub sibling: usize,
+    pub version: Version,
+}
+
+/// Findings of a consistency check
+#[derive(Debug, Clone, Default)]
+pub struct ConsistencyReport {
+    pub mismatches: Vec<RangeMismatch>,
+    pub unsatisfied: Vec<UnsatisfiedSibling>,
+}
+
+impl ConsistencyReport {
+    /// Whether nothing was found
+    pub fn is_clean(&self) -> bool {
+        self.mismatches.is_empty() && self.unsatisfied.is_empty()
+    }
+}
+
+/// Whether an npm specifier is a registry range that should be compared
+fn comparable_npm_spec(spec: &str) -> bool {
+    !spec.starts_with("workspace:")
+        && matches!(classify_npm_spec(spec), DependencySource::Registry { .. })
+}
+
+/// The lowest version a range accepts, for ordering ranges
+fn min_version(range: &str, cargo: bool) -> Option<Version> {
+    if cargo {
+        cargo_min_version(range)
+    } else {
+        NpmRange::parse(range)?.min_version()
+    }
+}
+
+/// The range in `uses` with the highest minimum version
+///
+/// Ties go to the range used by more members, then to the one seen first.
+fn highest_range(uses: &[RangeUse], cargo: bool) -> Option<String> {
+    let mut best: Option<(Version, usize, &str)> = None;
+    for usage in uses {
+        let Some(min) = min_version(&usage.range, cargo) else {
+            continue;
+        };
+        let count = uses.iter().filter(|u| u.range == usage.range).count();
+        let better = match &best {
+            None => true,
+            Some((best_min, best_count, _)) => (&min, count) > (best_min, *best_count),
+        };
+        if better {
+            best = Some((min, count, &usage.range));
+        }
+    }
+    best.map(|(_, _, range)| range.to_string())
+}
+
+/// Check the ranges declared by every member of `graph`
+pub fn check(graph: &WorkspaceGraph) -> ConsistencyReport {
+    let mut report = ConsistencyReport::default();
+
+    // Group requirements by (is crate, dependency name)
+    let mut groups: BTreeMap<(bool, &str), Vec<RangeUse>> = BTreeMap::new();
+    for (idx, member) in graph.members.iter().enumerate() {
+        for dependency in &member.dependencies {
+            let Some(range) = &dependency.range else {
+                continue;
+            };
+            let cargo = dependency.kind.is_cargo();
+            if !cargo && !comparable_npm_spec(range) {
+                continue;
+            }
+            groups
+                .entry((cargo, dependency.name.as_str()))
+                .or_default()
+                .push(RangeUse {
+                    member: idx,
+                    kind: dependency.kind,
+                    range: range.trim().to_string(),
+                });
+        }
+    }
+    for ((cargo, name), uses) in groups {
+        let first = &uses[0].range;
+        if uses.iter().all(|u| &u.range == first) {
+            continue;
+        }
+        report.mismatches.push(RangeMismatch {
+            name: name.to_string(),
+            cargo,
+            highest: highest_range(&uses, cargo),
+            uses,
+        });
+    }
+
+    for edge in graph.edges() {
+        let member = &graph.members[edge.from];
+        let sibling = &graph.members[edge.to];
+        let Some(version) = sibling
+            .version
+            .as_deref()
+            .and_then(|v| Version::parse(v).ok())
+        else {
+            continue;
+        };
+        let expected_name = if edge.kind.is_cargo() {
+            sibling.crate_name.as_deref()
+        } else {
+            Some(sibling.name.as_str())
+        };
+        for dependency in &member.dependencies {
+            if dependency.kind != edge.kind || Some(dependency.name.as_str()) != expected_name {
+                continue;
+            }
+            let Some(range) = &dependency.range else {
+                continue;
+            };
+            let satisfied = if edge.kind.is_cargo() {
+                cargo_matches(range, &version)
+            } else {
+                NpmRange::parse(range).map(|r| r.matches(&version))
+            };
+            // Ranges that do not parse (`workspace:^`, dist-tags) are not judged
+            if satisfied == Some(false) {
+                report.unsatisfied.push(UnsatisfiedSibling {
+                    usage: RangeUse {
+                        member: edge.from,
+                        kind: edge.kind,
+                        range: range.clone(),
+                    },
+                    sibling: edge.to,
+                    version: version.clone(),
+                });
+            }
+        }
+    }
+    report
+}
+
+/// Manifest section holding a dependency of `kind`
+fn section(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Npm | DependencyKind::Cargo => "dependencies",
+        DependencyKind::NpmDev => "devDependencies",
+        DependencyKind::CargoDev => "dev-dependencies",
+        DependencyKind::CargoBuild => "build-dependencies",
+    }
+}
+
+/// Align mismatched ranges to the highest one and sibling ranges to the sibling's version
+///
+/// Returns the manifests that were rewritten.
+pub fn fix(graph: &WorkspaceGraph, report: &ConsistencyReport) -> CliResult<Vec<PathBuf>> {
+    // (member, kind, dependency name) → new range
+    let mut edits: BTreeMap<(usize, DependencyKind, String), String> = BTreeMap::new();
+    for mismatch in &report.mismatches {
+        let Some(highest) = &mismatch.highest else {
+            continue;
+        };
+        for usage in &mismatch.uses {
+            if &usage.range != highest {
+                edits.insert(
+                    (usage.member, usage.kind, mismatch.name.clone()),
+                    highest.clone(),
+                );
+            }
+        }
+    }
+    for unsatisfied in &report.unsatisfied {
+        let sibling = &graph.members[unsatisfied.sibling];
+        let cargo = unsatisfied.usage.kind.is_cargo();
+        let name = if cargo {
+            sibling.crate_name.clone().unwrap_or_default()
+        } else {
+            sibling.name.clone()
+        };
+        let key = (unsatisfied.usage.member, unsatisfied.usage.kind, name);
+        let current = edits.get(&key).unwrap_or(&unsatisfied.usage.range);
+        let updated = if cargo {
+            bump_cargo_requirement(current, &unsatisfied.version)
+                .unwrap_or_else(|| unsatisfied.version.to_string())
+        } else {
+            bump_npm_range(current, &unsatisfied.version)
+                .unwrap_or_else(|| format!("^{}", unsatisfied.version))
+        };
+        edits.insert(key, updated);
+    }
+
+    let mut by_member: BTreeMap<usize, Vec<(DependencyKind, String, String)>> = BTreeMap::new();
+    for ((member, kind, name), range) in edits {
+        by_member
+            .entry(member)
+            .or_default()
+            .push((kind, name, range));
+    }
+
+    let mut changed = Vec::new();
+    for (member, edits) in by_member {
+        let dir = &graph.members[member].dir;
+        let (npm, cargo): (Vec<_>, Vec<_>) = edits.into_iter().partition(|(k, ..)| !k.is_cargo());
+        if !npm.is_empty() {
+            let mut manifest = JsonManifest::load(&dir.join("package.json"))?;
+            for (kind, name, range) in npm {
+                if let Some(spec) = manifest
+                    .value
+                    .get_mut(section(kind))
+                    .and_then(|deps| deps.get_mut(&name))
+                {
+                    *spec = Value::String(range);
+                }
+            }
+            manifest.save()?;
+            changed.push(manifest.path);
+        }
+        if !cargo.is_empty() {
+            let mut manifest = TomlManifest::load(&dir.join("Cargo.toml"))?;
+            for (kind, name, range) in cargo {
+                if let Some(table) = manifest
+                    .document
+                    .get_mut(section(kind))
+                    .and_then(|t| t.as_table_like_mut())
+                {
+                    rewrite_cargo_requirements(table, &name, |_| Some(range.clone()));
+                }
+            }
+            manifest.save()?;
+            changed.push(manifest.path);
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    const FIXTURE: &[(&str, &str)] = &[
+        (
+            "package.json",
+            r#"{"private":true,"workspaces":["packages/*"]}"#,
+        ),
+        ("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n"),
+        (
+            "packages/app/package.json",
+            "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"lodash\": \"^4.17.0\",\n        \"ui\": \"^1.0.0\"\n    }\n}\n",
+        ),
+        (
+            "packages/ui/package.json",
+            r#"{"name":"ui","version":"2.1.0","devDependencies":{"lodash":"^4.17.21"}}"#,
+        ),
+        (
+            "crates/core/Cargo.toml",
+            "[package]\nname = \"core\"\nversion = \"0.4.0\"\n\n[dependencies]\nserde = \"1.0.100\" # pinned\n",
+        ),
+        (
+            "crates/cli/Cargo.toml",
+            "[package]\nname = \"cli\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = { version = \"1\", features = [\"derive\"] }\ncore = { path = \"../core\", version = \"0.3\" }\n",
+        ),
+    ];
+
+    #[test]
+    fn test_check_reports_mismatches_and_siblings() {
+        let temp = tempfile::TempDir::new().unwrap();
+        fixture(temp.path(), FIXTURE);
+        let graph = WorkspaceGraph::load(temp.path()).unwrap();
+        let report = check(&graph);
+
+        let mismatches: Vec<_> = report
+            .mismatches
+            .iter()
+            .map(|m| (m.cargo, m.name.as_str(), m.highest.as_deref()))
+            .collect();
+        assert_eq!(
+            mismatches,
+            vec![
+                (false, "lodash", Some("^4.17.21")),
+                (true, "serde", Some("1.0.100"))
+            ]
+        );
+
+        let unsatisfied: Vec<_> = report
+            .unsatisfied
+            .iter()
+            .map(|u| {
+                (
+                    graph.members[u.usage.member].name.as_str(),
+                    graph.members[u.sibling].name.as_str(),
+                    u.usage.range.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            unsatisfied,
+            vec![("cli", "core", "0.3"), ("app", "ui", "^1.0.0")]
+        );
+    }
+
+    #[test]
+    fn test_fix_aligns_ranges_preserving_format() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        fixture(root, FIXTURE);
+        let graph = WorkspaceGraph::load(root).unwrap();
+        let changed = fix(&graph, &check(&graph)).unwrap();
+        assert_eq!(changed.len(), 2);
+
+        assert_eq!(
+            std::fs::read_to_string(root.join("packages/app/package.json")).unwrap(),
+            "{\n    \"name\": \"app\",\n    \"dependencies\": {\n        \"lodash\": \"^4.17.21\",\n        \"ui\": \"^2.1.0\"\n    }\n}\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("crates/cli/Cargo.toml")).unwrap(),
+            "[package]\nname = \"cli\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = { version = \"1.0.100\", features = [\"derive\"] }\ncore = { path = \"../core\", version = \"0.4\" }\n"
+        );
+        let graph = WorkspaceGraph::load(root).unwrap();
+        assert!(check(&graph).is_clean());
+    }
+}
@@ -0,0 +1,337 @@
+//! `.env` files
+//!
+//! `cpm run`, `cpm dev` and `cpm test` load variables from these files in the
+//! project directory, each overriding the ones before it:
+//!
+//! 1. `.env`
+//! 2. `.env.local`
+//! 3. `.env.<mode>`
+//! 4. `.env.<mode>.local`
+//!
+//! Variables already set in the environment always win, so a CI secret is
+//! never replaced by a checked-in default. The `.local` files are meant to
+//! stay out of version control.
+//!
+//! Lines are `NAME=value`, optionally prefixed with `export`. Values may be
+//! quoted: double quotes understand `\n`, `\t` and `\"` escapes and may span
+//! lines, single quotes are taken literally. Unquoted and double-quoted
+//! values expand `$NAME`, `${NAME}` and `${NAME:-default}` from the
+//! environment and the variables of all the files, so `.env.local` overriding
+//! `PORT` also changes a `URL` built from it in `.env`; `\$` is a literal `$`.
+
+use crate::cli::framework::{CliError, CliResult};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Default mode of `cpm dev`
+pub const DEVELOPMENT: &str = "development";
+/// Default mode of `cpm test`
+pub const TEST: &str = "test";
+
+/// Source of a variable set in the environment rather than a file
+pub const ENVIRONMENT: &str = "environment";
+
+/// A variable defined in a `.env` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVar {
+    pub name: String,
+    /// The value after expansion, or the environment's when already set
+    pub value: String,
+    /// File defining the variable, or [`ENVIRONMENT`] when the environment overrides it
+    pub source: String,
+}
+
+/// An assignment before expansion
+struct Entry {
+    name: String,
+    raw: String,
+    /// Quote around the value, if any
+    quote: Option<char>,
+}
+
+/// Expands the merged entries on demand, each at most once
+struct Resolver<'a> {
+    entries: &'a BTreeMap<String, (Entry, String)>,
+    env: &'a dyn Fn(&str) -> Option<String>,
+    values: RefCell<BTreeMap<String, String>>,
+    /// Variables being expanded, to cut reference cycles short
+    expanding: RefCell<Vec<String>>,
+}
+
+impl Resolver<'_> {
+    fn lookup(&self, name: &str) -> Option<String> {
+        if let Some(value) = (self.env)(name) {
+            return Some(value);
+        }
+        if let Some(value) = self.values.borrow().get(name) {
+            return Some(value.clone());
+        }
+        let (entry, _) = self.entries.get(name)?;
+        // A variable referring back to itself sees it as unset
+        if self.expanding.borrow().iter().any(|n| n == name) {
+            return None;
+        }
+        self.expanding.borrow_mut().push(name.to_string());
+        let value = interpolate(&entry.raw, entry.quote, &|name| self.lookup(name));
+        self.expanding.borrow_mut().pop();
+        self.values
+            .borrow_mut()
+            .insert(name.to_string(), value.clone());
+        Some(value)
+    }
+}
+
+/// The files to load for `mode`, lowest precedence first
+pub fn files(mode: Option<&str>) -> Vec<String> {
+    let mut files = vec![".env".to_string(), ".env.local".to_string()];
+    if let Some(mode) = mode {
+        files.push(format!(".env.{mode}"));
+        files.push(format!(".env.{mode}.local"));
+    }
+    files
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Index of the closing `quote` in `value`, skipping escaped ones in double quotes
+fn closing_quote(value: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, c) in value.char_indices() {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(idx),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Parse the assignments of a `.env` file, with the line of each error
+fn parse(content: &str) -> Result<Vec<Entry>, (usize, String)> {
+    let mut entries = Vec::new();
+    let mut lines = content.lines().enumerate();
+    while let Some((idx, line)) = lines.next() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let Some((name, value)) = line.split_once('=') else {
+            return Err((idx + 1, format!("expected NAME=value, found '{line}'")));
+        };
+        let name = name.trim();
+        if !is_name(name) {
+            return Err((idx + 1, format!("invalid variable name '{name}'")));
+        }
+        let value = value.trim();
+        let quote = value.chars().next().filter(|c| matches!(c, '"' | '\''));
+        let raw = match quote {
+            Some(quote) => {
+                // Quoted values may continue on the following lines
+                let mut value = value[1..].to_string();
+                loop {
+                    if let Some(end) = closing_quote(&value, quote) {
+                        value.truncate(end);
+                        break value;
+                    }
+                    let Some((_, next)) = lines.next() else {
+                        return Err((idx + 1, format!("unterminated {quote} quote")));
+                    };
+                    value.push('\n');
+                    value.push_str(next);
+                }
+            }
+            None => match value.find(" #") {
+                Some(comment) => value[..comment].trim_end().to_string(),
+                None => value.to_string(),
+            },
+        };
+        entries.push(Entry {
+            name: name.to_string(),
+            raw,
+            quote,
+        });
+    }
+    Ok(entries)
+}
+
+/// Resolve escapes and `$` references in a value
+fn interpolate(raw: &str, quote: Option<char>, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    if quote == Some('\'') {
+        return raw.to_string();
+    }
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.peek().copied() {
+                Some('$') => {
+                    chars.next();
+                    value.push('$');
+                }
+                Some(escape @ ('n' | 't' | 'r' | '"' | '\\')) if quote == Some('"') => {
+                    chars.next();
+                    value.push(match escape {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        other => other,
+                    });
+                }
+                _ => value.push('\\'),
+            },
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let reference: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let (name, default) = match reference.split_once(":-") {
+                    Some((name, default)) => (name, Some(default)),
+                    None => (reference.as_str(), None),
+                };
+                let found = lookup(name).filter(|v| !v.is_empty());
+                match (found, default) {
+                    (Some(found), _) => value.push_str(&found),
+                    (None, Some(default)) => value.push_str(&interpolate(default, quote, lookup)),
+                    (None, None) => {}
+                }
+            }
+            '$' if chars
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_') =>
+            {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                value.push_str(&lookup(&name).unwrap_or_default());
+            }
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+/// Load the `.env` files of `dir` for `mode`, sorted by name
+///
+/// `env` looks up the current environment; variables it has keep its value.
+/// Values are expanded once every file is merged.
+pub fn load_with(
+    dir: &Path,
+    mode: Option<&str>,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> CliResult<Vec<EnvVar>> {
+    let mut merged: BTreeMap<String, (Entry, String)> = BTreeMap::new();
+    for file in files(mode) {
+        let path = dir.join(&file);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let entries = parse(&content).map_err(|(line, message)| CliError::FileOperationError {
+            operation: "parse env file".to_string(),
+            path: path.display().to_string(),
+            message: format!("line {line}: {message}"),
+        })?;
+        for entry in entries {
+            merged.insert(entry.name.clone(), (entry, file.clone()));
+        }
+    }
+
+    let resolver = Resolver {
+        entries: &merged,
+        env,
+        values: RefCell::new(BTreeMap::new()),
+        expanding: RefCell::new(Vec::new()),
+    };
+    Ok(merged
+        .iter()
+        .map(|(name, (_, file))| {
+            let source = match env(name) {
+                Some(_) => ENVIRONMENT.to_string(),
+                None => file.clone(),
+            };
+            EnvVar {
+                name: name.clone(),
+                value: resolver.lookup(name).unwrap_or_default(),
+                source,
+            }
+        })
+        .collect())
+}
+
+/// Load the `.env` files of `dir` for `mode` against the process environment
+pub fn load(dir: &Path, mode: Option<&str>) -> CliResult<Vec<EnvVar>> {
+    load_with(dir, mode, &|name| std::env::var(name).ok())
+}
+
+/// Load the `.env` files of `dir` into the process environment, so every
+/// command started afterwards sees them, returning the files that were read
+pub fn apply(dir: &Path, mode: Option<&str>) -> CliResult<Vec<String>> {
+    let vars = load(dir, mode)?;
+    let mut sources: Vec<String> = Vec::new();
+    for var in vars.into_iter().filter(|var| var.source != ENVIRONMENT) {
+        std::env::set_var(&var.name, &var.value);
+        if !sources.contains(&var.source) {
+            sources.push(var.source);
+        }
+    }
+    let order = files(mode);
+    sources.sort_by_key(|source| order.iter().position(|file| file == source));
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_precedence_and_expansion() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let write =
+            |name: &str, content: &str| std::fs::write(temp.path().join(name), content).unwrap();
+        write(
+            ".env",
+            "# defaults\nHOST=localhost\nPORT=3000\nURL=http://${HOST}:$PORT/api\nexport GREETING=\"hi\\n\\\"there\\\"\"\nRAW='$HOST'\nCERT=\"line1\nline2\"\nSHELL_PATH=${CPM_TEST_MISSING:-fallback} # note\n",
+        );
+        write(".env.local", "PORT=4000\n");
+        write(".env.test", "HOST=test.internal\nCOST=\\$5\n");
+        write(".env.test.local", "TOKEN=from-file\nLOOP=${LOOP}x\n");
+        write(".env.production", "HOST=prod\n");
+
+        let env = |name: &str| (name == "TOKEN").then(|| "from-env".to_string());
+        let vars = load_with(temp.path(), Some("test"), &env).unwrap();
+        let get = |name: &str| vars.iter().find(|v| v.name == name).unwrap();
+        assert_eq!(get("HOST").value, "test.internal");
+        assert_eq!(get("HOST").source, ".env.test");
+        assert_eq!(get("PORT").value, "4000");
+        // Expanded after merging, so later files change what earlier ones build
+        assert_eq!(get("URL").value, "http://test.internal:4000/api");
+        assert_eq!(get("URL").source, ".env");
+        assert_eq!(get("GREETING").value, "hi\n\"there\"");
+        assert_eq!(get("RAW").value, "$HOST");
+        assert_eq!(get("CERT").value, "line1\nline2");
+        assert_eq!(get("SHELL_PATH").value, "fallback");
+        assert_eq!(get("COST").value, "$5");
+        assert_eq!(get("LOOP").value, "x");
+        assert_eq!(get("TOKEN").value, "from-env");
+        assert_eq!(get("TOKEN").source, ENVIRONMENT);
+
+        // Without a mode only .env and .env.local are read
+        let vars = load_with(temp.path(), None, &|_| None).unwrap();
+        assert!(
+            vars.iter()
+                .all(|v| v.source == ".env" || v.source == ".env.local")
+        );
+
+        write(".env.local", "not an assignment\n");
+        let error = load_with(temp.path(), None, &|_| None).unwrap_err();
+        assert!(error.to_string().contains("line 1"));
+    }
+}
@@ -147,7 +147,7 @@ pub fn should_trigger_easter_egg() -> bool {
     now.hash(&mut hasher);
     let hash = hasher.finish();
 
-    hash % 10 == 0
+    hash.is_multiple_of(10)
 }
 
 /// Check if easter egg should be triggered based on command
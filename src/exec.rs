@@ -0,0 +1,425 @@
+//! Package binaries for `cpm exec` and `cpm npx`
+//!
+//! `cpm exec` only runs what is already installed: the `node_modules/.bin`
+//! of the current directory and each of its ancestors, then the `bin` entries
+//! of the packages of the enclosing workspace.
+//!
+//! `cpm npx` installs packages it cannot find locally into a cache directory,
+//! one install per set of package specs. When every spec names a version or
+//! range (`create-foo@1.2`), a later call with the same specs reuses the
+//! install without touching the network. Specs without a version or with a
+//! dist-tag are installed again each time to pick up the latest release, and
+//! fall back to the previous install when offline.
+//!
+//! Installs run with `--ignore-scripts`. The install scripts of packages on
+//! the `[install-scripts]` allow-list of `cpm.toml` are run afterwards with
+//! `npm rebuild`, as for `cpm install`.
+//!
+//! The cache lives in `$CPM_CACHE_DIR`, `$XDG_CACHE_HOME/cpm` or
+//! `~/.cache/cpm`.
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::config::InstallScriptsConfig;
+use crate::lifecycle::ScriptPackage;
+use crate::policy::DependencySource;
+use crate::range::NpmRange;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the cache directory
+pub const CACHE_ENV: &str = "CPM_CACHE_DIR";
+
+/// npm on this platform
+pub fn npm() -> &'static str {
+    if cfg!(windows) { "npm.cmd" } else { "npm" }
+}
+
+/// The cache directory of cpm, if a home directory is known
+pub fn cache_dir() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+    if let Some(dir) = var(CACHE_ENV) {
+        return Some(PathBuf::from(dir));
+    }
+    if let Some(dir) = var("XDG_CACHE_HOME") {
+        return Some(PathBuf::from(dir).join("cpm"));
+    }
+    var("HOME")
+        .or_else(|| var("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".cache").join("cpm"))
+}
+
+/// The file to run for a binary named `name` in `dir`, trying the
+/// platform's executable extensions
+fn bin_in(dir: &Path, name: &str) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) {
+        &[".cmd", ".exe", ".ps1", ""]
+    } else {
+        &[""]
+    };
+    extensions
+        .iter()
+        .map(|extension| dir.join(format!("{name}{extension}")))
+        .find(|path| path.is_file())
+}
+
+/// The `bin` entries of a `package.json`, as names and paths
+fn manifest_bins(package_dir: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(content) = std::fs::read_to_string(package_dir.join("package.json")) else {
+        return Vec::new();
+    };
+    let Ok(manifest) = serde_json::from_str::<Value>(&content) else {
+        return Vec::new();
+    };
+    match manifest.get("bin") {
+        // A single binary is named after the package, without its scope
+        Some(Value::String(path)) => manifest
+            .get("name")
+            .and_then(Value::as_str)
+            .map(|name| {
+                let name = name.rsplit('/').next().unwrap_or(name);
+                vec![(name.to_string(), package_dir.join(path))]
+            })
+            .unwrap_or_default(),
+        Some(Value::Object(bins)) => bins
+            .iter()
+            .filter_map(|(name, path)| Some((name.clone(), package_dir.join(path.as_str()?))))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The closest npm workspace root at or above `dir`
+fn workspace_root(dir: &Path) -> CliResult<Option<PathBuf>> {
+    for root in dir.ancestors() {
+        if root.join("package.json").is_file() && !crate::workspace::npm_patterns(root)?.is_empty()
+        {
+            return Ok(Some(root.to_path_buf()));
+        }
+    }
+    Ok(None)
+}
+
+/// Every binary `cpm exec` can run from `dir`, closest first
+pub fn local_bins(dir: &Path) -> CliResult<Vec<(String, PathBuf)>> {
+    let dir = dir.canonicalize()?;
+    let mut bins = Vec::new();
+    for bin_dir in crate::script::bin_dirs(&dir) {
+        let Ok(entries) = std::fs::read_dir(&bin_dir) else {
+            continue;
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        names.sort();
+        for name in names {
+            let stem = if cfg!(windows) {
+                Path::new(&name)
+                    .file_stem()
+                    .map_or(name.clone(), |stem| stem.to_string_lossy().into_owned())
+            } else {
+                name.clone()
+            };
+            if let Some(path) = bin_in(&bin_dir, &stem) {
+                bins.push((stem, path));
+            }
+        }
+    }
+    if let Some(root) = workspace_root(&dir)? {
+        for member in crate::workspace::npm_members(&root)? {
+            bins.extend(manifest_bins(&member));
+        }
+    }
+    Ok(bins)
+}
+
+/// The local binary `name` for `dir`, see [`local_bins`]
+pub fn resolve_local(dir: &Path, name: &str) -> CliResult<Option<PathBuf>> {
+    Ok(local_bins(dir)?
+        .into_iter()
+        .find(|(bin, _)| bin == name)
+        .map(|(_, path)| path))
+}
+
+/// A package to install, such as `create-foo@1.2`, `@scope/tool@^2` or
+/// `git+ssh://git@host/tool.git`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spec {
+    /// Package name; for git, URL and file specs, a guess from the last path segment
+    pub name: String,
+    /// Version, range or dist-tag
+    pub version: Option<String>,
+    /// The spec as given, when it is not a registry package
+    pub source: Option<String>,
+}
+
+impl Spec {
+    pub fn parse(spec: &str) -> Self {
+        // `@` only separates the version of registry packages, a git URL
+        // like `git+ssh://git@host/x.git` contains one too
+        if !matches!(
+            crate::policy::classify_npm_spec(spec),
+            DependencySource::Registry { .. }
+        ) {
+            return Spec {
+                // Without the `#ref` of a git spec
+                name: crate::policy::parse_add_argument(spec.split('#').next().unwrap_or(spec))
+                    .name,
+                version: None,
+                source: Some(spec.to_string()),
+            };
+        }
+        // The `@` of a scope is not a version separator
+        let split = spec
+            .get(1..)
+            .and_then(|rest| rest.rfind('@'))
+            .map(|idx| idx + 1);
+        match split {
+            Some(idx) => Spec {
+                name: spec[..idx].to_string(),
+                version: Some(spec[idx + 1..].to_string()).filter(|v| !v.is_empty()),
+                source: None,
+            },
+            None => Spec {
+                name: spec.to_string(),
+                version: None,
+                source: None,
+            },
+        }
+    }
+
+    /// Whether the spec names a version or range rather than a dist-tag
+    ///
+    /// Git branches and local paths can change, so they are never pinned.
+    pub fn is_pinned(&self) -> bool {
+        self.source.is_none()
+            && self
+                .version
+                .as_deref()
+                .is_some_and(|version| version != "*" && NpmRange::parse(version).is_some())
+    }
+
+    /// Whether a locally installed binary may stand in for this spec
+    pub fn accepts_local(&self) -> bool {
+        self.version.is_none() && self.source.is_none()
+    }
+
+    /// The binary npx runs for this package when none is named
+    pub fn default_bin(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or(&self.name)
+    }
+}
+
+impl std::fmt::Display for Spec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.source, &self.version) {
+            (Some(source), _) => write!(f, "{source}"),
+            (None, Some(version)) => write!(f, "{}@{version}", self.name),
+            (None, None) => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Why a finished npm command failed, if it did
+fn check_status(status: std::io::Result<std::process::ExitStatus>) -> Result<(), String> {
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("npm exited with {status}")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// A cached install of a set of packages
+#[derive(Debug, Clone)]
+pub struct Install {
+    pub dir: PathBuf,
+    pub specs: Vec<Spec>,
+}
+
+impl Install {
+    /// The install for `specs` in the cache directory `cache`
+    pub fn new(cache: &Path, specs: Vec<Spec>) -> Self {
+        let mut hasher = Sha256::new();
+        for spec in &specs {
+            hasher.update(spec.to_string().as_bytes());
+            hasher.update([0]);
+        }
+        let hash = format!("{:x}", hasher.finalize());
+        Install {
+            dir: cache.join("npx").join(&hash[..16]),
+            specs,
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        self.dir.join("node_modules").is_dir()
+    }
+
+    /// Whether an existing install can be used without checking the registry
+    pub fn is_reusable(&self) -> bool {
+        self.exists() && self.specs.iter().all(Spec::is_pinned)
+    }
+
+    /// Install the packages with npm, replacing any previous install
+    ///
+    /// Only the install scripts of packages `scripts` allows are run; the
+    /// packages whose scripts were skipped are returned.
+    pub fn install(&self, scripts: Option<&InstallScriptsConfig>) -> CliResult<Vec<ScriptPackage>> {
+        let parent = self.dir.parent().unwrap_or(&self.dir);
+        std::fs::create_dir_all(parent)?;
+        let staging = parent.join(format!(
+            ".{}.{}.tmp",
+            self.dir.file_name().unwrap_or_default().to_string_lossy(),
+            std::process::id()
+        ));
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+        std::fs::create_dir_all(&staging)?;
+        std::fs::write(staging.join("package.json"), "{\"private\": true}\n")?;
+
+        let specs: Vec<String> = self.specs.iter().map(Spec::to_string).collect();
+        let status = std::process::Command::new(npm())
+            .args([
+                "install",
+                "--no-save",
+                "--no-package-lock",
+                "--no-audit",
+                "--no-fund",
+                "--ignore-scripts",
+            ])
+            .arg("--prefix")
+            .arg(&staging)
+            .args(&specs)
+            .stdout(std::process::Stdio::null())
+            .status();
+        if let Err(message) = check_status(status) {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(CliError::ExecutionError {
+                command: format!("npm install {}", specs.join(" ")),
+                message,
+            });
+        }
+
+        let packages = crate::lifecycle::packages_with_install_scripts(&staging)?;
+        let groups = crate::lifecycle::partition_by_approval(
+            packages,
+            &scripts.cloned().unwrap_or_default(),
+        );
+        if !groups.allowed.is_empty() {
+            let mut names: Vec<&str> = groups.allowed.iter().map(|p| p.name.as_str()).collect();
+            names.dedup();
+            let status = std::process::Command::new(npm())
+                .arg("rebuild")
+                .arg("--prefix")
+                .arg(&staging)
+                .args(&names)
+                .stdout(std::process::Stdio::null())
+                .status();
+            if let Err(message) = check_status(status) {
+                let _ = std::fs::remove_dir_all(&staging);
+                return Err(CliError::ExecutionError {
+                    command: format!("npm rebuild {}", names.join(" ")),
+                    message,
+                });
+            }
+        }
+
+        if self.dir.exists() {
+            std::fs::remove_dir_all(&self.dir)?;
+        }
+        std::fs::rename(&staging, &self.dir)?;
+        let mut skipped = groups.denied;
+        skipped.extend(groups.pending);
+        Ok(skipped)
+    }
+
+    /// `node_modules/.bin` of the install
+    pub fn bin_dir(&self) -> PathBuf {
+        self.dir.join("node_modules").join(".bin")
+    }
+
+    /// The binary to run: `name` if given, else the only binary of the first
+    /// package or the one named after it
+    pub fn bin(&self, name: Option<&str>) -> Option<PathBuf> {
+        if let Some(name) = name {
+            return bin_in(&self.bin_dir(), name);
+        }
+        let spec = self.specs.first()?;
+        let package_dir = self.dir.join("node_modules").join(&spec.name);
+        let bins = manifest_bins(&package_dir);
+        let name = match bins.as_slice() {
+            [(only, _)] => only.as_str(),
+            _ => spec.default_bin(),
+        };
+        bin_in(&self.bin_dir(), name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_parsing_and_pinning() {
+        let spec = Spec::parse("@scope/create-app@^1.2");
+        assert_eq!(spec.name, "@scope/create-app");
+        assert_eq!(spec.version.as_deref(), Some("^1.2"));
+        assert_eq!(spec.default_bin(), "create-app");
+        assert!(spec.is_pinned());
+        assert!(Spec::parse("create-foo@1.2.3").is_pinned());
+        assert!(!Spec::parse("create-foo").is_pinned());
+        assert!(!Spec::parse("create-foo@latest").is_pinned());
+        assert_eq!(Spec::parse("@scope/tool").version, None);
+
+        let git = Spec::parse("git+ssh://git@host/team/tool.git#v1");
+        assert_eq!(git.name, "tool");
+        assert_eq!(git.version, None);
+        assert_eq!(git.to_string(), "git+ssh://git@host/team/tool.git#v1");
+        assert!(!git.is_pinned());
+        assert!(!git.accepts_local());
+        assert_eq!(
+            Spec::parse("./tools/gen").source.as_deref(),
+            Some("./tools/gen")
+        );
+
+        let temp = tempfile::TempDir::new().unwrap();
+        let pinned = Install::new(temp.path(), vec![Spec::parse("a@1")]);
+        let latest = Install::new(temp.path(), vec![Spec::parse("a")]);
+        assert_ne!(pinned.dir, latest.dir);
+        assert!(!pinned.is_reusable());
+        std::fs::create_dir_all(pinned.dir.join("node_modules")).unwrap();
+        std::fs::create_dir_all(latest.dir.join("node_modules")).unwrap();
+        assert!(pinned.is_reusable());
+        assert!(!latest.is_reusable());
+    }
+
+    #[test]
+    fn test_local_bins_include_workspace_packages() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::write(
+            root.join("package.json"),
+            r#"{"name":"root","workspaces":["packages/*"]}"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("node_modules/.bin")).unwrap();
+        std::fs::write(root.join("node_modules/.bin/eslint"), "").unwrap();
+        std::fs::create_dir_all(root.join("packages/cli")).unwrap();
+        std::fs::write(
+            root.join("packages/cli/package.json"),
+            r#"{"name":"@x/cli","bin":"bin.js"}"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("packages/app")).unwrap();
+        std::fs::write(root.join("packages/app/package.json"), r#"{"name":"app"}"#).unwrap();
+
+        let app = root.join("packages/app");
+        assert!(resolve_local(&app, "eslint").unwrap().is_some());
+        let cli = resolve_local(&app, "cli").unwrap().unwrap();
+        assert!(cli.ends_with("packages/cli/bin.js"));
+        assert!(resolve_local(&app, "missing").unwrap().is_none());
+    }
+}
@@ -0,0 +1,337 @@
+//! Workspace package selection (`--filter`)
+//!
+//! pnpm-style selectors over the workspace graph:
+//!
+//! - `name` or a glob such as `@scope/*`, matched against package names
+//! - `./packages/ui` or `{./packages/*}`, matched against package paths
+//! - `[origin/main]`, packages with files changed since a git ref, plus their dependents
+//! - `...sel` adds the dependents of the selection, `sel...` its dependencies;
+//!   `^` (`...^sel`, `sel^...`) leaves the matched packages themselves out
+//! - `!sel` removes packages from the result
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::glob::{glob_match, glob_match_dot};
+use crate::graph::WorkspaceGraph;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Which packages a selector starts from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// Package name or name glob
+    Name(String),
+    /// Path or path glob relative to the workspace root
+    Path(String),
+    /// Packages changed since a git ref
+    Changed(String),
+}
+
+/// A parsed `--filter` value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    pub target: Target,
+    pub exclude: bool,
+    pub dependents: bool,
+    pub dependencies: bool,
+    /// Keep the matched packages themselves (no `^`)
+    pub include_self: bool,
+}
+
+impl Selector {
+    /// Parse a selector such as `...@app/web`, `./libs/*...` or `![origin/main]`
+    pub fn parse(input: &str) -> CliResult<Self> {
+        let invalid = |message: &str| CliError::ExecutionError {
+            command: format!("--filter {input}"),
+            message: message.to_string(),
+        };
+        let (exclude, mut rest) = match input.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        let mut include_self = true;
+        let dependents = match rest.strip_prefix("...") {
+            Some(after) => {
+                rest = after;
+                if let Some(after) = rest.strip_prefix('^') {
+                    include_self = false;
+                    rest = after;
+                }
+                true
+            }
+            None => false,
+        };
+        let dependencies = match rest.strip_suffix("...") {
+            Some(before) => {
+                rest = before;
+                if let Some(before) = rest.strip_suffix('^') {
+                    include_self = false;
+                    rest = before;
+                }
+                true
+            }
+            None => false,
+        };
+
+        let target = if let Some(reference) = rest.strip_prefix('[') {
+            let reference = reference
+                .strip_suffix(']')
+                .ok_or_else(|| invalid("missing ']' after the git ref"))?;
+            Target::Changed(reference.to_string())
+        } else if let Some(path) = rest.strip_prefix('{') {
+            let path = path
+                .strip_suffix('}')
+                .ok_or_else(|| invalid("missing '}' after the path"))?;
+            Target::Path(path.to_string())
+        } else if rest.starts_with('.') {
+            Target::Path(rest.to_string())
+        } else {
+            Target::Name(rest.to_string())
+        };
+        if matches!(&target, Target::Name(n) | Target::Path(n) | Target::Changed(n) if n.is_empty())
+        {
+            return Err(invalid("empty selector"));
+        }
+        Ok(Selector {
+            target,
+            exclude,
+            dependents,
+            dependencies,
+            include_self,
+        })
+    }
+}
+
+/// Whether `pattern` selects the package called `name`
+///
+/// Unscoped patterns also match the name without its `@scope/`.
+fn name_matches(pattern: &str, name: &str) -> bool {
+    if glob_match_dot(pattern, name) {
+        return true;
+    }
+    match name.split_once('/') {
+        Some((scope, bare)) if scope.starts_with('@') && !pattern.contains('/') => {
+            glob_match_dot(pattern, bare)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `pattern` selects the package at `path`, or a directory containing it
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+    if pattern.is_empty() || pattern == "." {
+        return true;
+    }
+    glob_match(pattern, path) || path.starts_with(&format!("{pattern}/"))
+}
+
+/// Packages owning a file changed since `reference`, committed or not
+///
+/// Paths from git are relative to `root`, the workspace root.
+fn changed_since(
+    root: &Path,
+    graph: &WorkspaceGraph,
+    reference: &str,
+) -> CliResult<BTreeSet<usize>> {
+    let output = crate::git::run(
+        root,
+        &["diff", "--name-only", "--relative", reference, "--"],
+    )?;
+    let untracked = crate::git::run(root, &["ls-files", "--others", "--exclude-standard"])?;
+    let files: Vec<&str> = output.lines().chain(untracked.lines()).collect();
+    Ok(graph
+        .members
+        .iter()
+        .enumerate()
+        .filter(|(_, member)| {
+            files
+                .iter()
+                .any(|file| file.starts_with(&format!("{}/", member.path)))
+        })
+        .map(|(idx, _)| idx)
+        .collect())
+}
+
+/// All packages reachable from `start` through `next`, not including `start` itself
+fn reachable<F>(start: &BTreeSet<usize>, next: F) -> BTreeSet<usize>
+where
+    F: Fn(usize) -> BTreeSet<usize>,
+{
+    let mut seen = BTreeSet::new();
+    let mut stack: Vec<usize> = start.iter().flat_map(|idx| next(*idx)).collect();
+    while let Some(idx) = stack.pop() {
+        if seen.insert(idx) {
+            stack.extend(next(idx));
+        }
+    }
+    seen
+}
+
+/// Packages selected by one selector, ignoring its `!`
+fn matching(
+    root: &Path,
+    graph: &WorkspaceGraph,
+    selector: &Selector,
+) -> CliResult<BTreeSet<usize>> {
+    let matched: BTreeSet<usize> = match &selector.target {
+        Target::Name(pattern) => graph
+            .members
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| {
+                name_matches(pattern, &m.name)
+                    || m.crate_name
+                        .as_deref()
+                        .is_some_and(|c| name_matches(pattern, c))
+            })
+            .map(|(idx, _)| idx)
+            .collect(),
+        Target::Path(pattern) => graph
+            .members
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| path_matches(pattern, &m.path))
+            .map(|(idx, _)| idx)
+            .collect(),
+        Target::Changed(reference) => {
+            let changed = changed_since(root, graph, reference)?;
+            // Anything depending on a changed package is affected too
+            let affected = reachable(&changed, |idx| graph.dependents(idx));
+            changed.union(&affected).copied().collect()
+        }
+    };
+
+    let mut selected = BTreeSet::new();
+    if selector.include_self {
+        selected.extend(&matched);
+    }
+    if selector.dependents {
+        selected.extend(reachable(&matched, |idx| graph.dependents(idx)));
+    }
+    if selector.dependencies {
+        selected.extend(reachable(&matched, |idx| graph.dependencies(idx).clone()));
+    }
+    Ok(selected)
+}
+
+/// Indices of the packages selected by `filters`
+///
+/// Without positive selectors every package is a candidate, so `!name` alone
+/// means "everything except name".
+pub fn select(
+    root: &Path,
+    graph: &WorkspaceGraph,
+    filters: &[String],
+) -> CliResult<BTreeSet<usize>> {
+    let selectors = filters
+        .iter()
+        .map(|filter| Selector::parse(filter))
+        .collect::<CliResult<Vec<_>>>()?;
+    let mut selected = BTreeSet::new();
+    if selectors.iter().all(|s| s.exclude) {
+        selected.extend(0..graph.members.len());
+    }
+    for selector in selectors.iter().filter(|s| !s.exclude) {
+        selected.extend(matching(root, graph, selector)?);
+    }
+    for selector in selectors.iter().filter(|s| s.exclude) {
+        for idx in matching(root, graph, selector)? {
+            selected.remove(&idx);
+        }
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, write};
+
+    /// `app → ui → theme`, plus an unrelated `docs`
+    const FIXTURE: &[(&str, &str)] = &[
+        (
+            "package.json",
+            r#"{"private":true,"workspaces":["packages/*","apps/*"]}"#,
+        ),
+        (
+            "apps/app/package.json",
+            r#"{"name":"@x/app","dependencies":{"@x/ui":"*"}}"#,
+        ),
+        (
+            "packages/ui/package.json",
+            r#"{"name":"@x/ui","dependencies":{"@x/theme":"*"}}"#,
+        ),
+        ("packages/theme/package.json", r#"{"name":"@x/theme"}"#),
+        ("packages/docs/package.json", r#"{"name":"docs"}"#),
+    ];
+
+    fn names(graph: &WorkspaceGraph, root: &Path, filters: &[&str]) -> Vec<String> {
+        let filters: Vec<String> = filters.iter().map(|f| f.to_string()).collect();
+        select(root, graph, &filters)
+            .unwrap()
+            .into_iter()
+            .map(|idx| graph.members[idx].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_selectors() {
+        let selector = Selector::parse("!...^./packages/*").unwrap();
+        assert_eq!(selector.target, Target::Path("./packages/*".to_string()));
+        assert!(selector.exclude && selector.dependents && !selector.dependencies);
+        assert!(!selector.include_self);
+
+        let selector = Selector::parse("[origin/main]...").unwrap();
+        assert_eq!(selector.target, Target::Changed("origin/main".to_string()));
+        assert!(selector.dependencies && selector.include_self);
+
+        assert!(Selector::parse("[main").is_err());
+        assert!(Selector::parse("...").is_err());
+    }
+
+    #[test]
+    fn test_select_by_name_path_and_graph() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        fixture(root, FIXTURE);
+        let graph = WorkspaceGraph::load(root).unwrap();
+
+        assert_eq!(
+            names(&graph, root, &["@x/*"]),
+            vec!["@x/app", "@x/theme", "@x/ui"]
+        );
+        assert_eq!(names(&graph, root, &["ui"]), vec!["@x/ui"]);
+        assert_eq!(names(&graph, root, &["./apps"]), vec!["@x/app"]);
+        assert_eq!(
+            names(&graph, root, &["@x/ui..."]),
+            vec!["@x/theme", "@x/ui"]
+        );
+        assert_eq!(
+            names(&graph, root, &["...@x/theme"]),
+            vec!["@x/app", "@x/theme", "@x/ui"]
+        );
+        assert_eq!(
+            names(&graph, root, &["...^@x/theme"]),
+            vec!["@x/app", "@x/ui"]
+        );
+        assert_eq!(names(&graph, root, &["!./packages/*"]), vec!["@x/app"]);
+    }
+
+    #[test]
+    fn test_select_changed_since_ref() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        fixture(root, FIXTURE);
+        let graph = WorkspaceGraph::load(root).unwrap();
+        let git = |args: &[&str]| crate::git::run(root, args).unwrap();
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "cpm"]);
+        git(&["config", "user.email", "cpm@example.com"]);
+        git(&["add", "."]);
+        git(&["commit", "-qm", "initial"]);
+
+        assert!(names(&graph, root, &["[HEAD]"]).is_empty());
+        write(root, "packages/ui/index.js", "export {}\n");
+        assert_eq!(names(&graph, root, &["[HEAD]"]), vec!["@x/app", "@x/ui"]);
+    }
+}
@@ -0,0 +1,67 @@
+//! Fuzzy string matching
+//!
+//! Edit distance helpers used for typo detection and "did you mean" suggestions.
+
+/// Optimal string alignment distance between two strings
+///
+/// Like Levenshtein distance, but an adjacent transposition (`lodahs` vs `lodash`)
+/// counts as a single edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Candidates closest to `target`, best first, within `max_distance` edits
+pub fn closest_matches<'a, I>(target: &str, candidates: I, max_distance: usize) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut scored: Vec<(usize, &'a str)> = candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(target, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    scored.sort();
+    scored.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("lodash", "lodash"), 0);
+        assert_eq!(edit_distance("lodahs", "lodash"), 1);
+        assert_eq!(edit_distance("expres", "express"), 1);
+        assert_eq!(edit_distance("react", "preact"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_closest_matches_sorted_by_distance() {
+        let candidates = ["build", "build:wasm", "test", "dev"];
+        assert_eq!(closest_matches("biuld", candidates, 2), vec!["build"]);
+        assert!(closest_matches("xyz", candidates, 1).is_empty());
+    }
+}
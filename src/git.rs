@@ -0,0 +1,64 @@
+//! Git helpers
+//!
+//! Thin wrappers around the `git` CLI for commands that commit, tag or inspect
+//! history. All functions take the repository directory explicitly.
+
+use crate::cli::framework::{CliError, CliResult};
+use std::path::Path;
+use std::process::Command;
+
+/// Run git in `dir` and return its trimmed stdout
+pub fn run(dir: &Path, args: &[&str]) -> CliResult<String> {
+    let output = Command::new("git").args(args).current_dir(dir).output()?;
+    if !output.status.success() {
+        return Err(CliError::ExecutionError {
+            command: format!("git {}", args.join(" ")),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .to_string())
+}
+
+/// Whether `dir` is inside a git work tree
+pub fn is_repo(dir: &Path) -> bool {
+    run(dir, &["rev-parse", "--is-inside-work-tree"]).is_ok_and(|out| out == "true")
+}
+
+/// Paths with uncommitted changes, as printed by `git status --porcelain`
+pub fn uncommitted_changes(dir: &Path) -> CliResult<Vec<String>> {
+    Ok(run(dir, &["status", "--porcelain"])?
+        .lines()
+        .map(|line| line.get(3..).unwrap_or(line).to_string())
+        .collect())
+}
+
+/// Whether a tag exists
+pub fn tag_exists(dir: &Path, tag: &str) -> bool {
+    run(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("refs/tags/{tag}"),
+        ],
+    )
+    .is_ok()
+}
+
+/// Stage `files` and commit them with `message`
+pub fn commit(dir: &Path, files: &[String], message: &str) -> CliResult<()> {
+    let mut args = vec!["add", "--"];
+    args.extend(files.iter().map(String::as_str));
+    run(dir, &args)?;
+    run(dir, &["commit", "-m", message])?;
+    Ok(())
+}
+
+/// Create an annotated tag on `HEAD`
+pub fn tag(dir: &Path, name: &str, message: &str) -> CliResult<()> {
+    run(dir, &["tag", "-a", name, "-m", message])?;
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//! Glob matching
+//!
+//! Minimal glob and gitignore-style pattern support shared by packing,
+//! workspace discovery and task inputs. Paths are always `/`-separated and
+//! relative to the directory the patterns belong to.
+
+use std::path::{Path, PathBuf};
+
+/// Expand `{a,b}` alternatives into separate patterns
+pub fn expand_braces(pattern: &str) -> Vec<String> {
+    let Some(open) = pattern.find('{') else {
+        return vec![pattern.to_string()];
+    };
+    let mut depth = 0;
+    let mut close = None;
+    for (idx, ch) in pattern[open..].char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(open + idx);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let Some(close) = close else {
+        return vec![pattern.to_string()];
+    };
+
+    // Split the alternatives on top-level commas only
+    let inner = &pattern[open + 1..close];
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, ch) in inner.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(&inner[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&inner[start..]);
+
+    let prefix = &pattern[..open];
+    let suffix = &pattern[close + 1..];
+    alternatives
+        .into_iter()
+        .flat_map(|alt| expand_braces(&format!("{prefix}{alt}{suffix}")))
+        .collect()
+}
+
+/// Match a character class body (`a-z`, `!abc`) against a character
+fn class_matches(class: &[char], ch: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') | Some('^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            if class[i] <= ch && ch <= class[i + 2] {
+                matched = true;
+            }
+            i += 3;
+        } else {
+            if class[i] == ch {
+                matched = true;
+            }
+            i += 1;
+        }
+    }
+    matched != negated
+}
+
+/// Match a single path segment against a pattern segment (`*`, `?`, `[...]`)
+fn segment_matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|skip| segment_matches(&pattern[1..], &text[skip..])),
+        Some('?') => !text.is_empty() && segment_matches(&pattern[1..], &text[1..]),
+        Some('[') => {
+            let Some(end) = pattern.iter().skip(1).position(|c| *c == ']') else {
+                return text.first() == Some(&'[') && segment_matches(&pattern[1..], &text[1..]);
+            };
+            let class = &pattern[1..end + 1];
+            !text.is_empty()
+                && class_matches(class, text[0])
+                && segment_matches(&pattern[end + 2..], &text[1..])
+        }
+        Some('\\') if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && segment_matches(&pattern[2..], &text[1..])
+        }
+        Some(ch) => text.first() == Some(ch) && segment_matches(&pattern[1..], &text[1..]),
+    }
+}
+
+fn segments_match(pattern: &[&str], path: &[&str], dot: bool) -> bool {
+    match pattern.first() {
+        None => path.is_empty(),
+        Some(&"**") => {
+            (0..=path.len()).any(|skip| segments_match(&pattern[1..], &path[skip..], dot))
+        }
+        Some(segment) => {
+            let Some(first) = path.first() else {
+                return false;
+            };
+            if !dot && first.starts_with('.') && segment.starts_with(['*', '?', '[']) {
+                return false;
+            }
+            let pattern_chars: Vec<char> = segment.chars().collect();
+            let text_chars: Vec<char> = first.chars().collect();
+            segment_matches(&pattern_chars, &text_chars)
+                && segments_match(&pattern[1..], &path[1..], dot)
+        }
+    }
+}
+
+fn match_path(pattern: &str, path: &str, dot: bool) -> bool {
+    let path = path.trim_start_matches("./");
+    let path_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    expand_braces(pattern).iter().any(|expanded| {
+        let expanded = expanded.trim_start_matches("./").trim_end_matches('/');
+        let pattern_segments: Vec<&str> = expanded.split('/').filter(|s| !s.is_empty()).collect();
+        segments_match(&pattern_segments, &path_segments, dot)
+    })
+}
+
+/// Match a `/`-separated relative path against a glob pattern
+///
+/// Supports `*`, `?`, `[...]`, `{a,b}` and `**` for any number of directories.
+/// As in shells, wildcards do not match a leading `.` in a segment.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    match_path(pattern, path, false)
+}
+
+/// Like [`glob_match`], but wildcards also match names starting with `.`
+pub fn glob_match_dot(pattern: &str, path: &str) -> bool {
+    match_path(pattern, path, true)
+}
+
+/// Whether a pattern contains glob metacharacters
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '{'])
+}
+
+/// Relative `/`-separated path of `path` below `base`
+pub fn relative_path(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Recursively list files under `dir`, skipping directories rejected by `descend`
+///
+/// `descend` receives the relative path of each directory. Results are sorted.
+pub fn walk_files<F>(dir: &Path, descend: F) -> Vec<PathBuf>
+where
+    F: Fn(&str) -> bool,
+{
+    fn walk<F: Fn(&str) -> bool>(root: &Path, dir: &Path, descend: &F, out: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if descend(&relative_path(root, &path)) {
+                    walk(root, &path, descend, out);
+                }
+            } else {
+                out.push(path);
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(dir, dir, &descend, &mut files);
+    files.sort();
+    files
+}
+
+/// Files under `dir` matching any of the glob `patterns`
+///
+/// Patterns starting with `!` exclude previously matched files.
+/// `node_modules` and `.git` are never searched.
+pub fn glob_files(dir: &Path, patterns: &[String]) -> Vec<PathBuf> {
+    let files = walk_files(dir, |rel| {
+        let name = rel.rsplit('/').next().unwrap_or(rel);
+        name != "node_modules" && name != ".git"
+    });
+    files
+        .into_iter()
+        .filter(|file| {
+            let rel = relative_path(dir, file);
+            let mut included = false;
+            for pattern in patterns {
+                match pattern.strip_prefix('!') {
+                    Some(negated) if glob_match(negated, &rel) => included = false,
+                    None if glob_match(pattern, &rel) => included = true,
+                    _ => {}
+                }
+            }
+            included
+        })
+        .collect()
+}
+
+/// Directories under `dir` matching any of the glob `patterns`
+///
+/// Patterns starting with `!` exclude previously matched directories.
+/// `node_modules`, `target` and `.git` are never searched.
+pub fn glob_dirs(dir: &Path, patterns: &[String]) -> Vec<PathBuf> {
+    fn walk(dir: &Path, out: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let skipped = matches!(name.to_str(), Some("node_modules" | "target" | ".git"));
+            if !skipped && entry.file_type().is_ok_and(|t| t.is_dir()) {
+                out.push(entry.path());
+                walk(&entry.path(), out);
+            }
+        }
+    }
+    let mut dirs = Vec::new();
+    walk(dir, &mut dirs);
+    dirs.sort();
+    dirs.retain(|candidate| {
+        let rel = relative_path(dir, candidate);
+        let mut included = false;
+        for pattern in patterns {
+            match pattern.strip_prefix('!') {
+                Some(negated) if glob_match(negated, &rel) => included = false,
+                None if glob_match(pattern, &rel) => included = true,
+                _ => {}
+            }
+        }
+        included
+    });
+    dirs
+}
+
+/// A single line of a `.gitignore` / `.npmignore` file
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// Gitignore-style rules; the last matching rule wins
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<IgnoreRule>,
+}
+
+impl IgnoreRules {
+    /// Parse the contents of an ignore file
+    pub fn parse(content: &str) -> Self {
+        let mut rules = IgnoreRules::default();
+        for line in content.lines() {
+            rules.add(line);
+        }
+        rules
+    }
+
+    /// Add a single pattern line
+    pub fn add(&mut self, line: &str) {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let dir_only = line.ends_with('/');
+        let line = line.trim_end_matches('/');
+        // Patterns without an inner slash match at any depth
+        let pattern = if line.contains('/') {
+            line.trim_start_matches('/').to_string()
+        } else {
+            format!("**/{line}")
+        };
+        self.rules.push(IgnoreRule {
+            pattern,
+            negated,
+            dir_only,
+        });
+    }
+
+    /// Decide whether a relative path is ignored
+    ///
+    /// Returns `None` when no rule mentions the path.
+    pub fn matched(&self, path: &str, is_dir: bool) -> Option<bool> {
+        let mut result = None;
+        for rule in &self.rules {
+            let direct = (is_dir || !rule.dir_only) && glob_match_dot(&rule.pattern, path);
+            // A rule matching a parent directory covers everything below it
+            let parent = path
+                .match_indices('/')
+                .any(|(idx, _)| glob_match_dot(&rule.pattern, &path[..idx]));
+            if direct || parent {
+                result = Some(!rule.negated);
+            }
+        }
+        result
+    }
+
+    /// Whether the rule set is empty
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("packages/*", "packages/core"));
+        assert!(!glob_match("packages/*", "packages/core/lib"));
+        assert!(glob_match("src/**/*.js", "src/index.js"));
+        assert!(glob_match("src/**/*.js", "src/a/b/c.js"));
+        assert!(glob_match("*.{js,ts}", "index.ts"));
+        assert!(glob_match("file[0-9].txt", "file7.txt"));
+        assert!(!glob_match("*", ".env"));
+        assert!(glob_match(".env*", ".env.local"));
+    }
+
+    #[test]
+    fn test_ignore_rules_last_match_wins() {
+        let rules = IgnoreRules::parse("# build output\ndist/\n*.log\n!keep.log\n/coverage\n");
+        assert_eq!(rules.matched("dist", true), Some(true));
+        assert_eq!(rules.matched("dist/index.js", false), Some(true));
+        assert_eq!(rules.matched("dist", false), None);
+        assert_eq!(rules.matched("logs/debug.log", false), Some(true));
+        assert_eq!(rules.matched("keep.log", false), Some(false));
+        assert_eq!(rules.matched("coverage/lcov.info", false), Some(true));
+        assert_eq!(rules.matched("src/coverage", true), None);
+        assert_eq!(
+            IgnoreRules::parse("*.swp").matched(".main.rs.swp", false),
+            Some(true)
+        );
+    }
+}
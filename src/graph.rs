@@ -0,0 +1,483 @@
+//! Workspace dependency graph
+//!
+//! Edges between workspace members come from npm `dependencies` /
+//! `devDependencies` naming another member and from Cargo dependencies that
+//! point at a member crate, either by `path` or by name. Commands that run
+//! something in every member use the graph to order the work, and
+//! `cpm workspace graph` renders it as DOT, Mermaid or JSON.
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::workspace::{DependencyKind, Member};
+use std::collections::{BTreeSet, VecDeque};
+use std::path::Path;
+
+/// A dependency of one member on another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    /// Index of the dependent member
+    pub from: usize,
+    /// Index of the member depended on
+    pub to: usize,
+    pub kind: DependencyKind,
+}
+
+/// Members of a workspace and the dependencies between them
+#[derive(Debug, Clone)]
+pub struct WorkspaceGraph {
+    pub members: Vec<Member>,
+    /// Every inter-member dependency, sorted
+    edges: Vec<Edge>,
+    /// Indices of the members each member depends on
+    dependencies: Vec<BTreeSet<usize>>,
+}
+
+impl WorkspaceGraph {
+    /// Discover the workspace rooted at `root` and build its graph
+    pub fn load(root: &Path) -> CliResult<Self> {
+        Ok(Self::build(crate::workspace::discover(root)?))
+    }
+
+    /// Build the graph for already loaded members
+    pub fn build(members: Vec<Member>) -> Self {
+        let canonical_dirs: Vec<_> = members
+            .iter()
+            .map(|m| m.dir.canonicalize().unwrap_or_else(|_| m.dir.clone()))
+            .collect();
+        let mut edges = Vec::new();
+        for (from, member) in members.iter().enumerate() {
+            for dependency in &member.dependencies {
+                let to = match &dependency.path {
+                    // Cargo path dependencies point at a directory
+                    Some(path) => path
+                        .canonicalize()
+                        .ok()
+                        .and_then(|path| canonical_dirs.iter().position(|dir| *dir == path)),
+                    None if dependency.kind.is_cargo() => members
+                        .iter()
+                        .position(|m| m.crate_name.as_deref() == Some(dependency.name.as_str())),
+                    None => members.iter().position(|m| m.name == dependency.name),
+                };
+                // A hybrid member's package.json may name its own crate
+                if let Some(to) = to.filter(|to| *to != from) {
+                    edges.push(Edge {
+                        from,
+                        to,
+                        kind: dependency.kind,
+                    });
+                }
+            }
+        }
+        edges.sort_by_key(|edge| (edge.from, edge.to, edge.kind));
+        edges.dedup();
+
+        let mut dependencies = vec![BTreeSet::new(); members.len()];
+        for edge in &edges {
+            dependencies[edge.from].insert(edge.to);
+        }
+        WorkspaceGraph {
+            members,
+            edges,
+            dependencies,
+        }
+    }
+
+    /// Every inter-member dependency
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Index of the member called `name`, matching npm or crate names
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.members
+            .iter()
+            .position(|m| m.name == name || m.crate_name.as_deref() == Some(name))
+    }
+
+    /// Direct dependencies of a member
+    pub fn dependencies(&self, idx: usize) -> &BTreeSet<usize> {
+        &self.dependencies[idx]
+    }
+
+    /// Members depending directly on a member
+    pub fn dependents(&self, idx: usize) -> BTreeSet<usize> {
+        (0..self.members.len())
+            .filter(|other| self.dependencies[*other].contains(&idx))
+            .collect()
+    }
+
+    /// Dependencies of each member in `selected`, limited to other selected members
+    ///
+    /// Unselected members are looked through, so when `a → b → c` and only `a`
+    /// and `c` are selected, `a` still waits for `c`. The result is indexed by
+    /// position in `selected`.
+    pub fn restricted(&self, selected: &[usize]) -> Vec<Vec<usize>> {
+        selected
+            .iter()
+            .map(|&idx| {
+                let mut seen = BTreeSet::new();
+                let mut queue: VecDeque<usize> = self.dependencies[idx].iter().copied().collect();
+                let mut deps = Vec::new();
+                while let Some(dep) = queue.pop_front() {
+                    if !seen.insert(dep) {
+                        continue;
+                    }
+                    match selected.iter().position(|s| *s == dep) {
+                        Some(pos) => deps.push(pos),
+                        None => queue.extend(self.dependencies[dep].iter().copied()),
+                    }
+                }
+                deps.sort_unstable();
+                deps
+            })
+            .collect()
+    }
+
+    /// Members ordered so that every member comes after its dependencies
+    ///
+    /// Ties are broken by member path, which keeps the order stable.
+    pub fn topological_order(&self) -> CliResult<Vec<usize>> {
+        let mut remaining: Vec<usize> = self.dependencies.iter().map(BTreeSet::len).collect();
+        let mut ready: BTreeSet<usize> = (0..self.members.len())
+            .filter(|idx| remaining[*idx] == 0)
+            .collect();
+        let mut order = Vec::with_capacity(self.members.len());
+        while let Some(idx) = ready.pop_first() {
+            order.push(idx);
+            for dependent in self.dependents(idx) {
+                remaining[dependent] -= 1;
+                if remaining[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+        if order.len() < self.members.len() {
+            let cycles: Vec<String> = self
+                .cycles()
+                .iter()
+                .map(|cycle| self.describe_cycle(cycle))
+                .collect();
+            return Err(CliError::ExecutionError {
+                command: "order workspace members".to_string(),
+                message: format!("dependency cycle: {}", cycles.join("; ")),
+            });
+        }
+        Ok(order)
+    }
+
+    /// Groups of members that depend on each other, found with Tarjan's algorithm
+    ///
+    /// Each group is a strongly connected component with more than one member,
+    /// sorted by index; groups are sorted by their first member.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        struct Tarjan<'a> {
+            graph: &'a WorkspaceGraph,
+            index: Vec<Option<usize>>,
+            lowlink: Vec<usize>,
+            on_stack: Vec<bool>,
+            stack: Vec<usize>,
+            next_index: usize,
+            components: Vec<Vec<usize>>,
+        }
+
+        impl Tarjan<'_> {
+            fn visit(&mut self, node: usize) {
+                self.index[node] = Some(self.next_index);
+                self.lowlink[node] = self.next_index;
+                self.next_index += 1;
+                self.stack.push(node);
+                self.on_stack[node] = true;
+
+                for &dep in &self.graph.dependencies[node] {
+                    match self.index[dep] {
+                        None => {
+                            self.visit(dep);
+                            self.lowlink[node] = self.lowlink[node].min(self.lowlink[dep]);
+                        }
+                        Some(dep_index) if self.on_stack[dep] => {
+                            self.lowlink[node] = self.lowlink[node].min(dep_index);
+                        }
+                        Some(_) => {}
+                    }
+                }
+
+                if Some(self.lowlink[node]) == self.index[node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = self.stack.pop() {
+                        self.on_stack[member] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 {
+                        component.sort_unstable();
+                        self.components.push(component);
+                    }
+                }
+            }
+        }
+
+        let count = self.members.len();
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: vec![None; count],
+            lowlink: vec![0; count],
+            on_stack: vec![false; count],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        };
+        for node in 0..count {
+            if tarjan.index[node].is_none() {
+                tarjan.visit(node);
+            }
+        }
+        let mut components = tarjan.components;
+        components.sort();
+        components
+    }
+
+    /// The shortest loop through the first member of a cycle group, as `a → b → a`
+    pub fn describe_cycle(&self, cycle: &[usize]) -> String {
+        let start = cycle[0];
+        // Breadth-first search inside the group for a path leading back to `start`
+        let mut parent: Vec<Option<usize>> = vec![None; self.members.len()];
+        let mut queue = VecDeque::from([start]);
+        let mut last = start;
+        while let Some(node) = queue.pop_front() {
+            if self.dependencies[node].contains(&start) {
+                last = node;
+                break;
+            }
+            for &dep in &self.dependencies[node] {
+                if dep != start && cycle.contains(&dep) && parent[dep].is_none() {
+                    parent[dep] = Some(node);
+                    queue.push_back(dep);
+                }
+            }
+        }
+        let mut path = vec![start];
+        let mut node = last;
+        while node != start {
+            path.push(node);
+            node = parent[node].unwrap_or(start);
+        }
+        path[1..].reverse();
+        path.push(start);
+        path.iter()
+            .map(|idx| self.members[*idx].name.as_str())
+            .collect::<Vec<_>>()
+            .join(" → ")
+    }
+
+    /// Graphviz DOT rendering, with edges labelled by dependency kind
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph workspace {\n    rankdir=LR;\n    node [shape=box];\n");
+        for member in &self.members {
+            out.push_str(&format!(
+                "    {} [label={}];\n",
+                dot_quote(&member.name),
+                dot_quote(&format!("{}\n{}", member.name, member.ecosystem.label()))
+            ));
+        }
+        for edge in &self.edges {
+            out.push_str(&format!(
+                "    {} -> {} [label={}];\n",
+                dot_quote(&self.members[edge.from].name),
+                dot_quote(&self.members[edge.to].name),
+                dot_quote(edge.kind.label())
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Mermaid flowchart rendering, suitable for embedding in Markdown
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::from("graph LR\n");
+        for (idx, member) in self.members.iter().enumerate() {
+            out.push_str(&format!(
+                "    n{}[\"{}\"]\n",
+                idx,
+                mermaid_escape(&member.name)
+            ));
+        }
+        for edge in &self.edges {
+            out.push_str(&format!(
+                "    n{} -->|{}| n{}\n",
+                edge.from,
+                edge.kind.label(),
+                edge.to
+            ));
+        }
+        out
+    }
+
+    /// JSON rendering: members, edges by member name, and cycles
+    pub fn to_json(&self) -> serde_json::Value {
+        let name = |idx: usize| self.members[idx].name.clone();
+        serde_json::json!({
+            "members": self.members,
+            "edges": self
+                .edges
+                .iter()
+                .map(|edge| serde_json::json!({
+                    "from": name(edge.from),
+                    "to": name(edge.to),
+                    "kind": edge.kind,
+                }))
+                .collect::<Vec<_>>(),
+            "cycles": self
+                .cycles()
+                .iter()
+                .map(|cycle| cycle.iter().map(|idx| name(*idx)).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Quote a DOT identifier
+fn dot_quote(text: &str) -> String {
+    format!(
+        "\"{}\"",
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+/// Escape text for a quoted Mermaid node label
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, write};
+
+    const FIXTURE: &[(&str, &str)] = &[
+        (
+            "package.json",
+            r#"{"name":"root","private":true,"workspaces":["packages/*"]}"#,
+        ),
+        ("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n"),
+        (
+            "packages/app/package.json",
+            r#"{"name":"@x/app","devDependencies":{"@x/ui":"*"},"scripts":{"build":"x"}}"#,
+        ),
+        (
+            "packages/ui/package.json",
+            r#"{"name":"@x/ui","dependencies":{"left-pad":"^1.0.0"}}"#,
+        ),
+        (
+            "crates/core/Cargo.toml",
+            "[package]\nname = \"x-core\"\nversion = \"0.1.0\"\n",
+        ),
+        (
+            "crates/cli/Cargo.toml",
+            "[package]\nname = \"x-cli\"\nversion = \"0.1.0\"\n\n[dependencies]\ncore = { package = \"x-core\", path = \"../core\" }\nserde = \"1\"\n",
+        ),
+    ];
+
+    #[test]
+    fn test_edges_from_npm_and_cargo() {
+        let temp = tempfile::TempDir::new().unwrap();
+        fixture(temp.path(), FIXTURE);
+        let graph = WorkspaceGraph::load(temp.path()).unwrap();
+
+        let app = graph.index_of("@x/app").unwrap();
+        let ui = graph.index_of("@x/ui").unwrap();
+        let cli = graph.index_of("x-cli").unwrap();
+        let core = graph.index_of("x-core").unwrap();
+        assert_eq!(graph.dependencies(app), &BTreeSet::from([ui]));
+        assert_eq!(graph.dependencies(cli), &BTreeSet::from([core]));
+        assert!(graph.dependencies(ui).is_empty());
+        assert_eq!(graph.dependents(core), BTreeSet::from([cli]));
+
+        let order = graph.topological_order().unwrap();
+        let position = |idx| order.iter().position(|o| *o == idx).unwrap();
+        assert!(position(ui) < position(app));
+        assert!(position(core) < position(cli));
+    }
+
+    #[test]
+    fn test_restricted_looks_through_unselected_members() {
+        let temp = tempfile::TempDir::new().unwrap();
+        fixture(temp.path(), FIXTURE);
+        write(
+            temp.path(),
+            "packages/ui/package.json",
+            r#"{"name":"@x/ui","dependencies":{"@x/theme":"*"}}"#,
+        );
+        write(
+            temp.path(),
+            "packages/theme/package.json",
+            r#"{"name":"@x/theme"}"#,
+        );
+        let graph = WorkspaceGraph::load(temp.path()).unwrap();
+        let app = graph.index_of("@x/app").unwrap();
+        let theme = graph.index_of("@x/theme").unwrap();
+        assert_eq!(graph.restricted(&[app, theme]), vec![vec![1], vec![]]);
+    }
+
+    #[test]
+    fn test_cycle_is_an_error() {
+        let temp = tempfile::TempDir::new().unwrap();
+        fixture(temp.path(), FIXTURE);
+        write(
+            temp.path(),
+            "packages/ui/package.json",
+            r#"{"name":"@x/ui","dependencies":{"@x/app":"*"}}"#,
+        );
+        let graph = WorkspaceGraph::load(temp.path()).unwrap();
+        let error = graph.topological_order().unwrap_err().to_string();
+        assert!(error.contains("@x/app → @x/ui → @x/app"), "{error}");
+
+        let app = graph.index_of("@x/app").unwrap();
+        let ui = graph.index_of("@x/ui").unwrap();
+        assert_eq!(graph.cycles(), vec![vec![app, ui]]);
+        assert_eq!(
+            graph.to_json()["cycles"],
+            serde_json::json!([["@x/app", "@x/ui"]])
+        );
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let temp = tempfile::TempDir::new().unwrap();
+        fixture(temp.path(), FIXTURE);
+        let graph = WorkspaceGraph::load(temp.path()).unwrap();
+        assert!(graph.cycles().is_empty());
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph workspace {"));
+        assert!(
+            dot.contains("\"x-cli\" -> \"x-core\" [label=\"cargo\"];"),
+            "{dot}"
+        );
+        assert!(
+            dot.contains("\"@x/app\" -> \"@x/ui\" [label=\"npm dev\"];"),
+            "{dot}"
+        );
+
+        let mermaid = graph.to_mermaid();
+        let app = graph.index_of("@x/app").unwrap();
+        let ui = graph.index_of("@x/ui").unwrap();
+        assert!(mermaid.starts_with("graph LR\n"));
+        assert!(
+            mermaid.contains(&format!("n{app}[\"@x/app\"]")),
+            "{mermaid}"
+        );
+        assert!(
+            mermaid.contains(&format!("n{app} -->|npm dev| n{ui}")),
+            "{mermaid}"
+        );
+
+        let json = graph.to_json();
+        assert_eq!(
+            json["edges"][0],
+            serde_json::json!({"from": "x-cli", "to": "x-core", "kind": "cargo"})
+        );
+    }
+}
@@ -3,10 +3,41 @@
 //! CPM is a package manager that acts as an intelligent wrapper around npm and cargo,
 //! providing a unified interface for JavaScript and Rust projects.
 
+pub mod cache;
+pub mod changelog;
 pub mod cli;
-pub mod templates;
+pub mod config;
+pub mod consistency;
+pub mod dotenv;
 pub mod easter_egg;
-
+pub mod exec;
+pub mod filter;
+pub mod fuzzy;
+pub mod git;
+pub mod glob;
+pub mod graph;
+pub mod lifecycle;
+pub mod link;
+pub mod manifest;
+pub mod pack;
+pub mod parallel;
+pub mod policy;
+pub mod publish;
+pub mod range;
+pub mod release;
+pub mod runtime;
+pub mod rust_script;
+pub mod sbom;
+pub mod scaffold;
+pub mod script;
+pub mod secrets;
+pub mod task;
+pub mod templates;
+#[cfg(test)]
+mod test_support;
+pub mod timestamp;
+pub mod version;
+pub mod workspace;
 
 // Re-export commonly used types
 pub use cli::framework::{CliApp, CliCommand, CliContext, CliError, CliResult};
@@ -0,0 +1,226 @@
+//! Dependency lifecycle scripts
+//!
+//! Finds installed dependencies that run `preinstall`/`install`/`postinstall`
+//! scripts so they can be checked against the allow-list in `cpm.toml`.
+
+use crate::cli::framework::CliResult;
+use crate::config::{InstallScriptsConfig, ScriptApproval};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Lifecycle events npm runs for a dependency during install
+pub const INSTALL_EVENTS: [&str; 3] = ["preinstall", "install", "postinstall"];
+
+/// A dependency that declares install-time scripts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptPackage {
+    pub name: String,
+    pub version: String,
+    /// Install location relative to the project (`node_modules/esbuild`)
+    pub path: String,
+    /// Lifecycle event name and command, in execution order
+    pub scripts: Vec<(String, String)>,
+}
+
+/// Install scripts declared in a package manifest
+///
+/// A `binding.gyp` without an explicit install script implies `node-gyp rebuild`,
+/// which npm runs as the default `install` script.
+fn install_scripts(package_dir: &Path, manifest: &Value) -> Vec<(String, String)> {
+    let mut scripts: Vec<(String, String)> = INSTALL_EVENTS
+        .iter()
+        .filter_map(|event| {
+            manifest
+                .get("scripts")
+                .and_then(|s| s.get(*event))
+                .and_then(|c| c.as_str())
+                .map(|command| (event.to_string(), command.to_string()))
+        })
+        .collect();
+    let has_install = scripts
+        .iter()
+        .any(|(e, _)| e == "install" || e == "preinstall");
+    if !has_install && package_dir.join("binding.gyp").exists() {
+        scripts.insert(0, ("install".to_string(), "node-gyp rebuild".to_string()));
+    }
+    scripts
+}
+
+fn read_manifest(path: &Path) -> Option<Value> {
+    let content = std::fs::read_to_string(path.join("package.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn name_from_path(path: &str) -> &str {
+    match path.rfind("node_modules/") {
+        Some(idx) => &path[idx + "node_modules/".len()..],
+        None => path,
+    }
+}
+
+/// Dependencies of the project in `dir` that have install scripts
+///
+/// Uses the `hasInstallScript` markers in `package-lock.json` and falls back to
+/// scanning `node_modules` when the lockfile does not record them.
+pub fn packages_with_install_scripts(dir: &Path) -> CliResult<Vec<ScriptPackage>> {
+    let mut found: BTreeMap<String, ScriptPackage> = BTreeMap::new();
+
+    let lock_path = dir.join("package-lock.json");
+    let lock: Option<Value> = if lock_path.exists() {
+        Some(serde_json::from_str(&std::fs::read_to_string(&lock_path)?)?)
+    } else {
+        None
+    };
+
+    if let Some(packages) = lock
+        .as_ref()
+        .and_then(|l| l.get("packages"))
+        .and_then(|p| p.as_object())
+    {
+        for (path, entry) in packages {
+            if path.is_empty()
+                || entry.get("hasInstallScript").and_then(|h| h.as_bool()) != Some(true)
+            {
+                continue;
+            }
+            let name = entry
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_else(|| name_from_path(path))
+                .to_string();
+            let version = entry
+                .get("version")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+            let package_dir = dir.join(path);
+            let scripts = read_manifest(&package_dir)
+                .map(|manifest| install_scripts(&package_dir, &manifest))
+                .unwrap_or_default();
+            found.insert(
+                path.clone(),
+                ScriptPackage {
+                    name,
+                    version,
+                    path: path.clone(),
+                    scripts,
+                },
+            );
+        }
+    } else {
+        scan_node_modules(dir, &dir.join("node_modules"), &mut found)?;
+    }
+
+    Ok(found.into_values().collect())
+}
+
+fn scan_node_modules(
+    root: &Path,
+    node_modules: &Path,
+    found: &mut BTreeMap<String, ScriptPackage>,
+) -> CliResult<()> {
+    let Ok(entries) = std::fs::read_dir(node_modules) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.starts_with('.') || !path.is_dir() {
+            continue;
+        }
+        if file_name.starts_with('@') {
+            scan_node_modules(root, &path, found)?;
+            continue;
+        }
+        if let Some(manifest) = read_manifest(&path) {
+            let scripts = install_scripts(&path, &manifest);
+            if !scripts.is_empty() {
+                let relative = path
+                    .strip_prefix(root)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                found.insert(
+                    relative.clone(),
+                    ScriptPackage {
+                        name: manifest["name"].as_str().unwrap_or(&file_name).to_string(),
+                        version: manifest["version"].as_str().unwrap_or_default().to_string(),
+                        path: relative,
+                        scripts,
+                    },
+                );
+            }
+        }
+        scan_node_modules(root, &path.join("node_modules"), found)?;
+    }
+    Ok(())
+}
+
+/// Packages with install scripts, grouped by [`ScriptApproval`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApprovalGroups {
+    pub allowed: Vec<ScriptPackage>,
+    pub denied: Vec<ScriptPackage>,
+    pub pending: Vec<ScriptPackage>,
+}
+
+/// Split packages with install scripts by their approval state
+pub fn partition_by_approval(
+    packages: Vec<ScriptPackage>,
+    config: &InstallScriptsConfig,
+) -> ApprovalGroups {
+    let mut groups = ApprovalGroups::default();
+    for package in packages {
+        match config.approval(&package.name) {
+            ScriptApproval::Allowed => groups.allowed.push(package),
+            ScriptApproval::Denied => groups.denied.push(package),
+            ScriptApproval::Pending => groups.pending.push(package),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_packages_with_install_scripts_from_lockfile() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        std::fs::write(
+            dir.join("package-lock.json"),
+            r#"{"lockfileVersion": 3, "packages": {
+                "": {"name": "app"},
+                "node_modules/esbuild": {"version": "0.20.0", "hasInstallScript": true},
+                "node_modules/lodash": {"version": "4.17.21"}
+            }}"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.join("node_modules/esbuild")).unwrap();
+        std::fs::write(
+            dir.join("node_modules/esbuild/package.json"),
+            r#"{"name": "esbuild", "scripts": {"postinstall": "node install.js"}}"#,
+        )
+        .unwrap();
+
+        let packages = packages_with_install_scripts(dir).unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name, "esbuild");
+        assert_eq!(
+            packages[0].scripts,
+            vec![("postinstall".to_string(), "node install.js".to_string())]
+        );
+
+        let config = InstallScriptsConfig {
+            allow: vec!["esbuild".to_string()],
+            deny: Vec::new(),
+        };
+        let groups = partition_by_approval(packages.clone(), &config);
+        assert_eq!(groups.allowed, packages);
+        assert!(groups.pending.is_empty());
+        let groups = partition_by_approval(packages, &InstallScriptsConfig::default());
+        assert_eq!(groups.pending.len(), 1);
+    }
+}
@@ -0,0 +1,333 @@
+//! Local package links (`cpm link`)
+//!
+//! Links a package from another checkout into the current project: npm
+//! packages are symlinked into `node_modules` (like `npm link`, without the
+//! global registration step) and crates get a `[patch.crates-io]` path
+//! override in `Cargo.toml`. Active links are recorded in `.cpm/links.json`
+//! so they can be listed, re-applied after installs and removed again.
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::manifest::{JsonManifest, TomlManifest};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Where active links are recorded, relative to the project
+pub const LINKS_FILE: &str = ".cpm/links.json";
+
+/// How a package is linked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// Symlink in `node_modules`
+    Npm,
+    /// `[patch.crates-io]` entry in `Cargo.toml`
+    Cargo,
+}
+
+impl LinkKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            LinkKind::Npm => "npm",
+            LinkKind::Cargo => "cargo",
+        }
+    }
+}
+
+/// An active link
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    /// npm package name or crate name
+    pub name: String,
+    pub kind: LinkKind,
+    /// Absolute path of the linked package
+    pub path: PathBuf,
+}
+
+impl Link {
+    /// Whether the linked package still exists
+    pub fn source_exists(&self) -> bool {
+        self.path.is_dir()
+    }
+}
+
+/// Active links of the project in `dir`
+pub fn load(dir: &Path) -> CliResult<Vec<Link>> {
+    let path = dir.join(LINKS_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&std::fs::read_to_string(&path)?).map_err(|e| {
+        CliError::FileOperationError {
+            operation: "parse links".to_string(),
+            path: path.display().to_string(),
+            message: e.to_string(),
+        }
+    })
+}
+
+fn save(dir: &Path, links: &[Link]) -> CliResult<()> {
+    let path = dir.join(LINKS_FILE);
+    if links.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(links)? + "\n")?;
+    Ok(())
+}
+
+/// `node_modules/<name>` for an npm package, scoped names included
+fn node_modules_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join("node_modules").join(name)
+}
+
+#[cfg(unix)]
+fn symlink_dir(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink_dir(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(target, link)
+}
+
+/// Remove whatever is installed at `path`, following neither symlinks nor junctions
+fn remove_installed(path: &Path) -> CliResult<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path)?,
+        // Symlinks to directories are removed with remove_dir on Windows
+        Ok(_) => std::fs::remove_file(path).or_else(|_| std::fs::remove_dir(path))?,
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+/// Point `node_modules/<name>` at the linked package
+fn apply_npm(dir: &Path, link: &Link) -> CliResult<()> {
+    let target = node_modules_path(dir, &link.name);
+    if std::fs::read_link(&target).is_ok_and(|current| current == link.path) {
+        return Ok(());
+    }
+    remove_installed(&target)?;
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    symlink_dir(&link.path, &target).map_err(|e| CliError::FileOperationError {
+        operation: "create symlink".to_string(),
+        path: target.display().to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// Add `[patch.crates-io] name = { path = "..." }` to the project's `Cargo.toml`
+fn apply_cargo(dir: &Path, link: &Link) -> CliResult<()> {
+    let mut manifest = TomlManifest::load(&dir.join("Cargo.toml"))?;
+    let patch = manifest
+        .document
+        .entry("patch")
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .ok_or_else(|| CliError::FileOperationError {
+            operation: "link crate".to_string(),
+            path: manifest.path.display().to_string(),
+            message: "[patch] is not a table".to_string(),
+        })?;
+    patch.set_implicit(true);
+    let registry = patch
+        .entry("crates-io")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or_else(|| CliError::FileOperationError {
+            operation: "link crate".to_string(),
+            path: manifest.path.display().to_string(),
+            message: "[patch.crates-io] is not a table".to_string(),
+        })?;
+    let path = link.path.display().to_string();
+    if let Some(existing) = registry.get(&link.name) {
+        let existing_path = existing.get("path").and_then(|p| p.as_str());
+        if existing_path == Some(path.as_str()) {
+            return Ok(());
+        }
+        return Err(CliError::ExecutionError {
+            command: format!("link {}", link.name),
+            message: format!("Cargo.toml already patches '{}'", link.name),
+        });
+    }
+    let mut entry = toml_edit::InlineTable::new();
+    entry.insert("path", path.into());
+    registry.insert(&link.name, toml_edit::value(entry));
+    manifest.save()
+}
+
+/// Remove the `[patch.crates-io]` entry, and the tables if they end up empty
+fn remove_cargo(dir: &Path, link: &Link) -> CliResult<()> {
+    let path = dir.join("Cargo.toml");
+    if !path.exists() {
+        return Ok(());
+    }
+    let mut manifest = TomlManifest::load(&path)?;
+    let Some(patch) = manifest
+        .document
+        .get_mut("patch")
+        .and_then(|p| p.as_table_like_mut())
+    else {
+        return Ok(());
+    };
+    if let Some(registry) = patch
+        .get_mut("crates-io")
+        .and_then(|r| r.as_table_like_mut())
+    {
+        registry.remove(&link.name);
+        if registry.is_empty() {
+            patch.remove("crates-io");
+        }
+    }
+    if patch.is_empty() {
+        manifest.document.remove("patch");
+    }
+    manifest.save()
+}
+
+/// Link the package at `source` into the project in `dir`
+///
+/// A package with both manifests is linked on each side the project has.
+/// Returns the links that were added or refreshed.
+pub fn link(dir: &Path, source: &Path) -> CliResult<Vec<Link>> {
+    let source = source
+        .canonicalize()
+        .map_err(|e| CliError::FileOperationError {
+            operation: "link".to_string(),
+            path: source.display().to_string(),
+            message: e.to_string(),
+        })?;
+    let mut candidates = Vec::new();
+    if dir.join("package.json").exists() && source.join("package.json").exists() {
+        if let Some(name) = JsonManifest::load(&source.join("package.json"))?.name() {
+            candidates.push((name.to_string(), LinkKind::Npm));
+        }
+    }
+    if dir.join("Cargo.toml").exists() && source.join("Cargo.toml").exists() {
+        if let Some(name) = TomlManifest::load(&source.join("Cargo.toml"))?.package_name() {
+            candidates.push((name.to_string(), LinkKind::Cargo));
+        }
+    }
+    if candidates.is_empty() {
+        return Err(CliError::ExecutionError {
+            command: format!("link {}", source.display()),
+            message: "No named package.json or Cargo.toml package matching this project"
+                .to_string(),
+        });
+    }
+
+    let mut links = load(dir)?;
+    let mut added = Vec::new();
+    for (name, kind) in candidates {
+        let link = Link {
+            name,
+            kind,
+            path: source.clone(),
+        };
+        match kind {
+            LinkKind::Npm => apply_npm(dir, &link)?,
+            LinkKind::Cargo => apply_cargo(dir, &link)?,
+        }
+        links.retain(|l| !(l.name == link.name && l.kind == link.kind));
+        links.push(link.clone());
+        added.push(link);
+    }
+    save(dir, &links)?;
+    Ok(added)
+}
+
+/// Remove the links matching `name` (package name or linked path), or all links
+///
+/// Returns the removed links. npm packages need a reinstall to get the
+/// registry version back.
+pub fn unlink(dir: &Path, name: Option<&str>) -> CliResult<Vec<Link>> {
+    let source = name.and_then(|n| Path::new(n).canonicalize().ok());
+    let (removed, kept): (Vec<Link>, Vec<Link>) = load(dir)?.into_iter().partition(|link| {
+        name.is_none_or(|n| link.name == n || source.as_deref() == Some(link.path.as_path()))
+    });
+    for link in &removed {
+        match link.kind {
+            LinkKind::Npm => {
+                let target = node_modules_path(dir, &link.name);
+                if std::fs::read_link(&target).is_ok() {
+                    remove_installed(&target)?;
+                }
+            }
+            LinkKind::Cargo => remove_cargo(dir, link)?,
+        }
+    }
+    save(dir, &kept)?;
+    Ok(removed)
+}
+
+/// Re-create `node_modules` symlinks, e.g. after an install pruned them
+///
+/// Links whose source no longer exists are skipped. Returns the links restored.
+pub fn restore_npm(dir: &Path) -> CliResult<Vec<Link>> {
+    let mut restored = Vec::new();
+    for link in load(dir)? {
+        if link.kind == LinkKind::Npm && link.source_exists() {
+            apply_npm(dir, &link)?;
+            restored.push(link);
+        }
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_link_and_unlink_hybrid_package() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let project = temp.path().join("app");
+        let library = temp.path().join("lib");
+        std::fs::create_dir_all(project.join("node_modules/@x/lib")).unwrap();
+        std::fs::create_dir_all(&library).unwrap();
+        std::fs::write(project.join("package.json"), r#"{"name":"app"}"#).unwrap();
+        let cargo = "[package]\nname = \"app\"\nversion = \"0.1.0\"\n";
+        std::fs::write(project.join("Cargo.toml"), cargo).unwrap();
+        std::fs::write(library.join("package.json"), r#"{"name":"@x/lib"}"#).unwrap();
+        std::fs::write(
+            library.join("Cargo.toml"),
+            "[package]\nname = \"lib_core\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+
+        let added = link(&project, &library).unwrap();
+        assert_eq!(added.len(), 2);
+        assert_eq!(load(&project).unwrap(), added);
+        let installed = project.join("node_modules/@x/lib");
+        assert_eq!(
+            std::fs::read_link(&installed).unwrap(),
+            library.canonicalize().unwrap()
+        );
+        let manifest = std::fs::read_to_string(project.join("Cargo.toml")).unwrap();
+        assert!(manifest.contains("[patch.crates-io]\nlib_core = { path = "));
+        // Linking again is a no-op
+        link(&project, &library).unwrap();
+        assert_eq!(load(&project).unwrap().len(), 2);
+
+        // An install pruning the symlink gets it back
+        std::fs::remove_file(&installed).unwrap();
+        assert_eq!(restore_npm(&project).unwrap().len(), 1);
+        assert!(std::fs::read_link(&installed).is_ok());
+
+        assert_eq!(unlink(&project, None).unwrap().len(), 2);
+        assert!(!installed.exists());
+        assert_eq!(
+            std::fs::read_to_string(project.join("Cargo.toml")).unwrap(),
+            cargo
+        );
+        assert!(!project.join(LINKS_FILE).exists());
+    }
+}
@@ -0,0 +1,280 @@
+//! Format-preserving manifest editing
+//!
+//! Commands that rewrite `package.json`, `package-lock.json`, `Cargo.toml` or
+//! `Cargo.lock` go through these types so that key order, indentation,
+//! comments and trailing newlines survive the edit.
+
+use crate::cli::framework::{CliError, CliResult};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+
+/// npm dependency sections, in the order npm writes them
+pub const NPM_DEPENDENCY_SECTIONS: &[&str] = &[
+    "dependencies",
+    "devDependencies",
+    "peerDependencies",
+    "optionalDependencies",
+];
+
+/// Cargo dependency tables
+pub const CARGO_DEPENDENCY_SECTIONS: &[&str] =
+    &["dependencies", "dev-dependencies", "build-dependencies"];
+
+/// Indentation used by a JSON document, defaulting to two spaces
+fn detect_indent(content: &str) -> String {
+    content
+        .lines()
+        .skip(1)
+        .find(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect::<String>()
+        })
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or_else(|| "  ".to_string())
+}
+
+/// A JSON manifest (`package.json`, `package-lock.json`) loaded for editing
+///
+/// Key order is preserved and the file is written back with its original
+/// indentation and line endings.
+#[derive(Debug, Clone)]
+pub struct JsonManifest {
+    pub path: PathBuf,
+    pub value: Value,
+    indent: String,
+    crlf: bool,
+    trailing_newline: bool,
+}
+
+impl JsonManifest {
+    /// Load a JSON file
+    pub fn load(path: &Path) -> CliResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| CliError::FileOperationError {
+            operation: "read manifest".to_string(),
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        let value = serde_json::from_str(&content).map_err(|e| CliError::FileOperationError {
+            operation: "parse manifest".to_string(),
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        Ok(JsonManifest {
+            path: path.to_path_buf(),
+            value,
+            indent: detect_indent(&content),
+            crlf: content.contains("\r\n"),
+            trailing_newline: content.ends_with('\n'),
+        })
+    }
+
+    /// Render the document the way it was formatted on disk
+    pub fn render(&self) -> CliResult<String> {
+        let mut buffer = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(self.indent.as_bytes());
+        let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, formatter);
+        self.value.serialize(&mut serializer)?;
+        let mut content = String::from_utf8_lossy(&buffer).into_owned();
+        if self.trailing_newline {
+            content.push('\n');
+        }
+        if self.crlf {
+            content = content.replace('\n', "\r\n");
+        }
+        Ok(content)
+    }
+
+    /// Write the document back to its file
+    pub fn save(&self) -> CliResult<()> {
+        std::fs::write(&self.path, self.render()?)?;
+        Ok(())
+    }
+
+    /// The `version` field
+    pub fn version(&self) -> Option<&str> {
+        self.value.get("version").and_then(Value::as_str)
+    }
+
+    /// The `name` field
+    pub fn name(&self) -> Option<&str> {
+        self.value.get("name").and_then(Value::as_str)
+    }
+
+    /// Set the top-level `version` field, keeping its position
+    pub fn set_version(&mut self, version: &str) {
+        if let Some(object) = self.value.as_object_mut() {
+            object.insert("version".to_string(), Value::String(version.to_string()));
+        }
+    }
+}
+
+/// A TOML manifest (`Cargo.toml`, `Cargo.lock`) loaded for editing
+#[derive(Debug, Clone)]
+pub struct TomlManifest {
+    pub path: PathBuf,
+    pub document: DocumentMut,
+}
+
+impl TomlManifest {
+    /// Load a TOML file
+    pub fn load(path: &Path) -> CliResult<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| CliError::FileOperationError {
+            operation: "read manifest".to_string(),
+            path: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        let document =
+            content
+                .parse()
+                .map_err(|e: toml_edit::TomlError| CliError::FileOperationError {
+                    operation: "parse manifest".to_string(),
+                    path: path.display().to_string(),
+                    message: e.to_string(),
+                })?;
+        Ok(TomlManifest {
+            path: path.to_path_buf(),
+            document,
+        })
+    }
+
+    /// Write the document back to its file
+    pub fn save(&self) -> CliResult<()> {
+        std::fs::write(&self.path, self.document.to_string())?;
+        Ok(())
+    }
+
+    /// `package.name`
+    pub fn package_name(&self) -> Option<&str> {
+        self.document.get("package")?.get("name")?.as_str()
+    }
+
+    /// `package.version`, resolving `version.workspace = true` through `[workspace.package]`
+    pub fn package_version(&self) -> Option<&str> {
+        let version = self.document.get("package")?.get("version")?;
+        match version.as_str() {
+            Some(version) => Some(version),
+            None => self
+                .document
+                .get("workspace")?
+                .get("package")?
+                .get("version")?
+                .as_str(),
+        }
+    }
+
+    /// Set the package version, or `[workspace.package] version` when it is inherited
+    ///
+    /// Returns `false` when there is no version to update.
+    pub fn set_package_version(&mut self, version: &str) -> bool {
+        let inherited = self
+            .document
+            .get("package")
+            .and_then(|p| p.get("version"))
+            .is_some_and(|v| !v.is_str());
+        let table = if inherited {
+            self.document
+                .get_mut("workspace")
+                .and_then(|w| w.get_mut("package"))
+        } else {
+            self.document.get_mut("package")
+        };
+        let Some(item) = table.and_then(|t| t.get_mut("version")) else {
+            return false;
+        };
+        set_string_preserving_decor(item, version);
+        true
+    }
+}
+
+/// Rewrite the requirements on `package` in a Cargo dependency table
+///
+/// Handles both `name = "1.0"` and `name = { version = "1.0", … }`, including
+/// renamed dependencies (`alias = { package = "name", … }`). `rewrite`
+/// receives the current requirement and returns its replacement, or `None` to
+/// leave it alone. Returns whether anything changed.
+pub fn rewrite_cargo_requirements<F>(
+    table: &mut dyn toml_edit::TableLike,
+    package: &str,
+    rewrite: F,
+) -> bool
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut changed = false;
+    for (key, item) in table.iter_mut() {
+        let name = item
+            .get("package")
+            .and_then(|p| p.as_str())
+            .unwrap_or(key.get())
+            .to_string();
+        if name != package {
+            continue;
+        }
+        let version = if item.is_str() {
+            Some(item)
+        } else {
+            item.get_mut("version")
+        };
+        let Some(version) = version else {
+            continue;
+        };
+        if let Some(updated) = version.as_str().and_then(&rewrite) {
+            set_string_preserving_decor(version, &updated);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Replace a string value, keeping the whitespace and comments around it
+pub fn set_string_preserving_decor(item: &mut toml_edit::Item, value: &str) {
+    if let Some(existing) = item.as_value_mut() {
+        let decor = existing.decor().clone();
+        *existing = toml_edit::Value::from(value);
+        *existing.decor_mut() = decor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_manifest_preserves_order_and_indent() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("package.json");
+        let original = "{\n    \"name\": \"demo\",\n    \"version\": \"1.0.0\",\n    \"main\": \"index.js\",\n    \"author\": \"\"\n}\n";
+        std::fs::write(&path, original).unwrap();
+
+        let mut manifest = JsonManifest::load(&path).unwrap();
+        manifest.set_version("1.1.0");
+        manifest.save().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            original.replace("1.0.0", "1.1.0")
+        );
+    }
+
+    #[test]
+    fn test_toml_manifest_workspace_inherited_version() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("Cargo.toml");
+        let original = "[package]\nname = \"demo\"\nversion.workspace = true\n\n[workspace.package]\nversion = \"0.1.0\" # shared\n";
+        std::fs::write(&path, original).unwrap();
+
+        let mut manifest = TomlManifest::load(&path).unwrap();
+        assert_eq!(manifest.package_version(), Some("0.1.0"));
+        assert!(manifest.set_package_version("0.2.0"));
+        manifest.save().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            original.replace("0.1.0", "0.2.0")
+        );
+    }
+}
@@ -0,0 +1,469 @@
+//! Package packing
+//!
+//! Computes the file set `npm publish` would upload (honoring `files`,
+//! `.npmignore`/`.gitignore` and npm's always-included/excluded rules) and
+//! writes it as a deterministic `.tgz`.
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::glob::{IgnoreRules, glob_match_dot};
+use base64::Engine;
+use serde_json::Value;
+use sha2::{Digest, Sha512};
+use std::path::Path;
+
+/// Paths npm never packs, in gitignore syntax
+const ALWAYS_EXCLUDED: &[&str] = &[
+    ".npmignore",
+    ".gitignore",
+    "**/.git",
+    "**/.svn",
+    "**/.hg",
+    "**/CVS",
+    "/.lock-wscript",
+    "/.wafpickle-*",
+    "/build/config.gypi",
+    "npm-debug.log",
+    "**/.npmrc",
+    ".*.swp",
+    ".DS_Store",
+    "._*",
+    "*.orig",
+    "/package-lock.json",
+    "/yarn.lock",
+    "/pnpm-lock.yaml",
+    "/archived-packages/",
+    "node_modules/",
+];
+
+/// Modification time npm stamps on every tarball entry (1985-10-26T08:15:00Z)
+const NPM_TARBALL_MTIME: u64 = 499_162_500;
+
+/// A file that will be part of the package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackEntry {
+    /// Path relative to the package root, `/`-separated
+    pub path: String,
+    pub size: u64,
+    pub executable: bool,
+}
+
+/// A packed file that probably should not be published
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackWarning {
+    pub path: String,
+    pub reason: &'static str,
+}
+
+/// The computed contents of a package
+#[derive(Debug, Clone)]
+pub struct PackPlan {
+    pub name: String,
+    pub version: String,
+    pub entries: Vec<PackEntry>,
+    pub warnings: Vec<PackWarning>,
+}
+
+impl PackPlan {
+    /// Sum of all file sizes
+    pub fn unpacked_size(&self) -> u64 {
+        self.entries.iter().map(|e| e.size).sum()
+    }
+
+    /// File name npm uses for the tarball (`@scope/pkg` becomes `scope-pkg-1.0.0.tgz`)
+    pub fn tarball_name(&self) -> String {
+        let name = self.name.trim_start_matches('@').replace('/', "-");
+        format!("{name}-{}.tgz", self.version)
+    }
+}
+
+/// A built tarball
+#[derive(Debug, Clone)]
+pub struct Tarball {
+    pub bytes: Vec<u8>,
+    /// Subresource Integrity string (`sha512-…`)
+    pub integrity: String,
+}
+
+/// Why a packed path looks like something that should stay private
+pub fn suspicious_reason(path: &str) -> Option<&'static str> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let lower = file_name.to_lowercase();
+    let first_segment = path.split('/').next().unwrap_or(path);
+
+    if first_segment == "jetcrab_build" {
+        return Some("JetCrab bundle build directory");
+    }
+    if first_segment == "target" {
+        return Some("Cargo build output");
+    }
+    if lower == ".env"
+        || (lower.starts_with(".env.")
+            && !["example", "sample", "template", "dist"]
+                .iter()
+                .any(|suffix| lower.ends_with(suffix)))
+    {
+        return Some("environment file may contain secrets");
+    }
+    if ["id_rsa", "id_dsa", "id_ecdsa", "id_ed25519"].contains(&lower.as_str())
+        || [".pem", ".key", ".p12", ".pfx", ".jks", ".keystore"]
+            .iter()
+            .any(|ext| lower.ends_with(ext))
+    {
+        return Some("private key or certificate");
+    }
+    if [".netrc", ".pgpass", "credentials.json", ".htpasswd"].contains(&lower.as_str()) {
+        return Some("credentials file");
+    }
+    None
+}
+
+/// Ignore rules from `.npmignore`, or `.gitignore` when there is no `.npmignore`
+fn directory_ignore_rules(dir: &Path) -> Option<IgnoreRules> {
+    for file in [".npmignore", ".gitignore"] {
+        if let Ok(content) = std::fs::read_to_string(dir.join(file)) {
+            return Some(IgnoreRules::parse(&content));
+        }
+    }
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+struct Walker<'a> {
+    root: &'a Path,
+    defaults: IgnoreRules,
+    files_field: Option<Vec<String>>,
+    entries: Vec<PackEntry>,
+}
+
+impl Walker<'_> {
+    /// Whether a `files` entry selects this path (a listed directory selects its contents)
+    fn selected_by_files(&self, rel: &str) -> bool {
+        let Some(patterns) = &self.files_field else {
+            return true;
+        };
+        patterns.iter().any(|pattern| {
+            let pattern = pattern.trim_start_matches("./").trim_start_matches('/');
+            let pattern = pattern.trim_end_matches('/');
+            glob_match_dot(pattern, rel)
+                || rel
+                    .match_indices('/')
+                    .any(|(idx, _)| glob_match_dot(pattern, &rel[..idx]))
+        })
+    }
+
+    fn walk(
+        &mut self,
+        dir: &Path,
+        rel_dir: &str,
+        stack: &mut Vec<(String, IgnoreRules)>,
+    ) -> CliResult<()> {
+        // With a `files` list the root ignore file is not consulted, nested ones still are
+        let pushed = if rel_dir.is_empty() && self.files_field.is_some() {
+            false
+        } else if let Some(rules) = directory_ignore_rules(dir) {
+            stack.push((rel_dir.to_string(), rules));
+            true
+        } else {
+            false
+        };
+
+        let mut children: Vec<_> = std::fs::read_dir(dir)?.flatten().collect();
+        children.sort_by_key(|entry| entry.file_name());
+        for child in children {
+            let name = child.file_name().to_string_lossy().to_string();
+            let rel = if rel_dir.is_empty() {
+                name.clone()
+            } else {
+                format!("{rel_dir}/{name}")
+            };
+            let Ok(metadata) = std::fs::metadata(child.path()) else {
+                continue;
+            };
+            let is_dir = metadata.is_dir();
+
+            if self.defaults.matched(&rel, is_dir) == Some(true) {
+                continue;
+            }
+            let mut ignored = false;
+            for (base, rules) in stack.iter() {
+                let local = if base.is_empty() {
+                    rel.as_str()
+                } else {
+                    &rel[base.len() + 1..]
+                };
+                if let Some(result) = rules.matched(local, is_dir) {
+                    ignored = result;
+                }
+            }
+            if ignored {
+                continue;
+            }
+
+            if is_dir {
+                self.walk(&child.path(), &rel, stack)?;
+            } else if self.selected_by_files(&rel) {
+                self.entries.push(PackEntry {
+                    path: rel,
+                    size: metadata.len(),
+                    executable: is_executable(&metadata),
+                });
+            }
+        }
+
+        if pushed {
+            stack.pop();
+        }
+        Ok(())
+    }
+}
+
+/// Files npm always packs from the package root, whatever the ignore rules say
+fn always_included(root: &Path, manifest: &Value) -> CliResult<Vec<String>> {
+    let mut paths = vec!["package.json".to_string()];
+    for entry in std::fs::read_dir(root)?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let upper = name.to_uppercase();
+        let is_doc = ["README", "LICENSE", "LICENCE"]
+            .iter()
+            .any(|prefix| upper == *prefix || upper.starts_with(&format!("{prefix}.")));
+        if is_doc && entry.path().is_file() {
+            paths.push(name);
+        }
+    }
+    if let Some(main) = manifest.get("main").and_then(|m| m.as_str()) {
+        paths.push(main.trim_start_matches("./").to_string());
+    }
+    paths.extend(bin_paths(manifest));
+    Ok(paths)
+}
+
+fn bin_paths(manifest: &Value) -> Vec<String> {
+    match manifest.get("bin") {
+        Some(Value::String(bin)) => vec![bin.trim_start_matches("./").to_string()],
+        Some(Value::Object(bins)) => bins
+            .values()
+            .filter_map(|b| b.as_str())
+            .map(|b| b.trim_start_matches("./").to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Compute the publish file set for the package in `root`
+pub fn plan(root: &Path) -> CliResult<PackPlan> {
+    let manifest_path = root.join("package.json");
+    if !manifest_path.exists() {
+        return Err(CliError::FileOperationError {
+            operation: "pack".to_string(),
+            path: manifest_path.display().to_string(),
+            message: "Not in a JavaScript project. Run 'cpm init' first.".to_string(),
+        });
+    }
+    let manifest: Value = serde_json::from_str(&std::fs::read_to_string(&manifest_path)?)?;
+    let name = manifest
+        .get("name")
+        .and_then(|n| n.as_str())
+        .ok_or_else(|| CliError::InternalError {
+            message: "package.json has no \"name\"".to_string(),
+        })?
+        .to_string();
+    let version = manifest
+        .get("version")
+        .and_then(|v| v.as_str())
+        .unwrap_or("0.0.0")
+        .to_string();
+
+    let files_field = manifest
+        .get("files")
+        .and_then(|f| f.as_array())
+        .map(|files| {
+            files
+                .iter()
+                .filter_map(|f| f.as_str())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        });
+
+    let mut defaults = IgnoreRules::parse(&ALWAYS_EXCLUDED.join("\n"));
+    // A tarball left behind by a previous `cpm pack` must not end up in the next one
+    let previous_tarball = PackPlan {
+        name: name.clone(),
+        version: version.clone(),
+        entries: Vec::new(),
+        warnings: Vec::new(),
+    }
+    .tarball_name();
+    defaults.add(&format!("/{previous_tarball}"));
+
+    let mut walker = Walker {
+        root,
+        defaults,
+        files_field,
+        entries: Vec::new(),
+    };
+    walker.walk(root, "", &mut Vec::new())?;
+
+    for path in always_included(walker.root, &manifest)? {
+        if walker.entries.iter().any(|e| e.path == path) {
+            continue;
+        }
+        if let Ok(metadata) = std::fs::metadata(root.join(&path)) {
+            if metadata.is_file() {
+                walker.entries.push(PackEntry {
+                    path,
+                    size: metadata.len(),
+                    executable: is_executable(&metadata),
+                });
+            }
+        }
+    }
+
+    let bins = bin_paths(&manifest);
+    for entry in &mut walker.entries {
+        if bins.contains(&entry.path) {
+            entry.executable = true;
+        }
+    }
+    walker.entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let warnings = walker
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            suspicious_reason(&entry.path).map(|reason| PackWarning {
+                path: entry.path.clone(),
+                reason,
+            })
+        })
+        .collect();
+
+    Ok(PackPlan {
+        name,
+        version,
+        entries: walker.entries,
+        warnings,
+    })
+}
+
+/// Build a reproducible gzipped tarball with every file under `package/`
+pub fn build_tarball(root: &Path, plan: &PackPlan) -> CliResult<Tarball> {
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.mode(tar::HeaderMode::Deterministic);
+    for entry in &plan.entries {
+        let data = std::fs::read(root.join(&entry.path))?;
+        let mut header = tar::Header::new_ustar();
+        header.set_size(data.len() as u64);
+        header.set_mode(if entry.executable { 0o755 } else { 0o644 });
+        header.set_mtime(NPM_TARBALL_MTIME);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_entry_type(tar::EntryType::Regular);
+        builder.append_data(
+            &mut header,
+            format!("package/{}", entry.path),
+            data.as_slice(),
+        )?;
+    }
+    let tar_bytes = builder.into_inner()?;
+
+    let mut encoder = flate2::GzBuilder::new()
+        .mtime(0)
+        .write(Vec::new(), flate2::Compression::best());
+    std::io::Write::write_all(&mut encoder, &tar_bytes)?;
+    let bytes = encoder.finish()?;
+
+    let digest = Sha512::digest(&bytes);
+    let integrity = format!(
+        "sha512-{}",
+        base64::engine::general_purpose::STANDARD.encode(digest)
+    );
+    Ok(Tarball { bytes, integrity })
+}
+
+/// Human readable size using npm's decimal units (`1.2kB`)
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=999 => format!("{bytes}B"),
+        1_000..=999_999 => format!("{:.1}kB", bytes as f64 / 1_000.0),
+        _ => format!("{:.1}MB", bytes as f64 / 1_000_000.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::write;
+
+    fn paths(plan: &PackPlan) -> Vec<&str> {
+        plan.entries.iter().map(|e| e.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_plan_honors_npmignore_and_defaults() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        write(root, "package.json", r#"{"name":"app","version":"1.0.0"}"#);
+        write(root, "index.js", "");
+        write(root, "README.md", "");
+        write(root, ".npmignore", "test/\n");
+        write(root, ".gitignore", "index.js\n");
+        write(root, "test/a.test.js", "");
+        write(root, "node_modules/x/index.js", "");
+        write(root, "package-lock.json", "{}");
+        write(root, ".env", "SECRET=1");
+
+        let plan = plan(root).unwrap();
+        assert_eq!(
+            paths(&plan),
+            vec![".env", "README.md", "index.js", "package.json"]
+        );
+        assert_eq!(plan.warnings.len(), 1);
+        assert_eq!(plan.warnings[0].path, ".env");
+    }
+
+    #[test]
+    fn test_plan_files_field_and_always_included() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        write(
+            root,
+            "package.json",
+            r#"{"name":"@acme/app","version":"2.0.0","main":"index.js","files":["dist"]}"#,
+        );
+        write(root, "index.js", "");
+        write(root, "LICENSE", "");
+        write(root, "dist/app.js", "");
+        write(root, "src/app.ts", "");
+        write(root, ".npmignore", "dist/\n");
+
+        let plan = plan(root).unwrap();
+        assert_eq!(
+            paths(&plan),
+            vec!["LICENSE", "dist/app.js", "index.js", "package.json"]
+        );
+        assert_eq!(plan.tarball_name(), "acme-app-2.0.0.tgz");
+    }
+
+    #[test]
+    fn test_tarball_is_deterministic() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        write(root, "package.json", r#"{"name":"app","version":"1.0.0"}"#);
+        write(root, "index.js", "console.log(1)");
+        let plan = plan(root).unwrap();
+        let first = build_tarball(root, &plan).unwrap();
+        let second = build_tarball(root, &plan).unwrap();
+        assert_eq!(first.bytes, second.bytes);
+        assert!(first.integrity.starts_with("sha512-"));
+    }
+}
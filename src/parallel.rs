@@ -0,0 +1,429 @@
+//! Running jobs with dependencies in parallel
+//!
+//! A small scheduler used for workspace-wide scripts: jobs start once their
+//! dependencies have passed, at most `concurrency` run at a time, and a
+//! failure either stops new jobs (`bail`) or only skips the jobs depending on
+//! it. Child process output is relayed line by line with a prefix so that
+//! interleaved output stays readable. [`run_group`] runs a fixed set of
+//! processes side by side instead, for `cpm run -p`.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Outcome of a single job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobStatus {
+    Passed,
+    /// Failed, with the exit code when the job was a process that exited normally
+    Failed(Option<i32>),
+    /// Not started, with the reason
+    Skipped(String),
+    /// Stopped while running because another job ended the group
+    Stopped,
+}
+
+impl JobStatus {
+    /// Status of a finished child process
+    pub fn from_exit(status: ExitStatus) -> Self {
+        if status.success() {
+            JobStatus::Passed
+        } else {
+            JobStatus::Failed(status.code())
+        }
+    }
+}
+
+/// A job's status and how long it ran
+#[derive(Debug, Clone)]
+pub struct JobResult {
+    pub status: JobStatus,
+    pub duration: Duration,
+}
+
+/// Scheduling options
+#[derive(Debug, Clone, Copy)]
+pub struct Schedule {
+    /// Maximum number of jobs running at once, at least one
+    pub concurrency: usize,
+    /// Stop starting jobs after the first failure
+    pub bail: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum State {
+    Pending,
+    Running,
+    Done(JobStatus),
+}
+
+enum Next {
+    Run(usize),
+    Wait,
+    Finished,
+}
+
+/// Skip jobs that can no longer run and claim the first ready one
+fn next_job(jobs: &mut [State], dependencies: &[Vec<usize>], bailed: bool) -> Next {
+    let passed = |jobs: &[State], dep: usize| jobs[dep] == State::Done(JobStatus::Passed);
+    let mut progressed = true;
+    while progressed {
+        progressed = false;
+        for idx in 0..jobs.len() {
+            if jobs[idx] != State::Pending {
+                continue;
+            }
+            let blocked = dependencies[idx]
+                .iter()
+                .any(|dep| matches!(jobs[*dep], State::Done(_)) && !passed(jobs, *dep));
+            let reason = if blocked {
+                "a dependency did not pass"
+            } else if bailed {
+                "stopped after a failure"
+            } else {
+                continue;
+            };
+            jobs[idx] = State::Done(JobStatus::Skipped(reason.to_string()));
+            progressed = true;
+        }
+    }
+
+    let ready = (0..jobs.len()).find(|idx| {
+        jobs[*idx] == State::Pending && dependencies[*idx].iter().all(|dep| passed(jobs, *dep))
+    });
+    if let Some(idx) = ready {
+        jobs[idx] = State::Running;
+        return Next::Run(idx);
+    }
+    if jobs.contains(&State::Running) {
+        return Next::Wait;
+    }
+    // Nothing runs and nothing is ready: whatever is left waits on a cycle
+    for state in jobs.iter_mut() {
+        if *state == State::Pending {
+            *state = State::Done(JobStatus::Skipped("dependency cycle".to_string()));
+        }
+    }
+    Next::Finished
+}
+
+/// Run `dependencies.len()` jobs, where job `i` waits for every job in `dependencies[i]`
+///
+/// `job` is called from worker threads with the job index. Jobs whose
+/// dependencies did not pass are skipped. Results are indexed like
+/// `dependencies`; a dependency cycle leaves the jobs in it skipped.
+pub fn run_jobs<F>(dependencies: &[Vec<usize>], schedule: Schedule, job: F) -> Vec<JobResult>
+where
+    F: Fn(usize) -> JobStatus + Sync,
+{
+    let count = dependencies.len();
+    let states = Mutex::new((vec![State::Pending; count], vec![Duration::ZERO; count]));
+    let changed = Condvar::new();
+    let failed = Mutex::new(false);
+
+    std::thread::scope(|scope| {
+        for _ in 0..schedule.concurrency.clamp(1, count.max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let next = {
+                        let mut guard = states.lock().unwrap();
+                        loop {
+                            let bailed = schedule.bail && *failed.lock().unwrap();
+                            match next_job(&mut guard.0, dependencies, bailed) {
+                                Next::Run(idx) => break Some(idx),
+                                Next::Finished => {
+                                    changed.notify_all();
+                                    break None;
+                                }
+                                Next::Wait => guard = changed.wait(guard).unwrap(),
+                            }
+                        }
+                    };
+                    let Some(idx) = next else {
+                        return;
+                    };
+
+                    let started = Instant::now();
+                    let status = job(idx);
+                    if status != JobStatus::Passed {
+                        *failed.lock().unwrap() = true;
+                    }
+                    let mut guard = states.lock().unwrap();
+                    guard.0[idx] = State::Done(status);
+                    guard.1[idx] = started.elapsed();
+                    changed.notify_all();
+                }
+            });
+        }
+    });
+
+    let (jobs, durations) = states.into_inner().unwrap();
+    jobs.into_iter()
+        .zip(durations)
+        .map(|(state, duration)| JobResult {
+            status: match state {
+                State::Done(status) => status,
+                _ => JobStatus::Skipped("not started".to_string()),
+            },
+            duration,
+        })
+        .collect()
+}
+
+/// Copy lines from `reader` to `out`, each starting with `prefix`
+fn relay<R: Read, W: Write>(reader: R, prefix: &str, mut out: W) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+        let text = String::from_utf8_lossy(&line);
+        let _ = writeln!(out, "{prefix}{}", text.trim_end_matches(['\r', '\n']));
+        line.clear();
+    }
+}
+
+/// Run `command`, relaying its stdout and stderr with every line prefixed
+pub fn run_prefixed(command: &mut Command, prefix: &str) -> std::io::Result<ExitStatus> {
+    let mut child = PrefixedChild::spawn(command, prefix)?;
+    let status = child.child.wait();
+    child.finish();
+    status
+}
+
+/// A running child process whose output is relayed with a prefix
+struct PrefixedChild {
+    child: Child,
+    relays: Vec<std::thread::JoinHandle<()>>,
+}
+
+impl PrefixedChild {
+    fn spawn(command: &mut Command, prefix: &str) -> std::io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut relays = Vec::new();
+        if let Some(stdout) = child.stdout.take() {
+            let prefix = prefix.to_string();
+            relays.push(std::thread::spawn(move || {
+                relay(stdout, &prefix, std::io::stdout())
+            }));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            let prefix = prefix.to_string();
+            relays.push(std::thread::spawn(move || {
+                relay(stderr, &prefix, std::io::stderr())
+            }));
+        }
+        Ok(Self { child, relays })
+    }
+
+    /// Wait for the relays to drain the remaining output
+    fn finish(self) {
+        for relay in self.relays {
+            let _ = relay.join();
+        }
+    }
+}
+
+/// Start `command` in its own process group, so the whole tree can be stopped
+#[cfg(unix)]
+fn spawn_group(command: &mut Command, prefix: &str) -> std::io::Result<PrefixedChild> {
+    use std::os::unix::process::CommandExt;
+    PrefixedChild::spawn(command.process_group(0), prefix)
+}
+
+#[cfg(not(unix))]
+fn spawn_group(command: &mut Command, prefix: &str) -> std::io::Result<PrefixedChild> {
+    PrefixedChild::spawn(command, prefix)
+}
+
+/// Stop the process started by [`spawn_group`] and everything it started
+#[cfg(unix)]
+fn kill_group(pid: u32) {
+    let _ = Command::new("kill")
+        .args(["-TERM", "--", &format!("-{pid}")])
+        .stderr(Stdio::null())
+        .status();
+}
+
+#[cfg(windows)]
+fn kill_group(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+#[cfg(not(any(unix, windows)))]
+fn kill_group(_pid: u32) {}
+
+/// How a group started by [`run_group`] ends
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GroupOptions {
+    /// Stop the other processes as soon as one exits, whatever its status
+    pub race: bool,
+    /// Let the other processes finish after one fails
+    pub continue_on_error: bool,
+}
+
+/// Run all `commands` at once, relaying each one's output with its prefix
+///
+/// Unless `continue_on_error` is set, the first failure stops the processes
+/// still running, which are reported as [`JobStatus::Stopped`]. Ctrl+C stops
+/// the whole group before exiting.
+pub fn run_group(commands: Vec<(Command, String)>, options: GroupOptions) -> Vec<JobResult> {
+    let started = Instant::now();
+    let pids = Arc::new(Mutex::new(Vec::new()));
+    let on_interrupt = Arc::clone(&pids);
+    // Fails when a handler is already installed; the group is still stopped on errors
+    let _ = ctrlc::set_handler(move || {
+        for pid in on_interrupt.lock().unwrap().iter() {
+            kill_group(*pid);
+        }
+        std::process::exit(130);
+    });
+
+    let mut children = Vec::new();
+    let mut results = Vec::new();
+    for (mut command, prefix) in commands {
+        match spawn_group(&mut command, &prefix) {
+            Ok(child) => {
+                pids.lock().unwrap().push(child.child.id());
+                children.push(Some(child));
+                results.push(None);
+            }
+            Err(e) => {
+                let _ = writeln!(std::io::stderr(), "{prefix}{e}");
+                children.push(None);
+                results.push(Some(JobResult {
+                    status: JobStatus::Failed(None),
+                    duration: Duration::ZERO,
+                }));
+            }
+        }
+    }
+
+    let mut stop = results.iter().flatten().next().is_some() && !options.continue_on_error;
+    while !stop && results.iter().any(Option::is_none) {
+        for (child, result) in children.iter_mut().zip(results.iter_mut()) {
+            let Some(running) = child.as_mut().filter(|_| result.is_none()) else {
+                continue;
+            };
+            let status = match running.child.try_wait() {
+                Ok(Some(status)) => JobStatus::from_exit(status),
+                Ok(None) => continue,
+                Err(_) => JobStatus::Failed(None),
+            };
+            let failed = status != JobStatus::Passed;
+            *result = Some(JobResult {
+                status,
+                duration: started.elapsed(),
+            });
+            if options.race || (failed && !options.continue_on_error) {
+                stop = true;
+                break;
+            }
+        }
+        if !stop {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    for (child, result) in children.into_iter().zip(results.iter_mut()) {
+        let Some(mut child) = child else {
+            continue;
+        };
+        if result.is_none() {
+            kill_group(child.child.id());
+            let _ = child.child.kill();
+            let _ = child.child.wait();
+            *result = Some(JobResult {
+                status: JobStatus::Stopped,
+                duration: started.elapsed(),
+            });
+        }
+        child.finish();
+    }
+    results.into_iter().flatten().collect()
+}
+
+/// Format a duration for summaries, e.g. `850ms` or `12.3s`
+pub fn format_duration(duration: Duration) -> String {
+    if duration.as_millis() < 1000 {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jobs_wait_for_dependencies() {
+        let order = Mutex::new(Vec::new());
+        let dependencies = vec![vec![1, 2], vec![2], vec![]];
+        let schedule = Schedule {
+            concurrency: 3,
+            bail: true,
+        };
+        let results = run_jobs(&dependencies, schedule, |idx| {
+            order.lock().unwrap().push(idx);
+            JobStatus::Passed
+        });
+        assert_eq!(order.into_inner().unwrap(), vec![2, 1, 0]);
+        assert!(results.iter().all(|r| r.status == JobStatus::Passed));
+    }
+
+    #[test]
+    fn test_failure_skips_dependents_and_bails() {
+        // 0 fails; 1 depends on 0; 2 is independent
+        let dependencies = vec![vec![], vec![0], vec![]];
+        let run = |bail| {
+            let schedule = Schedule {
+                concurrency: 1,
+                bail,
+            };
+            run_jobs(&dependencies, schedule, |idx| match idx {
+                0 => JobStatus::Failed(Some(2)),
+                _ => JobStatus::Passed,
+            })
+        };
+
+        let results = run(false);
+        assert_eq!(results[0].status, JobStatus::Failed(Some(2)));
+        assert!(matches!(results[1].status, JobStatus::Skipped(_)));
+        assert_eq!(results[2].status, JobStatus::Passed);
+
+        let results = run(true);
+        assert!(matches!(results[2].status, JobStatus::Skipped(_)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_group_failure_stops_the_others() {
+        let shell = |script: &str| {
+            let mut command = Command::new("sh");
+            command.args(["-c", script]);
+            (command, String::new())
+        };
+        let commands = vec![shell("sleep 10"), shell("exit 3")];
+
+        let results = run_group(commands, GroupOptions::default());
+        assert_eq!(results[0].status, JobStatus::Stopped);
+        assert_eq!(results[1].status, JobStatus::Failed(Some(3)));
+        assert!(results[0].duration < Duration::from_secs(5));
+
+        let options = GroupOptions {
+            race: true,
+            continue_on_error: false,
+        };
+        let results = run_group(vec![shell("sleep 10"), shell("true")], options);
+        assert_eq!(results[0].status, JobStatus::Stopped);
+        assert_eq!(results[1].status, JobStatus::Passed);
+    }
+}
@@ -0,0 +1,802 @@
+//! Dependency policy
+//!
+//! Evaluates the `[policy]` section of `cpm.toml` against the dependencies a
+//! command is about to add or install, before npm or cargo is invoked.
+
+use crate::cli::framework::{CliError, CliResult};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+
+fn default_true() -> bool {
+    true
+}
+
+/// The `[policy]` section of `cpm.toml`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PolicyConfig {
+    /// Packages and crates that may never be depended on
+    #[serde(default)]
+    pub banned: Vec<String>,
+    /// Registries dependencies may come from
+    ///
+    /// URL entries apply to npm (`https://registry.npmjs.org/`), bare names apply to
+    /// Cargo registries (`crates-io`, `my-company`). An empty list allows any registry.
+    #[serde(default)]
+    pub allowed_registries: Vec<String>,
+    /// Allow git dependencies (`git+https://…`, `github:user/repo`, `git = "…"`)
+    #[serde(default = "default_true")]
+    pub allow_git: bool,
+    /// Allow tarball URL dependencies (`https://…/pkg.tgz`)
+    #[serde(default = "default_true")]
+    pub allow_url: bool,
+    /// Allow local dependencies (`file:../lib`, `path = "…"`)
+    #[serde(default = "default_true")]
+    pub allow_file: bool,
+    /// Maximum age in days of a version being added
+    pub max_version_age_days: Option<u64>,
+    /// Reject names that look like typos of popular packages
+    #[serde(default = "default_true")]
+    pub typosquat_check: bool,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            banned: Vec::new(),
+            allowed_registries: Vec::new(),
+            allow_git: true,
+            allow_url: true,
+            allow_file: true,
+            max_version_age_days: None,
+            typosquat_check: true,
+        }
+    }
+}
+
+/// Popular npm packages and crates that typosquatters imitate
+#[rustfmt::skip]
+pub const POPULAR_PACKAGES: &[&str] = &[
+    // npm
+    "react", "react-dom", "vue", "angular", "svelte", "next", "nuxt", "express", "koa",
+    "fastify", "lodash", "underscore", "moment", "dayjs", "date-fns", "axios", "request",
+    "node-fetch", "chalk", "commander", "yargs", "debug", "dotenv", "uuid", "webpack", "rollup",
+    "vite", "esbuild", "typescript", "babel-core", "eslint", "prettier", "jest", "mocha",
+    "chai", "vitest", "rimraf", "mkdirp", "glob", "minimist", "semver", "async", "bluebird",
+    "rxjs", "redux", "mongoose", "mongodb", "mysql", "sequelize", "prisma", "socket.io",
+    "jsonwebtoken", "bcrypt", "cors", "body-parser", "cross-env", "nodemon", "inquirer", "ora",
+    "colors", "classnames", "tailwindcss", "postcss", "autoprefixer", "sass", "jquery",
+    "bootstrap", "electron", "puppeteer", "cheerio", "ws", "zod", "graphql", "core-js", "tslib",
+    "fs-extra", "http-proxy", "qs", "cookie-parser", "wasm-bindgen", "crypto-js",
+    "event-stream", "left-pad",
+    // crates.io
+    "serde", "serde_json", "tokio", "rand", "regex", "clap", "anyhow", "thiserror", "log",
+    "reqwest", "hyper", "syn", "quote", "proc-macro2", "futures", "chrono", "time", "tracing",
+    "rayon", "itertools", "once_cell", "lazy_static", "bytes", "libc", "web-sys", "js-sys",
+];
+
+/// Legitimate packages whose names are close to a popular one
+#[rustfmt::skip]
+pub const KNOWN_PACKAGES: &[&str] = &[
+    "mysql2", "preact", "color", "tslint", "redux-thunk", "vuex", "koa-router", "globby",
+    "nodemailer", "ioredis", "redis", "mongodb-memory-server", "axios-retry", "chalk-template",
+    "yargs-parser", "serde_yaml", "tokio-util", "regex-syntax", "clap_derive", "log4rs",
+];
+
+/// The kind of policy rule a violation belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PolicyRule {
+    Banned,
+    Registry,
+    Git,
+    Url,
+    File,
+    Age,
+    Typosquat,
+}
+
+impl PolicyRule {
+    /// Name accepted by `--allow` to override every violation of this rule
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PolicyRule::Banned => "banned",
+            PolicyRule::Registry => "registry",
+            PolicyRule::Git => "git",
+            PolicyRule::Url => "url",
+            PolicyRule::File => "file",
+            PolicyRule::Age => "age",
+            PolicyRule::Typosquat => "typosquat",
+        }
+    }
+}
+
+/// A dependency rejected by the policy
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Violation {
+    pub rule: PolicyRule,
+    /// Package or crate name the violation is about
+    pub subject: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {}",
+            self.rule.as_str(),
+            self.subject,
+            self.message
+        )
+    }
+}
+
+/// Ecosystem of a dependency under evaluation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ecosystem {
+    Npm,
+    Cargo,
+}
+
+/// Where a dependency is fetched from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencySource {
+    /// A registry version or range; `registry` is the npm URL or Cargo registry name
+    Registry {
+        range: String,
+        registry: Option<String>,
+    },
+    Git(String),
+    Url(String),
+    File(String),
+}
+
+/// A dependency being added or installed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    pub ecosystem: Ecosystem,
+    pub name: String,
+    pub source: DependencySource,
+}
+
+/// Classify an npm dependency specifier (`^1.0.0`, `github:a/b`, `file:../x`, …)
+pub fn classify_npm_spec(spec: &str) -> DependencySource {
+    let spec = spec.trim();
+    let is_git = spec.starts_with("git+")
+        || spec.starts_with("git://")
+        || spec.starts_with("git@")
+        || spec.starts_with("github:")
+        || spec.starts_with("gitlab:")
+        || spec.starts_with("bitbucket:")
+        || spec.starts_with("gist:")
+        || spec.ends_with(".git")
+        || (!spec.contains(':')
+            && !spec.starts_with('.')
+            && !spec.starts_with('@')
+            && spec.split('/').count() == 2
+            && !spec.contains(' '));
+    if is_git {
+        DependencySource::Git(spec.to_string())
+    } else if spec.starts_with("http://") || spec.starts_with("https://") {
+        DependencySource::Url(spec.to_string())
+    } else if spec.starts_with("file:")
+        || spec.starts_with("link:")
+        || spec.starts_with('.')
+        || spec.starts_with('/')
+        || spec.starts_with("~/")
+    {
+        DependencySource::File(spec.to_string())
+    } else {
+        DependencySource::Registry {
+            range: spec.to_string(),
+            registry: None,
+        }
+    }
+}
+
+/// Parse a `cpm add` argument (`lodash@^4`, `@scope/pkg@1.0.0`, `github:user/repo`, `./lib`)
+pub fn parse_add_argument(arg: &str) -> Dependency {
+    let source = classify_npm_spec(arg);
+    let name = match &source {
+        DependencySource::Registry { .. } => {
+            let (name, range) = match arg.strip_prefix('@') {
+                Some(rest) => match rest.split_once('@') {
+                    Some((name, range)) => (format!("@{name}"), range.to_string()),
+                    None => (arg.to_string(), String::new()),
+                },
+                None => match arg.split_once('@') {
+                    Some((name, range)) => (name.to_string(), range.to_string()),
+                    None => (arg.to_string(), String::new()),
+                },
+            };
+            return Dependency {
+                ecosystem: Ecosystem::Npm,
+                name,
+                source: DependencySource::Registry {
+                    range: if range.is_empty() {
+                        "latest".to_string()
+                    } else {
+                        range
+                    },
+                    registry: None,
+                },
+            };
+        }
+        _ => arg
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .rsplit(['/', ':'])
+            .next()
+            .unwrap_or(arg)
+            .to_string(),
+    };
+    Dependency {
+        ecosystem: Ecosystem::Npm,
+        name,
+        source,
+    }
+}
+
+/// Names are compared without separators so `cross_env` matches `cross-env`
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '-' | '_' | '.'))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Popular package a name appears to imitate, if any
+///
+/// Names on either list are trusted, and only popular names of six or more
+/// characters are compared by edit distance, since short names have too many
+/// legitimate neighbours.
+pub fn typosquat_target(name: &str) -> Option<&'static str> {
+    let bare = name.rsplit('/').next().unwrap_or(name);
+    let known = |candidate: &str| {
+        POPULAR_PACKAGES.contains(&candidate) || KNOWN_PACKAGES.contains(&candidate)
+    };
+    if known(name) || known(bare) {
+        return None;
+    }
+    let normalized = normalize_name(bare);
+    POPULAR_PACKAGES.iter().copied().find(|popular| {
+        let popular_normalized = normalize_name(popular);
+        if popular_normalized == normalized {
+            return true;
+        }
+        let max_distance = if popular.len() >= 8 { 2 } else { 1 };
+        popular.len() >= 6
+            && crate::fuzzy::edit_distance(&normalized, &popular_normalized) <= max_distance
+    })
+}
+
+fn registry_allowed(allowed: &[String], ecosystem: Ecosystem, registry: &str) -> bool {
+    let is_url = |entry: &str| entry.contains("://");
+    match ecosystem {
+        Ecosystem::Npm => {
+            let urls: Vec<&String> = allowed.iter().filter(|e| is_url(e)).collect();
+            urls.is_empty()
+                || urls
+                    .iter()
+                    .any(|entry| registry.starts_with(entry.trim_end_matches('/')))
+        }
+        Ecosystem::Cargo => {
+            let names: Vec<&String> = allowed.iter().filter(|e| !is_url(e)).collect();
+            (names.is_empty() && registry == "crates-io") || names.iter().any(|n| *n == registry)
+        }
+    }
+}
+
+/// Policy evaluation with the `--allow` overrides given on the command line
+pub struct Policy<'a> {
+    config: &'a PolicyConfig,
+    overrides: Vec<String>,
+}
+
+impl<'a> Policy<'a> {
+    pub fn new(config: &'a PolicyConfig, overrides: &[String]) -> Self {
+        Self {
+            config,
+            overrides: overrides.to_vec(),
+        }
+    }
+
+    /// Whether `--allow` overrides this violation (by rule name, package name or `rule:package`)
+    fn overridden(&self, violation: &Violation) -> bool {
+        self.overrides.iter().any(|o| {
+            o == violation.rule.as_str()
+                || *o == violation.subject
+                || *o == format!("{}:{}", violation.rule.as_str(), violation.subject)
+        })
+    }
+
+    fn push(
+        &self,
+        violations: &mut Vec<Violation>,
+        rule: PolicyRule,
+        subject: &str,
+        message: String,
+    ) {
+        let violation = Violation {
+            rule,
+            subject: subject.to_string(),
+            message,
+        };
+        if !self.overridden(&violation) {
+            violations.push(violation);
+        }
+    }
+
+    /// Check a single dependency against name and source rules
+    pub fn check_dependency(&self, dependency: &Dependency) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let name = dependency.name.as_str();
+
+        if self.config.banned.iter().any(|b| b == name) {
+            self.push(
+                &mut violations,
+                PolicyRule::Banned,
+                name,
+                "package is banned by the project policy".to_string(),
+            );
+        }
+
+        if self.config.typosquat_check {
+            if let Some(target) = typosquat_target(name) {
+                self.push(
+                    &mut violations,
+                    PolicyRule::Typosquat,
+                    name,
+                    format!("name is suspiciously close to the popular package '{target}'"),
+                );
+            }
+        }
+
+        match &dependency.source {
+            DependencySource::Git(url) if !self.config.allow_git => self.push(
+                &mut violations,
+                PolicyRule::Git,
+                name,
+                format!("git dependencies are not allowed ({url})"),
+            ),
+            DependencySource::Url(url) if !self.config.allow_url => self.push(
+                &mut violations,
+                PolicyRule::Url,
+                name,
+                format!("URL dependencies are not allowed ({url})"),
+            ),
+            DependencySource::File(path) if !self.config.allow_file => self.push(
+                &mut violations,
+                PolicyRule::File,
+                name,
+                format!("local file dependencies are not allowed ({path})"),
+            ),
+            DependencySource::Registry {
+                registry: Some(registry),
+                ..
+            } => {
+                if let Some(v) = self.check_registry(dependency.ecosystem, name, registry) {
+                    violations.push(v);
+                }
+            }
+            _ => {}
+        }
+        violations
+    }
+
+    /// Check that a package is fetched from an allowed registry
+    pub fn check_registry(
+        &self,
+        ecosystem: Ecosystem,
+        subject: &str,
+        registry: &str,
+    ) -> Option<Violation> {
+        if registry_allowed(&self.config.allowed_registries, ecosystem, registry) {
+            return None;
+        }
+        let mut violations = Vec::new();
+        self.push(
+            &mut violations,
+            PolicyRule::Registry,
+            subject,
+            format!(
+                "registry '{registry}' is not in allowed-registries ({})",
+                self.config.allowed_registries.join(", ")
+            ),
+        );
+        violations.pop()
+    }
+
+    /// Check the publish time of a version being added against `max-version-age-days`
+    pub fn check_version_age(
+        &self,
+        subject: &str,
+        version: &str,
+        published: u64,
+        now: u64,
+    ) -> Option<Violation> {
+        let max_days = self.config.max_version_age_days?;
+        let age_days = now.saturating_sub(published) / 86_400;
+        if age_days <= max_days {
+            return None;
+        }
+        let mut violations = Vec::new();
+        self.push(
+            &mut violations,
+            PolicyRule::Age,
+            subject,
+            format!("version {version} was published {age_days} days ago, the policy allows at most {max_days}"),
+        );
+        violations.pop()
+    }
+
+    /// Evaluate the dependencies declared by the project in `dir`
+    ///
+    /// Covers `package.json` (plus registry URLs recorded in `package-lock.json`)
+    /// and, when `include_cargo` is set, `Cargo.toml`. Names are only checked
+    /// for typosquatting when they are added, so dependencies a project
+    /// already uses never start failing `cpm install`.
+    pub fn check_project(&self, dir: &Path, include_cargo: bool) -> CliResult<Vec<Violation>> {
+        let mut violations = Vec::new();
+        for dependency in npm_dependencies(dir)? {
+            violations.extend(self.check_dependency(&dependency));
+        }
+        for (name, resolved) in npm_lockfile_registries(dir)? {
+            if let Some(v) = self.check_registry(Ecosystem::Npm, &name, &resolved) {
+                violations.push(v);
+            }
+        }
+        if include_cargo {
+            for dependency in cargo_dependencies(dir)? {
+                violations.extend(self.check_dependency(&dependency));
+            }
+        }
+        violations.retain(|violation| violation.rule != PolicyRule::Typosquat);
+        // The same dependency can be reported by several passes
+        let mut seen = std::collections::BTreeSet::new();
+        violations.retain(|violation| seen.insert(violation.clone()));
+        Ok(violations)
+    }
+}
+
+/// Dependencies declared in `package.json`
+pub fn npm_dependencies(dir: &Path) -> CliResult<Vec<Dependency>> {
+    let path = dir.join("package.json");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let manifest: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let mut dependencies = Vec::new();
+    for section in [
+        "dependencies",
+        "devDependencies",
+        "optionalDependencies",
+        "peerDependencies",
+    ] {
+        if let Some(deps) = manifest.get(section).and_then(|d| d.as_object()) {
+            for (name, spec) in deps {
+                let spec = spec.as_str().unwrap_or_default();
+                // npm aliases (`npm:real-name@1.0.0`) are checked under the real name
+                let (name, spec) = match spec.strip_prefix("npm:") {
+                    Some(alias) => {
+                        let parsed = parse_add_argument(alias);
+                        (parsed.name, alias.to_string())
+                    }
+                    None => (name.clone(), spec.to_string()),
+                };
+                if spec.starts_with("workspace:") {
+                    continue;
+                }
+                dependencies.push(Dependency {
+                    ecosystem: Ecosystem::Npm,
+                    name,
+                    source: classify_npm_spec(&spec),
+                });
+            }
+        }
+    }
+    Ok(dependencies)
+}
+
+/// Registry tarball URLs recorded in `package-lock.json`, keyed by package name
+fn npm_lockfile_registries(dir: &Path) -> CliResult<Vec<(String, String)>> {
+    let path = dir.join("package-lock.json");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let lock: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let mut registries = Vec::new();
+    if let Some(packages) = lock.get("packages").and_then(|p| p.as_object()) {
+        for (path, entry) in packages {
+            let Some(resolved) = entry.get("resolved").and_then(|r| r.as_str()) else {
+                continue;
+            };
+            if !(resolved.starts_with("http://") || resolved.starts_with("https://")) {
+                continue;
+            }
+            let name = path
+                .rsplit_once("node_modules/")
+                .map(|(_, n)| n)
+                .unwrap_or(path);
+            registries.push((name.to_string(), resolved.to_string()));
+        }
+    }
+    Ok(registries)
+}
+
+/// Dependencies declared in `Cargo.toml`
+pub fn cargo_dependencies(dir: &Path) -> CliResult<Vec<Dependency>> {
+    let path = dir.join("Cargo.toml");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let manifest: toml::Value = toml::from_str(&std::fs::read_to_string(path)?)?;
+    let mut dependencies = Vec::new();
+    for section in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        let Some(deps) = manifest.get(section).and_then(|d| d.as_table()) else {
+            continue;
+        };
+        for (key, spec) in deps {
+            let name = spec
+                .get("package")
+                .and_then(|p| p.as_str())
+                .unwrap_or(key)
+                .to_string();
+            let source = if let Some(git) = spec.get("git").and_then(|g| g.as_str()) {
+                DependencySource::Git(git.to_string())
+            } else if let Some(path) = spec.get("path").and_then(|p| p.as_str()) {
+                DependencySource::File(path.to_string())
+            } else {
+                DependencySource::Registry {
+                    range: spec
+                        .as_str()
+                        .or_else(|| spec.get("version").and_then(|v| v.as_str()))
+                        .unwrap_or("*")
+                        .to_string(),
+                    registry: Some(
+                        spec.get("registry")
+                            .and_then(|r| r.as_str())
+                            .unwrap_or("crates-io")
+                            .to_string(),
+                    ),
+                }
+            };
+            dependencies.push(Dependency {
+                ecosystem: Ecosystem::Cargo,
+                name,
+                source,
+            });
+        }
+    }
+    Ok(dependencies)
+}
+
+/// Resolve the version npm would install for `name@range` and its publish time
+///
+/// Returns `None` when the registry cannot be queried (for example offline).
+pub fn npm_version_published(name: &str, range: &str) -> Option<(String, u64)> {
+    let npm_cmd = if cfg!(target_os = "windows") {
+        "npm.cmd"
+    } else {
+        "npm"
+    };
+    let output = std::process::Command::new(npm_cmd)
+        .args([
+            "view",
+            &format!("{name}@{range}"),
+            "version",
+            "time",
+            "--json",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let json: Value = serde_json::from_slice(&output.stdout).ok()?;
+    // A range matching several versions yields one entry per version, oldest first
+    let entry = match json {
+        Value::Array(mut entries) => entries.pop()?,
+        other => other,
+    };
+    let version = entry.get("version")?.as_str()?.to_string();
+    let published = entry
+        .get("time")?
+        .get(&version)?
+        .as_str()
+        .and_then(crate::timestamp::parse_rfc3339)?;
+    Some((version, published))
+}
+
+/// Check the project in `dir` against its `[policy]`, if it has one
+pub fn enforce_project(dir: &Path, overrides: &[String], include_cargo: bool) -> CliResult<()> {
+    let config = crate::config::CpmConfig::load(dir)?;
+    let Some(policy_config) = config.policy else {
+        return Ok(());
+    };
+    let policy = Policy::new(&policy_config, overrides);
+    ensure_no_violations(&policy.check_project(dir, include_cargo)?)
+}
+
+/// Check the packages passed to `cpm add`, including the age of the resolved versions
+pub fn enforce_additions(dir: &Path, packages: &[String], overrides: &[String]) -> CliResult<()> {
+    let config = crate::config::CpmConfig::load(dir)?;
+    let Some(policy_config) = config.policy else {
+        return Ok(());
+    };
+    let policy = Policy::new(&policy_config, overrides);
+    let now = crate::timestamp::now();
+    let mut violations = Vec::new();
+    for package in packages {
+        let dependency = parse_add_argument(package);
+        violations.extend(policy.check_dependency(&dependency));
+        if policy_config.max_version_age_days.is_none() {
+            continue;
+        }
+        if let DependencySource::Registry { range, .. } = &dependency.source {
+            match npm_version_published(&dependency.name, range) {
+                Some((version, published)) => {
+                    violations.extend(policy.check_version_age(
+                        &dependency.name,
+                        &version,
+                        published,
+                        now,
+                    ));
+                }
+                None => eprintln!(
+                    "⚠️  Could not look up the publish date of {package}, skipping the age check"
+                ),
+            }
+        }
+    }
+    ensure_no_violations(&violations)
+}
+
+/// Fail with an explanation when any violations remain
+pub fn ensure_no_violations(violations: &[Violation]) -> CliResult<()> {
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let mut message = String::new();
+    for violation in violations {
+        message.push_str(&format!("\n  - {violation}"));
+    }
+    message.push_str("\nUse --allow <package|rule> to override a policy entry");
+    Err(CliError::PolicyViolation { message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strict_policy() -> PolicyConfig {
+        PolicyConfig {
+            banned: vec!["event-stream".to_string()],
+            allowed_registries: vec!["https://registry.npmjs.org/".to_string()],
+            allow_git: false,
+            allow_url: false,
+            allow_file: true,
+            max_version_age_days: Some(365),
+            typosquat_check: true,
+        }
+    }
+
+    #[test]
+    fn test_parse_add_argument() {
+        let dep = parse_add_argument("@types/node@^20");
+        assert_eq!(dep.name, "@types/node");
+        assert_eq!(
+            dep.source,
+            DependencySource::Registry {
+                range: "^20".to_string(),
+                registry: None
+            }
+        );
+        assert!(matches!(
+            parse_add_argument("github:user/repo").source,
+            DependencySource::Git(_)
+        ));
+        assert!(matches!(
+            parse_add_argument("user/repo").source,
+            DependencySource::Git(_)
+        ));
+        assert!(matches!(
+            parse_add_argument("../lib").source,
+            DependencySource::File(_)
+        ));
+        assert!(matches!(
+            classify_npm_spec("~1.2.3"),
+            DependencySource::Registry { .. }
+        ));
+    }
+
+    #[test]
+    fn test_policy_rejects_and_overrides() {
+        let config = strict_policy();
+        let policy = Policy::new(&config, &[]);
+        let violations = policy.check_dependency(&parse_add_argument("event-stream"));
+        assert_eq!(violations[0].rule, PolicyRule::Banned);
+        let violations = policy.check_dependency(&parse_add_argument("lodahs"));
+        assert_eq!(violations[0].rule, PolicyRule::Typosquat);
+        assert!(
+            policy
+                .check_dependency(&parse_add_argument("lodash"))
+                .is_empty()
+        );
+        assert_eq!(
+            policy.check_dependency(&parse_add_argument("github:a/b"))[0].rule,
+            PolicyRule::Git
+        );
+
+        let overrides = ["lodahs".to_string(), "git".to_string()];
+        let policy = Policy::new(&config, &overrides);
+        assert!(
+            policy
+                .check_dependency(&parse_add_argument("lodahs"))
+                .is_empty()
+        );
+        assert!(
+            policy
+                .check_dependency(&parse_add_argument("github:a/b"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_typosquat_exemptions() {
+        for legitimate in ["mysql2", "preact", "color", "tslint", "@types/react"] {
+            assert_eq!(typosquat_target(legitimate), None, "{legitimate}");
+        }
+        assert_eq!(typosquat_target("expresss"), Some("express"));
+        assert_eq!(typosquat_target("cross_env"), Some("cross-env"));
+
+        // Existing dependencies are not checked for typosquatting
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp.path().join("package.json"),
+            r#"{"dependencies":{"lodahs":"1.0.0"}}"#,
+        )
+        .unwrap();
+        let config = strict_policy();
+        let policy = Policy::new(&config, &[]);
+        assert!(policy.check_project(temp.path(), false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_registry_and_age_rules() {
+        let config = strict_policy();
+        let policy = Policy::new(&config, &[]);
+        assert!(
+            policy
+                .check_registry(
+                    Ecosystem::Npm,
+                    "a",
+                    "https://registry.npmjs.org/a/-/a-1.0.0.tgz"
+                )
+                .is_none()
+        );
+        assert!(
+            policy
+                .check_registry(Ecosystem::Npm, "a", "https://evil.example/a-1.0.0.tgz")
+                .is_some()
+        );
+        assert!(
+            policy
+                .check_registry(Ecosystem::Cargo, "serde", "crates-io")
+                .is_none()
+        );
+        let day = 86_400;
+        assert!(
+            policy
+                .check_version_age("a", "1.0.0", 0, 400 * day)
+                .is_some()
+        );
+        assert!(
+            policy
+                .check_version_age("a", "1.0.0", 100 * day, 400 * day)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Pre-publish checks
+//!
+//! Each check inspects the project in a directory and reports a single
+//! [`CheckResult`]. `cpm publish` prints the results as a checklist and refuses
+//! to publish while any check has failed.
+
+use crate::pack::PackPlan;
+use std::path::Path;
+use std::process::Command;
+use std::time::SystemTime;
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    /// Could not be verified; does not block publishing
+    Warning,
+    Failed,
+    Skipped,
+}
+
+impl CheckStatus {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            CheckStatus::Passed => "✅",
+            CheckStatus::Warning => "⚠️ ",
+            CheckStatus::Failed => "❌",
+            CheckStatus::Skipped => "⏭️ ",
+        }
+    }
+}
+
+/// Result of a single check
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// The working tree has no uncommitted changes
+pub fn check_git_clean(dir: &Path) -> CheckResult {
+    const NAME: &str = "Clean git working tree";
+    let output = Command::new("git")
+        .args(["status", "--porcelain"])
+        .current_dir(dir)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            let changed = String::from_utf8_lossy(&output.stdout).lines().count();
+            if changed == 0 {
+                CheckResult::new(NAME, CheckStatus::Passed, "no uncommitted changes")
+            } else {
+                CheckResult::new(
+                    NAME,
+                    CheckStatus::Failed,
+                    format!("{changed} uncommitted change(s); commit or stash them first"),
+                )
+            }
+        }
+        _ => CheckResult::new(NAME, CheckStatus::Warning, "not a git repository"),
+    }
+}
+
+/// The version in package.json is not on the registry yet
+pub fn check_version_unpublished(name: &str, version: &str, registry: Option<&str>) -> CheckResult {
+    const NAME: &str = "Version not yet published";
+    let npm_cmd = if cfg!(target_os = "windows") {
+        "npm.cmd"
+    } else {
+        "npm"
+    };
+    let mut command = Command::new(npm_cmd);
+    command.args(["view", &format!("{name}@{version}"), "version"]);
+    // Fail fast instead of retrying with backoff when the registry is unreachable
+    command.args(["--fetch-retries=0", "--fetch-timeout=15000"]);
+    if let Some(registry) = registry {
+        command.args(["--registry", registry]);
+    }
+    let Ok(output) = command.output() else {
+        return CheckResult::new(NAME, CheckStatus::Warning, "npm not found, lookup skipped");
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.success() && !stdout.trim().is_empty() {
+        CheckResult::new(
+            NAME,
+            CheckStatus::Failed,
+            format!("{name}@{version} is already published; bump the version"),
+        )
+    } else if output.status.success() || stderr.contains("E404") {
+        CheckResult::new(
+            NAME,
+            CheckStatus::Passed,
+            format!("{name}@{version} is new"),
+        )
+    } else {
+        CheckResult::new(NAME, CheckStatus::Warning, "registry lookup failed")
+    }
+}
+
+fn newest_mtime(path: &Path) -> Option<SystemTime> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if metadata.is_file() {
+        return metadata.modified().ok();
+    }
+    crate::glob::walk_files(path, |rel| !rel.ends_with("node_modules"))
+        .iter()
+        .filter_map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+/// The output of `cpm build` exists and is newer than the sources it is built from
+///
+/// Rust projects publish the wasm-pack output in `pkg/`. JavaScript-only
+/// packages have nothing to build, so the check is skipped for them.
+pub fn check_build_output(dir: &Path) -> CheckResult {
+    const NAME: &str = "Build output present and fresh";
+    if !dir.join("Cargo.toml").exists() {
+        return CheckResult::new(NAME, CheckStatus::Skipped, "JavaScript-only package");
+    }
+    let Some(built) = newest_mtime(&dir.join("pkg")) else {
+        return CheckResult::new(
+            NAME,
+            CheckStatus::Failed,
+            "pkg/ is missing; run 'cpm build'",
+        );
+    };
+    let sources = ["src", "Cargo.toml", "Cargo.lock"]
+        .iter()
+        .filter_map(|source| newest_mtime(&dir.join(source)))
+        .max();
+    match sources {
+        Some(modified) if modified > built => CheckResult::new(
+            NAME,
+            CheckStatus::Failed,
+            "sources changed since the last build; run 'cpm build'",
+        ),
+        _ => CheckResult::new(NAME, CheckStatus::Passed, "pkg/ is up to date"),
+    }
+}
+
+/// Run the project's tests (`npm test` when a test script exists, `cargo test` for Rust)
+pub fn check_tests(dir: &Path, manifest: &serde_json::Value) -> CheckResult {
+    const NAME: &str = "Tests pass";
+    let npm_cmd = if cfg!(target_os = "windows") {
+        "npm.cmd"
+    } else {
+        "npm"
+    };
+    let mut suites: Vec<(&str, Vec<&str>)> = Vec::new();
+    if manifest.pointer("/scripts/test").is_some() {
+        suites.push((npm_cmd, vec!["test"]));
+    }
+    if dir.join("Cargo.toml").exists() {
+        suites.push(("cargo", vec!["test"]));
+    }
+    if suites.is_empty() {
+        return CheckResult::new(NAME, CheckStatus::Skipped, "no tests configured");
+    }
+    for (program, args) in &suites {
+        let status = Command::new(program).args(args).current_dir(dir).status();
+        let label = format!("{} {}", program, args.join(" "));
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(_) => return CheckResult::new(NAME, CheckStatus::Failed, format!("{label} failed")),
+            Err(e) => {
+                return CheckResult::new(
+                    NAME,
+                    CheckStatus::Failed,
+                    format!("could not run {label}: {e}"),
+                );
+            }
+        }
+    }
+    let ran: Vec<String> = suites
+        .iter()
+        .map(|(program, args)| format!("{} {}", program, args.join(" ")))
+        .collect();
+    CheckResult::new(NAME, CheckStatus::Passed, ran.join(", "))
+}
+
+/// No packed file contains something that looks like a credential
+pub fn check_secrets(dir: &Path, plan: &PackPlan) -> CheckResult {
+    const NAME: &str = "No secrets in packed files";
+    let findings = crate::secrets::scan_files(dir, plan.entries.iter().map(|e| e.path.as_str()));
+    if findings.is_empty() {
+        return CheckResult::new(
+            NAME,
+            CheckStatus::Passed,
+            format!("{} file(s) scanned", plan.entries.len()),
+        );
+    }
+    let details: Vec<String> = findings
+        .iter()
+        .map(|f| format!("{}:{} {} ({})", f.path, f.line, f.kind, f.excerpt))
+        .collect();
+    CheckResult::new(NAME, CheckStatus::Failed, details.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_build_output() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        assert_eq!(check_build_output(dir).status, CheckStatus::Skipped);
+
+        std::fs::write(dir.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "").unwrap();
+        assert_eq!(check_build_output(dir).status, CheckStatus::Failed);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::create_dir_all(dir.join("pkg")).unwrap();
+        std::fs::write(dir.join("pkg/demo.js"), "").unwrap();
+        assert_eq!(check_build_output(dir).status, CheckStatus::Passed);
+    }
+}
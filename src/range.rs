@@ -0,0 +1,342 @@
+//! npm version ranges
+//!
+//! npm ranges (`^1.2`, `~1.2.3`, `1.x`, `>=1 <2`, `1.0.0 - 2.0.0`, `a || b`)
+//! differ from Cargo requirements, so they are desugared here into sets of
+//! primitive comparators on top of [`semver::Version`], following the rules
+//! of node-semver. Cargo requirements use [`semver::VersionReq`] directly.
+
+use semver::{Prerelease, Version, VersionReq};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparator {
+    op: Op,
+    version: Version,
+}
+
+impl Comparator {
+    fn matches(&self, version: &Version) -> bool {
+        let ordering = version.cmp_precedence(&self.version);
+        match self.op {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Gte => ordering != Ordering::Less,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Lte => ordering != Ordering::Greater,
+        }
+    }
+}
+
+/// A parsed npm range: any of several sets of comparators that must all match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NpmRange {
+    sets: Vec<Vec<Comparator>>,
+}
+
+/// A possibly partial version such as `1`, `1.2`, `1.x` or `1.2.3-beta.1`
+struct Partial {
+    major: Option<u64>,
+    minor: Option<u64>,
+    patch: Option<u64>,
+    pre: Prerelease,
+}
+
+impl Partial {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().trim_start_matches(['v', '=']);
+        // Build metadata never affects matching
+        let text = text.split('+').next().unwrap_or(text);
+        let (numbers, pre) = match text.split_once('-') {
+            Some((numbers, pre)) => (numbers, Prerelease::new(pre).ok()?),
+            None => (text, Prerelease::EMPTY),
+        };
+        let mut parts = [None; 3];
+        for (idx, part) in numbers.split('.').enumerate() {
+            if idx > 2 {
+                return None;
+            }
+            parts[idx] = match part {
+                "" if idx == 0 => None,
+                "x" | "X" | "*" => None,
+                number => Some(number.parse().ok()?),
+            };
+        }
+        // Nothing after a wildcard counts
+        if parts[0].is_none() {
+            parts = [None; 3];
+        } else if parts[1].is_none() {
+            parts[2] = None;
+        }
+        Some(Partial {
+            major: parts[0],
+            minor: parts[1],
+            patch: parts[2],
+            pre,
+        })
+    }
+
+    /// The lowest version the partial covers
+    fn floor(&self) -> Version {
+        let mut version = Version::new(
+            self.major.unwrap_or(0),
+            self.minor.unwrap_or(0),
+            self.patch.unwrap_or(0),
+        );
+        version.pre = self.pre.clone();
+        version
+    }
+
+    fn is_full(&self) -> bool {
+        self.patch.is_some()
+    }
+}
+
+/// `major.minor.patch-0`, the lowest version of a release line including prereleases
+fn before(major: u64, minor: u64, patch: u64) -> Version {
+    let mut version = Version::new(major, minor, patch);
+    version.pre = Prerelease::new("0").unwrap();
+    version
+}
+
+fn comparator(op: Op, version: Version) -> Comparator {
+    Comparator { op, version }
+}
+
+/// Desugar one token (`^1.2`, `>=1.0.0`, `1.x`) into primitive comparators
+fn desugar(token: &str) -> Option<Vec<Comparator>> {
+    let (op, rest) = ["~>", ">=", "<=", ">", "<", "=", "^", "~"]
+        .iter()
+        .find_map(|op| token.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("", token));
+    let partial = Partial::parse(rest)?;
+    let floor = partial.floor();
+    let (Some(major), minor, patch) = (partial.major, partial.minor, partial.patch) else {
+        // `*`, `x`, `>=*`: anything; `<*` and `>*`: nothing
+        return Some(match op {
+            "<" | ">" => vec![comparator(Op::Lt, Version::new(0, 0, 0))],
+            _ => vec![comparator(Op::Gte, Version::new(0, 0, 0))],
+        });
+    };
+
+    let comparators = match op {
+        "^" => {
+            let upper = match (major, minor, patch) {
+                (0, Some(0), Some(patch)) => before(0, 0, patch + 1),
+                (0, Some(minor), _) => before(0, minor + 1, 0),
+                (major, _, _) => before(major + 1, 0, 0),
+            };
+            vec![comparator(Op::Gte, floor), comparator(Op::Lt, upper)]
+        }
+        "~" | "~>" => {
+            let upper = match minor {
+                Some(minor) => before(major, minor + 1, 0),
+                None => before(major + 1, 0, 0),
+            };
+            vec![comparator(Op::Gte, floor), comparator(Op::Lt, upper)]
+        }
+        ">" if !partial.is_full() => {
+            let next = match minor {
+                Some(minor) => Version::new(major, minor + 1, 0),
+                None => Version::new(major + 1, 0, 0),
+            };
+            vec![comparator(Op::Gte, next)]
+        }
+        ">" => vec![comparator(Op::Gt, floor)],
+        ">=" => vec![comparator(Op::Gte, floor)],
+        "<" if !partial.is_full() => vec![comparator(Op::Lt, before(major, minor.unwrap_or(0), 0))],
+        "<" => vec![comparator(Op::Lt, floor)],
+        "<=" if !partial.is_full() => {
+            let upper = match minor {
+                Some(minor) => before(major, minor + 1, 0),
+                None => before(major + 1, 0, 0),
+            };
+            vec![comparator(Op::Lt, upper)]
+        }
+        "<=" => vec![comparator(Op::Lte, floor)],
+        _ if partial.is_full() => vec![comparator(Op::Eq, floor)],
+        _ => {
+            let upper = match minor {
+                Some(minor) => before(major, minor + 1, 0),
+                None => before(major + 1, 0, 0),
+            };
+            vec![comparator(Op::Gte, floor), comparator(Op::Lt, upper)]
+        }
+    };
+    Some(comparators)
+}
+
+/// Parse one `||`-separated alternative
+fn parse_set(text: &str) -> Option<Vec<Comparator>> {
+    let text = text.trim();
+    // Hyphen range: `1.2 - 2.3.4`
+    if let Some((low, high)) = text.split_once(" - ") {
+        let low = Partial::parse(low)?;
+        let high = Partial::parse(high)?;
+        let mut set = vec![comparator(Op::Gte, low.floor())];
+        match (high.major, high.minor) {
+            (None, _) => {}
+            (Some(major), None) => set.push(comparator(Op::Lt, before(major + 1, 0, 0))),
+            (Some(major), Some(minor)) if !high.is_full() => {
+                set.push(comparator(Op::Lt, before(major, minor + 1, 0)))
+            }
+            _ => set.push(comparator(Op::Lte, high.floor())),
+        }
+        return Some(set);
+    }
+
+    // Join operators separated from their version (`>= 1.2`)
+    let mut tokens: Vec<String> = Vec::new();
+    let mut pending = String::new();
+    for token in text.split_whitespace() {
+        if token.chars().all(|c| "<>=~^".contains(c)) {
+            pending.push_str(token);
+            continue;
+        }
+        tokens.push(format!("{pending}{token}"));
+        pending.clear();
+    }
+    if !pending.is_empty() {
+        return None;
+    }
+    if tokens.is_empty() {
+        tokens.push("*".to_string());
+    }
+    let mut set = Vec::new();
+    for token in tokens {
+        set.extend(desugar(&token)?);
+    }
+    Some(set)
+}
+
+impl NpmRange {
+    /// Parse an npm range; `workspace:` prefixes are accepted and ignored
+    ///
+    /// Returns `None` for dist-tags, URLs and other non-range specifiers.
+    pub fn parse(range: &str) -> Option<Self> {
+        let range = range.strip_prefix("workspace:").unwrap_or(range);
+        let sets = range
+            .split("||")
+            .map(parse_set)
+            .collect::<Option<Vec<_>>>()?;
+        Some(NpmRange { sets })
+    }
+
+    /// Whether `version` satisfies the range
+    ///
+    /// As in npm, a prerelease only matches when a comparator in the same set
+    /// names a prerelease of the same `major.minor.patch`.
+    pub fn matches(&self, version: &Version) -> bool {
+        self.sets.iter().any(|set| {
+            set.iter().all(|c| c.matches(version))
+                && (version.pre.is_empty()
+                    || set.iter().any(|c| {
+                        !c.version.pre.is_empty()
+                            && (c.version.major, c.version.minor, c.version.patch)
+                                == (version.major, version.minor, version.patch)
+                    }))
+        })
+    }
+
+    /// The lowest version the range can accept, used to compare ranges
+    pub fn min_version(&self) -> Option<Version> {
+        self.sets
+            .iter()
+            .filter_map(|set| {
+                let floor = set
+                    .iter()
+                    .filter(|c| matches!(c.op, Op::Eq | Op::Gte | Op::Gt))
+                    .map(|c| c.version.clone())
+                    .max()
+                    .unwrap_or_else(|| Version::new(0, 0, 0));
+                // A set whose upper bound is below its floor matches nothing
+                let empty = set.iter().any(|c| {
+                    matches!(c.op, Op::Lt | Op::Lte) && !c.matches(&floor) && c.version <= floor
+                });
+                (!empty).then_some(floor)
+            })
+            .min()
+    }
+}
+
+/// The lowest version a Cargo requirement can accept, used to compare requirements
+pub fn cargo_min_version(requirement: &str) -> Option<Version> {
+    let requirement = VersionReq::parse(requirement).ok()?;
+    let floor = requirement
+        .comparators
+        .iter()
+        .filter(|c| !matches!(c.op, semver::Op::Less | semver::Op::LessEq))
+        .map(|c| {
+            let mut version = Version::new(c.major, c.minor.unwrap_or(0), c.patch.unwrap_or(0));
+            version.pre = c.pre.clone();
+            version
+        })
+        .max()
+        .unwrap_or_else(|| Version::new(0, 0, 0));
+    Some(floor)
+}
+
+/// Whether `version` satisfies a Cargo requirement; `None` when it does not parse
+pub fn cargo_matches(requirement: &str, version: &Version) -> Option<bool> {
+    Some(VersionReq::parse(requirement).ok()?.matches(version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(range: &str, version: &str) -> bool {
+        NpmRange::parse(range)
+            .unwrap_or_else(|| panic!("{range} should parse"))
+            .matches(&Version::parse(version).unwrap())
+    }
+
+    #[test]
+    fn test_npm_range_operators() {
+        assert!(matches("^1.2.3", "1.9.0"));
+        assert!(!matches("^1.2.3", "2.0.0"));
+        assert!(matches("^0.2.3", "0.2.9"));
+        assert!(!matches("^0.2.3", "0.3.0"));
+        assert!(!matches("^0.0.3", "0.0.4"));
+        assert!(matches("~1.2", "1.2.9"));
+        assert!(!matches("~1.2", "1.3.0"));
+        assert!(matches("1.x", "1.4.0"));
+        assert!(matches("*", "3.0.0"));
+        assert!(matches("", "3.0.0"));
+        assert!(matches(">= 1.2 <2", "1.5.0"));
+        assert!(!matches(">1.2", "1.2.9"));
+        assert!(matches("<=1.2", "1.2.9"));
+        assert!(matches("1.0.0 - 2.1", "2.1.5"));
+        assert!(!matches("1.0.0 - 2.1.0", "2.1.5"));
+        assert!(matches("^1.0.0 || ^3.0.0", "3.1.0"));
+        assert!(matches("workspace:^1.0.0", "1.0.1"));
+        assert!(NpmRange::parse("latest").is_none());
+    }
+
+    #[test]
+    fn test_npm_prereleases_need_opt_in() {
+        assert!(!matches("^1.0.0", "1.2.0-beta.1"));
+        assert!(matches("^1.2.0-beta.0", "1.2.0-beta.1"));
+        assert!(!matches("^1.2.0-beta.0", "1.3.0-beta.1"));
+    }
+
+    #[test]
+    fn test_min_versions() {
+        let min = |range: &str| NpmRange::parse(range).unwrap().min_version().unwrap();
+        assert_eq!(min("^4.17.21"), Version::new(4, 17, 21));
+        assert_eq!(min("~4.17"), Version::new(4, 17, 0));
+        assert_eq!(min(">3 || ^2.1"), Version::new(2, 1, 0));
+        assert_eq!(min("*"), Version::new(0, 0, 0));
+        assert_eq!(cargo_min_version("1.0"), Some(Version::new(1, 0, 0)));
+        assert_eq!(cargo_min_version(">=1.2, <2"), Some(Version::new(1, 2, 0)));
+        assert_eq!(cargo_matches("0.3", &Version::new(0, 3, 4)), Some(true));
+    }
+}
@@ -0,0 +1,356 @@
+//! Coordinated releases
+//!
+//! Building blocks for `cpm release`: deciding which artifacts a project
+//! publishes, publishing them to npm/crates.io or to a local registry
+//! directory, and undoing the version commit and tag when a later step fails.
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::pack::PackPlan;
+use crate::version::VersionOutcome;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The `[release]` section of `cpm.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReleaseConfig {
+    /// Directory used instead of the real registries, for trying out releases
+    ///
+    /// npm tarballs are copied to `<dir>/npm/` and crates to `<dir>/crates/`.
+    pub registry_dir: Option<PathBuf>,
+}
+
+/// Where release artifacts go
+#[derive(Debug, Clone)]
+pub enum Destination {
+    /// The configured npm registry and crates.io
+    Registries {
+        /// npm dist-tag
+        tag: String,
+        otp: Option<String>,
+        access: Option<String>,
+    },
+    /// A local directory standing in for the registries
+    Directory(PathBuf),
+}
+
+/// Something that was published and may need to be reported or removed on rollback
+#[derive(Debug, Clone)]
+pub struct PublishedArtifact {
+    pub description: String,
+    /// Copy in the local registry directory, removed on rollback
+    pub local_copy: Option<PathBuf>,
+}
+
+/// Whether `dir` holds an npm package that is not `"private": true`
+pub fn npm_publishable(dir: &Path) -> CliResult<bool> {
+    let path = dir.join("package.json");
+    if !path.exists() {
+        return Ok(false);
+    }
+    let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(manifest.get("private").and_then(|p| p.as_bool()) != Some(true))
+}
+
+/// Whether `dir` holds a crate that is not marked `publish = false`
+pub fn cargo_publishable(dir: &Path) -> CliResult<bool> {
+    let path = dir.join("Cargo.toml");
+    if !path.exists() {
+        return Ok(false);
+    }
+    let manifest: toml::Value = toml::from_str(&std::fs::read_to_string(path)?)?;
+    let Some(package) = manifest.get("package") else {
+        return Ok(false);
+    };
+    Ok(package.get("publish").and_then(|p| p.as_bool()) != Some(false))
+}
+
+/// Pack the package in `dir` into `staging`, refusing packages with secrets
+pub fn stage_tarball(dir: &Path, staging: &Path) -> CliResult<(PackPlan, PathBuf)> {
+    let plan = crate::pack::plan(dir)?;
+    let secrets = crate::publish::check_secrets(dir, &plan);
+    if secrets.status == crate::publish::CheckStatus::Failed {
+        return Err(CliError::ExecutionError {
+            command: format!("pack {}", dir.display()),
+            message: format!("possible secrets in packed files: {}", secrets.detail),
+        });
+    }
+    let tarball = crate::pack::build_tarball(dir, &plan)?;
+    std::fs::create_dir_all(staging)?;
+    let path = staging.join(plan.tarball_name());
+    std::fs::write(&path, &tarball.bytes)?;
+    Ok((plan, path))
+}
+
+/// Copy `file` into `dir`, failing like a registry would if it already exists
+fn copy_to_directory(file: &Path, dir: &Path) -> CliResult<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let name = file.file_name().unwrap_or_default();
+    let target = dir.join(name);
+    if target.exists() {
+        return Err(CliError::FileExists {
+            path: target.display().to_string(),
+        });
+    }
+    std::fs::copy(file, &target)?;
+    Ok(target)
+}
+
+/// Publish a staged npm tarball
+pub fn publish_tarball(
+    tarball: &Path,
+    label: &str,
+    destination: &Destination,
+) -> CliResult<PublishedArtifact> {
+    match destination {
+        Destination::Directory(dir) => {
+            let copy = copy_to_directory(tarball, &dir.join("npm"))?;
+            Ok(PublishedArtifact {
+                description: format!("{} → {}", label, copy.display()),
+                local_copy: Some(copy),
+            })
+        }
+        Destination::Registries { tag, otp, access } => {
+            let npm_cmd = if cfg!(target_os = "windows") {
+                "npm.cmd"
+            } else {
+                "npm"
+            };
+            let mut command = Command::new(npm_cmd);
+            command.arg("publish").arg(tarball).args(["--tag", tag]);
+            if let Some(otp) = otp {
+                command.args(["--otp", otp]);
+            }
+            if let Some(access) = access {
+                command.args(["--access", access]);
+            }
+            if !command.status()?.success() {
+                return Err(CliError::ExecutionError {
+                    command: format!("npm publish {}", tarball.display()),
+                    message: "Publish failed".to_string(),
+                });
+            }
+            Ok(PublishedArtifact {
+                description: format!("{} → npm ({})", label, tag),
+                local_copy: None,
+            })
+        }
+    }
+}
+
+/// The target directory cargo uses for the crate in `dir`
+///
+/// Not simply `dir/target`: workspace members share the workspace's, and
+/// `CARGO_TARGET_DIR` or `build.target-dir` can move it anywhere.
+fn cargo_target_dir(dir: &Path) -> CliResult<PathBuf> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .current_dir(dir)
+        .output()?;
+    if !output.status.success() {
+        return Err(CliError::ExecutionError {
+            command: "cargo metadata".to_string(),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    metadata["target_directory"]
+        .as_str()
+        .map(PathBuf::from)
+        .ok_or_else(|| CliError::ExecutionError {
+            command: "cargo metadata".to_string(),
+            message: "No target_directory in the output".to_string(),
+        })
+}
+
+/// Publish the crate in `dir`
+///
+/// With a local registry directory the crate is packaged with
+/// `cargo package` and the `.crate` file is copied there.
+pub fn publish_crate(
+    dir: &Path,
+    name: &str,
+    version: &str,
+    destination: &Destination,
+) -> CliResult<PublishedArtifact> {
+    match destination {
+        Destination::Directory(registry) => {
+            let status = Command::new("cargo")
+                .args(["package", "--no-verify", "--allow-dirty"])
+                .current_dir(dir)
+                .status()?;
+            if !status.success() {
+                return Err(CliError::ExecutionError {
+                    command: "cargo package".to_string(),
+                    message: "Packaging the crate failed".to_string(),
+                });
+            }
+            let packaged = cargo_target_dir(dir)?
+                .join("package")
+                .join(format!("{}-{}.crate", name, version));
+            let copy = copy_to_directory(&packaged, &registry.join("crates"))?;
+            Ok(PublishedArtifact {
+                description: format!("{} {} → {}", name, version, copy.display()),
+                local_copy: Some(copy),
+            })
+        }
+        Destination::Registries { .. } => {
+            let status = Command::new("cargo")
+                .arg("publish")
+                .current_dir(dir)
+                .status()?;
+            if !status.success() {
+                return Err(CliError::ExecutionError {
+                    command: "cargo publish".to_string(),
+                    message: "Publish failed".to_string(),
+                });
+            }
+            Ok(PublishedArtifact {
+                description: format!("{} {} → crates.io", name, version),
+                local_copy: None,
+            })
+        }
+    }
+}
+
+/// Undo a failed release: remove local registry copies, then the tag and the
+/// version commit, or the version edits when the bump was not committed
+///
+/// Returns artifacts that were already pushed to a real registry and cannot be
+/// taken back automatically. The version bump is kept when there are any, as
+/// it matches what the registry now has.
+pub fn rollback(
+    root: &Path,
+    outcome: &VersionOutcome,
+    published: &[PublishedArtifact],
+) -> CliResult<Vec<String>> {
+    let mut remaining = Vec::new();
+    for artifact in published {
+        match &artifact.local_copy {
+            Some(copy) => std::fs::remove_file(copy)?,
+            None => remaining.push(artifact.description.clone()),
+        }
+    }
+    if !remaining.is_empty() {
+        return Ok(remaining);
+    }
+
+    let Some(tag) = &outcome.tag else {
+        for (path, original) in &outcome.originals {
+            match original {
+                Some(content) => std::fs::write(root.join(path), content)?,
+                None => std::fs::remove_file(root.join(path))?,
+            }
+        }
+        return Ok(remaining);
+    };
+    let head = crate::git::run(root, &["rev-parse", "HEAD"])?;
+    let tagged = crate::git::run(root, &["rev-parse", &format!("{tag}^{{commit}}")])?;
+    if head != tagged {
+        return Err(CliError::ExecutionError {
+            command: "cpm release".to_string(),
+            message: format!(
+                "HEAD is no longer the version commit of {tag}, undo the release by hand"
+            ),
+        });
+    }
+    crate::git::run(root, &["tag", "-d", tag])?;
+    // Unlike --hard, --keep refuses to drop uncommitted changes to the bumped files
+    crate::git::run(root, &["reset", "--keep", "HEAD~1"])?;
+    Ok(remaining)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publishable_packages() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        assert!(!npm_publishable(dir).unwrap());
+        std::fs::write(dir.join("package.json"), r#"{"name":"a","private":true}"#).unwrap();
+        assert!(!npm_publishable(dir).unwrap());
+        std::fs::write(dir.join("package.json"), r#"{"name":"a"}"#).unwrap();
+        assert!(npm_publishable(dir).unwrap());
+
+        std::fs::write(dir.join("Cargo.toml"), "[workspace]\nmembers = []\n").unwrap();
+        assert!(!cargo_publishable(dir).unwrap());
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"a\"\npublish = false\n",
+        )
+        .unwrap();
+        assert!(!cargo_publishable(dir).unwrap());
+        std::fs::write(dir.join("Cargo.toml"), "[package]\nname = \"a\"\n").unwrap();
+        assert!(cargo_publishable(dir).unwrap());
+    }
+
+    #[test]
+    fn test_workspace_member_uses_workspace_target_dir() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/a\"]\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("crates/a/src")).unwrap();
+        std::fs::write(
+            root.join("crates/a/Cargo.toml"),
+            "[package]\nname = \"a\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(root.join("crates/a/src/lib.rs"), "").unwrap();
+        let expected = match std::env::var_os("CARGO_TARGET_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => root.join("target"),
+        };
+        assert_eq!(cargo_target_dir(&root.join("crates/a")).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_directory_destination_rejects_republish() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let tarball = temp.path().join("a-1.0.0.tgz");
+        std::fs::write(&tarball, b"tarball").unwrap();
+        let destination = Destination::Directory(temp.path().join("registry"));
+
+        let artifact = publish_tarball(&tarball, "a@1.0.0", &destination).unwrap();
+        assert!(temp.path().join("registry/npm/a-1.0.0.tgz").exists());
+        assert!(publish_tarball(&tarball, "a@1.0.0", &destination).is_err());
+
+        // Outside git, the version edits are restored from before the bump
+        std::fs::write(temp.path().join("package.json"), r#"{"version":"1.0.0"}"#).unwrap();
+        let outcome = VersionOutcome {
+            previous: semver::Version::new(0, 9, 0),
+            version: semver::Version::new(1, 0, 0),
+            files: vec!["package.json".to_string()],
+            tag: None,
+            originals: vec![(
+                "package.json".to_string(),
+                Some(r#"{"version":"0.9.0"}"#.to_string()),
+            )],
+        };
+        let registry = PublishedArtifact {
+            description: "a@1.0.0 (npm)".to_string(),
+            local_copy: None,
+        };
+        let remaining = rollback(temp.path(), &outcome, &[artifact.clone(), registry]).unwrap();
+        assert_eq!(remaining, ["a@1.0.0 (npm)"]);
+        assert!(!temp.path().join("registry/npm/a-1.0.0.tgz").exists());
+        // Something reached a registry, so the bump stays
+        let manifest = std::fs::read_to_string(temp.path().join("package.json")).unwrap();
+        assert!(manifest.contains("1.0.0"));
+
+        std::fs::write(temp.path().join("registry/npm/a-1.0.0.tgz"), b"tarball").unwrap();
+        assert!(
+            rollback(temp.path(), &outcome, &[artifact])
+                .unwrap()
+                .is_empty()
+        );
+        let manifest = std::fs::read_to_string(temp.path().join("package.json")).unwrap();
+        assert!(manifest.contains("0.9.0"));
+    }
+}
@@ -0,0 +1,401 @@
+//! JavaScript runtimes for `cpm dev` and `cpm run file.js`
+//!
+//! Runtimes are tried in order of preference: the ones given to `--runtime`,
+//! else `[runtime] prefer` in `cpm.toml`, else JetCrab, Node.js, Deno and Bun:
+//!
+//! ```toml
+//! [runtime]
+//! prefer = ["bun", "node"]
+//! ```
+//!
+//! The first one that is installed, supports the requested mode and
+//! satisfies its entry in the `package.json` `engines` field is used.
+//! Runtimes that `engines` does not mention are not restricted by it.
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::range::NpmRange;
+use semver::Version;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Runtimes in their default order of preference
+pub const DEFAULT_PREFERENCE: &[&str] = &["jetcrab", "node", "deno", "bun"];
+
+/// The `[runtime]` section of `cpm.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RuntimeConfig {
+    /// Runtime names, most preferred first
+    #[serde(default)]
+    pub prefer: Vec<String>,
+}
+
+/// How to start the entry point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Run,
+    /// With the debugger listening
+    Inspect,
+    /// Restarting on file changes
+    Watch,
+}
+
+impl Mode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Mode::Run => "run",
+            Mode::Inspect => "inspect",
+            Mode::Watch => "watch",
+        }
+    }
+}
+
+/// First token of `--version` output that parses as a version, like
+/// `v20.11.0` or the `1.40.0` of `deno 1.40.0 (release)`
+pub fn parse_version(output: &str) -> Option<Version> {
+    output
+        .split_whitespace()
+        .find_map(|token| Version::parse(token.trim_start_matches('v')).ok())
+}
+
+/// A JavaScript runtime cpm can start
+pub trait Runtime {
+    /// Name used in `--runtime`, `cpm.toml` and `engines`
+    fn name(&self) -> &'static str;
+
+    /// Name shown to the user
+    fn label(&self) -> &'static str;
+
+    fn icon(&self) -> &'static str;
+
+    /// Executable to spawn
+    fn program(&self) -> &'static str {
+        self.name()
+    }
+
+    /// Arguments before the entry point for `mode`, or why it is unsupported
+    fn args(&self, mode: Mode, version: Option<&Version>) -> Result<Vec<String>, String>;
+
+    /// The installed version: `Err` when the runtime cannot be started, `None`
+    /// when its version output is not understood
+    fn version(&self) -> Result<Option<Version>, String> {
+        let output = std::process::Command::new(self.program())
+            .arg("--version")
+            .output()
+            .map_err(|_| "not installed".to_string())?;
+        if !output.status.success() {
+            return Err(format!("'{} --version' failed", self.program()));
+        }
+        Ok(parse_version(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+pub struct JetCrab;
+
+impl Runtime for JetCrab {
+    fn name(&self) -> &'static str {
+        "jetcrab"
+    }
+
+    fn label(&self) -> &'static str {
+        "JetCrab"
+    }
+
+    fn icon(&self) -> &'static str {
+        "🦀"
+    }
+
+    fn args(&self, mode: Mode, _version: Option<&Version>) -> Result<Vec<String>, String> {
+        match mode {
+            Mode::Run => Ok(args(&["run"])),
+            Mode::Inspect | Mode::Watch => Err(format!("does not support {} mode", mode.label())),
+        }
+    }
+}
+
+pub struct Node;
+
+impl Runtime for Node {
+    fn name(&self) -> &'static str {
+        "node"
+    }
+
+    fn label(&self) -> &'static str {
+        "Node.js"
+    }
+
+    fn icon(&self) -> &'static str {
+        "🟨"
+    }
+
+    fn args(&self, mode: Mode, version: Option<&Version>) -> Result<Vec<String>, String> {
+        match mode {
+            Mode::Run => Ok(Vec::new()),
+            Mode::Inspect => Ok(args(&["--inspect"])),
+            Mode::Watch => {
+                // `--watch` arrived in 18.11
+                if version.is_some_and(|v| *v < Version::new(18, 11, 0)) {
+                    return Err("watch mode needs Node.js 18.11 or later".to_string());
+                }
+                Ok(args(&["--watch"]))
+            }
+        }
+    }
+}
+
+pub struct Deno;
+
+impl Runtime for Deno {
+    fn name(&self) -> &'static str {
+        "deno"
+    }
+
+    fn label(&self) -> &'static str {
+        "Deno"
+    }
+
+    fn icon(&self) -> &'static str {
+        "🦕"
+    }
+
+    fn args(&self, mode: Mode, _version: Option<&Version>) -> Result<Vec<String>, String> {
+        // Scripts expect the unrestricted access other runtimes give them
+        Ok(match mode {
+            Mode::Run => args(&["run", "--allow-all"]),
+            Mode::Inspect => args(&["run", "--allow-all", "--inspect"]),
+            Mode::Watch => args(&["run", "--allow-all", "--watch"]),
+        })
+    }
+}
+
+pub struct Bun;
+
+impl Runtime for Bun {
+    fn name(&self) -> &'static str {
+        "bun"
+    }
+
+    fn label(&self) -> &'static str {
+        "Bun"
+    }
+
+    fn icon(&self) -> &'static str {
+        "🥟"
+    }
+
+    fn args(&self, mode: Mode, _version: Option<&Version>) -> Result<Vec<String>, String> {
+        Ok(match mode {
+            Mode::Run => args(&["run"]),
+            Mode::Inspect => args(&["run", "--inspect"]),
+            Mode::Watch => args(&["run", "--watch"]),
+        })
+    }
+}
+
+/// The runtime called `name`
+pub fn by_name(name: &str) -> Option<Box<dyn Runtime>> {
+    match name {
+        "jetcrab" => Some(Box::new(JetCrab)),
+        "node" => Some(Box::new(Node)),
+        "deno" => Some(Box::new(Deno)),
+        "bun" => Some(Box::new(Bun)),
+        _ => None,
+    }
+}
+
+/// Runtimes to try, from `--runtime` (comma-separated), else the config,
+/// else [`DEFAULT_PREFERENCE`]
+pub fn preference(
+    flag: Option<&str>,
+    config: Option<&RuntimeConfig>,
+) -> CliResult<Vec<Box<dyn Runtime>>> {
+    let names: Vec<String> = match (flag, config) {
+        (Some(flag), _) => flag
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .collect(),
+        (None, Some(config)) if !config.prefer.is_empty() => config.prefer.clone(),
+        _ => DEFAULT_PREFERENCE
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+    };
+    names
+        .iter()
+        .map(|name| {
+            by_name(name).ok_or_else(|| CliError::ExecutionError {
+                command: format!("runtime {name}"),
+                message: format!(
+                    "Unknown runtime, expected one of {}",
+                    DEFAULT_PREFERENCE.join(", ")
+                ),
+            })
+        })
+        .collect()
+}
+
+/// A runtime that passed every check
+pub struct Selected {
+    pub runtime: Box<dyn Runtime>,
+    pub version: Option<Version>,
+    /// Arguments before the entry point
+    pub args: Vec<String>,
+}
+
+impl Selected {
+    /// `name version`, as shown to the user
+    pub fn describe(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{} {version}", self.runtime.label()),
+            None => self.runtime.label().to_string(),
+        }
+    }
+
+    /// The command starting `entry` with `extra` arguments
+    pub fn command(&self, entry: &str, extra: &[String]) -> std::process::Command {
+        let mut command = std::process::Command::new(self.runtime.program());
+        command.args(&self.args).arg(entry).args(extra);
+        command
+    }
+}
+
+/// Why a runtime was passed over
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    pub name: &'static str,
+    pub version: Option<Version>,
+    pub reason: String,
+}
+
+/// Check a runtime with its detected version against `engines` and `mode`
+pub fn check(
+    runtime: &dyn Runtime,
+    detected: Result<Option<Version>, String>,
+    engines: Option<&Value>,
+    mode: Mode,
+) -> Result<(Option<Version>, Vec<String>), Rejection> {
+    let reject = |version: Option<Version>, reason: String| Rejection {
+        name: runtime.name(),
+        version,
+        reason,
+    };
+    let version = detected.map_err(|reason| reject(None, reason))?;
+    let required = engines
+        .and_then(|engines| engines.get(runtime.name()))
+        .and_then(Value::as_str);
+    if let Some(required) = required {
+        let satisfied = match (&version, NpmRange::parse(required)) {
+            (Some(version), Some(range)) => range.matches(version),
+            (None, _) => {
+                return Err(reject(
+                    None,
+                    format!(
+                        "version unknown, engines.{} requires {required}",
+                        runtime.name()
+                    ),
+                ));
+            }
+            // An unparseable range is npm's problem, not a reason to refuse
+            (Some(_), None) => true,
+        };
+        if !satisfied {
+            return Err(reject(
+                version,
+                format!("engines.{} requires {required}", runtime.name()),
+            ));
+        }
+    }
+    let args = runtime
+        .args(mode, version.as_ref())
+        .map_err(|reason| reject(version.clone(), reason))?;
+    Ok((version, args))
+}
+
+/// The first acceptable runtime among `candidates`, detected in order
+pub fn select(
+    candidates: Vec<Box<dyn Runtime>>,
+    engines: Option<&Value>,
+    mode: Mode,
+) -> CliResult<Selected> {
+    let mut rejections = Vec::new();
+    for runtime in candidates {
+        let detected = runtime.version();
+        match check(runtime.as_ref(), detected, engines, mode) {
+            Ok((version, args)) => {
+                return Ok(Selected {
+                    runtime,
+                    version,
+                    args,
+                });
+            }
+            Err(rejection) => rejections.push(rejection),
+        }
+    }
+    let details: Vec<String> = rejections
+        .iter()
+        .map(|rejection| match &rejection.version {
+            Some(version) => format!("  {} {version}: {}", rejection.name, rejection.reason),
+            None => format!("  {}: {}", rejection.name, rejection.reason),
+        })
+        .collect();
+    Err(CliError::ExecutionError {
+        command: format!("select runtime ({} mode)", mode.label()),
+        message: format!("No usable JavaScript runtime\n{}", details.join("\n")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_versions_engines_and_modes() {
+        assert_eq!(parse_version("v20.11.0\n"), Some(Version::new(20, 11, 0)));
+        assert_eq!(
+            parse_version("deno 1.40.0 (release, x86_64)\nv8 12.1"),
+            Some(Version::new(1, 40, 0))
+        );
+
+        let engines = serde_json::json!({ "node": ">=18" });
+        let node_16 = Ok(Some(Version::new(16, 20, 0)));
+        let rejection = check(&Node, node_16, Some(&engines), Mode::Run).unwrap_err();
+        assert_eq!(rejection.reason, "engines.node requires >=18");
+
+        let (_, args) = check(
+            &Node,
+            Ok(Some(Version::new(20, 0, 0))),
+            Some(&engines),
+            Mode::Watch,
+        )
+        .unwrap();
+        assert_eq!(args, ["--watch"]);
+        // Bun is not restricted by engines.node
+        let (_, args) = check(&Bun, Ok(None), Some(&engines), Mode::Inspect).unwrap();
+        assert_eq!(args, ["run", "--inspect"]);
+
+        let missing = check(&JetCrab, Err("not installed".to_string()), None, Mode::Run);
+        assert_eq!(missing.unwrap_err().reason, "not installed");
+        assert!(check(&JetCrab, Ok(None), None, Mode::Watch).is_err());
+
+        let names = |runtimes: Vec<Box<dyn Runtime>>| -> Vec<&str> {
+            runtimes.iter().map(|runtime| runtime.name()).collect()
+        };
+        let config = RuntimeConfig {
+            prefer: vec!["bun".to_string(), "node".to_string()],
+        };
+        assert_eq!(
+            names(preference(None, Some(&config)).unwrap()),
+            ["bun", "node"]
+        );
+        assert_eq!(
+            names(preference(Some("deno"), Some(&config)).unwrap()),
+            ["deno"]
+        );
+        assert_eq!(names(preference(None, None).unwrap()), DEFAULT_PREFERENCE);
+        assert!(preference(Some("rhino"), None).is_err());
+    }
+}
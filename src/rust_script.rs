@@ -0,0 +1,280 @@
+//! Single-file Rust scripts for `cpm run file.rs`
+//!
+//! A script may embed its manifest in a `cargo` code block of its leading
+//! doc comment, as with cargo-script:
+//!
+//! ```text
+//! //! ```cargo
+//! //! [dependencies]
+//! //! regex = "1"
+//! //! ```
+//!
+//! fn main() { /* ... */ }
+//! ```
+//!
+//! or in a `---cargo` frontmatter block at the top of the file. Each script
+//! becomes a throwaway crate in the cpm cache, keyed by the hash of its
+//! source, so an unchanged script runs its existing binary without invoking
+//! cargo at all. All scripts share one target directory so dependencies are
+//! compiled once.
+
+use crate::cli::framework::{CliError, CliResult};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Edition of scripts that do not set one
+pub const DEFAULT_EDITION: &str = "2021";
+
+/// A script split into its manifest and the Rust source to compile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    /// The embedded manifest, empty when there is none
+    pub manifest: String,
+    pub source: String,
+}
+
+/// Strip the `//!` marker of a doc comment line
+fn doc_line(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix("//!")?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+impl Script {
+    /// Split a script into its embedded manifest and source
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = content.lines().collect();
+        // A shebang line is neither Rust nor part of the manifest
+        let start = usize::from(
+            lines
+                .first()
+                .is_some_and(|line| line.starts_with("#!") && !line.starts_with("#![")),
+        );
+        let mut source: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+        if start == 1 {
+            source[0].clear();
+        }
+
+        // Frontmatter is not valid Rust, it is blanked to keep line numbers
+        if lines
+            .get(start)
+            .is_some_and(|line| line.trim_end() == "---cargo")
+        {
+            let end = lines[start + 1..]
+                .iter()
+                .position(|line| line.trim_end() == "---")
+                .map(|idx| start + 1 + idx)
+                .ok_or("unterminated ---cargo frontmatter")?;
+            for line in &mut source[start..=end] {
+                line.clear();
+            }
+            return Ok(Script {
+                manifest: lines[start + 1..end].join("\n"),
+                source: source.join("\n"),
+            });
+        }
+
+        let mut manifest = None;
+        let mut block: Option<Vec<&str>> = None;
+        for line in &lines[start..] {
+            if line.trim().is_empty() && block.is_none() {
+                continue;
+            }
+            let Some(doc) = doc_line(line) else {
+                break;
+            };
+            match &mut block {
+                None if doc.trim_end() == "```cargo" => block = Some(Vec::new()),
+                Some(_) if doc.trim_end() == "```" => {
+                    manifest = block.take().map(|lines| lines.join("\n"));
+                    break;
+                }
+                Some(lines) => lines.push(doc),
+                None => {}
+            }
+        }
+        if block.is_some() {
+            return Err("unterminated ```cargo block".to_string());
+        }
+        Ok(Script {
+            manifest: manifest.unwrap_or_default(),
+            source: source.join("\n"),
+        })
+    }
+
+    /// The `Cargo.toml` of the crate for a script named `name`
+    ///
+    /// Missing package fields are filled in and the crate is made its own
+    /// workspace, so a project around the cache cannot claim it.
+    pub fn cargo_toml(&self, name: &str) -> Result<String, String> {
+        let mut document: toml_edit::DocumentMut = self
+            .manifest
+            .parse()
+            .map_err(|e| format!("invalid embedded manifest: {e}"))?;
+        let package = document
+            .entry("package")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .ok_or("[package] is not a table")?;
+        package.entry("name").or_insert(toml_edit::value(name));
+        package
+            .entry("version")
+            .or_insert(toml_edit::value("0.0.0"));
+        package
+            .entry("edition")
+            .or_insert(toml_edit::value(DEFAULT_EDITION));
+        package.entry("publish").or_insert(toml_edit::value(false));
+        document.remove("bin");
+        let mut bin = toml_edit::Table::new();
+        bin.insert("name", toml_edit::value(name));
+        bin.insert("path", toml_edit::value("main.rs"));
+        let mut bins = toml_edit::ArrayOfTables::new();
+        bins.push(bin);
+        document.insert("bin", toml_edit::Item::ArrayOfTables(bins));
+        document.entry("workspace").or_insert_with(toml_edit::table);
+        Ok(document.to_string())
+    }
+}
+
+/// Binary target names cargo refuses, as they clash with its target directory layout
+const RESERVED_BIN_NAMES: &[&str] = &["build", "deps", "examples", "incremental"];
+
+/// Crate name for the script at `path`
+pub fn crate_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let name: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && !RESERVED_BIN_NAMES.contains(&name.as_str())
+    {
+        name
+    } else {
+        format!("script-{name}")
+    }
+}
+
+/// A script's crate in the cache
+#[derive(Debug, Clone)]
+pub struct ScriptCrate {
+    pub name: String,
+    /// Directory of this version of the script
+    pub dir: PathBuf,
+    /// Target directory shared by all scripts
+    pub target_dir: PathBuf,
+}
+
+impl ScriptCrate {
+    /// The crate for the script at `path` in the cache directory `cache`
+    pub fn new(cache: &Path, path: &Path, content: &str) -> Self {
+        let name = crate_name(path);
+        let mut hasher = Sha256::new();
+        for part in [env!("CARGO_PKG_VERSION"), name.as_str(), content] {
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        let hash = format!("{:x}", hasher.finalize());
+        ScriptCrate {
+            dir: cache.join("rust").join(format!("{name}-{}", &hash[..16])),
+            target_dir: cache.join("rust").join("target"),
+            name,
+        }
+    }
+
+    /// The built binary, copied out of the shared target directory
+    pub fn binary(&self) -> PathBuf {
+        self.dir
+            .join(format!("{}{}", self.name, std::env::consts::EXE_SUFFIX))
+    }
+
+    pub fn is_built(&self) -> bool {
+        self.binary().is_file()
+    }
+
+    /// Write the crate and build it with cargo
+    pub fn build(&self, script: &Script) -> CliResult<()> {
+        let cargo_toml =
+            script
+                .cargo_toml(&self.name)
+                .map_err(|message| CliError::FileOperationError {
+                    operation: "read embedded manifest".to_string(),
+                    path: self.name.clone(),
+                    message,
+                })?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.dir.join("Cargo.toml"), cargo_toml)?;
+        std::fs::write(self.dir.join("main.rs"), &script.source)?;
+
+        let status = std::process::Command::new("cargo")
+            .args(["build", "--quiet", "--manifest-path"])
+            .arg(self.dir.join("Cargo.toml"))
+            .arg("--target-dir")
+            .arg(&self.target_dir)
+            .status()
+            .map_err(|e| CliError::ExecutionError {
+                command: "cargo build".to_string(),
+                message: e.to_string(),
+            })?;
+        if !status.success() {
+            return Err(CliError::ExecutionError {
+                command: format!("cargo build ({})", self.name),
+                message: "Compilation failed".to_string(),
+            });
+        }
+        let built = self.target_dir.join("debug").join(format!(
+            "{}{}",
+            self.name,
+            std::env::consts::EXE_SUFFIX
+        ));
+        // Copy through a temporary file so a half-written binary is never run
+        let staging = self.dir.join(format!(".{}.tmp", std::process::id()));
+        std::fs::copy(&built, &staging)?;
+        std::fs::rename(&staging, self.binary())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_embedded_manifests() {
+        let script = Script::parse(
+            "#!/usr/bin/env cpm run\n//! A tool\n//!\n//! ```cargo\n//! [dependencies]\n//! regex = \"1\"\n//! ```\n\nfn main() {}\n",
+        )
+        .unwrap();
+        assert_eq!(script.manifest, "[dependencies]\nregex = \"1\"");
+        assert!(script.source.starts_with("\n//! A tool"));
+
+        let script =
+            Script::parse("---cargo\n[dependencies]\nx = \"1\"\n---\nfn main() {}").unwrap();
+        assert_eq!(script.manifest, "[dependencies]\nx = \"1\"");
+        assert_eq!(script.source, "\n\n\n\nfn main() {}");
+
+        let plain = Script::parse("#![allow(unused)]\nfn main() {}\n").unwrap();
+        assert_eq!(plain.source, "#![allow(unused)]\nfn main() {}");
+        assert_eq!(plain.manifest, "");
+        assert!(Script::parse("//! ```cargo\n//! [dependencies]\nfn main() {}").is_err());
+
+        let cargo_toml = script.cargo_toml("hello").unwrap();
+        let document: toml_edit::DocumentMut = cargo_toml.parse().unwrap();
+        assert_eq!(document["package"]["name"].as_str(), Some("hello"));
+        assert_eq!(
+            document["package"]["edition"].as_str(),
+            Some(DEFAULT_EDITION)
+        );
+        assert_eq!(document["dependencies"]["x"].as_str(), Some("1"));
+        assert!(document.contains_key("workspace"));
+
+        assert_eq!(crate_name(Path::new("scripts/Fetch_Data.rs")), "fetch-data");
+        assert_eq!(crate_name(Path::new("2fa.rs")), "script-2fa");
+        assert_eq!(crate_name(Path::new("build.rs")), "script-build");
+        let a = ScriptCrate::new(Path::new("/c"), Path::new("a.rs"), "fn main() {}");
+        let b = ScriptCrate::new(Path::new("/c"), Path::new("a.rs"), "fn main() { }");
+        assert_ne!(a.dir, b.dir);
+    }
+}
@@ -0,0 +1,984 @@
+//! Software Bill of Materials (SBOM) generation
+//!
+//! Merges the npm dependency graph from `package-lock.json` with the crate graph
+//! from `Cargo.lock` (including the JetCrab runtime crates used by `cpm build`)
+//! and renders it as CycloneDX or SPDX JSON.
+
+use crate::cli::framework::{CliError, CliResult};
+use base64::Engine;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Supported SBOM output formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    CycloneDxJson,
+    /// SPDX 2.3 JSON
+    SpdxJson,
+}
+
+impl SbomFormat {
+    /// Parse a format name as accepted by `cpm sbom --format`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "cyclonedx-json" | "cyclonedx" => Some(Self::CycloneDxJson),
+            "spdx-json" | "spdx" => Some(Self::SpdxJson),
+            _ => None,
+        }
+    }
+}
+
+/// Ecosystem a component belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Ecosystem {
+    Npm,
+    Cargo,
+}
+
+/// A content hash attached to a component
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentHash {
+    /// Algorithm name in CycloneDX spelling (`SHA-256`, `SHA-512`, `SHA-1`)
+    pub algorithm: &'static str,
+    /// Lowercase hex digest
+    pub hex: String,
+}
+
+/// A single package in the bill of materials
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub ecosystem: Ecosystem,
+    pub name: String,
+    /// `None` when only a version requirement is known, as for the bundled
+    /// crates before `cpm build` has locked them
+    pub version: Option<String>,
+    pub hashes: Vec<ComponentHash>,
+    pub license: Option<String>,
+    /// Where the package was downloaded from, if known
+    pub download_location: Option<String>,
+    /// Development-only dependency
+    pub dev: bool,
+    /// Package URLs of the direct dependencies of this component
+    pub depends_on: BTreeSet<String>,
+}
+
+impl Component {
+    fn new(ecosystem: Ecosystem, name: &str, version: &str) -> Self {
+        Self {
+            version: Some(version.to_string()),
+            ..Self::unresolved(ecosystem, name)
+        }
+    }
+
+    /// A component whose exact version is not known
+    fn unresolved(ecosystem: Ecosystem, name: &str) -> Self {
+        Self {
+            ecosystem,
+            name: name.to_string(),
+            version: None,
+            hashes: Vec::new(),
+            license: None,
+            download_location: None,
+            dev: false,
+            depends_on: BTreeSet::new(),
+        }
+    }
+
+    /// Package URL (purl) identifying this component, without a version
+    /// when it is unresolved
+    pub fn purl(&self) -> String {
+        match &self.version {
+            Some(version) => purl(self.ecosystem, &self.name, version),
+            None => package_purl(self.ecosystem, &self.name),
+        }
+    }
+}
+
+/// Package URL of a package regardless of version
+fn package_purl(ecosystem: Ecosystem, name: &str) -> String {
+    match ecosystem {
+        Ecosystem::Npm => {
+            let name = match name.strip_prefix('@') {
+                Some(scoped) => format!("%40{scoped}"),
+                None => name.to_string(),
+            };
+            format!("pkg:npm/{name}")
+        }
+        Ecosystem::Cargo => format!("pkg:cargo/{name}"),
+    }
+}
+
+/// Build a package URL for the given ecosystem, name and version
+pub fn purl(ecosystem: Ecosystem, name: &str, version: &str) -> String {
+    format!("{}@{version}", package_purl(ecosystem, name))
+}
+
+/// Options controlling SBOM collection
+#[derive(Debug, Clone, Default)]
+pub struct SbomOptions {
+    /// Include npm devDependencies
+    pub include_dev: bool,
+}
+
+/// The merged bill of materials for a project
+#[derive(Debug, Clone)]
+pub struct Sbom {
+    pub root: Component,
+    pub components: BTreeMap<String, Component>,
+}
+
+impl Sbom {
+    fn insert(&mut self, component: Component) {
+        let key = component.purl();
+        match self.components.get_mut(&key) {
+            Some(existing) => {
+                existing.depends_on.extend(component.depends_on);
+                existing.dev &= component.dev;
+                if existing.hashes.is_empty() {
+                    existing.hashes = component.hashes;
+                }
+                if existing.license.is_none() {
+                    existing.license = component.license;
+                }
+            }
+            None => {
+                self.components.insert(key, component);
+            }
+        }
+    }
+
+    /// Collect the SBOM for the project rooted at `dir`
+    pub fn collect(dir: &Path, options: &SbomOptions) -> CliResult<Self> {
+        let (ecosystem, name, version) = project_identity(dir);
+        let mut sbom = Sbom {
+            root: Component::new(ecosystem, &name, &version),
+            components: BTreeMap::new(),
+        };
+
+        let package_lock = dir.join("package-lock.json");
+        if package_lock.exists() {
+            let content = std::fs::read_to_string(&package_lock)?;
+            let lock: Value = serde_json::from_str(&content)?;
+            let (root_deps, components) = npm_components(dir, &lock, options)?;
+            sbom.root.depends_on.extend(root_deps);
+            for component in components {
+                sbom.insert(component);
+            }
+        }
+
+        let cargo_lock = dir.join("Cargo.lock");
+        if cargo_lock.exists() {
+            let root_crate = cargo_package_name(&dir.join("Cargo.toml"));
+            let (root_deps, components) = cargo_components(&cargo_lock, root_crate.as_deref())?;
+            sbom.root.depends_on.extend(root_deps);
+            for component in components {
+                sbom.insert(component);
+            }
+        }
+
+        // Crates linked into the standalone binary produced by `cpm build`
+        let bundle_lock = dir.join("jetcrab_build").join("Cargo.lock");
+        let bundle_root = bundle_crate_name(dir);
+        if bundle_lock.exists() {
+            let (root_deps, components) =
+                cargo_components(&bundle_lock, Some(bundle_root.as_str()))?;
+            sbom.root.depends_on.extend(root_deps);
+            for component in components {
+                sbom.insert(component);
+            }
+        } else {
+            for component in template_components(dir)? {
+                sbom.root.depends_on.insert(component.purl());
+                sbom.insert(component);
+            }
+        }
+
+        Ok(sbom)
+    }
+
+    /// Render the SBOM in the requested format
+    pub fn render(&self, format: SbomFormat, epoch: u64) -> Value {
+        match format {
+            SbomFormat::CycloneDxJson => self.to_cyclonedx(epoch),
+            SbomFormat::SpdxJson => self.to_spdx(epoch),
+        }
+    }
+
+    /// Stable identifier derived from the SBOM contents and timestamp
+    fn document_uuid(&self, epoch: u64) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.root.purl());
+        hasher.update(epoch.to_le_bytes());
+        for key in self.components.keys() {
+            hasher.update(key.as_bytes());
+        }
+        let digest = hasher.finalize();
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        bytes[6] = (bytes[6] & 0x0f) | 0x50;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+        let hex = hex_encode(&bytes);
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+
+    fn to_cyclonedx(&self, epoch: u64) -> Value {
+        let components: Vec<Value> = self
+            .components
+            .values()
+            .map(|c| {
+                let mut entry = json!({
+                    "type": "library",
+                    "bom-ref": c.purl(),
+                    "name": c.name,
+                    "purl": c.purl(),
+                    "scope": if c.dev { "optional" } else { "required" },
+                });
+                if let Some(version) = &c.version {
+                    entry["version"] = json!(version);
+                }
+                if !c.hashes.is_empty() {
+                    entry["hashes"] = c
+                        .hashes
+                        .iter()
+                        .map(|h| json!({ "alg": h.algorithm, "content": h.hex }))
+                        .collect();
+                }
+                if let Some(license) = &c.license {
+                    entry["licenses"] = json!([{ "expression": license }]);
+                }
+                if let Some(location) = &c.download_location {
+                    entry["externalReferences"] =
+                        json!([{ "type": "distribution", "url": location }]);
+                }
+                entry
+            })
+            .collect();
+
+        let mut dependencies = vec![json!({
+            "ref": self.root.purl(),
+            "dependsOn": self.root.depends_on,
+        })];
+        dependencies.extend(
+            self.components
+                .values()
+                .map(|c| json!({ "ref": c.purl(), "dependsOn": c.depends_on })),
+        );
+
+        json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "serialNumber": format!("urn:uuid:{}", self.document_uuid(epoch)),
+            "version": 1,
+            "metadata": {
+                "timestamp": crate::timestamp::format_rfc3339(epoch),
+                "tools": {
+                    "components": [{
+                        "type": "application",
+                        "name": "cpm",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    }]
+                },
+                "component": {
+                    "type": "application",
+                    "bom-ref": self.root.purl(),
+                    "name": self.root.name,
+                    "version": self.root.version,
+                    "purl": self.root.purl(),
+                }
+            },
+            "components": components,
+            "dependencies": dependencies,
+        })
+    }
+
+    fn to_spdx(&self, epoch: u64) -> Value {
+        // The project's own version always comes from its manifest
+        let root_version = self.root.version.as_deref().unwrap_or_default();
+        let root_id = spdx_id(&self.root);
+        let mut packages = vec![spdx_package(&self.root)];
+        packages.extend(self.components.values().map(spdx_package));
+
+        let mut relationships = vec![json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": root_id,
+        })];
+        let by_purl: BTreeMap<String, String> = self
+            .components
+            .values()
+            .map(|c| (c.purl(), spdx_id(c)))
+            .collect();
+        for component in std::iter::once(&self.root).chain(self.components.values()) {
+            let from = spdx_id(component);
+            for dep in &component.depends_on {
+                if let Some(to) = by_purl.get(dep) {
+                    let kind = match self.components.get(dep) {
+                        Some(c) if c.dev => "DEV_DEPENDENCY_OF",
+                        _ => "DEPENDS_ON",
+                    };
+                    let (element, related) = if kind == "DEPENDS_ON" {
+                        (from.clone(), to.clone())
+                    } else {
+                        (to.clone(), from.clone())
+                    };
+                    relationships.push(json!({
+                        "spdxElementId": element,
+                        "relationshipType": kind,
+                        "relatedSpdxElement": related,
+                    }));
+                }
+            }
+        }
+
+        json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": format!("{}-{}", self.root.name, root_version),
+            "documentNamespace": format!(
+                "https://jetcrab.dev/spdxdocs/{}-{}-{}",
+                self.root.name.trim_start_matches('@').replace('/', "-"),
+                root_version,
+                self.document_uuid(epoch)
+            ),
+            "creationInfo": {
+                "created": crate::timestamp::format_rfc3339(epoch),
+                "creators": [format!("Tool: cpm-{}", env!("CARGO_PKG_VERSION"))],
+            },
+            "packages": packages,
+            "relationships": relationships,
+        })
+    }
+}
+
+fn spdx_id(component: &Component) -> String {
+    let prefix = match component.ecosystem {
+        Ecosystem::Npm => "npm",
+        Ecosystem::Cargo => "cargo",
+    };
+    let raw = match &component.version {
+        Some(version) => format!("{prefix}-{}-{version}", component.name),
+        None => format!("{prefix}-{}", component.name),
+    };
+    let sanitized: String = raw
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("SPDXRef-{sanitized}")
+}
+
+fn spdx_package(component: &Component) -> Value {
+    let mut package = json!({
+        "SPDXID": spdx_id(component),
+        "name": component.name,
+        "downloadLocation": component.download_location.as_deref().unwrap_or("NOASSERTION"),
+        "filesAnalyzed": false,
+        "licenseConcluded": "NOASSERTION",
+        "licenseDeclared": component.license.as_deref().unwrap_or("NOASSERTION"),
+        "copyrightText": "NOASSERTION",
+        "externalRefs": [{
+            "referenceCategory": "PACKAGE-MANAGER",
+            "referenceType": "purl",
+            "referenceLocator": component.purl(),
+        }],
+    });
+    if let Some(version) = &component.version {
+        package["versionInfo"] = json!(version);
+    }
+    if !component.hashes.is_empty() {
+        package["checksums"] = component
+            .hashes
+            .iter()
+            .map(|h| json!({ "algorithm": h.algorithm.replace('-', ""), "checksumValue": h.hex }))
+            .collect();
+    }
+    package
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Convert an npm Subresource Integrity string into component hashes
+pub fn parse_integrity(integrity: &str) -> Vec<ComponentHash> {
+    integrity
+        .split_whitespace()
+        .filter_map(|entry| {
+            let (alg, b64) = entry.split_once('-')?;
+            let algorithm = match alg {
+                "sha512" => "SHA-512",
+                "sha384" => "SHA-384",
+                "sha256" => "SHA-256",
+                "sha1" => "SHA-1",
+                _ => return None,
+            };
+            let b64 = b64.split('?').next().unwrap_or(b64);
+            let bytes = base64::engine::general_purpose::STANDARD.decode(b64).ok()?;
+            Some(ComponentHash {
+                algorithm,
+                hex: hex_encode(&bytes),
+            })
+        })
+        .collect()
+}
+
+/// Ecosystem, name and version of the project, from package.json or Cargo.toml
+fn project_identity(dir: &Path) -> (Ecosystem, String, String) {
+    if let Ok(content) = std::fs::read_to_string(dir.join("package.json")) {
+        if let Ok(json) = serde_json::from_str::<Value>(&content) {
+            let name = json["name"].as_str().unwrap_or("unnamed").to_string();
+            let version = json["version"].as_str().unwrap_or("0.0.0").to_string();
+            return (Ecosystem::Npm, name, version);
+        }
+    }
+    if let Ok(content) = std::fs::read_to_string(dir.join("Cargo.toml")) {
+        if let Ok(toml) = toml::from_str::<toml::Value>(&content) {
+            let name = toml
+                .get("package")
+                .and_then(|p| p.get("name"))
+                .and_then(|n| n.as_str())
+                .unwrap_or("unnamed")
+                .to_string();
+            let version = toml
+                .get("package")
+                .and_then(|p| p.get("version"))
+                .and_then(|v| v.as_str())
+                .unwrap_or("0.0.0")
+                .to_string();
+            return (Ecosystem::Cargo, name, version);
+        }
+    }
+    (Ecosystem::Npm, "unnamed".to_string(), "0.0.0".to_string())
+}
+
+fn cargo_package_name(manifest: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(manifest).ok()?;
+    let toml: toml::Value = toml::from_str(&content).ok()?;
+    toml.get("package")?
+        .get("name")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Name of the bundle crate generated by `cpm build` for this project
+fn bundle_crate_name(dir: &Path) -> String {
+    cargo_package_name(&dir.join("jetcrab_build").join("Cargo.toml"))
+        .unwrap_or_else(|| project_identity(dir).1)
+}
+
+/// Install location of a package inside node_modules (`node_modules/a/node_modules/b`)
+fn npm_name_from_path(path: &str) -> &str {
+    match path.rfind("node_modules/") {
+        Some(idx) => &path[idx + "node_modules/".len()..],
+        None => path,
+    }
+}
+
+/// Resolve a dependency the way Node.js does: nearest `node_modules` wins
+fn npm_resolve<'a>(
+    packages: &'a serde_json::Map<String, Value>,
+    from: &str,
+    dep: &str,
+) -> Option<&'a str> {
+    let mut base = from.to_string();
+    loop {
+        let candidate = if base.is_empty() {
+            format!("node_modules/{dep}")
+        } else {
+            format!("{base}/node_modules/{dep}")
+        };
+        if let Some((key, _)) = packages.get_key_value(&candidate) {
+            return Some(key.as_str());
+        }
+        if base.is_empty() {
+            return None;
+        }
+        base = match base.rfind("/node_modules/") {
+            Some(idx) => base[..idx].to_string(),
+            None => String::new(),
+        };
+    }
+}
+
+fn npm_dependency_names(entry: &Value, include_dev: bool) -> Vec<String> {
+    let mut sections = vec!["dependencies", "optionalDependencies", "peerDependencies"];
+    if include_dev {
+        sections.push("devDependencies");
+    }
+    let mut names = Vec::new();
+    for section in sections {
+        if let Some(deps) = entry.get(section).and_then(|d| d.as_object()) {
+            names.extend(deps.keys().cloned());
+        }
+    }
+    names
+}
+
+fn npm_license(dir: &Path, path: &str, entry: &Value) -> Option<String> {
+    if let Some(license) = entry.get("license").and_then(|l| l.as_str()) {
+        return Some(license.to_string());
+    }
+    let manifest = std::fs::read_to_string(dir.join(path).join("package.json")).ok()?;
+    let json: Value = serde_json::from_str(&manifest).ok()?;
+    match json.get("license")? {
+        Value::String(s) => Some(s.clone()),
+        Value::Object(obj) => obj.get("type")?.as_str().map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Components from a `package-lock.json`, plus the purls the project depends on directly
+fn npm_components(
+    dir: &Path,
+    lock: &Value,
+    options: &SbomOptions,
+) -> CliResult<(Vec<String>, Vec<Component>)> {
+    if let Some(packages) = lock.get("packages").and_then(|p| p.as_object()) {
+        let mut purls = BTreeMap::new();
+        for (path, entry) in packages {
+            if path.is_empty() || entry.get("link").and_then(|l| l.as_bool()) == Some(true) {
+                continue;
+            }
+            let name = entry
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_else(|| npm_name_from_path(path));
+            if let Some(version) = entry.get("version").and_then(|v| v.as_str()) {
+                purls.insert(path.as_str(), purl(Ecosystem::Npm, name, version));
+            }
+        }
+
+        let mut components = Vec::new();
+        for (path, entry) in packages {
+            let Some(own_purl) = purls.get(path.as_str()) else {
+                continue;
+            };
+            let dev = entry.get("dev").and_then(|d| d.as_bool()).unwrap_or(false);
+            if dev && !options.include_dev {
+                continue;
+            }
+            let name = entry
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or_else(|| npm_name_from_path(path));
+            let version = own_purl
+                .rsplit_once('@')
+                .map(|(_, v)| v)
+                .unwrap_or_default();
+            let mut component = Component::new(Ecosystem::Npm, name, version);
+            component.dev = dev;
+            component.hashes = entry
+                .get("integrity")
+                .and_then(|i| i.as_str())
+                .map(parse_integrity)
+                .unwrap_or_default();
+            component.license = npm_license(dir, path, entry);
+            component.download_location = entry
+                .get("resolved")
+                .and_then(|r| r.as_str())
+                .map(str::to_string);
+            for dep in npm_dependency_names(entry, false) {
+                if let Some(resolved) = npm_resolve(packages, path, &dep) {
+                    if let Some(dep_purl) = purls.get(resolved) {
+                        component.depends_on.insert(dep_purl.clone());
+                    }
+                }
+            }
+            components.push(component);
+        }
+
+        let root_deps = packages
+            .get("")
+            .map(|root| npm_dependency_names(root, options.include_dev))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|dep| npm_resolve(packages, "", &dep))
+            .filter_map(|path| purls.get(path).cloned())
+            .collect();
+        return Ok((root_deps, components));
+    }
+
+    // lockfileVersion 1: nested "dependencies" tree
+    let mut components = Vec::new();
+    let mut root_deps = Vec::new();
+    if let Some(deps) = lock.get("dependencies").and_then(|d| d.as_object()) {
+        collect_npm_v1(deps, options, &mut components, &mut root_deps);
+    }
+    Ok((root_deps, components))
+}
+
+fn collect_npm_v1(
+    deps: &serde_json::Map<String, Value>,
+    options: &SbomOptions,
+    components: &mut Vec<Component>,
+    direct: &mut Vec<String>,
+) {
+    for (name, entry) in deps {
+        let dev = entry.get("dev").and_then(|d| d.as_bool()).unwrap_or(false);
+        if dev && !options.include_dev {
+            continue;
+        }
+        let Some(version) = entry.get("version").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let mut component = Component::new(Ecosystem::Npm, name, version);
+        component.dev = dev;
+        component.hashes = entry
+            .get("integrity")
+            .and_then(|i| i.as_str())
+            .map(parse_integrity)
+            .unwrap_or_default();
+        component.download_location = entry
+            .get("resolved")
+            .and_then(|r| r.as_str())
+            .map(str::to_string);
+        let mut nested_direct = Vec::new();
+        if let Some(nested) = entry.get("dependencies").and_then(|d| d.as_object()) {
+            collect_npm_v1(nested, options, components, &mut nested_direct);
+        }
+        if let Some(requires) = entry.get("requires").and_then(|r| r.as_object()) {
+            for dep in requires.keys() {
+                let nested = nested_direct.iter().find(|p| {
+                    p.strip_prefix("pkg:npm/")
+                        .and_then(|rest| rest.rsplit_once('@'))
+                        .map(|(n, _)| n.replace("%40", "@") == *dep)
+                        .unwrap_or(false)
+                });
+                if let Some(found) = nested {
+                    component.depends_on.insert(found.clone());
+                } else if let Some(top) = deps
+                    .get(dep)
+                    .and_then(|d| d.get("version"))
+                    .and_then(|v| v.as_str())
+                {
+                    component.depends_on.insert(purl(Ecosystem::Npm, dep, top));
+                }
+            }
+        }
+        direct.push(component.purl());
+        components.push(component);
+    }
+}
+
+/// Locate an unpacked crate source in the local cargo registry cache
+fn cargo_registry_manifest(name: &str, version: &str) -> Option<PathBuf> {
+    let cargo_home = std::env::var("CARGO_HOME")
+        .map(PathBuf::from)
+        .ok()
+        .or_else(|| {
+            std::env::var("HOME")
+                .or_else(|_| std::env::var("USERPROFILE"))
+                .ok()
+                .map(|home| PathBuf::from(home).join(".cargo"))
+        })?;
+    let src = cargo_home.join("registry").join("src");
+    for index in std::fs::read_dir(src).ok()?.flatten() {
+        let manifest = index
+            .path()
+            .join(format!("{name}-{version}"))
+            .join("Cargo.toml");
+        if manifest.exists() {
+            return Some(manifest);
+        }
+    }
+    None
+}
+
+fn cargo_license(manifest: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(manifest).ok()?;
+    let toml: toml::Value = toml::from_str(&content).ok()?;
+    toml.get("package")?
+        .get("license")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// Components from a `Cargo.lock`, plus the purls the root crate depends on directly
+fn cargo_components(
+    lock_path: &Path,
+    root_crate: Option<&str>,
+) -> CliResult<(Vec<String>, Vec<Component>)> {
+    let content = std::fs::read_to_string(lock_path)?;
+    let lock: toml::Value = toml::from_str(&content)?;
+    let packages = lock
+        .get("package")
+        .and_then(|p| p.as_array())
+        .cloned()
+        .unwrap_or_default();
+
+    let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for package in &packages {
+        if let (Some(name), Some(version)) = (
+            package.get("name").and_then(|n| n.as_str()),
+            package.get("version").and_then(|v| v.as_str()),
+        ) {
+            versions
+                .entry(name.to_string())
+                .or_default()
+                .push(version.to_string());
+        }
+    }
+
+    // Dependencies are written as "name", "name version" or "name version (source)"
+    let resolve = |spec: &str| -> Option<String> {
+        let mut parts = spec.split_whitespace();
+        let name = parts.next()?;
+        let version = match parts.next() {
+            Some(v) => v.to_string(),
+            None => versions.get(name)?.first()?.clone(),
+        };
+        Some(purl(Ecosystem::Cargo, name, &version))
+    };
+
+    let mut root_deps = Vec::new();
+    let mut components = Vec::new();
+    let manifest_dir = lock_path.parent().unwrap_or(Path::new("."));
+    for package in &packages {
+        let (Some(name), Some(version)) = (
+            package.get("name").and_then(|n| n.as_str()),
+            package.get("version").and_then(|v| v.as_str()),
+        ) else {
+            continue;
+        };
+        let deps: Vec<String> = package
+            .get("dependencies")
+            .and_then(|d| d.as_array())
+            .map(|deps| {
+                deps.iter()
+                    .filter_map(|d| d.as_str())
+                    .filter_map(&resolve)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if Some(name) == root_crate {
+            root_deps.extend(deps);
+            continue;
+        }
+
+        let source = package.get("source").and_then(|s| s.as_str());
+        let mut component = Component::new(Ecosystem::Cargo, name, version);
+        component.depends_on.extend(deps);
+        if let Some(checksum) = package.get("checksum").and_then(|c| c.as_str()) {
+            component.hashes.push(ComponentHash {
+                algorithm: "SHA-256",
+                hex: checksum.to_string(),
+            });
+        }
+        component.download_location = match source {
+            Some(s)
+                if s.starts_with("registry+https://github.com/rust-lang/crates.io-index")
+                    || s == "sparse+https://index.crates.io/" =>
+            {
+                Some(format!(
+                    "https://crates.io/api/v1/crates/{name}/{version}/download"
+                ))
+            }
+            Some(s) => Some(
+                s.split_once('+')
+                    .map(|(_, url)| url)
+                    .unwrap_or(s)
+                    .to_string(),
+            ),
+            None => None,
+        };
+        component.license = match source {
+            Some(_) => cargo_registry_manifest(name, version).and_then(|m| cargo_license(&m)),
+            None => path_dependency_manifest(manifest_dir, name).and_then(|m| cargo_license(&m)),
+        };
+        components.push(component);
+    }
+    Ok((root_deps, components))
+}
+
+/// Manifest of a path dependency declared by the crate next to the lockfile
+fn path_dependency_manifest(manifest_dir: &Path, name: &str) -> Option<PathBuf> {
+    let content = std::fs::read_to_string(manifest_dir.join("Cargo.toml")).ok()?;
+    let toml: toml::Value = toml::from_str(&content).ok()?;
+    let path = toml.get("dependencies")?.get(name)?.get("path")?.as_str()?;
+    Some(manifest_dir.join(path).join("Cargo.toml"))
+}
+
+/// Direct runtime crates from the bundling template, used before `cpm build` has run
+fn template_components(dir: &Path) -> CliResult<Vec<Component>> {
+    let template: toml::Value = toml::from_str(crate::templates::CARGO_TOML_TEMPLATE)?;
+    let jetcrab_path = std::env::var("JETCRAB_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| dir.join("../JetCrab"));
+    let mut components = Vec::new();
+    if let Some(deps) = template.get("dependencies").and_then(|d| d.as_table()) {
+        for (name, spec) in deps {
+            let component = if spec.get("path").is_some() {
+                let manifest = jetcrab_path.join("Cargo.toml");
+                let version = std::fs::read_to_string(&manifest)
+                    .ok()
+                    .and_then(|c| toml::from_str::<toml::Value>(&c).ok())
+                    .and_then(|t| {
+                        t.get("package")?
+                            .get("version")?
+                            .as_str()
+                            .map(str::to_string)
+                    });
+                let mut component = match version {
+                    Some(version) => Component::new(Ecosystem::Cargo, name, &version),
+                    None => Component::unresolved(Ecosystem::Cargo, name),
+                };
+                component.license = cargo_license(&manifest);
+                component
+            } else {
+                // Only a requirement is known until `cpm build` locks it
+                Component::unresolved(Ecosystem::Cargo, name)
+            };
+            components.push(component);
+        }
+    }
+    Ok(components)
+}
+
+/// Write a rendered SBOM to a file, or stdout when no path is given
+pub fn write_sbom(document: &Value, output: Option<&Path>) -> CliResult<()> {
+    let rendered = serde_json::to_string_pretty(document)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, rendered + "\n").map_err(|e| CliError::FileOperationError {
+                operation: "write SBOM".to_string(),
+                path: path.display().to_string(),
+                message: e.to_string(),
+            })
+        }
+        None => {
+            use std::io::Write;
+            writeln!(std::io::stdout().lock(), "{rendered}")?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_purl_scoped_npm_package() {
+        assert_eq!(
+            purl(Ecosystem::Npm, "@types/node", "20.1.0"),
+            "pkg:npm/%40types/node@20.1.0"
+        );
+        assert_eq!(
+            purl(Ecosystem::Cargo, "serde", "1.0.0"),
+            "pkg:cargo/serde@1.0.0"
+        );
+    }
+
+    #[test]
+    fn test_parse_integrity() {
+        let hashes = parse_integrity("sha1-qUwdvsrEKIOlGcmOVYF/Ifj3vwU=");
+        assert_eq!(hashes.len(), 1);
+        assert_eq!(hashes[0].algorithm, "SHA-1");
+        assert_eq!(hashes[0].hex, "a94c1dbecac42883a519c98e55817f21f8f7bf05");
+    }
+
+    #[test]
+    fn test_cargo_only_project_root() {
+        let temp = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            temp.path().join("Cargo.toml"),
+            "[package]\nname = \"tool\"\nversion = \"0.3.0\"\n",
+        )
+        .unwrap();
+        let sbom = Sbom::collect(temp.path(), &SbomOptions::default()).unwrap();
+        assert_eq!(sbom.root.ecosystem, Ecosystem::Cargo);
+        assert_eq!(sbom.root.purl(), "pkg:cargo/tool@0.3.0");
+    }
+
+    #[test]
+    fn test_collect_merges_npm_and_cargo() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        std::fs::write(
+            dir.join("package.json"),
+            r#"{"name":"app","version":"1.0.0"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("package-lock.json"),
+            r#"{
+  "lockfileVersion": 3,
+  "packages": {
+    "": {"name": "app", "version": "1.0.0", "dependencies": {"a": "^1.0.0"}},
+    "node_modules/a": {"version": "1.0.0", "license": "MIT", "dependencies": {"b": "^2.0.0"}},
+    "node_modules/b": {"version": "2.0.0", "license": "ISC"},
+    "node_modules/jest": {"version": "29.0.0", "dev": true}
+  }
+}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"app\"\nversion = \"1.0.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("Cargo.lock"),
+            r#"version = 3
+
+[[package]]
+name = "app"
+version = "1.0.0"
+dependencies = ["wasm-bindgen"]
+
+[[package]]
+name = "wasm-bindgen"
+version = "0.2.92"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "4be2531df63900aeb2bca0daaaddec08491ee64ceecbee5076636a3b026795a8"
+"#,
+        )
+        .unwrap();
+
+        let sbom = Sbom::collect(dir, &SbomOptions::default()).unwrap();
+        assert!(sbom.components.contains_key("pkg:npm/a@1.0.0"));
+        assert!(!sbom.components.contains_key("pkg:npm/jest@29.0.0"));
+        assert!(
+            sbom.components["pkg:npm/a@1.0.0"]
+                .depends_on
+                .contains("pkg:npm/b@2.0.0")
+        );
+        assert!(
+            sbom.root
+                .depends_on
+                .contains("pkg:cargo/wasm-bindgen@0.2.92")
+        );
+        assert_eq!(sbom.root.ecosystem, Ecosystem::Npm);
+        let tokio = &sbom.components["pkg:cargo/tokio"];
+        assert_eq!(tokio.version, None);
+
+        let cyclonedx = sbom.render(SbomFormat::CycloneDxJson, 0);
+        let entry = cyclonedx["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["name"] == "tokio")
+            .unwrap();
+        assert!(entry.get("version").is_none());
+        assert_eq!(cyclonedx["bomFormat"], "CycloneDX");
+        let spdx = sbom.render(SbomFormat::SpdxJson, 0);
+        assert_eq!(spdx["spdxVersion"], "SPDX-2.3");
+    }
+}
@@ -0,0 +1,320 @@
+//! Scaffolding workspace members
+//!
+//! `cpm workspace new` writes the same files as `cpm init` and `cpm add-rust`
+//! directly into the member directory, without changing the working directory
+//! or running `npm init`/`cargo init`, then registers the member with the
+//! root `package.json` and/or `Cargo.toml`.
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::glob::glob_match;
+use crate::manifest::{JsonManifest, TomlManifest};
+use crate::templates;
+use crate::workspace::Ecosystem;
+use serde_json::{Map, Value, json};
+use std::path::{Path, PathBuf};
+
+/// What to create
+#[derive(Debug, Clone)]
+pub struct NewMember {
+    /// Directory relative to the workspace root, `/`-separated
+    pub path: String,
+    /// npm package name
+    pub name: String,
+    pub ecosystem: Ecosystem,
+}
+
+impl NewMember {
+    /// Crate name derived from the package name, as `cpm add-rust` does
+    pub fn crate_name(&self) -> String {
+        let bare = self.name.rsplit('/').next().unwrap_or(&self.name);
+        bare.replace('-', "_")
+    }
+
+    fn has_npm(&self) -> bool {
+        self.ecosystem != Ecosystem::Rust
+    }
+
+    fn has_cargo(&self) -> bool {
+        self.ecosystem != Ecosystem::JavaScript
+    }
+}
+
+/// `package.json` as `npm init -y` followed by `cpm init` would leave it
+fn package_json(member: &NewMember) -> Value {
+    let mut scripts = Map::new();
+    for (name, command) in templates::CPM_SCRIPTS {
+        scripts.insert(name.to_string(), Value::String(command.to_string()));
+    }
+    let mut manifest = json!({
+        "name": member.name,
+        "version": "1.0.0",
+        "description": "",
+        "main": "index.js",
+        "scripts": scripts,
+        "keywords": [],
+        "author": "",
+        "license": "ISC",
+    });
+    if member.has_cargo() {
+        let dependencies: Map<String, Value> = templates::WASM_NPM_DEPENDENCIES
+            .iter()
+            .map(|(name, range)| (name.to_string(), Value::String(range.to_string())))
+            .collect();
+        manifest["dependencies"] = Value::Object(dependencies);
+    }
+    manifest
+}
+
+/// `Cargo.toml` as `cargo init --lib` followed by `cpm add-rust` would leave it
+fn cargo_toml(member: &NewMember) -> String {
+    let mut content = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[lib]\ncrate-type = [\"cdylib\"]\n\n[dependencies]\n",
+        member.crate_name()
+    );
+    for (name, version) in templates::WASM_CARGO_DEPENDENCIES {
+        content.push_str(&format!("{name} = \"{version}\"\n"));
+    }
+    content
+}
+
+/// Write the member's files below `root`, returning the created paths
+///
+/// Fails if the member directory already exists and is not empty.
+pub fn create(root: &Path, member: &NewMember) -> CliResult<Vec<PathBuf>> {
+    let dir = root.join(&member.path);
+    if dir
+        .read_dir()
+        .is_ok_and(|mut entries| entries.next().is_some())
+    {
+        return Err(CliError::FileExists {
+            path: member.path.clone(),
+        });
+    }
+    std::fs::create_dir_all(&dir)?;
+
+    let mut files: Vec<(PathBuf, String)> = Vec::new();
+    if member.has_npm() {
+        let manifest = serde_json::to_string_pretty(&package_json(member))? + "\n";
+        files.push((dir.join("package.json"), manifest));
+        files.push((
+            dir.join("index.js"),
+            templates::INDEX_JS_TEMPLATE.to_string(),
+        ));
+        files.push((
+            dir.join("README.md"),
+            templates::README_TEMPLATE.replace("PROJECT_NAME", &member.name),
+        ));
+    }
+    if member.has_cargo() {
+        files.push((dir.join("Cargo.toml"), cargo_toml(member)));
+        files.push((
+            dir.join("src/lib.rs"),
+            templates::WASM_LIB_TEMPLATE.to_string(),
+        ));
+    }
+    for (path, content) in &files {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+    }
+    if member.has_npm() && member.has_cargo() {
+        std::fs::create_dir_all(dir.join("pkg"))?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Whether `patterns` (with `!` exclusions) already cover `path`
+fn covered(patterns: &[String], path: &str) -> bool {
+    let mut included = false;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(negated) if glob_match(negated, path) => included = false,
+            None if glob_match(pattern, path) => included = true,
+            _ => {}
+        }
+    }
+    included
+}
+
+/// Add the member to the root `workspaces` and/or `[workspace] members`
+///
+/// Root manifests are created when missing. Returns the manifests that changed.
+pub fn register(root: &Path, member: &NewMember) -> CliResult<Vec<PathBuf>> {
+    let mut changed = Vec::new();
+
+    if member.has_npm() {
+        let path = root.join("package.json");
+        if !path.exists() {
+            std::fs::write(&path, "{\n  \"private\": true\n}\n")?;
+        }
+        let mut manifest = JsonManifest::load(&path)?;
+        if !covered(&crate::workspace::npm_patterns(root)?, &member.path) {
+            let object =
+                manifest
+                    .value
+                    .as_object_mut()
+                    .ok_or_else(|| CliError::FileOperationError {
+                        operation: "register workspace".to_string(),
+                        path: path.display().to_string(),
+                        message: "package.json is not an object".to_string(),
+                    })?;
+            let workspaces = object
+                .entry("workspaces")
+                .or_insert_with(|| Value::Array(Vec::new()));
+            // `{ "packages": [...] }` form
+            let list = match workspaces {
+                Value::Object(form) => form
+                    .entry("packages")
+                    .or_insert_with(|| Value::Array(Vec::new())),
+                other => other,
+            };
+            if let Some(list) = list.as_array_mut() {
+                list.push(Value::String(member.path.clone()));
+            }
+            manifest.save()?;
+            changed.push(path);
+        }
+    }
+
+    if member.has_cargo() {
+        let path = root.join("Cargo.toml");
+        if !path.exists() {
+            std::fs::write(&path, "[workspace]\nresolver = \"2\"\nmembers = []\n")?;
+        }
+        if !covered(&crate::workspace::cargo_patterns(root)?, &member.path) {
+            let mut manifest = TomlManifest::load(&path)?;
+            let workspace = manifest
+                .document
+                .entry("workspace")
+                .or_insert_with(toml_edit::table)
+                .as_table_like_mut()
+                .ok_or_else(|| CliError::FileOperationError {
+                    operation: "register workspace".to_string(),
+                    path: path.display().to_string(),
+                    message: "[workspace] is not a table".to_string(),
+                })?;
+            let members = workspace
+                .entry("members")
+                .or_insert(toml_edit::value(toml_edit::Array::new()));
+            if let Some(array) = members.as_array_mut() {
+                array.push(member.path.as_str());
+            }
+            manifest.save()?;
+            changed.push(path);
+        }
+    }
+    Ok(changed)
+}
+
+/// Relative path from one workspace member directory to another
+fn relative_between(from: &str, to: &str) -> String {
+    let from: Vec<&str> = from.split('/').filter(|s| !s.is_empty()).collect();
+    let to: Vec<&str> = to.split('/').filter(|s| !s.is_empty()).collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts = vec![".."; from.len() - common];
+    parts.extend(&to[common..]);
+    parts.join("/")
+}
+
+/// Make the existing member at `dependent` (relative path) depend on the new member
+///
+/// npm members get a `^version` range, Cargo members a `path` dependency.
+/// Returns the manifests that changed.
+pub fn add_as_dependency(
+    root: &Path,
+    member: &NewMember,
+    dependent: &str,
+) -> CliResult<Vec<PathBuf>> {
+    let dir = root.join(dependent);
+    let mut changed = Vec::new();
+
+    let npm_path = dir.join("package.json");
+    if member.has_npm() && npm_path.exists() {
+        let mut manifest = JsonManifest::load(&npm_path)?;
+        if let Some(object) = manifest.value.as_object_mut() {
+            let dependencies = object
+                .entry("dependencies")
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Some(dependencies) = dependencies.as_object_mut() {
+                dependencies.insert(member.name.clone(), Value::String("^1.0.0".to_string()));
+            }
+        }
+        manifest.save()?;
+        changed.push(npm_path);
+    }
+
+    let cargo_path = dir.join("Cargo.toml");
+    if member.has_cargo() && cargo_path.exists() {
+        let mut manifest = TomlManifest::load(&cargo_path)?;
+        let dependencies = manifest
+            .document
+            .entry("dependencies")
+            .or_insert_with(toml_edit::table)
+            .as_table_like_mut()
+            .ok_or_else(|| CliError::FileOperationError {
+                operation: "add dependency".to_string(),
+                path: cargo_path.display().to_string(),
+                message: "[dependencies] is not a table".to_string(),
+            })?;
+        let mut entry = toml_edit::InlineTable::new();
+        entry.insert("path", relative_between(dependent, &member.path).into());
+        entry.insert("version", "0.1.0".into());
+        dependencies.insert(&member.crate_name(), toml_edit::value(entry));
+        manifest.save()?;
+        changed.push(cargo_path);
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_hybrid_member_is_registered_and_wired() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::write(
+            root.join("package.json"),
+            "{\n  \"name\": \"root\",\n  \"private\": true,\n  \"workspaces\": [\n    \"apps/*\"\n  ]\n}\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("apps/web")).unwrap();
+        std::fs::write(root.join("apps/web/package.json"), r#"{"name":"web"}"#).unwrap();
+
+        let member = NewMember {
+            path: "packages/wasm-utils".to_string(),
+            name: "@demo/wasm-utils".to_string(),
+            ecosystem: Ecosystem::Hybrid,
+        };
+        create(root, &member).unwrap();
+        assert!(root.join("packages/wasm-utils/src/lib.rs").exists());
+        assert!(root.join("packages/wasm-utils/index.js").exists());
+        assert!(create(root, &member).is_err());
+
+        register(root, &member).unwrap();
+        let members = crate::workspace::discover(root).unwrap();
+        let names: Vec<&str> = members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["web", "@demo/wasm-utils"]);
+        assert_eq!(members[1].crate_name.as_deref(), Some("wasm_utils"));
+        assert_eq!(members[1].ecosystem, Ecosystem::Hybrid);
+        // Registering again changes nothing
+        assert!(register(root, &member).unwrap().is_empty());
+
+        add_as_dependency(root, &member, "apps/web").unwrap();
+        let graph = crate::graph::WorkspaceGraph::load(root).unwrap();
+        let web = graph.index_of("web").unwrap();
+        let wasm = graph.index_of("@demo/wasm-utils").unwrap();
+        assert!(graph.dependencies(web).contains(&wasm));
+    }
+
+    #[test]
+    fn test_relative_between_members() {
+        assert_eq!(relative_between("crates/cli", "crates/core"), "../core");
+        assert_eq!(
+            relative_between("apps/web", "packages/ui"),
+            "../../packages/ui"
+        );
+    }
+}
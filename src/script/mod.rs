@@ -0,0 +1,559 @@
+//! Running `package.json` scripts
+//!
+//! Scripts run the way `npm run` runs them, without starting npm: in the
+//! package directory, through the platform shell, with `node_modules/.bin` of
+//! the package and its ancestors on `PATH`, the `npm_*` environment npm sets,
+//! and `pre<name>`/`post<name>` hooks around the script.
+//!
+//! Projects can opt into the embedded [`shell`] instead of `sh`/`cmd` with
+//!
+//! ```toml
+//! [scripts]
+//! shell = "builtin"
+//! ```
+
+pub mod shell;
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::config::{CONFIG_FILE, CpmConfig};
+use serde::Deserialize;
+use serde_json::Value;
+use std::ffi::OsString;
+use std::io::{PipeReader, PipeWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Which shell runs scripts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScriptShell {
+    /// `sh -c` on Unix, `cmd /d /s /c` on Windows, as npm does
+    #[default]
+    System,
+    /// The embedded cross-platform [`shell`]
+    Builtin,
+}
+
+impl ScriptShell {
+    /// The shell configured for the package in `dir`
+    ///
+    /// The closest `cpm.toml` at or above `dir` decides, so a workspace root can
+    /// set it for all members.
+    pub fn for_package(dir: &Path) -> CliResult<Self> {
+        let dir = dir.canonicalize()?;
+        for ancestor in dir.ancestors() {
+            if ancestor.join(CONFIG_FILE).is_file() {
+                let config = CpmConfig::load(ancestor)?;
+                return Ok(config.scripts.map(|s| s.shell).unwrap_or_default());
+            }
+        }
+        Ok(ScriptShell::System)
+    }
+}
+
+/// The `[scripts]` section of `cpm.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ScriptsConfig {
+    #[serde(default)]
+    pub shell: ScriptShell,
+}
+
+/// A package whose scripts can be run
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub dir: PathBuf,
+    pub manifest: Value,
+}
+
+impl Package {
+    /// Load the package in `dir`
+    pub fn load(dir: &Path) -> CliResult<Self> {
+        let path = dir.join("package.json");
+        let content = std::fs::read_to_string(&path)?;
+        let manifest =
+            serde_json::from_str(&content).map_err(|e| CliError::FileOperationError {
+                operation: "parse manifest".to_string(),
+                path: path.display().to_string(),
+                message: e.to_string(),
+            })?;
+        Ok(Package {
+            dir: dir.to_path_buf(),
+            manifest,
+        })
+    }
+
+    /// The package in `dir`, or an empty one when there is no `package.json`
+    pub fn at(dir: &Path) -> CliResult<Self> {
+        if dir.join("package.json").is_file() {
+            return Package::load(dir);
+        }
+        Ok(Package {
+            dir: dir.to_path_buf(),
+            manifest: Value::Null,
+        })
+    }
+
+    /// The closest package at or above `start`, like npm's prefix lookup
+    pub fn find(start: &Path) -> CliResult<Option<Self>> {
+        let start = start.canonicalize()?;
+        match start
+            .ancestors()
+            .find(|dir| dir.join("package.json").is_file())
+        {
+            Some(dir) => Ok(Some(Package::load(dir)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The command of script `name`
+    pub fn script(&self, name: &str) -> Option<&str> {
+        self.manifest.get("scripts")?.get(name)?.as_str()
+    }
+
+    /// All scripts, in manifest order
+    pub fn scripts(&self) -> Vec<(String, String)> {
+        self.manifest
+            .get("scripts")
+            .and_then(Value::as_object)
+            .map(|scripts| {
+                scripts
+                    .iter()
+                    .filter_map(|(name, command)| {
+                        Some((name.clone(), command.as_str()?.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Description of script `name` from the `scripts-info` map
+    pub fn description(&self, name: &str) -> Option<&str> {
+        self.manifest.get("scripts-info")?.get(name)?.as_str()
+    }
+
+    fn field(&self, key: &str) -> Option<&str> {
+        self.manifest.get(key).and_then(Value::as_str)
+    }
+}
+
+/// One script invocation: a hook or the script itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    /// Lifecycle event, e.g. `prebuild`
+    pub event: String,
+    /// The script as written in `package.json`
+    pub script: String,
+    /// Extra arguments; npm only passes them to the script itself, not its hooks
+    pub args: Vec<String>,
+}
+
+impl Step {
+    /// The command line for `shell`, arguments quoted and appended
+    pub fn command_line(&self, shell: ScriptShell) -> String {
+        let mut line = self.script.clone();
+        for arg in &self.args {
+            line.push(' ');
+            line.push_str(&match shell {
+                ScriptShell::System => quote_arg(arg),
+                ScriptShell::Builtin => quote_posix(arg),
+            });
+        }
+        line
+    }
+
+    /// The system shell process running this step for `package`
+    pub fn command(&self, package: &Package) -> CliResult<Command> {
+        let mut command = shell_command(&self.command_line(ScriptShell::System));
+        command
+            .current_dir(&package.dir)
+            .env("PATH", search_path(&package.dir)?)
+            .envs(environment(package, &self.event, &self.script));
+        Ok(command)
+    }
+
+    /// Run the step, failing with the script's exit code
+    pub fn run(&self, package: &Package, shell: ScriptShell) -> CliResult<()> {
+        let code = self.exit_code(package, shell, None)?;
+        self.check(code)
+    }
+
+    /// Run the step like [`Step::run`], also keeping a copy of its output
+    pub fn run_captured(&self, package: &Package, shell: ScriptShell) -> CliResult<Captured> {
+        let (stdout_reader, stdout_writer) = std::io::pipe()?;
+        let (stderr_reader, stderr_writer) = std::io::pipe()?;
+        let (code, stdout, stderr) = std::thread::scope(|scope| {
+            let stdout = scope.spawn(|| tee(stdout_reader, std::io::stdout()));
+            let stderr = scope.spawn(|| tee(stderr_reader, std::io::stderr()));
+            // Consumes the writers, so the readers see the end once the step is done
+            let code = self.exit_code(package, shell, Some((stdout_writer, stderr_writer)));
+            (code, stdout.join(), stderr.join())
+        });
+        self.check(code?)?;
+        Ok(Captured {
+            stdout: stdout.unwrap_or_default(),
+            stderr: stderr.unwrap_or_default(),
+        })
+    }
+
+    fn exit_code(
+        &self,
+        package: &Package,
+        shell: ScriptShell,
+        capture: Option<(PipeWriter, PipeWriter)>,
+    ) -> CliResult<i32> {
+        Ok(match shell {
+            ScriptShell::System => {
+                let mut command = self.command(package)?;
+                if let Some((stdout, stderr)) = capture {
+                    command.stdout(stdout).stderr(stderr);
+                }
+                let status = command.status()?;
+                status.code().unwrap_or(1)
+            }
+            ScriptShell::Builtin => {
+                let vars = std::env::vars()
+                    .chain([(
+                        "PATH".to_string(),
+                        search_path(&package.dir)?.to_string_lossy().into_owned(),
+                    )])
+                    .chain(environment(package, &self.event, &self.script));
+                let mut interpreter = shell::Shell::new(&package.dir.canonicalize()?, vars);
+                if let Some((stdout, stderr)) = capture {
+                    interpreter.capture(stdout, stderr);
+                }
+                interpreter.run(&self.command_line(shell))?
+            }
+        })
+    }
+
+    fn check(&self, code: i32) -> CliResult<()> {
+        if code == 0 {
+            return Ok(());
+        }
+        Err(CliError::ScriptFailed {
+            script: self.event.clone(),
+            code,
+        })
+    }
+}
+
+/// Output of a step run with [`Step::run_captured`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Captured {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// Copy everything from `reader` to `out` as it arrives, returning a copy
+fn tee<W: Write>(mut reader: PipeReader, mut out: W) -> Vec<u8> {
+    let mut copy = Vec::new();
+    let mut buffer = [0u8; 8192];
+    while let Ok(n) = reader.read(&mut buffer) {
+        if n == 0 {
+            break;
+        }
+        let _ = out.write_all(&buffer[..n]);
+        let _ = out.flush();
+        copy.extend_from_slice(&buffer[..n]);
+    }
+    copy
+}
+
+/// The steps `npm run <name>` would take: `pre<name>`, `<name>`, `post<name>`
+///
+/// `None` when the package has no script called `name`.
+pub fn plan(package: &Package, name: &str, args: &[String]) -> Option<Vec<Step>> {
+    let script = package.script(name)?;
+    let mut steps = Vec::new();
+    let hook = |event: String| {
+        package.script(&event).map(|script| Step {
+            script: script.to_string(),
+            event,
+            args: Vec::new(),
+        })
+    };
+    steps.extend(hook(format!("pre{name}")));
+    steps.push(Step {
+        event: name.to_string(),
+        script: script.to_string(),
+        args: args.to_vec(),
+    });
+    steps.extend(hook(format!("post{name}")));
+    Some(steps)
+}
+
+/// Lifecycle events npm runs before packing a package it publishes, in order
+///
+/// `npm publish <tarball>` runs none of the publish lifecycle, so cpm runs
+/// these before building the tarball, [`POSTPACK_EVENTS`] after it and
+/// [`POSTPUBLISH_EVENTS`] once the tarball is published.
+pub const PREPUBLISH_EVENTS: &[&str] = &["prepublishOnly", "prepack", "prepare"];
+
+/// Lifecycle events npm runs once the tarball is packed
+pub const POSTPACK_EVENTS: &[&str] = &["postpack"];
+
+/// Lifecycle events npm runs after a successful publish, in order
+pub const POSTPUBLISH_EVENTS: &[&str] = &["publish", "postpublish"];
+
+/// The steps for the lifecycle `events` the package defines, in order
+///
+/// Unlike [`plan`], lifecycle events get no `pre`/`post` hooks of their own.
+pub fn lifecycle(package: &Package, events: &[&str]) -> Vec<Step> {
+    events
+        .iter()
+        .filter_map(|event| {
+            package.script(event).map(|script| Step {
+                event: event.to_string(),
+                script: script.to_string(),
+                args: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// Expand `patterns` against the script and task `names`, as `npm-run-all` does
+///
+/// In globs `:` separates segments like `/` in paths, so `watch:*` matches
+/// `watch:css` but not `watch:css:min`, while `watch:**` matches both.
+/// Plain names are kept as given. The result keeps the order of `patterns`,
+/// then of `names`, without duplicates; the error is a glob matching nothing.
+pub fn expand_patterns<'a, I>(names: I, patterns: &[String]) -> Result<Vec<String>, String>
+where
+    I: IntoIterator<Item = &'a str> + Clone,
+{
+    let mut expanded: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let matched: Vec<String> = if crate::glob::is_glob(pattern) {
+            let glob = pattern.replace(':', "/");
+            names
+                .clone()
+                .into_iter()
+                .filter(|name| crate::glob::glob_match_dot(&glob, &name.replace(':', "/")))
+                .map(str::to_string)
+                .collect()
+        } else {
+            vec![pattern.clone()]
+        };
+        if matched.is_empty() {
+            return Err(pattern.clone());
+        }
+        for name in matched {
+            if !expanded.contains(&name) {
+                expanded.push(name);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// `node_modules/.bin` of `dir` and each of its ancestors, closest first
+pub fn bin_dirs(dir: &Path) -> Vec<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join("node_modules").join(".bin"))
+        .filter(|bin| bin.is_dir())
+        .collect()
+}
+
+/// `PATH` with the package's bin directories in front
+pub fn search_path(dir: &Path) -> CliResult<OsString> {
+    let dir = dir.canonicalize()?;
+    let mut paths = bin_dirs(&dir);
+    if let Some(existing) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&existing));
+    }
+    std::env::join_paths(paths).map_err(|e| CliError::InternalError {
+        message: format!("cannot build PATH: {e}"),
+    })
+}
+
+/// Turn a manifest key into the suffix npm uses in variable names
+fn env_key(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// The `npm_*` variables npm sets for a lifecycle event
+pub fn environment(package: &Package, event: &str, script: &str) -> Vec<(String, String)> {
+    let mut env = vec![
+        ("npm_lifecycle_event".to_string(), event.to_string()),
+        ("npm_lifecycle_script".to_string(), script.to_string()),
+        ("npm_command".to_string(), "run-script".to_string()),
+        (
+            "npm_config_user_agent".to_string(),
+            format!("cpm/{}", env!("CARGO_PKG_VERSION")),
+        ),
+    ];
+    if !package.manifest.is_null() {
+        env.push((
+            "npm_package_json".to_string(),
+            package.dir.join("package.json").display().to_string(),
+        ));
+    }
+    for key in ["name", "version"] {
+        if let Some(value) = package.field(key) {
+            env.push((format!("npm_package_{key}"), value.to_string()));
+        }
+    }
+    for section in ["engines", "config"] {
+        let Some(values) = package.manifest.get(section).and_then(Value::as_object) else {
+            continue;
+        };
+        for (key, value) in values {
+            let value = match value {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            env.push((format!("npm_package_{section}_{}", env_key(key)), value));
+        }
+    }
+    if let Ok(exe) = std::env::current_exe() {
+        env.push(("npm_execpath".to_string(), exe.display().to_string()));
+    }
+    // Nested runs keep the directory the outermost command was started from
+    if std::env::var_os("INIT_CWD").is_none() {
+        if let Ok(cwd) = std::env::current_dir() {
+            env.push(("INIT_CWD".to_string(), cwd.display().to_string()));
+        }
+    }
+    env
+}
+
+/// Quote an argument for `sh` and the embedded shell
+pub fn quote_posix(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-.,:/@%+=".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Quote an argument for the system shell
+#[cfg(not(windows))]
+pub fn quote_arg(arg: &str) -> String {
+    quote_posix(arg)
+}
+
+/// Quote an argument for the script shell
+#[cfg(windows)]
+pub fn quote_arg(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-.,:/\\@+=".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("\"{}\"", arg.replace('"', "\"\""))
+}
+
+/// The platform shell running `line`, as npm's default `script-shell`
+#[cfg(not(windows))]
+pub fn shell_command(line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(line);
+    command
+}
+
+/// The platform shell running `line`, as npm's default `script-shell`
+#[cfg(windows)]
+pub fn shell_command(line: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    let mut command = Command::new("cmd");
+    command
+        .args(["/d", "/s", "/c"])
+        .raw_arg(format!("\"{line}\""));
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(root: &Path, manifest: &str) -> Package {
+        std::fs::write(root.join("package.json"), manifest).unwrap();
+        Package::load(root).unwrap()
+    }
+
+    #[test]
+    fn test_plan_runs_hooks_around_the_script() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let package = package(
+            temp.path(),
+            r#"{"scripts":{"prebuild":"echo pre","build":"tsc","postbuild":"echo post"}}"#,
+        );
+        let args = vec!["--watch".to_string(), "a b".to_string()];
+        let steps = plan(&package, "build", &args).unwrap();
+        let events: Vec<&str> = steps.iter().map(|s| s.event.as_str()).collect();
+        assert_eq!(events, vec!["prebuild", "build", "postbuild"]);
+        assert!(steps[0].args.is_empty());
+        assert_eq!(steps[1].args, args);
+        assert!(plan(&package, "test", &[]).is_none());
+    }
+
+    #[test]
+    fn test_lifecycle_runs_only_defined_events() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let package = package(
+            temp.path(),
+            r#"{"scripts":{"prepare":"tsc","prepack":"echo pack","preprepare":"echo no"}}"#,
+        );
+        let events: Vec<String> = lifecycle(&package, PREPUBLISH_EVENTS)
+            .into_iter()
+            .map(|step| step.event)
+            .collect();
+        assert_eq!(events, ["prepack", "prepare"]);
+    }
+
+    #[test]
+    fn test_expand_patterns_with_colon_segments() {
+        let names = ["watch:css", "watch:css:min", "watch:js", "lint", "test"];
+        let patterns = |list: &[&str]| list.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let expand = |list: &[&str]| expand_patterns(names, &patterns(list));
+        assert_eq!(
+            expand(&["lint", "watch:*"]).unwrap(),
+            vec!["lint", "watch:css", "watch:js"]
+        );
+        assert_eq!(expand(&["watch:**", "watch:js"]).unwrap().len(), 3);
+        assert_eq!(expand(&["typecheck"]).unwrap(), vec!["typecheck"]);
+        assert_eq!(expand(&["build:*"]).unwrap_err(), "build:*");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_step_environment_path_and_exit_code() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::create_dir_all(root.join("node_modules/.bin")).unwrap();
+        std::fs::create_dir_all(root.join("packages/app/node_modules/.bin")).unwrap();
+        std::fs::write(
+            root.join("node_modules/.bin/hello"),
+            "#!/bin/sh\necho \"hello $npm_package_name@$npm_package_version $*\"\n",
+        )
+        .unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(
+            root.join("node_modules/.bin/hello"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+        let app = package(
+            &root.join("packages/app"),
+            r#"{"name":"app","version":"2.0.0","scripts":{"greet":"hello","fail":"exit 7"}}"#,
+        );
+
+        let steps = plan(&app, "greet", &["it's".to_string()]).unwrap();
+        let output = steps[0].command(&app).unwrap().output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "hello app@2.0.0 it's\n"
+        );
+
+        let steps = plan(&app, "fail", &[]).unwrap();
+        match steps[0].run(&app, ScriptShell::System) {
+            Err(CliError::ScriptFailed { script, code }) => {
+                assert_eq!((script.as_str(), code), ("fail", 7))
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,1392 @@
+//! Embedded script shell
+//!
+//! A small sh-like interpreter for `package.json` scripts, so scripts such as
+//! `rm -rf dist && NODE_ENV=production node build.js` behave the same on every
+//! platform without cross-env or rimraf. Supported:
+//!
+//! - lists with `&&`, `||`, `;` and newlines, pipelines with `|`
+//! - redirects `>`, `>>`, `<`, `2>`, `2>>`, `&>`, `2>&1` and `>&2`; `/dev/null` works on Windows
+//! - `NAME=value` assignments, for the rest of the script or for a single command
+//! - `$NAME`, `${NAME}` and `$?`, single and double quotes, backslash escapes
+//! - `*`, `?` and `[...]` in unquoted words, left as written when nothing matches
+//! - built-ins `echo`, `exit`, `cd`, `pwd`, `export`, `true`, `false`, `rm`, `mkdir`, `cp` and `mv`
+//!
+//! Unlike sh, expanded variables are neither split into words nor globbed.
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::glob::glob_match;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{PipeReader, PipeWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+
+/// Exit status when a command cannot be found, as in sh
+const NOT_FOUND: i32 = 127;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    /// Literal text, and whether it was quoted (quoted text is never globbed)
+    Text(String, bool),
+    /// `$NAME`, `${NAME}` or `$?`
+    Var(String),
+}
+
+/// A word before expansion
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Word(Vec<Part>);
+
+impl Word {
+    fn push_char(&mut self, c: char, quoted: bool) {
+        if let Some(Part::Text(text, q)) = self.0.last_mut() {
+            if *q == quoted {
+                text.push(c);
+                return;
+            }
+        }
+        self.0.push(Part::Text(c.to_string(), quoted));
+    }
+
+    /// `NAME=value`, when the word is an assignment
+    fn assignment(&self) -> Option<(String, Word)> {
+        let Some(Part::Text(text, false)) = self.0.first() else {
+            return None;
+        };
+        let (name, value) = text.split_once('=')?;
+        if !is_name(name) {
+            return None;
+        }
+        let mut parts = vec![Part::Text(value.to_string(), false)];
+        parts.extend(self.0[1..].iter().cloned());
+        Some((name.to_string(), Word(parts)))
+    }
+
+    /// Whether an unquoted part contains a wildcard
+    fn has_glob(&self) -> bool {
+        self.0
+            .iter()
+            .any(|part| matches!(part, Part::Text(text, false) if text.contains(['*', '?', '['])))
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RedirectOp {
+    /// `>`, `>>`, `2>`, `2>>`
+    Write { fd: u8, append: bool },
+    /// `&>`, `&>>`
+    WriteAll { append: bool },
+    /// `<`
+    Read,
+    /// `2>&1`, `>&2`: make `fd` a copy of `to`
+    Dup { fd: u8, to: u8 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(Word),
+    And,
+    Or,
+    Semi,
+    Pipe,
+    Redirect(RedirectOp),
+}
+
+impl Token {
+    fn describe(&self) -> &'static str {
+        match self {
+            Token::Word(_) => "word",
+            Token::And => "`&&`",
+            Token::Or => "`||`",
+            Token::Semi => "`;`",
+            Token::Pipe => "`|`",
+            Token::Redirect(_) => "redirect",
+        }
+    }
+}
+
+/// Read `$...` starting at `i`, returning the index after it
+fn read_var(chars: &[char], i: usize, word: &mut Word, quoted: bool) -> Result<usize, String> {
+    match chars.get(i + 1) {
+        Some('{') => {
+            let end = chars[i + 2..]
+                .iter()
+                .position(|c| *c == '}')
+                .ok_or("missing `}`")?;
+            let name: String = chars[i + 2..i + 2 + end].iter().collect();
+            if !is_name(&name) && name != "?" {
+                return Err(format!("bad substitution `${{{name}}}`"));
+            }
+            word.0.push(Part::Var(name));
+            Ok(i + end + 3)
+        }
+        Some('?') => {
+            word.0.push(Part::Var("?".to_string()));
+            Ok(i + 2)
+        }
+        Some('(') => Err("command substitution is not supported".to_string()),
+        Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+            let name: String = chars[i + 1..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .collect();
+            let end = i + 1 + name.len();
+            word.0.push(Part::Var(name));
+            Ok(end)
+        }
+        _ => {
+            word.push_char('$', quoted);
+            Ok(i + 1)
+        }
+    }
+}
+
+/// Read a word starting at `i`, returning it and the index after it
+fn read_word(chars: &[char], mut i: usize) -> Result<(Word, usize), String> {
+    let mut word = Word::default();
+    while let Some(&c) = chars.get(i) {
+        match c {
+            ' ' | '\t' | '\r' | '\n' | ';' | '&' | '|' | '<' | '>' | '(' | ')' => break,
+            '\'' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|c| *c == '\'')
+                    .ok_or("unterminated single quote")?;
+                // An empty '' is still an argument
+                word.0.push(Part::Text(String::new(), true));
+                for &c in &chars[i + 1..i + 1 + end] {
+                    word.push_char(c, true);
+                }
+                i += end + 2;
+            }
+            '"' => {
+                word.0.push(Part::Text(String::new(), true));
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err("unterminated double quote".to_string()),
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some('\\') if matches!(chars.get(i + 1), Some('"' | '\\' | '$' | '`')) => {
+                            word.push_char(chars[i + 1], true);
+                            i += 2;
+                        }
+                        Some('$') => i = read_var(chars, i, &mut word, true)?,
+                        Some(&c) => {
+                            word.push_char(c, true);
+                            i += 1;
+                        }
+                    }
+                }
+            }
+            '\\' => match chars.get(i + 1) {
+                // Line continuation
+                Some('\n') => i += 2,
+                Some(&c) => {
+                    word.push_char(c, true);
+                    i += 2;
+                }
+                None => {
+                    word.push_char('\\', false);
+                    i += 1;
+                }
+            },
+            '$' => i = read_var(chars, i, &mut word, false)?,
+            '`' => return Err("command substitution is not supported".to_string()),
+            _ => {
+                word.push_char(c, false);
+                i += 1;
+            }
+        }
+    }
+    Ok((word, i))
+}
+
+fn tokenize(script: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = script.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while let Some(&c) = chars.get(i) {
+        let next = chars.get(i + 1).copied();
+        match c {
+            ' ' | '\t' | '\r' => i += 1,
+            '\n' | ';' => {
+                tokens.push(Token::Semi);
+                i += 1;
+            }
+            '#' => {
+                while chars.get(i).is_some_and(|c| *c != '\n') {
+                    i += 1;
+                }
+            }
+            '&' if next == Some('&') => {
+                tokens.push(Token::And);
+                i += 2;
+            }
+            '&' if next == Some('>') => {
+                let append = chars.get(i + 2) == Some(&'>');
+                tokens.push(Token::Redirect(RedirectOp::WriteAll { append }));
+                i += if append { 3 } else { 2 };
+            }
+            '&' => return Err("background jobs (`&`) are not supported".to_string()),
+            '|' if next == Some('|') => {
+                tokens.push(Token::Or);
+                i += 2;
+            }
+            '|' => {
+                tokens.push(Token::Pipe);
+                i += 1;
+            }
+            '<' => {
+                tokens.push(Token::Redirect(RedirectOp::Read));
+                i += 1;
+            }
+            '>' | '1' | '2' if c == '>' || next == Some('>') => {
+                let (fd, mut end) = match c {
+                    '>' => (1, i + 1),
+                    '1' => (1, i + 2),
+                    _ => (2, i + 2),
+                };
+                let op = match (chars.get(end), chars.get(end + 1)) {
+                    (Some('&'), Some(&to @ ('1' | '2'))) => {
+                        end += 2;
+                        RedirectOp::Dup {
+                            fd,
+                            to: if to == '1' { 1 } else { 2 },
+                        }
+                    }
+                    (Some('>'), _) => {
+                        end += 1;
+                        RedirectOp::Write { fd, append: true }
+                    }
+                    _ => RedirectOp::Write { fd, append: false },
+                };
+                tokens.push(Token::Redirect(op));
+                i = end;
+            }
+            '(' | ')' => return Err("subshells are not supported".to_string()),
+            _ => {
+                let (word, end) = read_word(&chars, i)?;
+                tokens.push(Token::Word(word));
+                i = end;
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Redirect {
+    op: RedirectOp,
+    /// File name; `Dup` has none
+    target: Option<Word>,
+}
+
+/// One command of a pipeline
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct SimpleCommand {
+    assignments: Vec<(String, Word)>,
+    words: Vec<Word>,
+    redirects: Vec<Redirect>,
+}
+
+type Pipeline = Vec<SimpleCommand>;
+
+/// Pipelines joined by `&&` (`true`) and `||` (`false`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AndOrList {
+    first: Pipeline,
+    rest: Vec<(bool, Pipeline)>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn unexpected(&self) -> String {
+        match self.peek() {
+            Some(token) => format!("unexpected {}", token.describe()),
+            None => "unexpected end of script".to_string(),
+        }
+    }
+
+    fn script(&mut self) -> Result<Vec<AndOrList>, String> {
+        let mut lists = Vec::new();
+        loop {
+            while self.eat(&Token::Semi) {}
+            if self.peek().is_none() {
+                return Ok(lists);
+            }
+            lists.push(self.and_or()?);
+            if self.peek().is_some() && !self.eat(&Token::Semi) {
+                return Err(self.unexpected());
+            }
+        }
+    }
+
+    fn and_or(&mut self) -> Result<AndOrList, String> {
+        let first = self.pipeline()?;
+        let mut rest = Vec::new();
+        loop {
+            let and = match self.peek() {
+                Some(Token::And) => true,
+                Some(Token::Or) => false,
+                _ => return Ok(AndOrList { first, rest }),
+            };
+            self.pos += 1;
+            // `a &&` may continue on the next line
+            while self.eat(&Token::Semi) {}
+            rest.push((and, self.pipeline()?));
+        }
+    }
+
+    fn pipeline(&mut self) -> Result<Pipeline, String> {
+        let mut commands = vec![self.command()?];
+        while self.eat(&Token::Pipe) {
+            commands.push(self.command()?);
+        }
+        Ok(commands)
+    }
+
+    fn command(&mut self) -> Result<SimpleCommand, String> {
+        let mut command = SimpleCommand::default();
+        loop {
+            match self.peek() {
+                Some(Token::Word(word)) => {
+                    let word = word.clone();
+                    self.pos += 1;
+                    match word.assignment() {
+                        Some(assignment) if command.words.is_empty() => {
+                            command.assignments.push(assignment)
+                        }
+                        _ => command.words.push(word),
+                    }
+                }
+                Some(Token::Redirect(op)) => {
+                    let op = *op;
+                    self.pos += 1;
+                    let target = match (op, self.peek()) {
+                        (RedirectOp::Dup { .. }, _) => None,
+                        (_, Some(Token::Word(word))) => {
+                            let word = word.clone();
+                            self.pos += 1;
+                            Some(word)
+                        }
+                        _ => return Err("missing file name after redirect".to_string()),
+                    };
+                    command.redirects.push(Redirect { op, target });
+                }
+                _ => break,
+            }
+        }
+        if command == SimpleCommand::default() {
+            return Err(self.unexpected());
+        }
+        Ok(command)
+    }
+}
+
+fn parse(script: &str) -> Result<Vec<AndOrList>, String> {
+    Parser {
+        tokens: tokenize(script)?,
+        pos: 0,
+    }
+    .script()
+}
+
+/// Where a command reads from
+enum Input {
+    Inherit,
+    Pipe(PipeReader),
+    File(File),
+}
+
+impl Input {
+    fn stdio(self) -> Stdio {
+        match self {
+            Input::Inherit => Stdio::inherit(),
+            Input::Pipe(reader) => reader.into(),
+            Input::File(file) => file.into(),
+        }
+    }
+}
+
+/// Where a command writes to
+enum Output {
+    Stdout,
+    Stderr,
+    Pipe(PipeWriter),
+    File(File),
+}
+
+impl Output {
+    fn try_clone(&self) -> std::io::Result<Output> {
+        Ok(match self {
+            Output::Stdout => Output::Stdout,
+            Output::Stderr => Output::Stderr,
+            Output::Pipe(writer) => Output::Pipe(writer.try_clone()?),
+            Output::File(file) => Output::File(file.try_clone()?),
+        })
+    }
+
+    fn stdio(self) -> Stdio {
+        match self {
+            Output::Stdout => std::io::stdout().into(),
+            Output::Stderr => std::io::stderr().into(),
+            Output::Pipe(writer) => writer.into(),
+            Output::File(file) => file.into(),
+        }
+    }
+
+    fn writer(self) -> Box<dyn Write> {
+        match self {
+            Output::Stdout => Box::new(std::io::stdout()),
+            Output::Stderr => Box::new(std::io::stderr()),
+            Output::Pipe(writer) => Box::new(writer),
+            Output::File(file) => Box::new(file),
+        }
+    }
+}
+
+struct Streams {
+    stdin: Input,
+    stdout: Output,
+    stderr: Output,
+}
+
+impl Streams {
+    /// Write a diagnostic to the command's stderr
+    fn report(&self, message: &str) {
+        if let Ok(stderr) = self.stderr.try_clone() {
+            let _ = writeln!(stderr.writer(), "cpm shell: {message}");
+        }
+    }
+}
+
+/// A command that was started
+enum Started {
+    Finished(i32),
+    Running(Child),
+}
+
+impl Started {
+    fn wait(self) -> i32 {
+        match self {
+            Started::Finished(status) => status,
+            Started::Running(mut child) => child.wait().map(exit_code).unwrap_or(1),
+        }
+    }
+}
+
+/// sh-style exit status: the exit code, or 128 + signal number
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// Message for an I/O error, without the OS error number so output is the same everywhere
+fn io_message(error: &std::io::Error) -> String {
+    match error.kind() {
+        std::io::ErrorKind::NotFound => "No such file or directory".to_string(),
+        std::io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+        std::io::ErrorKind::AlreadyExists => "File exists".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+/// Escape glob metacharacters in text that must match literally
+fn escape_glob(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape_glob(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Paths matching `pattern`, relative to `cwd` unless the pattern is absolute, sorted per segment
+fn glob_paths(cwd: &Path, pattern: &str) -> Vec<String> {
+    let (root, rest) = match pattern.strip_prefix('/') {
+        Some(rest) => (PathBuf::from("/"), rest),
+        None => (cwd.to_path_buf(), pattern),
+    };
+    let prefix = if pattern.starts_with('/') { "/" } else { "" };
+    let mut matches = vec![(root, prefix.to_string())];
+    for segment in rest.split('/').filter(|s| !s.is_empty()) {
+        let mut next = Vec::new();
+        for (dir, shown) in &matches {
+            let join = |name: &str| {
+                let shown = if shown.is_empty() || shown.ends_with('/') {
+                    format!("{shown}{name}")
+                } else {
+                    format!("{shown}/{name}")
+                };
+                (dir.join(name), shown)
+            };
+            if !segment.contains(['*', '?', '[']) {
+                let literal = unescape_glob(segment);
+                let (path, shown) = join(&literal);
+                if path.symlink_metadata().is_ok() {
+                    next.push((path, shown));
+                }
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            let mut names: Vec<String> = entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| glob_match(segment, name))
+                .collect();
+            names.sort();
+            next.extend(names.iter().map(|name| join(name)));
+        }
+        matches = next;
+    }
+    matches.into_iter().map(|(_, shown)| shown).collect()
+}
+
+type Builtin = fn(&mut Shell, &[String], &mut dyn Write, &mut dyn Write) -> i32;
+
+fn builtin(name: &str) -> Option<Builtin> {
+    let builtin: Builtin = match name {
+        "echo" => echo,
+        "exit" => exit,
+        "cd" => cd,
+        "pwd" => pwd,
+        "export" => export,
+        "true" => |_, _, _, _| 0,
+        "false" => |_, _, _, _| 1,
+        "rm" => rm,
+        "mkdir" => mkdir,
+        "cp" => cp,
+        "mv" => mv,
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+/// Split leading flags such as `-rf` or `--force` from the operands
+///
+/// `allowed` lists the accepted short flags; `--` ends the flags.
+fn flags<'a>(args: &'a [String], allowed: &str) -> Result<(Vec<char>, &'a [String]), String> {
+    const LONG: [(&str, char); 3] = [("recursive", 'r'), ("force", 'f'), ("parents", 'p')];
+    let mut flags = Vec::new();
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        if arg == "--" {
+            i += 1;
+            break;
+        }
+        if let Some(long) = arg.strip_prefix("--") {
+            match LONG.iter().find(|(name, _)| *name == long) {
+                Some((_, flag)) if allowed.contains(*flag) => flags.push(*flag),
+                _ => return Err(format!("unrecognized option '{arg}'")),
+            }
+        } else {
+            match arg.strip_prefix('-') {
+                Some(letters) if !letters.is_empty() => {
+                    for flag in letters.chars() {
+                        if !allowed.contains(flag) {
+                            return Err(format!("invalid option -- '{flag}'"));
+                        }
+                        flags.push(flag);
+                    }
+                }
+                _ => break,
+            }
+        }
+        i += 1;
+    }
+    Ok((flags, &args[i..]))
+}
+
+fn echo(_: &mut Shell, args: &[String], out: &mut dyn Write, _: &mut dyn Write) -> i32 {
+    let (newline, args) = match args.first() {
+        Some(flag) if flag == "-n" => (false, &args[1..]),
+        _ => (true, args),
+    };
+    let mut text = args.join(" ");
+    if newline {
+        text.push('\n');
+    }
+    match out.write_all(text.as_bytes()) {
+        Ok(()) => 0,
+        Err(_) => 1,
+    }
+}
+
+fn exit(shell: &mut Shell, args: &[String], _: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let code = match args.first() {
+        None => shell.status,
+        Some(arg) => match arg.parse::<i32>() {
+            Ok(code) => code.rem_euclid(256),
+            Err(_) => {
+                let _ = writeln!(err, "exit: {arg}: numeric argument required");
+                2
+            }
+        },
+    };
+    shell.exit = Some(code);
+    code
+}
+
+fn cd(shell: &mut Shell, args: &[String], _: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let Some(target) = args.first().cloned().or_else(|| shell.var("HOME")) else {
+        let _ = writeln!(err, "cd: HOME not set");
+        return 1;
+    };
+    match shell.cwd.join(&target).canonicalize() {
+        Ok(dir) if dir.is_dir() => {
+            shell.cwd = dir;
+            0
+        }
+        Ok(_) => {
+            let _ = writeln!(err, "cd: {target}: Not a directory");
+            1
+        }
+        Err(e) => {
+            let _ = writeln!(err, "cd: {target}: {}", io_message(&e));
+            1
+        }
+    }
+}
+
+fn pwd(shell: &mut Shell, _: &[String], out: &mut dyn Write, _: &mut dyn Write) -> i32 {
+    match writeln!(out, "{}", shell.cwd.display()) {
+        Ok(()) => 0,
+        Err(_) => 1,
+    }
+}
+
+fn export(shell: &mut Shell, args: &[String], _: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut status = 0;
+    for arg in args {
+        // Every variable is exported already, so only assignments matter
+        match arg.split_once('=') {
+            Some((name, value)) if is_name(name) => shell.set_var(name, value),
+            None if is_name(arg) => {}
+            _ => {
+                let _ = writeln!(err, "export: `{arg}': not a valid identifier");
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+fn rm(shell: &mut Shell, args: &[String], _: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let (flags, paths) = match flags(args, "rRf") {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            let _ = writeln!(err, "rm: {message}");
+            return 1;
+        }
+    };
+    let recursive = flags.iter().any(|f| *f == 'r' || *f == 'R');
+    let force = flags.contains(&'f');
+    if paths.is_empty() && !force {
+        let _ = writeln!(err, "rm: missing operand");
+        return 1;
+    }
+    let mut status = 0;
+    for path in paths {
+        let full = shell.cwd.join(path);
+        let result = match std::fs::symlink_metadata(&full) {
+            Err(_) if force => continue,
+            Err(e) => Err(io_message(&e)),
+            Ok(metadata) if metadata.is_dir() && !recursive => Err("Is a directory".to_string()),
+            Ok(metadata) if metadata.is_dir() => {
+                std::fs::remove_dir_all(&full).map_err(|e| io_message(&e))
+            }
+            Ok(_) => std::fs::remove_file(&full).map_err(|e| io_message(&e)),
+        };
+        if let Err(message) = result {
+            let _ = writeln!(err, "rm: cannot remove '{path}': {message}");
+            status = 1;
+        }
+    }
+    status
+}
+
+fn mkdir(shell: &mut Shell, args: &[String], _: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let (flags, paths) = match flags(args, "p") {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            let _ = writeln!(err, "mkdir: {message}");
+            return 1;
+        }
+    };
+    if paths.is_empty() {
+        let _ = writeln!(err, "mkdir: missing operand");
+        return 1;
+    }
+    let mut status = 0;
+    for path in paths {
+        let full = shell.cwd.join(path);
+        let result = if flags.contains(&'p') {
+            std::fs::create_dir_all(&full)
+        } else {
+            std::fs::create_dir(&full)
+        };
+        if let Err(e) = result {
+            let _ = writeln!(
+                err,
+                "mkdir: cannot create directory '{path}': {}",
+                io_message(&e)
+            );
+            status = 1;
+        }
+    }
+    status
+}
+
+fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    if !from.is_dir() {
+        std::fs::copy(from, to)?;
+        return Ok(());
+    }
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Shared by `cp` and `mv`: sources and the path each one goes to
+fn destinations(
+    shell: &Shell,
+    name: &str,
+    operands: &[String],
+    err: &mut dyn Write,
+) -> Option<Vec<(String, PathBuf, PathBuf)>> {
+    let [sources @ .., dest] = operands else {
+        let _ = writeln!(err, "{name}: missing file operand");
+        return None;
+    };
+    if sources.is_empty() {
+        let _ = writeln!(
+            err,
+            "{name}: missing destination file operand after '{dest}'"
+        );
+        return None;
+    }
+    let dest_path = shell.cwd.join(dest);
+    let into_dir = dest_path.is_dir();
+    if sources.len() > 1 && !into_dir {
+        let _ = writeln!(err, "{name}: target '{dest}' is not a directory");
+        return None;
+    }
+    Some(
+        sources
+            .iter()
+            .map(|source| {
+                let from = shell.cwd.join(source);
+                let to = match (into_dir, from.file_name()) {
+                    (true, Some(file_name)) => dest_path.join(file_name),
+                    _ => dest_path.clone(),
+                };
+                (source.clone(), from, to)
+            })
+            .collect(),
+    )
+}
+
+fn cp(shell: &mut Shell, args: &[String], _: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let (flags, operands) = match flags(args, "rRf") {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            let _ = writeln!(err, "cp: {message}");
+            return 1;
+        }
+    };
+    let recursive = flags.iter().any(|f| *f == 'r' || *f == 'R');
+    let Some(copies) = destinations(shell, "cp", operands, err) else {
+        return 1;
+    };
+    let mut status = 0;
+    for (source, from, to) in copies {
+        let result = if from.is_dir() && !recursive {
+            Err("-r not specified; omitting directory".to_string())
+        } else if !from.exists() {
+            Err(io_message(&std::io::ErrorKind::NotFound.into()))
+        } else {
+            copy_recursive(&from, &to).map_err(|e| io_message(&e))
+        };
+        if let Err(message) = result {
+            let _ = writeln!(err, "cp: cannot copy '{source}': {message}");
+            status = 1;
+        }
+    }
+    status
+}
+
+fn mv(shell: &mut Shell, args: &[String], _: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let (_, operands) = match flags(args, "f") {
+        Ok(parsed) => parsed,
+        Err(message) => {
+            let _ = writeln!(err, "mv: {message}");
+            return 1;
+        }
+    };
+    let Some(moves) = destinations(shell, "mv", operands, err) else {
+        return 1;
+    };
+    let mut status = 0;
+    for (source, from, to) in moves {
+        // rename fails across file systems; fall back to copy and delete
+        let result = std::fs::rename(&from, &to).or_else(|e| {
+            if !from.exists() {
+                return Err(e);
+            }
+            copy_recursive(&from, &to)?;
+            if from.is_dir() {
+                std::fs::remove_dir_all(&from)
+            } else {
+                std::fs::remove_file(&from)
+            }
+        });
+        if let Err(e) = result {
+            let _ = writeln!(err, "mv: cannot move '{source}': {}", io_message(&e));
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Interpreter state: working directory, variables and the last exit status
+///
+/// All variables are exported to the commands the shell starts.
+#[derive(Debug, Clone)]
+pub struct Shell {
+    cwd: PathBuf,
+    vars: BTreeMap<String, String>,
+    status: i32,
+    /// Set by `exit`
+    exit: Option<i32>,
+    /// Replaces the process's stdout and stderr, see [`Shell::capture`]
+    captured: Option<Arc<(PipeWriter, PipeWriter)>>,
+}
+
+impl Shell {
+    /// A shell in `cwd` with the given variables
+    pub fn new<I>(cwd: &Path, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut shell = Shell {
+            cwd: cwd.to_path_buf(),
+            vars: BTreeMap::new(),
+            status: 0,
+            exit: None,
+            captured: None,
+        };
+        for (name, value) in vars {
+            shell.set_var(&name, &value);
+        }
+        shell
+    }
+
+    /// Variable names are case-insensitive on Windows, like the environment
+    fn key(name: &str) -> String {
+        if cfg!(windows) {
+            name.to_ascii_uppercase()
+        } else {
+            name.to_string()
+        }
+    }
+
+    pub fn var(&self, name: &str) -> Option<String> {
+        self.vars.get(&Self::key(name)).cloned()
+    }
+
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        self.vars.insert(Self::key(name), value.to_string());
+    }
+
+    /// Send the output of the commands run from now on to these pipes
+    pub fn capture(&mut self, stdout: PipeWriter, stderr: PipeWriter) {
+        self.captured = Some(Arc::new((stdout, stderr)));
+    }
+
+    fn stdout(&self) -> Output {
+        match &self.captured {
+            Some(pipes) => pipes.0.try_clone().map_or(Output::Stdout, Output::Pipe),
+            None => Output::Stdout,
+        }
+    }
+
+    fn stderr(&self) -> Output {
+        match &self.captured {
+            Some(pipes) => pipes.1.try_clone().map_or(Output::Stderr, Output::Pipe),
+            None => Output::Stderr,
+        }
+    }
+
+    /// Run a script, returning its exit status
+    ///
+    /// Syntax errors are reported before anything runs.
+    pub fn run(&mut self, script: &str) -> CliResult<i32> {
+        let lists = parse(script).map_err(|message| CliError::ExecutionError {
+            command: script.to_string(),
+            message: format!("syntax error: {message}"),
+        })?;
+        for list in &lists {
+            let mut status = self.pipeline(&list.first);
+            for (and, pipeline) in &list.rest {
+                if self.exit.is_some() {
+                    break;
+                }
+                if (status == 0) == *and {
+                    status = self.pipeline(pipeline);
+                }
+            }
+            if let Some(code) = self.exit {
+                return Ok(code);
+            }
+        }
+        Ok(self.status)
+    }
+
+    fn lookup(&self, name: &str) -> String {
+        if name == "?" {
+            return self.status.to_string();
+        }
+        self.var(name).unwrap_or_default()
+    }
+
+    /// A word with its variables substituted
+    fn expand_text(&self, word: &Word) -> String {
+        word.0
+            .iter()
+            .map(|part| match part {
+                Part::Text(text, _) => text.clone(),
+                Part::Var(name) => self.lookup(name),
+            })
+            .collect()
+    }
+
+    /// A word as arguments: variables substituted and unquoted wildcards matched against files
+    fn expand(&self, word: &Word) -> Vec<String> {
+        let text = self.expand_text(word);
+        if !word.has_glob() {
+            return vec![text];
+        }
+        let pattern: String = word
+            .0
+            .iter()
+            .map(|part| match part {
+                Part::Text(text, false) => text.clone(),
+                Part::Text(text, true) => escape_glob(text),
+                Part::Var(name) => escape_glob(&self.lookup(name)),
+            })
+            .collect();
+        let matches = glob_paths(&self.cwd, &pattern);
+        if matches.is_empty() {
+            vec![text]
+        } else {
+            matches
+        }
+    }
+
+    fn redirect_path(&self, word: &Word) -> Result<PathBuf, String> {
+        let mut expanded = self.expand(word);
+        if expanded.len() != 1 {
+            return Err(format!("{}: ambiguous redirect", self.expand_text(word)));
+        }
+        let target = expanded.remove(0);
+        if cfg!(windows) && target == "/dev/null" {
+            return Ok(PathBuf::from("NUL"));
+        }
+        Ok(self.cwd.join(target))
+    }
+
+    fn redirect(&self, redirect: &Redirect, streams: &mut Streams) -> Result<(), String> {
+        let path = match &redirect.target {
+            Some(word) => self.redirect_path(word)?,
+            None => PathBuf::new(),
+        };
+        let failed = |e: std::io::Error| format!("{}: {}", path.display(), io_message(&e));
+        let open = |append: bool| {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(append)
+                .truncate(!append)
+                .open(&path)
+                .map_err(failed)
+        };
+        match redirect.op {
+            RedirectOp::Write { fd, append } => {
+                let file = Output::File(open(append)?);
+                if fd == 2 {
+                    streams.stderr = file;
+                } else {
+                    streams.stdout = file;
+                }
+            }
+            RedirectOp::WriteAll { append } => {
+                let file = open(append)?;
+                streams.stderr = Output::File(file.try_clone().map_err(failed)?);
+                streams.stdout = Output::File(file);
+            }
+            RedirectOp::Read => streams.stdin = Input::File(File::open(&path).map_err(failed)?),
+            RedirectOp::Dup { fd, to } => {
+                let source = if to == 2 {
+                    &streams.stderr
+                } else {
+                    &streams.stdout
+                };
+                let copy = source.try_clone().map_err(|e| e.to_string())?;
+                if fd == 2 {
+                    streams.stderr = copy;
+                } else {
+                    streams.stdout = copy;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_executable(path: &Path) -> bool {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            path.metadata()
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        }
+        #[cfg(not(unix))]
+        {
+            path.is_file()
+        }
+    }
+
+    /// Find a program the way the shell would: as a path, or on `PATH`
+    fn resolve(&self, program: &str) -> Option<PathBuf> {
+        let extensions: Vec<String> = if cfg!(windows) {
+            let pathext = self
+                .var("PATHEXT")
+                .unwrap_or_else(|| ".COM;.EXE;.BAT;.CMD".to_string());
+            pathext.split(';').map(|e| e.to_ascii_lowercase()).collect()
+        } else {
+            Vec::new()
+        };
+        let candidates = |path: PathBuf| {
+            let mut candidates = vec![path.clone()];
+            if path.extension().is_none() {
+                candidates.extend(extensions.iter().map(|e| {
+                    let mut name = path.clone().into_os_string();
+                    name.push(e);
+                    PathBuf::from(name)
+                }));
+            }
+            candidates
+        };
+        if program.contains('/') || (cfg!(windows) && program.contains('\\')) {
+            return candidates(self.cwd.join(program))
+                .into_iter()
+                .find(|p| Self::is_executable(p));
+        }
+        let path = self.var("PATH")?;
+        std::env::split_paths(&path)
+            .flat_map(|dir| candidates(dir.join(program)))
+            .find(|p| Self::is_executable(p))
+    }
+
+    /// Start one command; built-ins and failures finish right away
+    fn start(&mut self, command: &SimpleCommand, mut streams: Streams) -> Started {
+        for redirect in &command.redirects {
+            if let Err(message) = self.redirect(redirect, &mut streams) {
+                streams.report(&message);
+                return Started::Finished(1);
+            }
+        }
+        let args: Vec<String> = command.words.iter().flat_map(|w| self.expand(w)).collect();
+        let assignments: Vec<(String, String)> = command
+            .assignments
+            .iter()
+            .map(|(name, value)| (Self::key(name), self.expand_text(value)))
+            .collect();
+        let Some(program) = args.first() else {
+            for (name, value) in assignments {
+                self.set_var(&name, &value);
+            }
+            return Started::Finished(0);
+        };
+
+        if let Some(builtin) = builtin(program) {
+            let mut out = streams.stdout.writer();
+            let mut err = streams.stderr.writer();
+            let status = builtin(self, &args[1..], &mut out, &mut err);
+            let _ = out.flush();
+            let _ = err.flush();
+            return Started::Finished(status);
+        }
+        let Some(path) = self.resolve(program) else {
+            streams.report(&format!("{program}: command not found"));
+            return Started::Finished(NOT_FOUND);
+        };
+        let diagnostics = streams.stderr.try_clone();
+        let mut process = Command::new(path);
+        process
+            .args(&args[1..])
+            .current_dir(&self.cwd)
+            .env_clear()
+            .envs(&self.vars)
+            .envs(assignments)
+            .stdin(streams.stdin.stdio())
+            .stdout(streams.stdout.stdio())
+            .stderr(streams.stderr.stdio());
+        match process.spawn() {
+            Ok(child) => Started::Running(child),
+            Err(e) => {
+                if let Ok(stderr) = diagnostics {
+                    let _ = writeln!(stderr.writer(), "cpm shell: {program}: {e}");
+                }
+                Started::Finished(126)
+            }
+        }
+    }
+
+    /// Run a pipeline; its status is the status of the last command
+    fn pipeline(&mut self, commands: &[SimpleCommand]) -> i32 {
+        let status = match commands {
+            [command] => {
+                let streams = Streams {
+                    stdin: Input::Inherit,
+                    stdout: self.stdout(),
+                    stderr: self.stderr(),
+                };
+                self.start(command, streams).wait()
+            }
+            _ => self.run_pipe(commands),
+        };
+        self.status = status;
+        status
+    }
+
+    fn run_pipe(&mut self, commands: &[SimpleCommand]) -> i32 {
+        let mut streams: Vec<Streams> = Vec::with_capacity(commands.len());
+        let mut previous: Option<PipeReader> = None;
+        for i in 0..commands.len() {
+            let stdin = match previous.take() {
+                Some(reader) => Input::Pipe(reader),
+                None => Input::Inherit,
+            };
+            let stdout = if i + 1 == commands.len() {
+                self.stdout()
+            } else {
+                match std::io::pipe() {
+                    Ok((reader, writer)) => {
+                        previous = Some(reader);
+                        Output::Pipe(writer)
+                    }
+                    Err(e) => {
+                        eprintln!("cpm shell: cannot create pipe: {e}");
+                        return 1;
+                    }
+                }
+            };
+            streams.push(Streams {
+                stdin,
+                stdout,
+                stderr: self.stderr(),
+            });
+        }
+        // Start from the end so readers exist before built-ins write into the pipes.
+        // Every command runs in its own copy of the shell, as in a subshell.
+        let mut started: Vec<Started> = commands
+            .iter()
+            .zip(streams)
+            .rev()
+            .map(|(command, streams)| self.clone().start(command, streams))
+            .collect();
+        started.reverse();
+        let statuses: Vec<i32> = started.into_iter().map(Started::wait).collect();
+        statuses.last().copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell(dir: &Path) -> Shell {
+        Shell::new(dir, std::env::vars())
+    }
+
+    fn read(dir: &Path, path: &str) -> String {
+        std::fs::read_to_string(dir.join(path)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_lists_pipelines_and_redirects() {
+        let lists =
+            parse("A=1 B=\"x y\" cmd 'a b' > out 2>&1 && next || other; last | grep x").unwrap();
+        assert_eq!(lists.len(), 2);
+        assert_eq!(lists[0].rest.len(), 2);
+        let command = &lists[0].first[0];
+        assert_eq!(command.assignments.len(), 2);
+        assert_eq!(command.words.len(), 2);
+        assert_eq!(
+            command.redirects.iter().map(|r| r.op).collect::<Vec<_>>(),
+            vec![
+                RedirectOp::Write {
+                    fd: 1,
+                    append: false
+                },
+                RedirectOp::Dup { fd: 2, to: 1 }
+            ]
+        );
+        assert_eq!(lists[1].first.len(), 2);
+
+        let tokens = tokenize("a >> b 2>> c >&2").unwrap();
+        assert_eq!(
+            tokens[1],
+            Token::Redirect(RedirectOp::Write {
+                fd: 1,
+                append: true
+            })
+        );
+        assert_eq!(
+            tokens[3],
+            Token::Redirect(RedirectOp::Write {
+                fd: 2,
+                append: true
+            })
+        );
+        assert_eq!(tokens[5], Token::Redirect(RedirectOp::Dup { fd: 1, to: 2 }));
+
+        assert!(parse("echo 'open").is_err());
+        assert!(parse("&& echo").is_err());
+        assert!(parse("echo a |").is_err());
+        assert!(parse("sleep 1 &").is_err());
+        assert!(parse("echo > ").is_err());
+    }
+
+    #[test]
+    fn test_builtins_manage_files() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("dist/old")).unwrap();
+        let script = "rm -rf dist && mkdir -p dist/js && echo 'a  b' \"c\" > dist/js/a.txt \
+            && echo more >> dist/js/a.txt && cp -r dist out && mv out/js/a.txt out/b.txt";
+        assert_eq!(shell(dir).run(script).unwrap(), 0);
+        assert!(!dir.join("dist/old").exists());
+        assert_eq!(read(dir, "dist/js/a.txt"), "a  b c\nmore\n");
+        assert_eq!(read(dir, "out/b.txt"), "a  b c\nmore\n");
+        assert!(!dir.join("out/js/a.txt").exists());
+
+        assert_eq!(shell(dir).run("rm missing 2> err.txt").unwrap(), 1);
+        assert_eq!(
+            read(dir, "err.txt"),
+            "rm: cannot remove 'missing': No such file or directory\n"
+        );
+        assert_eq!(shell(dir).run("mkdir dist 2> err.txt").unwrap(), 1);
+        assert_eq!(shell(dir).run("rm dist 2> err.txt").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_control_flow_variables_and_globs() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        std::fs::write(dir.join("b.txt"), "").unwrap();
+        std::fs::write(dir.join("a.txt"), "").unwrap();
+        std::fs::write(dir.join(".hidden.txt"), "").unwrap();
+
+        let script =
+            "false || echo yes > or.txt; false && echo no > and.txt; exit 3; echo no > after.txt";
+        assert_eq!(shell(dir).run(script).unwrap(), 3);
+        assert_eq!(read(dir, "or.txt"), "yes\n");
+        assert!(!dir.join("and.txt").exists() && !dir.join("after.txt").exists());
+
+        let script =
+            "NAME=cpm; false; echo \"hi $NAME\" ${NAME}! $? *.txt '*.txt' nothing*.js > out.log";
+        assert_eq!(shell(dir).run(script).unwrap(), 0);
+        assert_eq!(
+            read(dir, "out.log"),
+            "hi cpm cpm! 1 a.txt b.txt or.txt *.txt nothing*.js\n"
+        );
+
+        let mut shell = shell(dir);
+        assert_eq!(
+            shell
+                .run("mkdir sub && cd sub && export MODE=prod && pwd > ../pwd.txt")
+                .unwrap(),
+            0
+        );
+        assert_eq!(shell.var("MODE").as_deref(), Some("prod"));
+        assert!(read(dir, "pwd.txt").trim_end().ends_with("sub"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_external_commands_pipes_and_env() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        let script = "FOO=bar sh -c 'echo $FOO; echo oops >&2' 2>&1 | tr a-z A-Z > up.txt";
+        assert_eq!(shell(dir).run(script).unwrap(), 0);
+        assert_eq!(read(dir, "up.txt"), "BAR\nOOPS\n");
+
+        // Per-command assignments don't stick; `echo` output feeds the next command
+        let script = "FOO=bar true; echo \"[$FOO]\" | cat > foo.txt; sh -c 'exit 4'";
+        assert_eq!(shell(dir).run(script).unwrap(), 4);
+        assert_eq!(read(dir, "foo.txt"), "[]\n");
+
+        assert_eq!(
+            shell(dir).run("no-such-command 2> err.txt").unwrap(),
+            NOT_FOUND
+        );
+        assert_eq!(
+            read(dir, "err.txt"),
+            "cpm shell: no-such-command: command not found\n"
+        );
+        assert!(shell(dir).run("echo 'unterminated").is_err());
+    }
+}
@@ -0,0 +1,386 @@
+//! Tasks declared in `cpm.toml`
+//!
+//! Tasks work like `package.json` scripts, including in projects without a
+//! `package.json`, and can carry a description for `cpm run`'s listing:
+//!
+//! ```toml
+//! [tasks.ci]
+//! command = "cpm run lint && cpm test"
+//! description = "Everything CI runs"
+//! ```
+//!
+//! Tasks can depend on other tasks and be cached:
+//!
+//! ```toml
+//! [tasks.build]
+//! command = "tsc -b"
+//! dependsOn = ["codegen", "^build"]
+//! inputs = ["src/**", "tsconfig.json"]
+//! outputs = ["dist/**"]
+//! env = ["NODE_ENV"]
+//! ```
+//!
+//! `^build` is the `build` task (or script) of every workspace package this
+//! package depends on. A task without `command` runs the `package.json`
+//! script of the same name, if any. A task declaring `inputs` is cached: its
+//! fingerprint covers the commands, the input files, the listed environment
+//! variables and the fingerprints of the cached tasks it depends on, see
+//! [`crate::cache`].
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::config::CpmConfig;
+use crate::graph::WorkspaceGraph;
+use crate::script::{Package, Step};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// A `[tasks.<name>]` entry
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskConfig {
+    /// Shell command, run the way a `package.json` script is; defaults to
+    /// the script of the same name
+    pub command: Option<String>,
+    /// Shown next to the task by `cpm run`
+    pub description: Option<String>,
+    /// Tasks to run first; `^name` runs `name` in workspace dependencies
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Globs of the files the task reads, relative to the package
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// Globs of the files the task writes, restored on cache hits
+    #[serde(default)]
+    pub outputs: Vec<String>,
+    /// Environment variables that change the task's result
+    #[serde(default)]
+    pub env: Vec<String>,
+}
+
+impl TaskConfig {
+    /// Whether results are cached, which needs declared inputs
+    pub fn is_cached(&self) -> bool {
+        !self.inputs.is_empty()
+    }
+}
+
+/// A task or script to run as part of a task graph
+#[derive(Debug, Clone)]
+pub struct TaskNode {
+    /// Package directory
+    pub dir: PathBuf,
+    /// Package name, or the directory name without a `package.json` name
+    pub package: String,
+    pub name: String,
+    /// `None` for a `package.json` script
+    pub task: Option<TaskConfig>,
+    /// What runs, in order; empty for a task that only groups its dependencies
+    pub steps: Vec<Step>,
+    /// Indices of the nodes to run first
+    pub dependencies: Vec<usize>,
+}
+
+/// Tasks and scripts available in one package
+struct PackageTasks {
+    package: Package,
+    config: CpmConfig,
+    /// Index in the workspace graph
+    member: Option<usize>,
+}
+
+impl PackageTasks {
+    fn label(&self) -> String {
+        match self.package.manifest.get("name").and_then(|n| n.as_str()) {
+            Some(name) => name.to_string(),
+            None => self
+                .package
+                .dir
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| ".".to_string()),
+        }
+    }
+
+    fn defines(&self, name: &str) -> bool {
+        self.config.tasks.contains_key(name) || self.package.script(name).is_some()
+    }
+}
+
+/// Builds the node list depth first, so dependencies come before dependents
+struct Planner {
+    workspace: Option<WorkspaceGraph>,
+    packages: Vec<PackageTasks>,
+    nodes: Vec<TaskNode>,
+    /// `(package, name)` of each node, and of the nodes being visited
+    keys: Vec<(usize, String)>,
+    visiting: Vec<(usize, String)>,
+}
+
+impl Planner {
+    fn package(&mut self, dir: &Path) -> CliResult<usize> {
+        if let Some(idx) = self.packages.iter().position(|p| p.package.dir == dir) {
+            return Ok(idx);
+        }
+        let member = self.workspace.as_ref().and_then(|graph| {
+            graph
+                .members
+                .iter()
+                .position(|m| m.dir.canonicalize().is_ok_and(|d| d == dir))
+        });
+        self.packages.push(PackageTasks {
+            package: Package::at(dir)?,
+            config: CpmConfig::load(dir)?,
+            member,
+        });
+        Ok(self.packages.len() - 1)
+    }
+
+    fn visit(&mut self, package: usize, name: &str, args: &[String]) -> CliResult<usize> {
+        let key = (package, name.to_string());
+        if let Some(idx) = self.keys.iter().position(|k| *k == key) {
+            return Ok(idx);
+        }
+        if let Some(start) = self.visiting.iter().position(|k| *k == key) {
+            let cycle: Vec<&str> = self.visiting[start..]
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .chain([name])
+                .collect();
+            return Err(CliError::ExecutionError {
+                command: format!("run {}", self.visiting[0].1),
+                message: format!("Task cycle: {}", cycle.join(" → ")),
+            });
+        }
+        let task = self.packages[package].config.tasks.get(name).cloned();
+        if task.is_none() && self.packages[package].package.script(name).is_none() {
+            return Err(CliError::ExecutionError {
+                command: format!("run {}", name),
+                message: format!(
+                    "No task or script '{}' in {}",
+                    name,
+                    self.packages[package].label()
+                ),
+            });
+        }
+
+        self.visiting.push(key.clone());
+        let mut dependencies = Vec::new();
+        for dependency in task.iter().flat_map(|t| &t.depends_on) {
+            match dependency.strip_prefix('^') {
+                Some(upstream) => {
+                    for dir in self.upstream_dirs(package) {
+                        let idx = self.package(&dir)?;
+                        if self.packages[idx].defines(upstream) {
+                            dependencies.push(self.visit(idx, upstream, &[])?);
+                        }
+                    }
+                }
+                None => dependencies.push(self.visit(package, dependency, &[])?),
+            }
+        }
+        self.visiting.pop();
+
+        dependencies.sort_unstable();
+        dependencies.dedup();
+        let scripts = &self.packages[package].package;
+        let steps = match task.as_ref().and_then(|t| t.command.as_ref()) {
+            Some(command) => vec![Step {
+                event: name.to_string(),
+                script: command.clone(),
+                args: args.to_vec(),
+            }],
+            None => crate::script::plan(scripts, name, args).unwrap_or_default(),
+        };
+        self.nodes.push(TaskNode {
+            dir: scripts.dir.clone(),
+            package: self.packages[package].label(),
+            name: name.to_string(),
+            task,
+            steps,
+            dependencies,
+        });
+        self.keys.push(key);
+        Ok(self.nodes.len() - 1)
+    }
+
+    /// Directories of the workspace members `package` depends on
+    fn upstream_dirs(&self, package: usize) -> Vec<PathBuf> {
+        let (Some(graph), Some(member)) = (&self.workspace, self.packages[package].member) else {
+            return Vec::new();
+        };
+        graph
+            .dependencies(member)
+            .iter()
+            .filter_map(|idx| graph.members[*idx].dir.canonicalize().ok())
+            .collect()
+    }
+}
+
+/// The closest workspace containing `dir` as a member
+fn workspace_of(dir: &Path) -> CliResult<Option<WorkspaceGraph>> {
+    for root in dir.ancestors().skip(1) {
+        let has_manifest = root.join("package.json").is_file() || root.join("Cargo.toml").is_file();
+        if has_manifest
+            && crate::workspace::is_workspace(root)?
+            && crate::workspace::member_dirs(root)?
+                .iter()
+                .any(|member| member.canonicalize().is_ok_and(|m| m == dir))
+        {
+            return Ok(Some(WorkspaceGraph::load(root)?));
+        }
+    }
+    Ok(None)
+}
+
+/// Everything `cpm run <name>` runs in the package at `dir`, dependencies first
+///
+/// The last node is `name` itself, the only one receiving `args`.
+pub fn plan(dir: &Path, name: &str, args: &[String]) -> CliResult<Vec<TaskNode>> {
+    let dir = dir.canonicalize()?;
+    let mut planner = Planner {
+        workspace: workspace_of(&dir)?,
+        packages: Vec::new(),
+        nodes: Vec::new(),
+        keys: Vec::new(),
+        visiting: Vec::new(),
+    };
+    let package = planner.package(&dir)?;
+    planner.visit(package, name, args)?;
+    Ok(planner.nodes)
+}
+
+/// Hash of everything that determines a cached task's result
+///
+/// `dependencies` are the fingerprints of the cached tasks it depends on.
+pub fn fingerprint(
+    dir: &Path,
+    task: &TaskConfig,
+    steps: &[Step],
+    dependencies: &[String],
+) -> CliResult<String> {
+    let mut hasher = Sha256::new();
+    let mut field = |label: &str, value: &[u8]| {
+        hasher.update(label.as_bytes());
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(value);
+    };
+    field("version", env!("CARGO_PKG_VERSION").as_bytes());
+    for step in steps {
+        field("event", step.event.as_bytes());
+        field("script", step.script.as_bytes());
+        for arg in &step.args {
+            field("arg", arg.as_bytes());
+        }
+    }
+    let mut env = task.env.clone();
+    env.sort();
+    for name in &env {
+        field("env", name.as_bytes());
+        match std::env::var_os(name) {
+            Some(value) => field("value", value.to_string_lossy().as_bytes()),
+            None => field("unset", b""),
+        }
+    }
+    for file in crate::glob::glob_files(dir, &task.inputs) {
+        field("input", crate::glob::relative_path(dir, &file).as_bytes());
+        field("content", &Sha256::digest(std::fs::read(&file)?));
+    }
+    for dependency in dependencies {
+        field("dependency", dependency.as_bytes());
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_orders_dependencies_across_the_workspace() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::write(
+            root.join("package.json"),
+            r#"{"name":"root","workspaces":["packages/*"]}"#,
+        )
+        .unwrap();
+        for (name, dependencies) in [("app", r#"{"lib":"*"}"#), ("lib", "{}")] {
+            let dir = root.join("packages").join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join("package.json"),
+                format!(
+                    r#"{{"name":"{name}","dependencies":{dependencies},"scripts":{{"build":"tsc","codegen":"gen"}}}}"#
+                ),
+            )
+            .unwrap();
+        }
+        std::fs::write(
+            root.join("packages/app/cpm.toml"),
+            "[tasks.ci]\ndependsOn = [\"build\"]\n\n[tasks.build]\ncommand = \"tsc -b\"\ndependsOn = [\"codegen\", \"^build\"]\ninputs = [\"src/**\"]\n",
+        )
+        .unwrap();
+
+        let nodes = plan(&root.join("packages/app"), "ci", &[]).unwrap();
+        let names: Vec<String> = nodes
+            .iter()
+            .map(|n| format!("{}#{}", n.package, n.name))
+            .collect();
+        assert_eq!(
+            names,
+            vec!["app#codegen", "lib#build", "app#build", "app#ci"]
+        );
+        assert_eq!(nodes[2].dependencies, vec![0, 1]);
+        assert!(nodes[1].task.is_none());
+        assert_eq!(nodes[1].steps[0].script, "tsc");
+        assert!(nodes[2].task.as_ref().unwrap().is_cached());
+        assert!(nodes[3].steps.is_empty());
+
+        std::fs::write(
+            root.join("packages/lib/cpm.toml"),
+            "[tasks.a]\ncommand = \"x\"\ndependsOn = [\"b\"]\n\n[tasks.b]\ncommand = \"y\"\ndependsOn = [\"a\"]\n",
+        )
+        .unwrap();
+        let error = plan(&root.join("packages/lib"), "a", &[]).unwrap_err();
+        assert!(error.to_string().contains("a → b → a"));
+    }
+
+    #[test]
+    fn test_fingerprint_follows_inputs_and_env() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let dir = temp.path();
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/main.ts"), "one").unwrap();
+        std::fs::write(dir.join("notes.md"), "ignored").unwrap();
+        let task = TaskConfig {
+            inputs: vec!["src/**".to_string()],
+            ..TaskConfig::default()
+        };
+        let step = |args: &[&str]| Step {
+            event: "build".to_string(),
+            script: "tsc".to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        };
+        let hash = || fingerprint(dir, &task, &[step(&[])], &[]).unwrap();
+
+        let first = hash();
+        std::fs::write(dir.join("notes.md"), "changed").unwrap();
+        assert_eq!(hash(), first);
+        std::fs::write(dir.join("src/main.ts"), "two").unwrap();
+        let second = hash();
+        assert_ne!(second, first);
+        let watch = fingerprint(dir, &task, &[step(&["--watch"])], &[]).unwrap();
+        assert_ne!(watch, second);
+        let with_path = TaskConfig {
+            env: vec!["PATH".to_string()],
+            ..task.clone()
+        };
+        let steps = [step(&[])];
+        assert_ne!(fingerprint(dir, &with_path, &steps, &[]).unwrap(), second);
+    }
+}
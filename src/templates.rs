@@ -24,3 +24,131 @@ edition = "2021"
 jetcrab = { path = "JETCRAB_PATH" }
 tokio = { version = "1.0", features = ["full"] }
 "#;
+
+pub const CPM_TOML_TEMPLATE: &str = r#"# CPM project configuration
+
+# Dependencies allowed to run preinstall/install/postinstall scripts.
+# Review pending packages with `cpm approve-scripts`.
+[install-scripts]
+allow = []
+deny = []
+"#;
+
+/// `index.js` of a new JavaScript project
+pub const INDEX_JS_TEMPLATE: &str = r#"// CPM JavaScript Project
+console.log('Hello from CPM! 🦀');
+
+// Example function
+function greet(name) {
+    console.log(`Hello, ${name}! Welcome to CPM.`);
+}
+
+// Call the function
+greet('World');
+"#;
+
+/// `README.md` of a new JavaScript project; `PROJECT_NAME` is replaced
+pub const README_TEMPLATE: &str = r#"# PROJECT_NAME - CPM JavaScript Project
+
+This is a JavaScript project managed by CPM (Crab Package Manager).
+
+## Getting Started
+
+1. **Install dependencies:**
+   ```bash
+   cpm install
+   ```
+
+2. **Start development server:**
+   ```bash
+   cpm dev
+   ```
+
+3. **Build the project:**
+   ```bash
+   cpm build
+   ```
+
+## Adding Rust (Optional)
+
+To add Rust to this project later:
+```bash
+cpm add-rust
+```
+
+## Available Commands
+
+- `cpm install` - Install dependencies
+- `cpm build` - Build the project
+- `cpm dev` - Start development server
+- `cpm test` - Run tests
+- `cpm add-rust` - Add Rust to the project
+- `cpm rust-status` - Check Rust status
+- `cpm approve-scripts` - Review dependency install scripts
+"#;
+
+/// Scripts added to `package.json` of a new project
+pub const CPM_SCRIPTS: &[(&str, &str)] = &[
+    ("dev", "cpm dev"),
+    ("build", "cpm build"),
+    ("test", "cpm test"),
+];
+
+/// `src/lib.rs` of the Rust/WASM part of a project
+pub const WASM_LIB_TEMPLATE: &str = r#"use wasm_bindgen::prelude::*;
+
+// Import console.log from web-sys
+use web_sys::console;
+
+// A macro to provide `eprintln!(..)`-style syntax for `console.log` logging.
+macro_rules! log {
+    ( $( $t:tt )* ) => {
+        console::log_1(&format!( $( $t )* ).into());
+    }
+}
+
+#[wasm_bindgen]
+extern "C" {
+    fn alert(s: &str);
+}
+
+/// Greet a user with a message from Rust
+#[wasm_bindgen]
+pub fn greet(name: &str) {
+    alert(&format!("Hello, {}! You've been greeted from Rust!", name));
+}
+
+/// Add two numbers and return the result
+#[wasm_bindgen]
+pub fn add(a: i32, b: i32) -> i32 {
+    log!("Adding {} + {}", a, b);
+    a + b
+}
+
+/// Calculate the nth Fibonacci number
+#[wasm_bindgen]
+pub fn fibonacci(n: i32) -> i32 {
+    if n <= 1 {
+        n
+    } else {
+        fibonacci(n - 1) + fibonacci(n - 2)
+    }
+}
+
+/// Get the current Rust version
+#[wasm_bindgen]
+pub fn get_rust_version() -> String {
+    env!("CARGO_PKG_VERSION").to_string()
+}
+"#;
+
+/// Crates the Rust/WASM part of a project depends on
+pub const WASM_CARGO_DEPENDENCIES: &[(&str, &str)] = &[
+    ("wasm-bindgen", "0.2"),
+    ("serde", "1.0"),
+    ("serde-wasm-bindgen", "0.6"),
+    ("web-sys", "0.3"),
+];
+
+/// npm packages added to `package.json` alongside the Rust/WASM part
+pub const WASM_NPM_DEPENDENCIES: &[(&str, &str)] = &[("wasm-bindgen", "^0.2")];
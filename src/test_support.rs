@@ -0,0 +1,17 @@
+//! Helpers shared by the unit tests
+
+use std::path::Path;
+
+/// Write `content` to `path` under `root`, creating parent directories
+pub fn write(root: &Path, path: &str, content: &str) {
+    let path = root.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, content).unwrap();
+}
+
+/// Write each `(path, content)` pair under `root`
+pub fn fixture(root: &Path, files: &[(&str, &str)]) {
+    for (path, content) in files {
+        write(root, path, content);
+    }
+}
@@ -0,0 +1,120 @@
+//! Timestamp helpers
+//!
+//! Small UTC formatting helpers so CPM does not need a full date/time crate.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch used for generated documents
+///
+/// Honors `SOURCE_DATE_EPOCH` so generated artifacts can be reproducible.
+pub fn build_epoch() -> u64 {
+    if let Some(epoch) = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+    {
+        return epoch;
+    }
+    now()
+}
+
+/// Seconds since the Unix epoch, for comparing against real dates
+///
+/// Unlike [`build_epoch`], never pinned by `SOURCE_DATE_EPOCH`.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Convert days since the Unix epoch into a (year, month, day) civil date
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Convert a (year, month, day) civil date into days since the Unix epoch
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let yoe = year - era * 400;
+    let month = i64::from(month);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parse an RFC 3339 UTC timestamp (as printed by the npm registry) into epoch seconds
+///
+/// Fractional seconds are ignored; only `Z` / `+00:00` offsets are supported.
+pub fn parse_rfc3339(value: &str) -> Option<u64> {
+    let (date, time) = value.split_once('T')?;
+    let mut date_parts = date.splitn(3, '-');
+    let year: i64 = date_parts.next()?.parse().ok()?;
+    let month: u32 = date_parts.next()?.parse().ok()?;
+    let day: u32 = date_parts.next()?.parse().ok()?;
+    let time = time
+        .trim_end_matches('Z')
+        .trim_end_matches("+00:00")
+        .split('.')
+        .next()?;
+    let mut time_parts = time.splitn(3, ':');
+    let hour: u64 = time_parts.next()?.parse().ok()?;
+    let minute: u64 = time_parts.next()?.parse().ok()?;
+    let second: u64 = time_parts.next().unwrap_or("0").parse().ok()?;
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+    Some(days * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Format epoch seconds as an RFC 3339 UTC timestamp (`2025-09-02T10:00:00Z`)
+pub fn format_rfc3339(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+/// Format epoch seconds as a calendar date (`2025-09-02`)
+pub fn format_date(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_rfc3339() {
+        assert_eq!(format_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(1_756_807_200), "2025-09-02T10:00:00Z");
+    }
+
+    #[test]
+    fn test_parse_rfc3339_roundtrip() {
+        assert_eq!(
+            parse_rfc3339("2025-09-02T10:00:00.123Z"),
+            Some(1_756_807_200)
+        );
+        assert_eq!(parse_rfc3339("not a date"), None);
+    }
+
+    #[test]
+    fn test_format_date_leap_year() {
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+    }
+}
@@ -0,0 +1,626 @@
+//! Project version bumping
+//!
+//! Hybrid projects carry a version in `package.json` and another in
+//! `Cargo.toml`. This module reads both, computes the next version with npm's
+//! bump rules and writes it to the manifests, their lockfiles and the
+//! workspace members that depend on the package.
+
+use crate::cli::framework::{CliError, CliResult};
+use crate::manifest::{
+    CARGO_DEPENDENCY_SECTIONS, JsonManifest, NPM_DEPENDENCY_SECTIONS, TomlManifest,
+    rewrite_cargo_requirements, set_string_preserving_decor,
+};
+use semver::{Prerelease, Version};
+use serde_json::Value;
+use std::path::Path;
+
+/// How to compute the next version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+    Prerelease,
+    /// An explicit `x.y.z` version
+    Exact(Version),
+}
+
+impl Bump {
+    /// Parse `major`, `minor`, `patch`, `prerelease` or an explicit version
+    pub fn parse(value: &str) -> CliResult<Self> {
+        match value {
+            "major" => Ok(Bump::Major),
+            "minor" => Ok(Bump::Minor),
+            "patch" => Ok(Bump::Patch),
+            "prerelease" => Ok(Bump::Prerelease),
+            other => Version::parse(other.trim_start_matches('v'))
+                .map(Bump::Exact)
+                .map_err(|e| CliError::InternalError {
+                    message: format!(
+                        "Invalid version '{}': expected major, minor, patch, prerelease or x.y.z ({})",
+                        other, e
+                    ),
+                }),
+        }
+    }
+
+    /// Compute the version following `current`, using npm's rules
+    ///
+    /// Bumping a prerelease to the release it precedes drops the prerelease
+    /// (`2.0.0-rc.1` → `2.0.0` for `major`). `prerelease` increments the last
+    /// numeric identifier, or starts `<patch+1>-<preid>.0` on a release.
+    pub fn apply(&self, current: &Version, preid: Option<&str>) -> CliResult<Version> {
+        let mut next = current.clone();
+        next.build = semver::BuildMetadata::EMPTY;
+        let is_pre = !current.pre.is_empty();
+        match self {
+            Bump::Major => {
+                if !(is_pre && current.minor == 0 && current.patch == 0) {
+                    next.major += 1;
+                    next.minor = 0;
+                    next.patch = 0;
+                }
+                next.pre = Prerelease::EMPTY;
+            }
+            Bump::Minor => {
+                if !(is_pre && current.patch == 0) {
+                    next.minor += 1;
+                    next.patch = 0;
+                }
+                next.pre = Prerelease::EMPTY;
+            }
+            Bump::Patch => {
+                if !is_pre {
+                    next.patch += 1;
+                }
+                next.pre = Prerelease::EMPTY;
+            }
+            Bump::Prerelease => {
+                let same_id = preid.is_none_or(|id| current.pre.as_str().starts_with(id));
+                let pre = if is_pre && same_id {
+                    increment_prerelease(current.pre.as_str())
+                } else {
+                    if !is_pre {
+                        next.patch += 1;
+                    }
+                    preid.map_or("0".to_string(), |id| format!("{id}.0"))
+                };
+                next.pre = Prerelease::new(&pre).map_err(|e| CliError::InternalError {
+                    message: format!("Invalid prerelease identifier '{}': {}", pre, e),
+                })?;
+            }
+            Bump::Exact(version) => {
+                if version == current {
+                    return Err(CliError::InternalError {
+                        message: format!("Version not changed: already at {}", current),
+                    });
+                }
+                next = version.clone();
+            }
+        }
+        Ok(next)
+    }
+}
+
+impl std::fmt::Display for Bump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bump::Major => write!(f, "major"),
+            Bump::Minor => write!(f, "minor"),
+            Bump::Patch => write!(f, "patch"),
+            Bump::Prerelease => write!(f, "prerelease"),
+            Bump::Exact(version) => write!(f, "{}", version),
+        }
+    }
+}
+
+/// Increment the last numeric identifier of a prerelease (`beta.1` → `beta.2`)
+fn increment_prerelease(pre: &str) -> String {
+    let mut parts: Vec<String> = pre.split('.').map(str::to_string).collect();
+    match parts.iter().rposition(|p| p.parse::<u64>().is_ok()) {
+        Some(idx) => {
+            let number: u64 = parts[idx].parse().unwrap_or(0);
+            parts[idx] = (number + 1).to_string();
+        }
+        None => parts.push("0".to_string()),
+    }
+    parts.join(".")
+}
+
+/// The package name and version declared by each manifest of a project
+#[derive(Debug, Clone, Default)]
+pub struct ProjectVersions {
+    /// `name` and `version` from `package.json`
+    pub npm: Option<(String, Version)>,
+    /// `package.name` and `package.version` from `Cargo.toml`
+    pub cargo: Option<(String, Version)>,
+}
+
+fn parse_version(version: &str, file: &str) -> CliResult<Version> {
+    Version::parse(version).map_err(|e| CliError::FileOperationError {
+        operation: "read version".to_string(),
+        path: file.to_string(),
+        message: format!("'{}' is not a valid semver version: {}", version, e),
+    })
+}
+
+impl ProjectVersions {
+    /// Read the versions of the project in `root`
+    pub fn read(root: &Path) -> CliResult<Self> {
+        let mut versions = ProjectVersions::default();
+        let package_json = root.join("package.json");
+        if package_json.exists() {
+            let manifest = JsonManifest::load(&package_json)?;
+            if let (Some(name), Some(version)) = (manifest.name(), manifest.version()) {
+                versions.npm = Some((name.to_string(), parse_version(version, "package.json")?));
+            }
+        }
+        let cargo_toml = root.join("Cargo.toml");
+        if cargo_toml.exists() {
+            let manifest = TomlManifest::load(&cargo_toml)?;
+            if let (Some(name), Some(version)) =
+                (manifest.package_name(), manifest.package_version())
+            {
+                versions.cargo = Some((name.to_string(), parse_version(version, "Cargo.toml")?));
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Description of the disagreement when both manifests declare different versions
+    pub fn mismatch(&self) -> Option<String> {
+        match (&self.npm, &self.cargo) {
+            (Some((_, npm)), Some((_, cargo))) if npm != cargo => Some(format!(
+                "package.json is at {} but Cargo.toml is at {}",
+                npm, cargo
+            )),
+            _ => None,
+        }
+    }
+
+    /// The project version, preferring `package.json`
+    pub fn current(&self) -> Option<&Version> {
+        self.npm.as_ref().or(self.cargo.as_ref()).map(|(_, v)| v)
+    }
+}
+
+/// Rewrite an npm range pinned to a version (`^1.2.3`, `~1.2.3`, `workspace:^1.2.3`)
+///
+/// Returns `None` for ranges that are not tied to a single version (`*`, `file:…`).
+pub fn bump_npm_range(spec: &str, new: &Version) -> Option<String> {
+    let (protocol, range) = match spec.strip_prefix("workspace:") {
+        Some(range) => ("workspace:", range),
+        None => ("", spec),
+    };
+    let (operator, version) = range.split_at(range.find(|c: char| c.is_ascii_digit())?);
+    if !["", "^", "~", ">=", "="].contains(&operator) {
+        return None;
+    }
+    Version::parse(version).ok()?;
+    Some(format!("{protocol}{operator}{new}"))
+}
+
+/// Rewrite a Cargo version requirement, keeping its operator and precision
+pub fn bump_cargo_requirement(requirement: &str, new: &Version) -> Option<String> {
+    let (operator, version) = requirement.split_at(requirement.find(|c: char| c.is_ascii_digit())?);
+    if !["", "^", "~", "="].contains(&operator.trim()) {
+        return None;
+    }
+    let precision = version.split('.').count();
+    let partial = version.split('.').all(|p| p.parse::<u64>().is_ok());
+    let replacement = match precision {
+        1 if partial && new.pre.is_empty() => new.major.to_string(),
+        2 if partial && new.pre.is_empty() => format!("{}.{}", new.major, new.minor),
+        _ if Version::parse(version).is_ok() || partial => new.to_string(),
+        _ => return None,
+    };
+    Some(format!("{operator}{replacement}"))
+}
+
+/// Update the ranges of `package` in the npm dependency sections of `manifest`
+fn update_npm_dependents(manifest: &mut Value, package: &str, new: &Version) -> bool {
+    let mut changed = false;
+    for section in NPM_DEPENDENCY_SECTIONS {
+        let Some(spec) = manifest.get_mut(*section).and_then(|d| d.get_mut(package)) else {
+            continue;
+        };
+        if let Some(updated) = spec.as_str().and_then(|s| bump_npm_range(s, new)) {
+            *spec = Value::String(updated);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Update the requirements on `crate_name` in a Cargo dependency table
+fn update_cargo_table(
+    table: &mut dyn toml_edit::TableLike,
+    crate_name: &str,
+    new: &Version,
+) -> bool {
+    rewrite_cargo_requirements(table, crate_name, |requirement| {
+        bump_cargo_requirement(requirement, new)
+    })
+}
+
+/// Update the requirements on `crate_name` in every dependency table of a Cargo manifest
+fn update_cargo_dependents(manifest: &mut TomlManifest, crate_name: &str, new: &Version) -> bool {
+    let mut changed = false;
+    for section in CARGO_DEPENDENCY_SECTIONS {
+        if let Some(table) = manifest
+            .document
+            .get_mut(section)
+            .and_then(|t| t.as_table_like_mut())
+        {
+            changed |= update_cargo_table(table, crate_name, new);
+        }
+    }
+    if let Some(table) = manifest
+        .document
+        .get_mut("workspace")
+        .and_then(|w| w.get_mut("dependencies"))
+        .and_then(|t| t.as_table_like_mut())
+    {
+        changed |= update_cargo_table(table, crate_name, new);
+    }
+    changed
+}
+
+/// Write `new` to the manifests, lockfiles and dependent workspace members
+///
+/// Returns the changed files relative to `root`.
+pub fn write_version(
+    root: &Path,
+    versions: &ProjectVersions,
+    new: &Version,
+) -> CliResult<Vec<String>> {
+    let version = new.to_string();
+    let members = crate::workspace::member_dirs(root)?;
+    let mut changed = Vec::new();
+
+    if let Some((name, _)) = &versions.npm {
+        let mut manifest = JsonManifest::load(&root.join("package.json"))?;
+        manifest.set_version(&version);
+        update_npm_dependents(&mut manifest.value, name, new);
+        manifest.save()?;
+        changed.push("package.json".to_string());
+
+        let lock_path = root.join("package-lock.json");
+        if lock_path.exists() {
+            let mut lock = JsonManifest::load(&lock_path)?;
+            lock.set_version(&version);
+            if let Some(Value::Object(packages)) = lock.value.get_mut("packages") {
+                for (path, entry) in packages.iter_mut() {
+                    if path.is_empty() {
+                        if let Some(object) = entry.as_object_mut() {
+                            object.insert("version".to_string(), Value::String(version.clone()));
+                        }
+                    } else if !path.contains("node_modules") {
+                        update_npm_dependents(entry, name, new);
+                    }
+                }
+            }
+            lock.save()?;
+            changed.push("package-lock.json".to_string());
+        }
+
+        for dir in &members {
+            let path = dir.join("package.json");
+            if !path.exists() {
+                continue;
+            }
+            let mut member = JsonManifest::load(&path)?;
+            if update_npm_dependents(&mut member.value, name, new) {
+                member.save()?;
+                changed.push(crate::glob::relative_path(root, &path));
+            }
+        }
+    }
+
+    if let Some((name, previous)) = &versions.cargo {
+        let mut manifest = TomlManifest::load(&root.join("Cargo.toml"))?;
+        manifest.set_package_version(&version);
+        update_cargo_dependents(&mut manifest, name, new);
+        manifest.save()?;
+        changed.push("Cargo.toml".to_string());
+
+        let lock_path = root.join("Cargo.lock");
+        if lock_path.exists() {
+            let mut lock = TomlManifest::load(&lock_path)?;
+            let previous = previous.to_string();
+            let packages = lock
+                .document
+                .get_mut("package")
+                .and_then(|p| p.as_array_of_tables_mut());
+            for package in packages.into_iter().flat_map(|p| p.iter_mut()) {
+                let matches = package.get("name").and_then(|n| n.as_str()) == Some(name)
+                    && package.get("version").and_then(|v| v.as_str()) == Some(&previous);
+                if matches {
+                    if let Some(item) = package.get_mut("version") {
+                        set_string_preserving_decor(item, &version);
+                    }
+                }
+            }
+            lock.save()?;
+            changed.push("Cargo.lock".to_string());
+        }
+
+        for dir in &members {
+            let path = dir.join("Cargo.toml");
+            if !path.exists() {
+                continue;
+            }
+            let mut member = TomlManifest::load(&path)?;
+            if update_cargo_dependents(&mut member, name, new) {
+                member.save()?;
+                changed.push(crate::glob::relative_path(root, &path));
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Options for [`bump_project`]
+#[derive(Debug, Clone)]
+pub struct VersionOptions<'a> {
+    pub bump: Bump,
+    pub preid: Option<&'a str>,
+    /// Promote the `Unreleased` section of CHANGELOG.md
+    pub changelog: bool,
+    /// Commit the changes and create a `v<version>` tag
+    pub git: bool,
+    /// Commit and tag message; `%s` is replaced with the new version
+    pub message: &'a str,
+}
+
+/// Result of a version bump
+#[derive(Debug, Clone)]
+pub struct VersionOutcome {
+    pub previous: Version,
+    pub version: Version,
+    /// Changed files relative to the project root
+    pub files: Vec<String>,
+    /// Tag created for the new version, if any
+    pub tag: Option<String>,
+    /// Contents of `files` before the bump, `None` for files it created
+    pub originals: Vec<(String, Option<String>)>,
+}
+
+/// Current contents of every file a bump of `root` may change
+fn snapshot(root: &Path) -> CliResult<Vec<(String, Option<String>)>> {
+    let mut paths: Vec<String> = [
+        "package.json",
+        "package-lock.json",
+        "Cargo.toml",
+        "Cargo.lock",
+        crate::changelog::CHANGELOG_FILE,
+    ]
+    .iter()
+    .map(|file| file.to_string())
+    .collect();
+    for dir in crate::workspace::member_dirs(root)? {
+        for file in ["package.json", "Cargo.toml"] {
+            paths.push(crate::glob::relative_path(root, &dir.join(file)));
+        }
+    }
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let content = std::fs::read_to_string(root.join(&path)).ok();
+            (path, content)
+        })
+        .collect())
+}
+
+/// Bump the version of the project in `root`
+pub fn bump_project(root: &Path, options: &VersionOptions) -> CliResult<VersionOutcome> {
+    let versions = ProjectVersions::read(root)?;
+    let Some(previous) = versions.current().cloned() else {
+        return Err(CliError::FileOperationError {
+            operation: "version".to_string(),
+            path: root.display().to_string(),
+            message: "No version found in package.json or Cargo.toml".to_string(),
+        });
+    };
+    let version = match (versions.mismatch(), &options.bump) {
+        // An explicit version brings both manifests back in sync, and differs
+        // from at least one of them even when it is the current version
+        (Some(_), Bump::Exact(version)) => version.clone(),
+        (Some(mismatch), _) => {
+            return Err(CliError::ExecutionError {
+                command: "cpm version".to_string(),
+                message: format!("{}; pass an explicit x.y.z to sync them", mismatch),
+            });
+        }
+        (None, bump) => bump.apply(&previous, options.preid)?,
+    };
+
+    let git = options.git && crate::git::is_repo(root);
+    let tag = format!("v{}", version);
+    if git {
+        if !crate::git::uncommitted_changes(root)?.is_empty() {
+            return Err(CliError::ExecutionError {
+                command: "cpm version".to_string(),
+                message: "Git working directory not clean".to_string(),
+            });
+        }
+        if crate::git::tag_exists(root, &tag) {
+            return Err(CliError::FileExists { path: tag });
+        }
+    }
+
+    let mut originals = snapshot(root)?;
+    let mut files = write_version(root, &versions, &version)?;
+    if options.changelog {
+        let path = root.join(crate::changelog::CHANGELOG_FILE);
+        let content = std::fs::read_to_string(&path).unwrap_or_default();
+        let date = crate::timestamp::format_date(crate::timestamp::build_epoch());
+        let updated = crate::changelog::promote_unreleased(&content, &version.to_string(), &date);
+        std::fs::write(&path, updated)?;
+        files.push(crate::changelog::CHANGELOG_FILE.to_string());
+    }
+
+    let tag = if git {
+        let message = options.message.replace("%s", &version.to_string());
+        crate::git::commit(root, &files, &message)?;
+        crate::git::tag(root, &tag, &message)?;
+        Some(tag)
+    } else {
+        None
+    };
+
+    originals.retain(|(path, _)| files.contains(path));
+    Ok(VersionOutcome {
+        previous,
+        version,
+        files,
+        tag,
+        originals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn next(current: &str, bump: &str, preid: Option<&str>) -> String {
+        Bump::parse(bump)
+            .unwrap()
+            .apply(&Version::parse(current).unwrap(), preid)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_bump_follows_npm_rules() {
+        assert_eq!(next("1.2.3", "major", None), "2.0.0");
+        assert_eq!(next("1.2.3", "minor", None), "1.3.0");
+        assert_eq!(next("1.2.3", "patch", None), "1.2.4");
+        assert_eq!(next("2.0.0-rc.1", "major", None), "2.0.0");
+        assert_eq!(next("1.3.0-beta.2", "minor", None), "1.3.0");
+        assert_eq!(next("1.2.4-0", "patch", None), "1.2.4");
+        assert_eq!(next("1.2.3", "prerelease", None), "1.2.4-0");
+        assert_eq!(next("1.2.3", "prerelease", Some("beta")), "1.2.4-beta.0");
+        assert_eq!(
+            next("1.2.4-beta.0", "prerelease", Some("beta")),
+            "1.2.4-beta.1"
+        );
+        assert_eq!(
+            next("1.2.4-alpha.3", "prerelease", Some("beta")),
+            "1.2.4-beta.0"
+        );
+        assert_eq!(next("1.2.3", "v2.0.0", None), "2.0.0");
+        assert!(Bump::parse("huge").is_err());
+    }
+
+    #[test]
+    fn test_bump_ranges_keep_operator() {
+        let new = Version::parse("1.3.0").unwrap();
+        assert_eq!(bump_npm_range("^1.2.3", &new).as_deref(), Some("^1.3.0"));
+        assert_eq!(
+            bump_npm_range("workspace:~1.2.3", &new).as_deref(),
+            Some("workspace:~1.3.0")
+        );
+        assert_eq!(bump_npm_range("workspace:*", &new), None);
+        assert_eq!(bump_npm_range("file:../core", &new), None);
+        assert_eq!(bump_cargo_requirement("1.2", &new).as_deref(), Some("1.3"));
+        assert_eq!(
+            bump_cargo_requirement("=1.2.3", &new).as_deref(),
+            Some("=1.3.0")
+        );
+    }
+
+    #[test]
+    fn test_write_version_updates_hybrid_workspace() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::write(
+            root.join("package.json"),
+            "{\n  \"name\": \"demo\",\n  \"version\": \"0.1.0\",\n  \"workspaces\": [\"packages/*\"]\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("package-lock.json"),
+            "{\n  \"name\": \"demo\",\n  \"version\": \"0.1.0\",\n  \"lockfileVersion\": 3,\n  \"packages\": {\n    \"\": {\n      \"name\": \"demo\",\n      \"version\": \"0.1.0\"\n    }\n  }\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\" # keep in sync\n\n[workspace]\nmembers = [\"crates/*\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"demo\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("packages/app")).unwrap();
+        std::fs::write(
+            root.join("packages/app/package.json"),
+            "{\n  \"name\": \"app\",\n  \"dependencies\": {\n    \"demo\": \"^0.1.0\"\n  }\n}\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("crates/cli")).unwrap();
+        std::fs::write(
+            root.join("crates/cli/Cargo.toml"),
+            "[package]\nname = \"cli\"\n\n[dependencies]\ndemo = { path = \"../..\", version = \"0.1\" }\n",
+        )
+        .unwrap();
+
+        let versions = ProjectVersions::read(root).unwrap();
+        assert!(versions.mismatch().is_none());
+        let files = write_version(root, &versions, &Version::parse("0.2.0").unwrap()).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                "package.json",
+                "package-lock.json",
+                "packages/app/package.json",
+                "Cargo.toml",
+                "Cargo.lock",
+                "crates/cli/Cargo.toml"
+            ]
+        );
+
+        let read = |path: &str| std::fs::read_to_string(root.join(path)).unwrap();
+        assert!(read("Cargo.toml").contains("version = \"0.2.0\" # keep in sync"));
+        assert!(read("package-lock.json").contains("      \"version\": \"0.2.0\""));
+        assert!(read("packages/app/package.json").contains("\"demo\": \"^0.2.0\""));
+        assert!(read("crates/cli/Cargo.toml").contains("version = \"0.2\" }"));
+        assert!(read("Cargo.lock").contains("version = \"0.2.0\""));
+        let versions = ProjectVersions::read(root).unwrap();
+        assert_eq!(versions.current().unwrap().to_string(), "0.2.0");
+    }
+
+    #[test]
+    fn test_exact_version_syncs_mismatched_manifests() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::write(
+            root.join("package.json"),
+            "{\n  \"name\": \"demo\",\n  \"version\": \"1.2.0\"\n}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"1.1.0\"\n",
+        )
+        .unwrap();
+        let options = |bump: &str| VersionOptions {
+            bump: Bump::parse(bump).unwrap(),
+            preid: None,
+            changelog: false,
+            git: false,
+            message: "v%s",
+        };
+        assert!(bump_project(root, &options("patch")).is_err());
+
+        // Cargo.toml catches up with the version package.json already has
+        let outcome = bump_project(root, &options("1.2.0")).unwrap();
+        assert_eq!(outcome.version.to_string(), "1.2.0");
+        let versions = ProjectVersions::read(root).unwrap();
+        assert!(versions.mismatch().is_none());
+        assert_eq!(versions.cargo.unwrap().1.to_string(), "1.2.0");
+    }
+}
@@ -0,0 +1,483 @@
+//! Workspace members
+//!
+//! Resolves the member directories of an npm workspace (`workspaces` in
+//! `package.json`) and of a Cargo workspace (`[workspace] members`) rooted at
+//! the same directory, and loads each member's manifests.
+
+use crate::cli::framework::CliResult;
+use crate::glob::{glob_dirs, relative_path};
+use crate::manifest::{CARGO_DEPENDENCY_SECTIONS, JsonManifest, TomlManifest};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// Patterns from the `workspaces` field (array or `{ "packages": [...] }` form)
+pub fn npm_patterns(root: &Path) -> CliResult<Vec<String>> {
+    let path = root.join("package.json");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let manifest: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let workspaces = match manifest.get("workspaces") {
+        Some(Value::Object(object)) => object.get("packages"),
+        other => other,
+    };
+    Ok(workspaces
+        .and_then(Value::as_array)
+        .map(|patterns| {
+            patterns
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// `[workspace] members`, with `exclude` entries turned into negated patterns
+pub fn cargo_patterns(root: &Path) -> CliResult<Vec<String>> {
+    let path = root.join("Cargo.toml");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let manifest: toml::Value = toml::from_str(&std::fs::read_to_string(path)?)?;
+    let Some(workspace) = manifest.get("workspace") else {
+        return Ok(Vec::new());
+    };
+    let strings = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(|v| v.as_array())
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut patterns = strings("members");
+    patterns.extend(strings("exclude").into_iter().map(|e| format!("!{e}")));
+    Ok(patterns)
+}
+
+/// Directories matching `patterns` that contain `manifest`
+fn resolve(root: &Path, patterns: &[String], manifest: &str) -> Vec<PathBuf> {
+    let patterns: Vec<String> = patterns
+        .iter()
+        .map(|p| p.trim_start_matches("./").trim_end_matches('/').to_string())
+        .collect();
+    glob_dirs(root, &patterns)
+        .into_iter()
+        .filter(|dir| dir.join(manifest).exists())
+        .collect()
+}
+
+/// Member directories of the npm workspace
+pub fn npm_members(root: &Path) -> CliResult<Vec<PathBuf>> {
+    Ok(resolve(root, &npm_patterns(root)?, "package.json"))
+}
+
+/// Member directories of the Cargo workspace
+pub fn cargo_members(root: &Path) -> CliResult<Vec<PathBuf>> {
+    Ok(resolve(root, &cargo_patterns(root)?, "Cargo.toml"))
+}
+
+/// All member directories of both workspaces, sorted and deduplicated
+pub fn member_dirs(root: &Path) -> CliResult<Vec<PathBuf>> {
+    let mut dirs = npm_members(root)?;
+    dirs.extend(cargo_members(root)?);
+    dirs.sort();
+    dirs.dedup();
+    Ok(dirs)
+}
+
+/// Which manifests a member has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ecosystem {
+    /// `package.json` only
+    #[serde(rename = "js")]
+    JavaScript,
+    /// `Cargo.toml` only
+    Rust,
+    /// Both manifests
+    Hybrid,
+}
+
+impl Ecosystem {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Ecosystem::JavaScript => "JS",
+            Ecosystem::Rust => "Rust",
+            Ecosystem::Hybrid => "hybrid",
+        }
+    }
+}
+
+/// The manifest section a dependency is declared in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DependencyKind {
+    /// `dependencies` in `package.json`
+    Npm,
+    /// `devDependencies` in `package.json`
+    NpmDev,
+    /// `[dependencies]` in `Cargo.toml`
+    Cargo,
+    /// `[dev-dependencies]` in `Cargo.toml`
+    CargoDev,
+    /// `[build-dependencies]` in `Cargo.toml`
+    CargoBuild,
+}
+
+impl DependencyKind {
+    /// The kind for a `package.json` dependency section
+    fn from_npm_section(section: &str) -> Option<Self> {
+        match section {
+            "dependencies" => Some(DependencyKind::Npm),
+            "devDependencies" => Some(DependencyKind::NpmDev),
+            _ => None,
+        }
+    }
+
+    /// The kind for a `Cargo.toml` dependency table
+    fn from_cargo_section(section: &str) -> Option<Self> {
+        match section {
+            "dependencies" => Some(DependencyKind::Cargo),
+            "dev-dependencies" => Some(DependencyKind::CargoDev),
+            "build-dependencies" => Some(DependencyKind::CargoBuild),
+            _ => None,
+        }
+    }
+
+    /// Whether the dependency is declared in `Cargo.toml`
+    pub fn is_cargo(&self) -> bool {
+        matches!(
+            self,
+            DependencyKind::Cargo | DependencyKind::CargoDev | DependencyKind::CargoBuild
+        )
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DependencyKind::Npm => "npm",
+            DependencyKind::NpmDev => "npm dev",
+            DependencyKind::Cargo => "cargo",
+            DependencyKind::CargoDev => "cargo dev",
+            DependencyKind::CargoBuild => "cargo build",
+        }
+    }
+}
+
+/// A dependency declared in a member's manifest
+#[derive(Debug, Clone)]
+pub struct MemberDependency {
+    /// npm package name, or the Cargo package name (honouring `package = "..."`)
+    pub name: String,
+    pub kind: DependencyKind,
+    /// The npm range or Cargo version requirement, when there is one
+    pub range: Option<String>,
+    /// Directory of a Cargo `path` dependency
+    pub path: Option<PathBuf>,
+}
+
+/// A workspace member package
+#[derive(Debug, Clone, Serialize)]
+pub struct Member {
+    pub name: String,
+    pub version: Option<String>,
+    /// Path relative to the workspace root, `/`-separated
+    pub path: String,
+    #[serde(skip)]
+    pub dir: PathBuf,
+    /// `"private": true` or `publish = false`
+    pub private: bool,
+    pub ecosystem: Ecosystem,
+    /// `package.name` from `Cargo.toml`, when the member is a crate
+    #[serde(skip)]
+    pub crate_name: Option<String>,
+    /// `dependencies` and `devDependencies` from `package.json`, then the Cargo dependency tables
+    #[serde(skip)]
+    pub dependencies: Vec<MemberDependency>,
+    /// Names of the scripts in `package.json`
+    #[serde(skip)]
+    pub scripts: Vec<String>,
+}
+
+impl Member {
+    /// Load the member in `dir`
+    ///
+    /// `workspace_version` is the root `[workspace.package] version`, used by
+    /// crates declaring `version.workspace = true`.
+    fn load(root: &Path, dir: &Path, workspace_version: Option<&str>) -> CliResult<Self> {
+        let npm = match dir.join("package.json") {
+            path if path.exists() => Some(JsonManifest::load(&path)?),
+            _ => None,
+        };
+        let cargo = match dir.join("Cargo.toml") {
+            path if path.exists() => Some(TomlManifest::load(&path)?),
+            _ => None,
+        };
+        let ecosystem = match (&npm, &cargo) {
+            (Some(_), Some(_)) => Ecosystem::Hybrid,
+            (None, Some(_)) => Ecosystem::Rust,
+            _ => Ecosystem::JavaScript,
+        };
+
+        let cargo_version = cargo.as_ref().and_then(|manifest| {
+            manifest
+                .package_version()
+                .or_else(|| {
+                    let inherited = manifest.document.get("package")?.get("version")?;
+                    inherited.get("workspace").and(workspace_version)
+                })
+                .map(str::to_string)
+        });
+        let path = relative_path(root, dir);
+        let name = npm
+            .as_ref()
+            .and_then(|m| m.name())
+            .or_else(|| cargo.as_ref().and_then(|m| m.package_name()))
+            .map(str::to_string)
+            .unwrap_or_else(|| path.rsplit('/').next().unwrap_or(&path).to_string());
+        let version = npm
+            .as_ref()
+            .and_then(|m| m.version())
+            .map(str::to_string)
+            .or(cargo_version);
+        let private = npm
+            .as_ref()
+            .is_some_and(|m| m.value.get("private").and_then(Value::as_bool) == Some(true))
+            || cargo.as_ref().is_some_and(|m| {
+                m.document
+                    .get("package")
+                    .and_then(|p| p.get("publish"))
+                    .and_then(|p| p.as_bool())
+                    == Some(false)
+            });
+
+        let mut dependencies: Vec<MemberDependency> = npm
+            .as_ref()
+            .map(|m| {
+                ["dependencies", "devDependencies"]
+                    .iter()
+                    .filter_map(|section| {
+                        let kind = DependencyKind::from_npm_section(section)?;
+                        Some((kind, m.value.get(section)?.as_object()?))
+                    })
+                    .flat_map(|(kind, deps)| {
+                        deps.iter().map(move |(name, spec)| MemberDependency {
+                            name: name.clone(),
+                            kind,
+                            range: spec.as_str().map(str::to_string),
+                            path: None,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let scripts = npm
+            .as_ref()
+            .and_then(|m| m.value.get("scripts")?.as_object())
+            .map(|scripts| scripts.keys().cloned().collect())
+            .unwrap_or_default();
+        if let Some(manifest) = &cargo {
+            dependencies.extend(cargo_dependencies(&manifest.document, dir));
+        }
+
+        Ok(Member {
+            name,
+            version,
+            path,
+            dir: dir.to_path_buf(),
+            private,
+            ecosystem,
+            crate_name: cargo
+                .as_ref()
+                .and_then(|m| m.package_name())
+                .map(str::to_string),
+            dependencies,
+            scripts,
+        })
+    }
+}
+
+/// Dependencies from the Cargo dependency tables, with `path` resolved against `dir`
+fn cargo_dependencies(document: &toml_edit::DocumentMut, dir: &Path) -> Vec<MemberDependency> {
+    let mut dependencies = Vec::new();
+    for section in CARGO_DEPENDENCY_SECTIONS {
+        let Some(table) = document.get(section).and_then(|t| t.as_table_like()) else {
+            continue;
+        };
+        let Some(kind) = DependencyKind::from_cargo_section(section) else {
+            continue;
+        };
+        for (key, item) in table.iter() {
+            let detail = item.as_table_like();
+            // `foo = { package = "bar" }` depends on `bar`
+            let name = detail
+                .and_then(|d| d.get("package"))
+                .and_then(|p| p.as_str())
+                .unwrap_or(key);
+            let path = detail
+                .and_then(|d| d.get("path"))
+                .and_then(|p| p.as_str())
+                .map(|p| dir.join(p));
+            let range = match detail {
+                Some(detail) => detail.get("version").and_then(|v| v.as_str()),
+                None => item.as_str(),
+            };
+            dependencies.push(MemberDependency {
+                name: name.to_string(),
+                kind,
+                range: range.map(str::to_string),
+                path,
+            });
+        }
+    }
+    dependencies
+}
+
+/// Whether `root` declares an npm or Cargo workspace
+pub fn is_workspace(root: &Path) -> CliResult<bool> {
+    Ok(!npm_patterns(root)?.is_empty() || !cargo_patterns(root)?.is_empty())
+}
+
+/// Load every member of the workspace rooted at `root`, sorted by path
+pub fn discover(root: &Path) -> CliResult<Vec<Member>> {
+    let root_cargo = root.join("Cargo.toml");
+    let workspace_version = if root_cargo.exists() {
+        TomlManifest::load(&root_cargo)?
+            .document
+            .get("workspace")
+            .and_then(|w| w.get("package"))
+            .and_then(|p| p.get("version"))
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    } else {
+        None
+    };
+    member_dirs(root)?
+        .iter()
+        .map(|dir| Member::load(root, dir, workspace_version.as_deref()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_member_dirs_from_both_workspaces() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::write(
+            root.join("package.json"),
+            r#"{"name":"root","workspaces":["packages/*","!packages/legacy"]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/scratch\"]\n",
+        )
+        .unwrap();
+        for (dir, manifest) in [
+            ("packages/app", "package.json"),
+            ("packages/legacy", "package.json"),
+            ("packages/docs", "README.md"),
+            ("crates/core", "Cargo.toml"),
+            ("crates/scratch", "Cargo.toml"),
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join(manifest), "{}").unwrap();
+        }
+
+        let members: Vec<String> = member_dirs(root)
+            .unwrap()
+            .iter()
+            .map(|dir| crate::glob::relative_path(root, dir))
+            .collect();
+        assert_eq!(members, vec!["crates/core", "packages/app"]);
+    }
+
+    #[test]
+    fn test_discover_detects_ecosystems() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path();
+        std::fs::write(
+            root.join("package.json"),
+            r#"{"name":"root","private":true,"workspaces":["packages/*"]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"packages/*\"]\n\n[workspace.package]\nversion = \"0.3.0\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("packages/web")).unwrap();
+        std::fs::write(
+            root.join("packages/web/package.json"),
+            r#"{"name":"@demo/web","version":"1.0.0","private":true}"#,
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("packages/wasm")).unwrap();
+        std::fs::write(
+            root.join("packages/wasm/package.json"),
+            r#"{"name":"@demo/wasm","version":"0.3.0"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("packages/wasm/Cargo.toml"),
+            "[package]\nname = \"demo-wasm\"\nversion.workspace = true\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(root.join("packages/core")).unwrap();
+        std::fs::write(
+            root.join("packages/core/Cargo.toml"),
+            "[package]\nname = \"demo-core\"\nversion.workspace = true\npublish = false\n",
+        )
+        .unwrap();
+
+        let members = discover(root).unwrap();
+        let summary: Vec<_> = members
+            .iter()
+            .map(|m| {
+                (
+                    m.name.as_str(),
+                    m.version.as_deref(),
+                    m.path.as_str(),
+                    m.private,
+                    m.ecosystem,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "demo-core",
+                    Some("0.3.0"),
+                    "packages/core",
+                    true,
+                    Ecosystem::Rust
+                ),
+                (
+                    "@demo/wasm",
+                    Some("0.3.0"),
+                    "packages/wasm",
+                    false,
+                    Ecosystem::Hybrid
+                ),
+                (
+                    "@demo/web",
+                    Some("1.0.0"),
+                    "packages/web",
+                    true,
+                    Ecosystem::JavaScript
+                ),
+            ]
+        );
+    }
+}